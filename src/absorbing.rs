@@ -0,0 +1,368 @@
+use crate::diagnostics::DiagnosticMonitor;
+use crate::domain::Domain;
+use crate::kd_tree::{Grid2D, Points2D};
+use crate::meshfree::MeshfreeNodes;
+use crate::runge_kutta::{rk_step, RkScheme};
+use crate::sparse_matrix::CsrMatrix;
+use crate::stencil::DiffOperator;
+
+// 円環状の完全整合層 (PML)。inner_radius の内側が本来の領域で、外周では u = 0
+// 動径方向の減衰 ζ_r = σ_max ξ^p (ξ = (r - r_in) / (r_out - r_in)) と接線方向の ζ_θ = (1/r) ∫ ζ_r dr
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmlLayer {
+    pub center: Grid2D,
+    pub inner_radius: f64,
+    pub outer_radius: f64,
+    pub strength: f64, // σ_max
+    pub power: i32,
+}
+
+impl PmlLayer {
+    // 法線入射の理論反射率 exp(-2/c ∫ ζ_r dr) が reflection になる強さにする
+    #[allow(dead_code)]
+    pub fn new(inner_radius: f64, outer_radius: f64, speed: f64, reflection: f64) -> Self {
+        let power = 2;
+        let width = outer_radius - inner_radius;
+        PmlLayer {
+            center: Grid2D::new(0.0, 0.0),
+            inner_radius,
+            outer_radius,
+            strength: (power + 1) as f64 * speed * (1.0 / reflection).ln() / (2.0 * width),
+            power,
+        }
+    }
+
+    // (ζ_r, ζ_θ) と中心から外向きの単位ベクトル
+    #[allow(dead_code)]
+    pub fn damping(&self, x: f64, y: f64) -> (f64, f64, Grid2D) {
+        let (dx, dy) = (x - self.center.x, y - self.center.y);
+        let r = dx.hypot(dy);
+        let e = if r > 0.0 {
+            Grid2D::new(dx / r, dy / r)
+        } else {
+            Grid2D::new(1.0, 0.0)
+        };
+        if r <= self.inner_radius {
+            return (0.0, 0.0, e);
+        }
+        let width = self.outer_radius - self.inner_radius;
+        let xi = (r - self.inner_radius) / width;
+        let zeta_r = self.strength * xi.powi(self.power);
+        let zeta_theta =
+            self.strength * width * xi.powi(self.power + 1) / ((self.power + 1) as f64 * r);
+        (zeta_r, zeta_theta, e)
+    }
+}
+
+// 波動方程式 u_tt = c^2 Δu の外側の境界の扱い
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Absorbing {
+    Reflecting, // u = 0 (比較用)
+    // u_t + c (∂u/∂n + κ u / 2) = 0。κ = 0 で 1 次の Engquist-Majda (Sommerfeld)、
+    // 円なら κ = 1 / 半径 として曲率の補正を入れる
+    Sommerfeld { curvature: f64 },
+    // Grote-Sim の PML: u_tt + (ζ_r + ζ_θ) u_t + ζ_r ζ_θ u = c^2 Δu + ∇·ψ,
+    // ψ_t = -Z ψ + c^2 D ∇u (Z, D は動径・接線方向に対角)。外周は u = 0
+    Pml(PmlLayer),
+}
+
+// 節点とステンシルの上で Runge-Kutta 4 次で時間発展する波動方程式。安定化はしていないので、
+// Laplacian に実部が正や虚部の大きい固有値 (eigen::unstable_eigenpairs) があると長時間では増大する
+// 状態は Reflecting / Pml では内部点の u、Sommerfeld では全節点の u に続けて内部点の u_t、
+// Pml ではさらに全節点の ψ_x, ψ_y
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct AbsorbingWave {
+    pub mesh: MeshfreeNodes,
+    pub speed: f64,
+    pub boundary: Absorbing,
+    pub state: Vec<f64>,
+    pub time: f64,
+    laplacian: CsrMatrix,  // 内部点の行 x 全節点
+    gradient_x: CsrMatrix, // 全節点 x 全節点
+    gradient_y: CsrMatrix,
+    radiation: CsrMatrix, // 境界点の行 ∂u/∂n + κ u / 2
+    damping: Vec<(f64, f64, Grid2D)>,
+}
+
+impl AbsorbingWave {
+    #[allow(dead_code)]
+    pub fn new(
+        domain: &Domain,
+        interior: &Points2D,
+        boundary: &Points2D,
+        degree: usize,
+        num_neighbor: usize,
+        speed: f64,
+        absorbing: Absorbing,
+    ) -> Result<Self, String> {
+        let mut mesh = MeshfreeNodes::new(interior, boundary, degree, num_neighbor);
+        mesh.normals = boundary
+            .points
+            .iter()
+            .map(|p| domain.normal(p.x, p.y))
+            .collect();
+        let n_all = mesh.num_nodes();
+        let laplacian =
+            CsrMatrix::from_rows(n_all, &mesh.operator_rows(&DiffOperator::laplacian())?);
+        let gradient_x = CsrMatrix::from_rows(n_all, &mesh.node_rows(&DiffOperator::d_x())?);
+        let gradient_y = CsrMatrix::from_rows(n_all, &mesh.node_rows(&DiffOperator::d_y())?);
+        let radiation = match &absorbing {
+            Absorbing::Sommerfeld { curvature } => {
+                let rows: Vec<Vec<(usize, f64)>> = (0..mesh.num_boundary())
+                    .map(|k| mesh.boundary_row(k, 0.5 * curvature, 1.0))
+                    .collect::<Result<_, _>>()?;
+                CsrMatrix::from_rows(n_all, &rows)
+            }
+            _ => CsrMatrix::from_rows(n_all, &[]),
+        };
+        let damping = match &absorbing {
+            Absorbing::Pml(layer) => mesh
+                .nodes
+                .points
+                .iter()
+                .map(|p| layer.damping(p.x, p.y))
+                .collect(),
+            _ => vec![],
+        };
+        let n = mesh.num_interior;
+        let size = match absorbing {
+            Absorbing::Reflecting => 2 * n,
+            Absorbing::Sommerfeld { .. } => n_all + n,
+            Absorbing::Pml(_) => 2 * n + 2 * n_all,
+        };
+        Ok(AbsorbingWave {
+            mesh,
+            speed,
+            boundary: absorbing,
+            state: vec![0.0; size],
+            time: 0.0,
+            laplacian,
+            gradient_x,
+            gradient_y,
+            radiation,
+            damping,
+        })
+    }
+
+    // 全節点の u(x, y, 0) と u_t(x, y, 0)。ψ は 0 から始める
+    #[allow(dead_code)]
+    pub fn set_initial_condition(
+        &mut self,
+        displacement: &dyn Fn(f64, f64) -> f64,
+        velocity: &dyn Fn(f64, f64) -> f64,
+    ) {
+        let n = self.mesh.num_interior;
+        let size = self.state.len();
+        let points = &self.mesh.nodes.points;
+        let u_len = self.displacement_len();
+        let mut state: Vec<f64> = points[0..u_len]
+            .iter()
+            .map(|p| displacement(p.x, p.y))
+            .collect();
+        state.extend(points[0..n].iter().map(|p| velocity(p.x, p.y)));
+        state.resize(size, 0.0);
+        self.state = state;
+        self.time = 0.0;
+    }
+
+    fn displacement_len(&self) -> usize {
+        match self.boundary {
+            Absorbing::Sommerfeld { .. } => self.mesh.num_nodes(),
+            _ => self.mesh.num_interior,
+        }
+    }
+
+    // 全節点の u (境界で u = 0 の場合は 0 を補う)
+    #[allow(dead_code)]
+    pub fn displacement(&self) -> Vec<f64> {
+        displacement_of(&self.state, self.displacement_len(), self.mesh.num_nodes())
+    }
+
+    // 全節点の u_t
+    #[allow(dead_code)]
+    pub fn velocity(&self) -> Vec<f64> {
+        let n = self.mesh.num_interior;
+        let u_len = self.displacement_len();
+        let mut v = self.state[u_len..(u_len + n)].to_vec();
+        match self.boundary {
+            Absorbing::Sommerfeld { .. } => {
+                let u = self.displacement();
+                v.extend(self.radiation.mul_vec(&u).iter().map(|r| -self.speed * r));
+            }
+            _ => v.resize(self.mesh.num_nodes(), 0.0),
+        }
+        v
+    }
+
+    fn rhs(&self, state: &[f64]) -> Vec<f64> {
+        let n = self.mesh.num_interior;
+        let n_all = self.mesh.num_nodes();
+        let u_len = self.displacement_len();
+        let u = displacement_of(state, u_len, n_all);
+        let v = &state[u_len..(u_len + n)];
+        let c2 = self.speed * self.speed;
+        let lap = self.laplacian.mul_vec(&u);
+        let mut f = v.to_vec();
+        match &self.boundary {
+            Absorbing::Reflecting => {
+                f.extend(lap.iter().map(|l| c2 * l));
+            }
+            Absorbing::Sommerfeld { .. } => {
+                f.extend(self.radiation.mul_vec(&u).iter().map(|r| -self.speed * r));
+                f.extend(lap.iter().map(|l| c2 * l));
+            }
+            Absorbing::Pml(_) => {
+                let psi_x = &state[(2 * n)..(2 * n + n_all)];
+                let psi_y = &state[(2 * n + n_all)..];
+                let div: Vec<f64> = self
+                    .gradient_x
+                    .mul_vec(psi_x)
+                    .iter()
+                    .zip(self.gradient_y.mul_vec(psi_y).iter())
+                    .map(|(a, b)| a + b)
+                    .collect();
+                for i in 0..n {
+                    let (zr, zt, _) = &self.damping[i];
+                    f.push(c2 * lap[i] + div[i] - (zr + zt) * v[i] - zr * zt * u[i]);
+                }
+                let gx = self.gradient_x.mul_vec(&u);
+                let gy = self.gradient_y.mul_vec(&u);
+                let mut f_x = Vec::with_capacity(n_all);
+                let mut f_y = Vec::with_capacity(n_all);
+                for k in 0..n_all {
+                    let (zr, zt, e) = &self.damping[k];
+                    // Z ψ = ζ_θ ψ + (ζ_r - ζ_θ)(e·ψ) e,  D g = (ζ_θ - ζ_r)(2 (e·g) e - g)
+                    let e_psi = e.x * psi_x[k] + e.y * psi_y[k];
+                    let e_g = e.x * gx[k] + e.y * gy[k];
+                    let z_x = zt * psi_x[k] + (zr - zt) * e_psi * e.x;
+                    let z_y = zt * psi_y[k] + (zr - zt) * e_psi * e.y;
+                    let d_x = (zt - zr) * (2.0 * e_g * e.x - gx[k]);
+                    let d_y = (zt - zr) * (2.0 * e_g * e.y - gy[k]);
+                    f_x.push(-z_x + c2 * d_x);
+                    f_y.push(-z_y + c2 * d_y);
+                }
+                f.extend(f_x);
+                f.extend(f_y);
+            }
+        }
+        f
+    }
+
+    #[allow(dead_code)]
+    pub fn step(&mut self, dt: f64) {
+        let tableau = RkScheme::Rk4.tableau();
+        let (next, _) = rk_step(&tableau, &self.state, self.time, dt, &mut |s, _| {
+            self.rhs(s)
+        });
+        self.state = next;
+        self.time += dt;
+    }
+
+    // 節点の最小間隔 h に対する dt = safety h / c
+    #[allow(dead_code)]
+    pub fn cfl_dt(&self, safety: f64) -> f64 {
+        let h = (0..self.mesh.num_nodes())
+            .map(|i| {
+                let stencil = if i < self.mesh.num_interior {
+                    &self.mesh.stencils[i]
+                } else {
+                    &self.mesh.boundary_stencils[i - self.mesh.num_interior]
+                };
+                let p = self.mesh.point(i);
+                stencil
+                    .iter()
+                    .filter(|j| **j != i)
+                    .map(|j| self.mesh.point(*j).distance_square(p).sqrt())
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .fold(f64::INFINITY, f64::min);
+        safety * h / self.speed
+    }
+
+    // final_time まで進めながら monitor に記録し、振幅の反射率 sqrt(E(T) / E(0)) を返す。
+    // monitor は restrict_to で測りたい領域 (PML なら内側) に絞っておく
+    #[allow(dead_code)]
+    pub fn reflection_coefficient(
+        &mut self,
+        monitor: &mut DiagnosticMonitor,
+        dt: f64,
+        final_time: f64,
+    ) -> Result<f64, String> {
+        let initial = monitor
+            .record_values(self.time, &self.displacement(), &self.velocity())?
+            .energy();
+        while self.time < final_time - 1.0e-12 {
+            self.step(dt.min(final_time - self.time));
+            monitor.record_values(self.time, &self.displacement(), &self.velocity())?;
+        }
+        let last = monitor.records.last().unwrap().energy();
+        Ok((last / initial).sqrt())
+    }
+}
+
+fn displacement_of(state: &[f64], u_len: usize, n_all: usize) -> Vec<f64> {
+    let mut u = state[0..u_len].to_vec();
+    u.resize(n_all, 0.0);
+    u
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::Diagnostics;
+    use crate::node_generator::{NodeGenerator, NodeStrategy, Spacing};
+
+    // 中心の Gaussian パルスが半径 1 の円を出ていった後に円内に残るエネルギーから求めた反射率。
+    // 中心化したステンシルの Laplacian は虚部をもつ固有値があり長時間では増大するので、
+    // パルスが出ていった直後で測る
+    fn reflection(absorbing: Absorbing, radius: f64) -> f64 {
+        let domain = Domain::Circle {
+            cx: 0.0,
+            cy: 0.0,
+            r: radius,
+        };
+        let generator = NodeGenerator::new(
+            domain.clone(),
+            Spacing::Uniform(0.05),
+            NodeStrategy::HexLattice,
+        );
+        let (boundary, interior) = generator.generate();
+        let mut wave =
+            AbsorbingWave::new(&domain, &interior, &boundary, 2, 12, 1.0, absorbing).unwrap();
+        wave.set_initial_condition(&|x, y| (-60.0 * (x * x + y * y)).exp(), &|_, _| 0.0);
+        let mut monitor =
+            DiagnosticMonitor::from_nodes(&domain, &interior, &boundary, Diagnostics::default())
+                .unwrap();
+        monitor.restrict_to(&wave.mesh.nodes, |x, y| x * x + y * y < 1.0);
+        let dt = wave.cfl_dt(0.3);
+        wave.reflection_coefficient(&mut monitor, dt, 2.5).unwrap()
+    }
+
+    #[test]
+    fn pml_damping_profile() {
+        let layer = PmlLayer::new(1.0, 1.5, 1.0, 1.0e-3);
+        assert_eq!(layer.damping(0.5, 0.5).0, 0.0);
+        let (zr, zt, e) = layer.damping(0.0, 1.5);
+        assert!((zr - layer.strength).abs() < 1.0e-12);
+        assert!((zt - layer.strength * 0.5 / 3.0 / 1.5).abs() < 1.0e-12);
+        assert!((e.y - 1.0).abs() < 1.0e-12);
+        // ∫ ζ_r dr = σ_max L / (p + 1) から理論反射率を戻す
+        let integral = layer.strength * 0.5 / 3.0;
+        assert!(((-2.0 * integral).exp() - 1.0e-3).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn absorbing_boundaries_reduce_reflection() {
+        let reflecting = reflection(Absorbing::Reflecting, 1.0);
+        let sommerfeld = reflection(Absorbing::Sommerfeld { curvature: 1.0 }, 1.0);
+        let pml = reflection(Absorbing::Pml(PmlLayer::new(1.0, 1.4, 1.0, 1.0e-3)), 1.4);
+        // 2 次元の波は尾を引くので、反射がなくても t = 2.5 で円内に 0.1 程度残る
+        // (半径 3 の反射壁で測ると 0.104)
+        assert!(reflecting > 0.9, "{}", reflecting);
+        assert!(sommerfeld < 0.3, "{}", sommerfeld);
+        assert!(pml < sommerfeld, "{} {}", pml, sommerfeld);
+    }
+}
@@ -0,0 +1,103 @@
+// 第 1 種 Bessel 関数 J_n と、その正の零点 (円形膜の固有振動の検証用)
+
+// Miller の後退漸化式 J_{k-1} = (2k / x) J_k - J_{k+1} を 1 = J_0 + 2 Σ J_{2k} で正規化する
+#[allow(dead_code)]
+pub fn bessel_j(n: usize, x: f64) -> f64 {
+    if x == 0.0 {
+        return if n == 0 { 1.0 } else { 0.0 };
+    }
+    if x < 0.0 {
+        let sign = if n.is_multiple_of(2) { 1.0 } else { -1.0 };
+        return sign * bessel_j(n, -x);
+    }
+    let top = n.max(x as usize);
+    let start = 2 * ((top + 20 + (40.0 * top as f64).sqrt() as usize) / 2);
+    let mut j_next = 0.0; // J_{k+1}
+    let mut j = 1.0e-30; // J_k
+    let mut sum = 0.0;
+    let mut result = 0.0;
+    for k in (1..(start + 1)).rev() {
+        let j_prev = 2.0 * k as f64 / x * j - j_next;
+        j_next = j;
+        j = j_prev;
+        // ここで j = J_{k-1}
+        if k - 1 == n {
+            result = j;
+        }
+        if k - 1 > 0 && (k - 1).is_multiple_of(2) {
+            sum += 2.0 * j;
+        }
+        if j.abs() > 1.0e250 {
+            j *= 1.0e-250;
+            j_next *= 1.0e-250;
+            sum *= 1.0e-250;
+            result *= 1.0e-250;
+        }
+    }
+    sum += j;
+    result / sum
+}
+
+// J_n の s 番目 (s = 1, 2, ...) の正の零点
+#[allow(dead_code)]
+pub fn bessel_zero(n: usize, s: usize) -> f64 {
+    assert!(s >= 1, "zeros are numbered from 1");
+    // 零点の間隔はおよそ π なので 0.1 刻みで符号変化を数える
+    let step = 0.1;
+    let mut count = 0;
+    let mut a = step;
+    let mut f_a = bessel_j(n, a);
+    loop {
+        let b = a + step;
+        let f_b = bessel_j(n, b);
+        if f_a * f_b < 0.0 {
+            count += 1;
+            if count == s {
+                return bisect(n, a, b, f_a);
+            }
+        }
+        a = b;
+        f_a = f_b;
+    }
+}
+
+fn bisect(n: usize, mut a: f64, mut b: f64, mut f_a: f64) -> f64 {
+    for _ in 0..100 {
+        let c = 0.5 * (a + b);
+        if c == a || c == b {
+            break;
+        }
+        let f_c = bessel_j(n, c);
+        if f_a * f_c <= 0.0 {
+            b = c;
+        } else {
+            a = c;
+            f_a = f_c;
+        }
+    }
+    0.5 * (a + b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values() {
+        assert!((bessel_j(0, 1.0) - 0.7651976865579666).abs() < 1.0e-14);
+        assert!((bessel_j(1, 2.5) - 0.4970941024642741).abs() < 1.0e-14);
+        assert!((bessel_j(2, 10.0) - 0.254630313685121).abs() < 1.0e-13);
+        assert!((bessel_j(5, 0.5) - 8.053627241357474e-6).abs() < 1.0e-18);
+        assert!((bessel_j(1, -2.5) + 0.4970941024642741).abs() < 1.0e-14);
+        assert_eq!(bessel_j(3, 0.0), 0.0);
+    }
+
+    #[test]
+    fn zeros() {
+        assert!((bessel_zero(0, 1) - 2.404825557695773).abs() < 1.0e-12);
+        assert!((bessel_zero(0, 2) - 5.520078110286311).abs() < 1.0e-12);
+        assert!((bessel_zero(1, 1) - 3.831705970207512).abs() < 1.0e-12);
+        assert!((bessel_zero(2, 1) - 5.135622301840683).abs() < 1.0e-12);
+        assert!((bessel_zero(3, 2) - 9.76102312998167).abs() < 1.0e-12);
+    }
+}
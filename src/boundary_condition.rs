@@ -0,0 +1,336 @@
+use crate::kd_tree::{Grid2D, Points2D};
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+
+// 境界上の値 g(x, y, t)
+pub type BoundaryFn = Rc<dyn Fn(f64, f64, f64) -> f64>;
+
+// 境界上の点 (x, y) がその区間に属するか
+pub type SegmentSelector = Rc<dyn Fn(f64, f64) -> bool>;
+
+#[allow(dead_code)]
+#[derive(Clone)]
+pub enum BoundaryCondition {
+    Dirichlet(BoundaryFn), // u = g
+    Neumann(BoundaryFn),   // ∂u/∂n = h
+    Robin {
+        alpha: f64,
+        beta: f64,
+        r: BoundaryFn, // α u + β ∂u/∂n = r
+    },
+}
+
+impl BoundaryCondition {
+    #[allow(dead_code)]
+    pub fn dirichlet_zero() -> Self {
+        BoundaryCondition::Dirichlet(Rc::new(|_, _, _| 0.0))
+    }
+
+    // 断熱壁・反射壁
+    #[allow(dead_code)]
+    pub fn insulated() -> Self {
+        BoundaryCondition::Neumann(Rc::new(|_, _, _| 0.0))
+    }
+
+    #[allow(dead_code)]
+    pub fn is_dirichlet(&self) -> bool {
+        matches!(self, BoundaryCondition::Dirichlet(_))
+    }
+
+    // Robin 形式 α u + β ∂u/∂n = r の (α, β, r)
+    #[allow(dead_code)]
+    pub fn coefficients(&self, x: f64, y: f64, t: f64) -> (f64, f64, f64) {
+        match self {
+            BoundaryCondition::Dirichlet(g) => (1.0, 0.0, g(x, y, t)),
+            BoundaryCondition::Neumann(h) => (0.0, 1.0, h(x, y, t)),
+            BoundaryCondition::Robin { alpha, beta, r } => (*alpha, *beta, r(x, y, t)),
+        }
+    }
+}
+
+impl fmt::Debug for BoundaryCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BoundaryCondition::Dirichlet(_) => write!(f, "Dirichlet"),
+            BoundaryCondition::Neumann(_) => write!(f, "Neumann"),
+            BoundaryCondition::Robin { alpha, beta, .. } => {
+                write!(f, "Robin {{ alpha: {}, beta: {} }}", alpha, beta)
+            }
+        }
+    }
+}
+
+// 境界の区間ごとの条件。selector が真になる最初の区間を使い、なければ default
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct BoundaryConditions {
+    pub segments: Vec<(SegmentSelector, BoundaryCondition)>,
+    pub default: BoundaryCondition,
+}
+
+impl BoundaryConditions {
+    #[allow(dead_code)]
+    pub fn new(default: BoundaryCondition) -> Self {
+        BoundaryConditions {
+            segments: vec![],
+            default,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn homogeneous_dirichlet() -> Self {
+        BoundaryConditions::new(BoundaryCondition::dirichlet_zero())
+    }
+
+    #[allow(dead_code)]
+    pub fn add_segment(
+        &mut self,
+        selector: impl Fn(f64, f64) -> bool + 'static,
+        condition: BoundaryCondition,
+    ) {
+        self.segments.push((Rc::new(selector), condition));
+    }
+
+    #[allow(dead_code)]
+    pub fn condition_at(&self, x: f64, y: f64) -> &BoundaryCondition {
+        self.segments
+            .iter()
+            .find(|(selector, _)| selector(x, y))
+            .map(|(_, condition)| condition)
+            .unwrap_or(&self.default)
+    }
+}
+
+impl fmt::Debug for BoundaryConditions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BoundaryConditions")
+            .field(
+                "segments",
+                &self.segments.iter().map(|s| &s.1).collect::<Vec<_>>(),
+            )
+            .field("default", &self.default)
+            .finish()
+    }
+}
+
+// 境界点ごとに表で与えた値。時刻方向は線形補間し、範囲外は端の値を使う
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundaryTable {
+    pub points: Points2D,
+    pub times: Vec<f64>,
+    pub values: Vec<Vec<f64>>, // values[時刻][点]
+}
+
+impl BoundaryTable {
+    #[allow(dead_code)]
+    pub fn new(points: Points2D, times: Vec<f64>, values: Vec<Vec<f64>>) -> Self {
+        assert!(!times.is_empty(), "boundary table has no time levels");
+        assert_eq!(times.len(), values.len());
+        assert!(
+            times.windows(2).all(|w| w[0] < w[1]),
+            "boundary table times must be strictly increasing"
+        );
+        for v in &values {
+            assert_eq!(v.len(), points.points.len());
+        }
+        BoundaryTable {
+            points,
+            times,
+            values,
+        }
+    }
+
+    // 境界点 k の時刻 t での値
+    #[allow(dead_code)]
+    pub fn value(&self, k: usize, t: f64) -> f64 {
+        let last = self.times.len() - 1;
+        if t <= self.times[0] {
+            return self.values[0][k];
+        }
+        if t >= self.times[last] {
+            return self.values[last][k];
+        }
+        let m = self.times.iter().position(|s| *s > t).unwrap();
+        let s = (t - self.times[m - 1]) / (self.times[m] - self.times[m - 1]);
+        (1.0 - s) * self.values[m - 1][k] + s * self.values[m][k]
+    }
+
+    // (x, y) に最も近い表の点の値
+    #[allow(dead_code)]
+    pub fn eval(&self, x: f64, y: f64, t: f64) -> f64 {
+        let q = Grid2D::new(x, y);
+        let k = (0..self.points.points.len())
+            .min_by(|a, b| {
+                let da = self.points.points[*a].distance_square(&q);
+                let db = self.points.points[*b].distance_square(&q);
+                da.total_cmp(&db)
+            })
+            .expect("boundary table has no points");
+        self.value(k, t)
+    }
+
+    #[allow(dead_code)]
+    pub fn into_fn(self) -> BoundaryFn {
+        Rc::new(move |x, y, t| self.eval(x, y, t))
+    }
+
+    // 1 行に "t x y g"。# 以降と空行は無視する。同じ t の行が 1 つの時刻で、
+    // どの時刻も同じ点を同じ順に並べる
+    #[allow(dead_code)]
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let invalid = |line: usize, msg: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("boundary table line {}: {}", line + 1, msg),
+            )
+        };
+        let mut points = Points2D::new();
+        let mut times: Vec<f64> = vec![];
+        let mut values: Vec<Vec<f64>> = vec![];
+        for (line, text) in text.lines().enumerate() {
+            let text = text.split('#').next().unwrap().trim();
+            if text.is_empty() {
+                continue;
+            }
+            let fields = text
+                .split_whitespace()
+                .map(|f| f.parse::<f64>())
+                .collect::<Result<Vec<f64>, _>>()
+                .map_err(|e| invalid(line, &e.to_string()))?;
+            if fields.len() != 4 {
+                return Err(invalid(line, "expected 4 columns: t x y g"));
+            }
+            if fields.iter().any(|f| !f.is_finite()) {
+                return Err(invalid(line, "values must be finite"));
+            }
+            let (t, x, y, g) = (fields[0], fields[1], fields[2], fields[3]);
+            if times.last() != Some(&t) {
+                if times.last().is_some_and(|s| t < *s) {
+                    return Err(invalid(line, "times must be strictly increasing"));
+                }
+                if let Some(v) = values.last() {
+                    if v.len() != points.points.len() {
+                        return Err(invalid(
+                            line,
+                            "time levels have different numbers of points",
+                        ));
+                    }
+                }
+                times.push(t);
+                values.push(vec![]);
+            }
+            let k = values.last().unwrap().len();
+            if times.len() == 1 {
+                points.push(x, y);
+            } else if k >= points.points.len() || points.points[k] != Grid2D::new(x, y) {
+                return Err(invalid(line, "point does not match the first time level"));
+            }
+            values.last_mut().unwrap().push(g);
+        }
+        if times.is_empty() {
+            return Err(invalid(0, "no data"));
+        }
+        if values.last().unwrap().len() != points.points.len() {
+            return Err(invalid(
+                text.lines().count() - 1,
+                "time levels have different numbers of points",
+            ));
+        }
+        Ok(BoundaryTable::new(points, times, values))
+    }
+
+    #[allow(dead_code)]
+    pub fn load(path: &str) -> io::Result<Self> {
+        BoundaryTable::parse(&std::fs::read_to_string(path)?)
+    }
+
+    #[allow(dead_code)]
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut text = String::new();
+        for (t, v) in self.times.iter().zip(self.values.iter()) {
+            for (p, g) in self.points.points.iter().zip(v.iter()) {
+                text.push_str(&format!("{:e} {:e} {:e} {:e}\n", t, p.x, p.y, g));
+            }
+        }
+        std::fs::write(path, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments() {
+        let mut bc = BoundaryConditions::homogeneous_dirichlet();
+        bc.add_segment(|_, y| y > 0.0, BoundaryCondition::insulated());
+        bc.add_segment(
+            |x, _| x > 0.0,
+            BoundaryCondition::Robin {
+                alpha: 2.0,
+                beta: 0.5,
+                r: Rc::new(|x, y, t| x + y + t),
+            },
+        );
+        assert_eq!(
+            bc.condition_at(0.0, 1.0).coefficients(0.0, 1.0, 0.0),
+            (0.0, 1.0, 0.0)
+        );
+        assert_eq!(
+            bc.condition_at(1.0, -0.5).coefficients(1.0, -0.5, 1.0),
+            (2.0, 0.5, 1.5)
+        );
+        assert!(bc.condition_at(-1.0, -0.5).is_dirichlet());
+    }
+
+    #[test]
+    fn table_interpolation() {
+        let mut points = Points2D::new();
+        points.push(1.0, 0.0);
+        points.push(-1.0, 0.0);
+        let table =
+            BoundaryTable::new(points, vec![0.0, 1.0], vec![vec![0.0, 1.0], vec![2.0, 3.0]]);
+        assert_eq!(table.value(0, 0.25), 0.5);
+        assert_eq!(table.value(1, -1.0), 1.0);
+        assert_eq!(table.value(1, 5.0), 3.0);
+        let g = table.into_fn();
+        assert_eq!(g(-0.9, 0.1, 0.5), 2.0);
+    }
+
+    #[test]
+    fn table_parse_and_save() {
+        let text = "# t x y g\n0 1 0 0.0\n0 0 1 1.0\n\n1 1 0 2.0\n1 0 1 3.0 # end\n";
+        let table = BoundaryTable::parse(text).unwrap();
+        assert_eq!(table.times, vec![0.0, 1.0]);
+        assert_eq!(table.values, vec![vec![0.0, 1.0], vec![2.0, 3.0]]);
+        let path = std::env::temp_dir().join("boundary_table_test.txt");
+        let path = path.to_str().unwrap();
+        table.save(path).unwrap();
+        assert_eq!(BoundaryTable::load(path).unwrap(), table);
+        std::fs::remove_file(path).unwrap();
+        assert!(BoundaryTable::parse("0 1 0\n").is_err());
+        assert!(BoundaryTable::parse("0 1 0 0\n1 0 1 0\n").is_err());
+        assert!(BoundaryTable::parse("0 1 0 0\n0 0 1 0\n1 1 0 0\n").is_err());
+    }
+
+    #[test]
+    fn table_parse_rejects_bad_data() {
+        for text in [
+            // NaN や無限大の値
+            "0 1 0 nan\n1 1 0 0\n",
+            "0 1 0 0\n0 inf 1 0\n",
+            // 同じ時刻が 2 回
+            "0 1 0 0\n1 1 0 1\n1 1 0 2\n",
+            "0 1 0 0\n1 1 0 1\n0 1 0 2\n",
+            // 時刻が減る
+            "1 1 0 0\n0 1 0 1\n",
+        ] {
+            let e = BoundaryTable::parse(text).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData, "{:?}", text);
+        }
+        let table = BoundaryTable::parse("0 1 0 0\n1 1 0 1\n").unwrap();
+        assert_eq!(table.eval(f64::NAN, 0.0, 0.5), 0.5);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::kd_tree::{Grid2D, Points2D};
+
+// 一様格子によるセル分割。半径 cell_size 以内の近傍探索が O(1) 個のセルで済む
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct CellList {
+    pub x_min: f64,
+    pub y_min: f64,
+    pub cell_size: f64,
+    pub nx: usize,
+    pub ny: usize,
+    pub cells: Vec<Vec<usize>>,
+}
+
+impl CellList {
+    #[allow(dead_code)]
+    pub fn new(points: &[Grid2D], cell_size: f64) -> Self {
+        let (mut x_min, mut y_min) = (f64::INFINITY, f64::INFINITY);
+        let (mut x_max, mut y_max) = (-f64::INFINITY, -f64::INFINITY);
+        for p in points {
+            x_min = x_min.min(p.x);
+            y_min = y_min.min(p.y);
+            x_max = x_max.max(p.x);
+            y_max = y_max.max(p.y);
+        }
+        if points.is_empty() {
+            (x_min, y_min, x_max, y_max) = (0.0, 0.0, 0.0, 0.0);
+        }
+        // 点数に比べてセルが多くなりすぎないようにする
+        let extent = (x_max - x_min).max(y_max - y_min);
+        let cell_size = cell_size.max(extent / (4.0 * (points.len() as f64).sqrt() + 1.0));
+        let nx = ((x_max - x_min) / cell_size) as usize + 1;
+        let ny = ((y_max - y_min) / cell_size) as usize + 1;
+        let mut list = CellList {
+            x_min,
+            y_min,
+            cell_size,
+            nx,
+            ny,
+            cells: vec![vec![]; nx * ny],
+        };
+        for (i, p) in points.iter().enumerate() {
+            let (cx, cy) = list.cell_of(p);
+            list.cells[cy * nx + cx].push(i);
+        }
+        list
+    }
+
+    fn cell_of(&self, p: &Grid2D) -> (usize, usize) {
+        let cx = ((p.x - self.x_min) / self.cell_size).max(0.0) as usize;
+        let cy = ((p.y - self.y_min) / self.cell_size).max(0.0) as usize;
+        (cx.min(self.nx - 1), cy.min(self.ny - 1))
+    }
+
+    // p から radius 以内の点の番号 (順不同)
+    #[allow(dead_code)]
+    pub fn neighbors_within(&self, points: &[Grid2D], p: &Grid2D, radius: f64) -> Vec<usize> {
+        let reach = (radius / self.cell_size).ceil() as usize;
+        let (cx, cy) = self.cell_of(p);
+        let r2 = radius * radius;
+        let mut near = vec![];
+        for j in cy.saturating_sub(reach)..(cy + reach + 1).min(self.ny) {
+            for i in cx.saturating_sub(reach)..(cx + reach + 1).min(self.nx) {
+                for k in &self.cells[j * self.nx + i] {
+                    if points[*k].distance_square(p) <= r2 {
+                        near.push(*k);
+                    }
+                }
+            }
+        }
+        near
+    }
+}
+
+// 打ち切り距離 + skin 以内の近傍リスト。どれかの点が skin / 2 より動いたら作り直す
+// 番号は内部点 0..n の後に境界点が続く
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct VerletList {
+    pub cutoff: f64,
+    pub skin: f64,
+    pub lists: Vec<Vec<usize>>, // 内部点ごと (自分自身は含まない)
+    pub rebuilds: usize,
+    reference: Vec<Grid2D>,
+}
+
+impl VerletList {
+    #[allow(dead_code)]
+    pub fn new(interior: &Points2D, boundary: &Points2D, cutoff: f64, skin: f64) -> Self {
+        let mut list = VerletList {
+            cutoff,
+            skin,
+            lists: vec![],
+            rebuilds: 0,
+            reference: vec![],
+        };
+        list.rebuild(interior, boundary);
+        list
+    }
+
+    #[allow(dead_code)]
+    pub fn rebuild(&mut self, interior: &Points2D, boundary: &Points2D) {
+        let all: Vec<Grid2D> = interior
+            .points
+            .iter()
+            .chain(boundary.points.iter())
+            .cloned()
+            .collect();
+        let radius = self.cutoff + self.skin;
+        let cells = CellList::new(&all, radius);
+        self.lists = interior
+            .points
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let mut near: Vec<usize> = cells
+                    .neighbors_within(&all, p, radius)
+                    .into_iter()
+                    .filter(|k| *k != i)
+                    .collect();
+                near.sort_unstable();
+                near
+            })
+            .collect();
+        self.reference = interior.points.clone();
+        self.rebuilds += 1;
+    }
+
+    // 必要なら作り直し、作り直したら true
+    #[allow(dead_code)]
+    pub fn update(&mut self, interior: &Points2D, boundary: &Points2D) -> bool {
+        let limit = 0.25 * self.skin * self.skin;
+        let moved = interior.points.len() != self.reference.len()
+            || interior
+                .points
+                .iter()
+                .zip(self.reference.iter())
+                .any(|(p, q)| p.distance_square(q) > limit);
+        if moved {
+            self.rebuild(interior, boundary);
+        }
+        moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_generator::halton;
+
+    fn cloud(n: usize) -> Points2D {
+        let mut points = Points2D::new();
+        for k in 1..=n {
+            points.push(halton(k, 2), halton(k, 3));
+        }
+        points
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let points = cloud(500);
+        let cells = CellList::new(&points.points, 0.07);
+        for p in points.points.iter().step_by(37) {
+            let mut near = cells.neighbors_within(&points.points, p, 0.07);
+            near.sort_unstable();
+            let brute: Vec<usize> = (0..points.points.len())
+                .filter(|k| points.points[*k].distance_square(p) <= 0.07 * 0.07)
+                .collect();
+            assert_eq!(near, brute);
+        }
+    }
+
+    #[test]
+    fn verlet_rebuild() {
+        let mut interior = cloud(200);
+        // 内部点 0 を境界点の近くに置く
+        interior.points[0] = Grid2D::new(0.05, 0.02);
+        let mut boundary = Points2D::new();
+        boundary.push(0.0, 0.0);
+        let mut list = VerletList::new(&interior, &boundary, 0.1, 0.04);
+        assert!(list.lists[0].iter().all(|k| *k != 0));
+        // 境界点は番号 200
+        assert!(list.lists[0].contains(&200));
+        assert!(!list.lists[1].contains(&200));
+        interior.points[3].x += 0.01;
+        assert!(!list.update(&interior, &boundary));
+        interior.points[3].x += 0.02;
+        assert!(list.update(&interior, &boundary));
+        assert_eq!(list.rebuilds, 2);
+        assert!(list.lists[0].contains(&200));
+    }
+}
@@ -0,0 +1,402 @@
+use crate::domain::Domain;
+use crate::kd_tree::{Grid2D, Points2D};
+use crate::two_variable_polynomial::TwoPolynomial;
+use crate::wave_eqation::WaveEq;
+use std::io;
+
+// WaveEq の途中状態を保存するバイナリ形式。数値はリトルエンディアンの u64 / f64 で、読み戻すと
+// ビット単位で一致する。境界条件と右辺は関数なので保存しない (読み込んだ後に設定し直す)
+pub const MAGIC: &[u8; 8] = b"WAVECKPT";
+pub const VERSION: u32 = 1;
+
+// 保存した状態とステップ数
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub wave: WaveEq,
+    pub step: usize,
+}
+
+impl Checkpoint {
+    #[allow(dead_code)]
+    pub fn new(wave: &WaveEq, step: usize) -> Self {
+        Checkpoint {
+            wave: wave.clone(),
+            step,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let wave = &self.wave;
+        let mut w = Writer(Vec::new());
+        w.0.extend_from_slice(MAGIC);
+        w.0.extend_from_slice(&VERSION.to_le_bytes());
+        w.usize(self.step);
+        w.f64(wave.time);
+        w.domain(&wave.domain);
+        w.points(&wave.interior);
+        w.points(&wave.boundary);
+        for values in [&wave.value, &wave.value_1, &wave.value_2, &wave.velocity] {
+            w.f64s(values);
+        }
+        for lists in [
+            &wave.near_points_boundary,
+            &wave.near_points_interior,
+            &wave.near,
+        ] {
+            w.usize(lists.len());
+            for list in lists {
+                w.usizes(list);
+            }
+        }
+        w.usize(wave.poly.len());
+        for p in &wave.poly {
+            w.usize(p.degree);
+            w.f64s(&p.two_poly);
+        }
+        w.0
+    }
+
+    #[allow(dead_code)]
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let mut r = Reader { bytes, pos: 0 };
+        if r.take(8)? != MAGIC {
+            return Err(invalid("not a WaveEq checkpoint"));
+        }
+        let version = u32::from_le_bytes(r.take(4)?.try_into().unwrap());
+        if version != VERSION {
+            return Err(invalid(&format!(
+                "checkpoint version {} is not supported (expected {})",
+                version, VERSION
+            )));
+        }
+        let step = r.usize()?;
+        let mut wave = WaveEq::new();
+        wave.time = r.f64()?;
+        wave.domain = r.domain()?;
+        wave.interior = r.points()?;
+        wave.boundary = r.points()?;
+        wave.value = r.f64s()?;
+        wave.value_1 = r.f64s()?;
+        wave.value_2 = r.f64s()?;
+        wave.velocity = r.f64s()?;
+        for lists in [
+            &mut wave.near_points_boundary,
+            &mut wave.near_points_interior,
+            &mut wave.near,
+        ] {
+            let n = r.usize()?;
+            for _ in 0..n {
+                lists.push(r.usizes()?);
+            }
+        }
+        let n = r.usize()?;
+        for _ in 0..n {
+            let degree = r.usize()?;
+            let two_poly = r.f64s()?;
+            if two_poly.len() != (degree + 1) * (degree + 1) {
+                return Err(invalid("polynomial coefficients do not match its degree"));
+            }
+            wave.poly.push(TwoPolynomial { two_poly, degree });
+        }
+        if r.pos != bytes.len() {
+            return Err(invalid("trailing data after checkpoint"));
+        }
+        check_sizes(&wave)?;
+        Ok(Checkpoint { wave, step })
+    }
+
+    #[allow(dead_code)]
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        // 書きかけのファイルで前の checkpoint を壊さないよう、一時ファイルから置き換える
+        let temporary = format!("{}.tmp", path);
+        std::fs::write(&temporary, self.to_bytes())?;
+        std::fs::rename(&temporary, path)
+    }
+
+    #[allow(dead_code)]
+    pub fn load(path: &str) -> io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Checkpoint::from_bytes(&bytes)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// 節点数と各配列・近傍リストの長さと番号の範囲が合っているか
+fn check_sizes(wave: &WaveEq) -> io::Result<()> {
+    let n = wave.interior.points.len();
+    let m = wave.boundary.points.len();
+    for (name, len) in [
+        ("value", wave.value.len()),
+        ("value_1", wave.value_1.len()),
+        ("value_2", wave.value_2.len()),
+    ] {
+        if len != n {
+            return Err(invalid(&format!(
+                "{} has {} entries for {} interior nodes",
+                name, len, n
+            )));
+        }
+    }
+    if !wave.velocity.is_empty() && wave.velocity.len() != n {
+        return Err(invalid("velocity does not match the interior nodes"));
+    }
+    for (name, lists, bound) in [
+        ("near_points_interior", &wave.near_points_interior, n),
+        ("near_points_boundary", &wave.near_points_boundary, m),
+        ("near", &wave.near, n),
+    ] {
+        if lists.iter().flatten().any(|j| *j >= bound) {
+            return Err(invalid(&format!("{} refers to a missing node", name)));
+        }
+    }
+    Ok(())
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn usize(&mut self, v: usize) {
+        self.0.extend_from_slice(&(v as u64).to_le_bytes());
+    }
+
+    fn f64(&mut self, v: f64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn f64s(&mut self, v: &[f64]) {
+        self.usize(v.len());
+        v.iter().for_each(|x| self.f64(*x));
+    }
+
+    fn usizes(&mut self, v: &[usize]) {
+        self.usize(v.len());
+        v.iter().for_each(|x| self.usize(*x));
+    }
+
+    fn points(&mut self, p: &Points2D) {
+        self.usize(p.points.len());
+        for q in &p.points {
+            self.f64(q.x);
+            self.f64(q.y);
+        }
+    }
+
+    // 先頭の 1 バイトで種類を区別する
+    fn domain(&mut self, d: &Domain) {
+        match d {
+            Domain::Circle { cx, cy, r } => {
+                self.0.push(0);
+                [cx, cy, r].iter().for_each(|v| self.f64(**v));
+            }
+            Domain::Ellipse { cx, cy, a, b } => {
+                self.0.push(1);
+                [cx, cy, a, b].iter().for_each(|v| self.f64(**v));
+            }
+            Domain::Rectangle {
+                x_min,
+                y_min,
+                x_max,
+                y_max,
+            } => {
+                self.0.push(2);
+                [x_min, y_min, x_max, y_max]
+                    .iter()
+                    .for_each(|v| self.f64(**v));
+            }
+            Domain::Polygon { vertices } => {
+                self.0.push(3);
+                self.points(&Points2D {
+                    points: vertices.clone(),
+                });
+            }
+            Domain::Union(a, b) => {
+                self.0.push(4);
+                self.domain(a);
+                self.domain(b);
+            }
+            Domain::Difference(a, b) => {
+                self.0.push(5);
+                self.domain(a);
+                self.domain(b);
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> io::Result<&[u8]> {
+        if self.bytes.len() - self.pos < n {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "checkpoint is truncated",
+            ));
+        }
+        self.pos += n;
+        Ok(&self.bytes[(self.pos - n)..self.pos])
+    }
+
+    fn usize(&mut self) -> io::Result<usize> {
+        let v = u64::from_le_bytes(self.take(8)?.try_into().unwrap());
+        // 壊れたファイルで巨大な確保をしないよう、残りのバイト数を超える長さは拒む
+        if v > self.bytes.len() as u64 * 8 {
+            return Err(invalid("length out of range"));
+        }
+        Ok(v as usize)
+    }
+
+    fn f64(&mut self) -> io::Result<f64> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f64s(&mut self) -> io::Result<Vec<f64>> {
+        let n = self.usize()?;
+        (0..n).map(|_| self.f64()).collect()
+    }
+
+    fn usizes(&mut self) -> io::Result<Vec<usize>> {
+        let n = self.usize()?;
+        (0..n).map(|_| self.usize()).collect()
+    }
+
+    fn points(&mut self) -> io::Result<Points2D> {
+        let n = self.usize()?;
+        let mut p = Points2D::new();
+        for _ in 0..n {
+            let x = self.f64()?;
+            let y = self.f64()?;
+            p.points.push(Grid2D::new(x, y));
+        }
+        Ok(p)
+    }
+
+    fn domain(&mut self) -> io::Result<Domain> {
+        let tag = self.take(1)?[0];
+        Ok(match tag {
+            0 => Domain::Circle {
+                cx: self.f64()?,
+                cy: self.f64()?,
+                r: self.f64()?,
+            },
+            1 => Domain::Ellipse {
+                cx: self.f64()?,
+                cy: self.f64()?,
+                a: self.f64()?,
+                b: self.f64()?,
+            },
+            2 => Domain::Rectangle {
+                x_min: self.f64()?,
+                y_min: self.f64()?,
+                x_max: self.f64()?,
+                y_max: self.f64()?,
+            },
+            3 => Domain::Polygon {
+                vertices: self.points()?.points,
+            },
+            4 => Domain::Union(Box::new(self.domain()?), Box::new(self.domain()?)),
+            5 => Domain::Difference(Box::new(self.domain()?), Box::new(self.domain()?)),
+            _ => return Err(invalid(&format!("unknown domain tag {}", tag))),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_generator::{NodeGenerator, NodeStrategy, Spacing};
+
+    fn small_wave() -> WaveEq {
+        let mut wave = WaveEq::new();
+        wave.create_with(&NodeGenerator::new(
+            Domain::Difference(
+                Box::new(Domain::unit_circle()),
+                Box::new(Domain::Circle {
+                    cx: 0.3,
+                    cy: 0.0,
+                    r: 0.2,
+                }),
+            ),
+            Spacing::Uniform(0.2),
+            NodeStrategy::HexLattice,
+        ));
+        wave.set_initial_condition_with(&|x, y| (-4.0 * (x * x + y * y)).exp(), &|x, _| x, 1.0e-3);
+        wave.set_boundary_near_points();
+        wave.set_interior_near_points(4);
+        wave.set_interior_near_points_plus_boundary();
+        wave.init_poly(2);
+        wave.set_poly(1.0e-4);
+        wave.time = 0.125;
+        wave
+    }
+
+    fn assert_same(a: &WaveEq, b: &WaveEq) {
+        assert_eq!(a.interior, b.interior);
+        assert_eq!(a.boundary, b.boundary);
+        assert_eq!(a.domain, b.domain);
+        assert_eq!(a.time.to_bits(), b.time.to_bits());
+        for (x, y) in [
+            (&a.value, &b.value),
+            (&a.value_1, &b.value_1),
+            (&a.value_2, &b.value_2),
+            (&a.velocity, &b.velocity),
+        ] {
+            assert_eq!(x, y);
+        }
+        assert_eq!(a.near_points_boundary, b.near_points_boundary);
+        assert_eq!(a.near_points_interior, b.near_points_interior);
+        assert_eq!(a.near, b.near);
+        assert_eq!(a.poly.len(), b.poly.len());
+        for (p, q) in a.poly.iter().zip(b.poly.iter()) {
+            assert_eq!((p.degree, &p.two_poly), (q.degree, &q.two_poly));
+        }
+    }
+
+    #[test]
+    fn round_trip_is_exact() {
+        let mut wave = small_wave();
+        let path = std::env::temp_dir().join("checkpoint_round_trip.bin");
+        let path = path.to_str().unwrap();
+        Checkpoint::new(&wave, 42).save(path).unwrap();
+        let restored = Checkpoint::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(restored.step, 42);
+        assert_same(&wave, &restored.wave);
+
+        // 続きを計算しても一致する
+        let mut restored = restored.wave;
+        wave.step(1.0e-4, 1.0e-3);
+        restored.step(1.0e-4, 1.0e-3);
+        assert_same(&wave, &restored);
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        let bytes = Checkpoint::new(&small_wave(), 3).to_bytes();
+        let err = Checkpoint::from_bytes(&bytes[..bytes.len() - 5]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut other = bytes.clone();
+        other[8] = 9;
+        let err = Checkpoint::from_bytes(&other).unwrap_err();
+        assert!(err.to_string().contains("version 9"), "{}", err);
+
+        let mut other = bytes.clone();
+        other[0] = b'X';
+        assert!(Checkpoint::from_bytes(&other).is_err());
+
+        let mut other = bytes;
+        other.push(0);
+        assert!(Checkpoint::from_bytes(&other).is_err());
+        assert!(Checkpoint::load("/nonexistent/checkpoint.bin").is_err());
+    }
+}
@@ -0,0 +1,501 @@
+use crate::bessel;
+use crate::domain::Domain;
+use crate::implicit::{ImplicitScheme, ImplicitStepper};
+use crate::kd_tree::Points2D;
+use crate::meshfree::MeshfreeNodes;
+use crate::node_generator::{NodeGenerator, NodeStrategy, Spacing};
+use crate::runge_kutta::{rk_step, RkScheme};
+use crate::steady::SteadySolver;
+use crate::stencil;
+use crate::stencil::DiffOperator;
+use crate::wave_eqation::WaveEq;
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+
+// 厳密解やソース項 f(x, y, t)
+pub type ExactFn = Rc<dyn Fn(f64, f64, f64) -> f64>;
+
+// 厳密解のわかっている問題
+#[allow(dead_code)]
+#[derive(Clone)]
+pub enum Problem {
+    // -Δu = f, 境界で u = exact
+    Poisson {
+        exact: ExactFn,
+        source: ExactFn,
+    },
+    // u_t = Δu + f, 境界で u = exact。Crank-Nicolson で final_time まで
+    Heat {
+        exact: ExactFn,
+        source: ExactFn,
+        final_time: f64,
+        dt: f64,
+    },
+    // Heat と同じ問題を WaveEq の多項式の当てはめと陽的 Euler 法 (WaveEq::step) で final_time まで。
+    // dt は拡散の CFL 的な目安 WaveEq::cfl_dt_diffusion までに縮める。tol は当てはめの許容誤差
+    WaveEqHeat {
+        exact: ExactFn,
+        source: ExactFn,
+        final_time: f64,
+        dt: f64,
+        tol: f64,
+    },
+    // 単位円の膜 u_tt = Δu の固有振動 J_m(k r) cos(m θ) cos(k t), k = j_{m,s}。RK4 で final_time まで
+    DrumMode {
+        m: usize,
+        s: usize,
+        final_time: f64,
+        dt: f64,
+    },
+}
+
+impl Problem {
+    // u = sin(πx) sin(πy) + x^3 y の -Δu = 2π^2 sin(πx) sin(πy) - 6xy
+    #[allow(dead_code)]
+    pub fn manufactured_poisson() -> Self {
+        let pi = std::f64::consts::PI;
+        Problem::Poisson {
+            exact: Rc::new(move |x, y, _| (pi * x).sin() * (pi * y).sin() + x * x * x * y),
+            source: Rc::new(move |x, y, _| {
+                2.0 * pi * pi * (pi * x).sin() * (pi * y).sin() - 6.0 * x * y
+            }),
+        }
+    }
+
+    // u = e^{-t} cos(x) sin(2y) + t x のとき f = u_t - Δu = 4 e^{-t} cos(x) sin(2y) + x
+    #[allow(dead_code)]
+    pub fn manufactured_heat(final_time: f64, dt: f64) -> Self {
+        Problem::Heat {
+            exact: Rc::new(|x, y, t| (-t).exp() * x.cos() * (2.0 * y).sin() + t * x),
+            source: Rc::new(|x, y, t| 4.0 * (-t).exp() * x.cos() * (2.0 * y).sin() + x),
+            final_time,
+            dt,
+        }
+    }
+
+    // manufactured_heat と同じ解を WaveEq で解く
+    #[allow(dead_code)]
+    pub fn manufactured_wave_eq_heat(final_time: f64, dt: f64, tol: f64) -> Self {
+        let Problem::Heat { exact, source, .. } = Problem::manufactured_heat(final_time, dt) else {
+            unreachable!()
+        };
+        Problem::WaveEqHeat {
+            exact,
+            source,
+            final_time,
+            dt,
+            tol,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn exact(&self, x: f64, y: f64, t: f64) -> f64 {
+        match self {
+            Problem::Poisson { exact, .. }
+            | Problem::Heat { exact, .. }
+            | Problem::WaveEqHeat { exact, .. } => exact(x, y, t),
+            Problem::DrumMode { m, s, .. } => {
+                let k = bessel::bessel_zero(*m, *s);
+                let r = (x * x + y * y).sqrt();
+                let theta = y.atan2(x);
+                bessel::bessel_j(*m, k * r) * (*m as f64 * theta).cos() * (k * t).cos()
+            }
+        }
+    }
+
+    // 誤差を測る時刻
+    #[allow(dead_code)]
+    pub fn final_time(&self) -> f64 {
+        match self {
+            Problem::Poisson { .. } => 0.0,
+            Problem::Heat { final_time, .. }
+            | Problem::WaveEqHeat { final_time, .. }
+            | Problem::DrumMode { final_time, .. } => *final_time,
+        }
+    }
+
+    // 節点上の数値解 (内部点、境界点の順) と、線形ソルバーが収束したか。
+    // ステンシルが退化していれば Err
+    #[allow(dead_code)]
+    pub fn solve(&self, mesh: &MeshfreeNodes) -> Result<(Vec<f64>, bool), String> {
+        let n = mesh.num_interior;
+        let laplacian = DiffOperator::laplacian();
+        match self {
+            Problem::Poisson { exact, source } => {
+                let solver = SteadySolver::new(mesh.clone());
+                let solution =
+                    solver.solve_poisson(&|x, y| source(x, y, 0.0), &|x, y| exact(x, y, 0.0))?;
+                Ok((solution.value, solution.converged))
+            }
+            Problem::Heat {
+                exact,
+                source,
+                final_time,
+                dt,
+            } => {
+                let (a_ii, a_ib) = mesh.interior_system(&laplacian)?;
+                let boundary = &mesh.nodes.points[n..];
+                let forcing = |t: f64| -> Vec<f64> {
+                    let g: Vec<f64> = boundary.iter().map(|p| exact(p.x, p.y, t)).collect();
+                    let lift = a_ib.mul_vec(&g);
+                    (0..n)
+                        .map(|i| {
+                            let p = mesh.point(i);
+                            lift[i] + source(p.x, p.y, t)
+                        })
+                        .collect()
+                };
+                let mut stepper = ImplicitStepper::new(a_ii, ImplicitScheme::CrankNicolson);
+                let mut u: Vec<f64> = (0..n)
+                    .map(|i| {
+                        let p = mesh.point(i);
+                        exact(p.x, p.y, 0.0)
+                    })
+                    .collect();
+                let steps = (final_time / dt).ceil().max(1.0) as usize;
+                let dt = final_time / steps as f64;
+                let mut converged = true;
+                for k in 0..steps {
+                    match stepper.step(&u, k as f64 * dt, dt, &forcing) {
+                        Ok(next) => u = next,
+                        Err(_) => {
+                            converged = false;
+                            break;
+                        }
+                    }
+                }
+                u.extend(boundary.iter().map(|p| exact(p.x, p.y, *final_time)));
+                Ok((u, converged))
+            }
+            Problem::WaveEqHeat {
+                exact,
+                source,
+                final_time,
+                dt,
+                tol,
+            } => {
+                let boundary = &mesh.nodes.points[n..];
+                let mut wave = WaveEq::new();
+                wave.interior.points = mesh.nodes.points[..n].to_vec();
+                wave.boundary.points = boundary.to_vec();
+                wave.set_boundary_near_points();
+                wave.set_interior_near_points(mesh.stencils.first().map_or(0, |s| s.len()));
+                wave.set_interior_near_points_plus_boundary();
+                wave.init_poly(mesh.degree);
+                let (g, f) = (exact.clone(), source.clone());
+                wave.set_dirichlet(move |x, y, t| g(x, y, t));
+                wave.set_source(move |x, y, t| f(x, y, t));
+                wave.set_initial_condition_with(&|x, y| exact(x, y, 0.0), &|_, _| 0.0, 0.0);
+                let stable = wave.cfl_dt_diffusion(1.0, 0.5);
+                let steps = (final_time / dt.min(stable)).ceil().max(1.0) as usize;
+                let dt = final_time / steps as f64;
+                for _ in 0..steps {
+                    wave.step(*tol, dt);
+                }
+                let mut u = wave.value;
+                u.extend(boundary.iter().map(|p| exact(p.x, p.y, *final_time)));
+                Ok((u, true))
+            }
+            Problem::DrumMode { final_time, dt, .. } => {
+                // (u, v) の 1 階系。境界は同次 Dirichlet
+                let (a_ii, _) = mesh.interior_system(&laplacian)?;
+                let tableau = RkScheme::Rk4.tableau();
+                let stable =
+                    RkScheme::Rk4.imaginary_stability_bound() / a_ii.spectral_radius(100).sqrt();
+                let steps = (final_time / dt.min(0.5 * stable)).ceil().max(1.0) as usize;
+                let dt = final_time / steps as f64;
+                let mut y: Vec<f64> = (0..n)
+                    .map(|i| {
+                        let p = mesh.point(i);
+                        self.exact(p.x, p.y, 0.0)
+                    })
+                    .collect();
+                y.extend(vec![0.0; n]);
+                let mut f = |y: &[f64], _t: f64| -> Vec<f64> {
+                    let mut dy = y[n..].to_vec();
+                    dy.extend(a_ii.mul_vec(&y[..n]));
+                    dy
+                };
+                for k in 0..steps {
+                    y = rk_step(&tableau, &y, k as f64 * dt, dt, &mut f).0;
+                }
+                y.truncate(n);
+                y.extend(vec![0.0; mesh.num_boundary()]);
+                Ok((y, true))
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Poisson { .. } => write!(f, "Poisson"),
+            Problem::Heat { final_time, dt, .. } => {
+                write!(f, "Heat {{ final_time: {}, dt: {} }}", final_time, dt)
+            }
+            Problem::WaveEqHeat {
+                final_time,
+                dt,
+                tol,
+                ..
+            } => write!(
+                f,
+                "WaveEqHeat {{ final_time: {}, dt: {}, tol: {} }}",
+                final_time, dt, tol
+            ),
+            Problem::DrumMode {
+                m,
+                s,
+                final_time,
+                dt,
+            } => write!(
+                f,
+                "DrumMode {{ m: {}, s: {}, final_time: {}, dt: {} }}",
+                m, s, final_time, dt
+            ),
+        }
+    }
+}
+
+// 1 回の計算の誤差
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConvergenceRecord {
+    pub num_interior: usize,
+    pub h: f64, // sqrt(面積 / 内部点数)
+    pub num_neighbor: usize,
+    pub degree: usize,
+    pub l2: f64, // 内部点での二乗平均
+    pub linf: f64,
+    pub converged: bool, // 線形ソルバーが収束し、解が有限
+}
+
+// (num_neighbor, degree) ごとの log(誤差) と log(h) の最小二乗の傾き。収束しなかった計算は除く
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObservedRate {
+    pub num_neighbor: usize,
+    pub degree: usize,
+    pub l2: f64,
+    pub linf: f64,
+}
+
+// 節点数・ステンシルの点数・次数を振った収束試験
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct ConvergenceStudy {
+    pub problem: Problem,
+    pub domain: Domain,
+    pub strategy: NodeStrategy,
+    pub node_counts: Vec<usize>, // 内部点数の目安
+    pub stencil_sizes: Vec<usize>,
+    pub degrees: Vec<usize>,
+}
+
+impl ConvergenceStudy {
+    #[allow(dead_code)]
+    pub fn new(problem: Problem) -> Self {
+        ConvergenceStudy {
+            problem,
+            domain: Domain::unit_circle(),
+            strategy: NodeStrategy::HexLattice,
+            node_counts: vec![250, 1000, 4000],
+            stencil_sizes: vec![12],
+            degrees: vec![2],
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn nodes(&self, count: usize) -> (Points2D, Points2D) {
+        let h = (self.domain.area() / count as f64).sqrt();
+        NodeGenerator::new(self.domain.clone(), Spacing::Uniform(h), self.strategy).generate()
+    }
+
+    // 単項式の数より少ないステンシルの組は飛ばす
+    #[allow(dead_code)]
+    pub fn run(&self) -> Vec<ConvergenceRecord> {
+        let mut records = vec![];
+        for count in &self.node_counts {
+            let (boundary, interior) = self.nodes(*count);
+            for degree in &self.degrees {
+                for num_neighbor in &self.stencil_sizes {
+                    if *num_neighbor < stencil::monomial_exponents(*degree).len() {
+                        continue;
+                    }
+                    let mesh = MeshfreeNodes::new(&interior, &boundary, *degree, *num_neighbor);
+                    records.push(self.measure(&mesh));
+                }
+            }
+        }
+        records
+    }
+
+    // 解けなかった計算は収束しなかった記録にする
+    #[allow(dead_code)]
+    pub fn measure(&self, mesh: &MeshfreeNodes) -> ConvergenceRecord {
+        let n = mesh.num_interior;
+        let (u, converged) = self
+            .problem
+            .solve(mesh)
+            .unwrap_or_else(|_| (vec![f64::NAN; mesh.num_nodes()], false));
+        let t = self.problem.final_time();
+        let errors: Vec<f64> = (0..n)
+            .map(|i| {
+                let p = mesh.point(i);
+                (u[i] - self.problem.exact(p.x, p.y, t)).abs()
+            })
+            .collect();
+        ConvergenceRecord {
+            num_interior: n,
+            h: (self.domain.area() / n as f64).sqrt(),
+            num_neighbor: mesh.stencils.first().map_or(0, |s| s.len()),
+            degree: mesh.degree,
+            l2: (errors.iter().map(|e| e * e).sum::<f64>() / n as f64).sqrt(),
+            linf: errors.iter().cloned().fold(0.0, f64::max),
+            converged: converged && u.iter().all(|v| v.is_finite()),
+        }
+    }
+}
+
+// log(y) = p log(x) + c の p
+#[allow(dead_code)]
+pub fn fit_rate(x: &[f64], y: &[f64]) -> f64 {
+    let lx: Vec<f64> = x.iter().map(|v| v.ln()).collect();
+    let ly: Vec<f64> = y.iter().map(|v| v.ln()).collect();
+    let m = lx.len() as f64;
+    let mx = lx.iter().sum::<f64>() / m;
+    let my = ly.iter().sum::<f64>() / m;
+    let sxy: f64 = lx
+        .iter()
+        .zip(ly.iter())
+        .map(|(a, b)| (a - mx) * (b - my))
+        .sum();
+    let sxx: f64 = lx.iter().map(|a| (a - mx) * (a - mx)).sum();
+    sxy / sxx
+}
+
+#[allow(dead_code)]
+pub fn observed_rates(records: &[ConvergenceRecord]) -> Vec<ObservedRate> {
+    let mut keys: Vec<(usize, usize)> =
+        records.iter().map(|r| (r.num_neighbor, r.degree)).collect();
+    keys.sort_unstable();
+    keys.dedup();
+    keys.into_iter()
+        .filter_map(|(num_neighbor, degree)| {
+            let group: Vec<&ConvergenceRecord> = records
+                .iter()
+                .filter(|r| r.num_neighbor == num_neighbor && r.degree == degree && r.converged)
+                .collect();
+            if group.len() < 2 {
+                return None;
+            }
+            let h: Vec<f64> = group.iter().map(|r| r.h).collect();
+            let l2: Vec<f64> = group.iter().map(|r| r.l2).collect();
+            let linf: Vec<f64> = group.iter().map(|r| r.linf).collect();
+            Some(ObservedRate {
+                num_neighbor,
+                degree,
+                l2: fit_rate(&h, &l2),
+                linf: fit_rate(&h, &linf),
+            })
+        })
+        .collect()
+}
+
+#[allow(dead_code)]
+pub fn records_to_csv(records: &[ConvergenceRecord]) -> String {
+    let mut text = String::from("num_interior,h,num_neighbor,degree,l2,linf,converged\n");
+    for r in records {
+        text.push_str(&format!(
+            "{},{:e},{},{},{:e},{:e},{}\n",
+            r.num_interior, r.h, r.num_neighbor, r.degree, r.l2, r.linf, r.converged
+        ));
+    }
+    text
+}
+
+#[allow(dead_code)]
+pub fn save_csv(path: &str, records: &[ConvergenceRecord]) -> io::Result<()> {
+    std::fs::write(path, records_to_csv(records))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_of_power_law() {
+        let h = [0.1, 0.05, 0.025];
+        let e: Vec<f64> = h.iter().map(|v| 3.0 * v * v).collect();
+        assert!((fit_rate(&h, &e) - 2.0).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn poisson_converges() {
+        let mut study = ConvergenceStudy::new(Problem::manufactured_poisson());
+        study.node_counts = vec![200, 800, 3200];
+        study.stencil_sizes = vec![5, 12];
+        let records = study.run();
+        // 5 点では 2 次の単項式 6 個に足りない
+        assert_eq!(records.len(), 3);
+        assert!(records.iter().all(|r| r.converged));
+        for w in records.windows(2) {
+            assert!(w[1].l2 < w[0].l2, "{:?}", records);
+        }
+        let rates = observed_rates(&records);
+        assert_eq!(rates.len(), 1);
+        assert!(rates[0].l2 > 1.5, "{:?}", rates);
+        let csv = records_to_csv(&records);
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.lines().nth(1).unwrap().contains(",12,2,"));
+    }
+
+    #[test]
+    fn heat_and_drum_mode() {
+        for problem in [
+            Problem::manufactured_heat(0.1, 1.0e-3),
+            Problem::DrumMode {
+                m: 1,
+                s: 1,
+                final_time: 0.1,
+                dt: 1.0e-3,
+            },
+        ] {
+            let mut study = ConvergenceStudy::new(problem.clone());
+            study.node_counts = vec![300, 1200];
+            let records = study.run();
+            assert!(records[1].l2 < records[0].l2, "{:?} {:?}", problem, records);
+            assert!(records[1].linf < 1.0e-2, "{:?} {:?}", problem, records);
+        }
+    }
+
+    #[test]
+    fn wave_eq_heat_converges() {
+        // WaveEq の当てはめは遅いので粗い 2 段階で 1 ステップだけ
+        let mut study =
+            ConvergenceStudy::new(Problem::manufactured_wave_eq_heat(1.0e-3, 1.0e-3, 1.0e-6));
+        study.node_counts = vec![30, 60];
+        let records = study.run();
+        assert!(records.iter().all(|r| r.converged));
+        let rates = observed_rates(&records);
+        assert!(rates[0].l2 > 1.5, "{:?}", records);
+    }
+
+    #[test]
+    fn drum_mode_is_exact_eigenfunction() {
+        // 境界で 0、時刻 π / k で符号が反転する
+        let problem = Problem::DrumMode {
+            m: 2,
+            s: 1,
+            final_time: 0.0,
+            dt: 0.0,
+        };
+        let k = bessel::bessel_zero(2, 1);
+        assert!(problem.exact(0.6, 0.8, 0.0).abs() < 1.0e-12);
+        let u0 = problem.exact(0.3, 0.1, 0.0);
+        let u1 = problem.exact(0.3, 0.1, std::f64::consts::PI / k);
+        assert!((u0 + u1).abs() < 1.0e-12);
+    }
+}
@@ -0,0 +1,382 @@
+// 小さな密行列用の補助関数（局所フィッティング用）
+
+// 部分ピボット付きガウス消去で a x = b を解く。特異なら None
+#[allow(dead_code)]
+pub fn solve(a: &[Vec<f64>], b: &[f64]) -> Option<Vec<f64>> {
+    let n = b.len();
+    let mut m: Vec<Vec<f64>> = a.to_vec();
+    let mut x = b.to_vec();
+    let scale = m
+        .iter()
+        .flat_map(|row| row.iter())
+        .fold(0.0_f64, |s, v| s.max(v.abs()));
+    if scale == 0.0 {
+        return None;
+    }
+    for k in 0..n {
+        let mut p = k;
+        for i in (k + 1)..n {
+            if m[i][k].abs() > m[p][k].abs() {
+                p = i;
+            }
+        }
+        if m[p][k].abs() < 1.0e-13 * scale {
+            return None;
+        }
+        m.swap(k, p);
+        x.swap(k, p);
+        let pivot = m[k].clone();
+        for i in (k + 1)..n {
+            let f = m[i][k] / pivot[k];
+            if f != 0.0 {
+                for (mij, pj) in m[i][k..].iter_mut().zip(pivot[k..].iter()) {
+                    *mij -= f * pj;
+                }
+                x[i] -= f * x[k];
+            }
+        }
+    }
+    for k in (0..n).rev() {
+        let mut t = x[k];
+        for j in (k + 1)..n {
+            t -= m[k][j] * x[j];
+        }
+        x[k] = t / m[k][k];
+    }
+    Some(x)
+}
+
+// 対称行列の固有値を巡回 Jacobi 法で求める (昇順)
+#[allow(dead_code)]
+pub fn symmetric_eigenvalues(a: &[Vec<f64>]) -> Vec<f64> {
+    let n = a.len();
+    let mut m: Vec<Vec<f64>> = a.to_vec();
+    for _sweep in 0..100 {
+        let off: f64 = (0..n)
+            .flat_map(|i| (0..n).filter(move |j| *j != i).map(move |j| (i, j)))
+            .map(|(i, j)| m[i][j] * m[i][j])
+            .sum();
+        let diag: f64 = (0..n).map(|i| m[i][i] * m[i][i]).sum();
+        if off <= 1.0e-30 * diag || off == 0.0 {
+            break;
+        }
+        for p in 0..n {
+            for q in (p + 1)..n {
+                if m[p][q] == 0.0 {
+                    continue;
+                }
+                let theta = (m[q][q] - m[p][p]) / (2.0 * m[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for row in m.iter_mut() {
+                    let (mp, mq) = (row[p], row[q]);
+                    row[p] = c * mp - s * mq;
+                    row[q] = s * mp + c * mq;
+                }
+                let (head, tail) = m.split_at_mut(q);
+                for (mp, mq) in head[p].iter_mut().zip(tail[0].iter_mut()) {
+                    let (a, b) = (*mp, *mq);
+                    *mp = c * a - s * b;
+                    *mq = s * a + c * b;
+                }
+            }
+        }
+    }
+    let mut lambda: Vec<f64> = (0..n).map(|i| m[i][i]).collect();
+    lambda.sort_by(|a, b| a.total_cmp(b));
+    lambda
+}
+
+// 一般の実正方行列の固有値 (re, im)。Hessenberg 形に直してから二重シフト QR 法
+#[allow(dead_code)]
+pub fn eigenvalues(a: &[Vec<f64>]) -> Vec<(f64, f64)> {
+    hessenberg_eigenvalues(&hessenberg(a))
+}
+
+// ピボット付きの消去による上 Hessenberg 形への相似変換
+#[allow(dead_code)]
+pub fn hessenberg(a: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let n = a.len();
+    let mut h: Vec<Vec<f64>> = a.to_vec();
+    for m in 1..n.saturating_sub(1) {
+        let mut x = 0.0_f64;
+        let mut p = m;
+        for (j, row) in h.iter().enumerate().skip(m) {
+            if row[m - 1].abs() > x.abs() {
+                x = row[m - 1];
+                p = j;
+            }
+        }
+        if p != m {
+            h.swap(p, m);
+            for row in h.iter_mut() {
+                row.swap(p, m);
+            }
+        }
+        if x != 0.0 {
+            for i in (m + 1)..n {
+                let y = h[i][m - 1] / x;
+                if y != 0.0 {
+                    h[i][m - 1] = 0.0;
+                    let (upper, lower) = h.split_at_mut(i);
+                    for (hij, hmj) in lower[0][m..].iter_mut().zip(upper[m][m..].iter()) {
+                        *hij -= y * hmj;
+                    }
+                    for row in h.iter_mut() {
+                        row[m] += y * row[i];
+                    }
+                }
+            }
+        }
+    }
+    h
+}
+
+// 上 Hessenberg 行列の固有値 (Numerical Recipes の hqr)。収束しなければ NaN を返す
+#[allow(dead_code)]
+pub fn hessenberg_eigenvalues(h: &[Vec<f64>]) -> Vec<(f64, f64)> {
+    let n = h.len();
+    let mut a: Vec<Vec<f64>> = h.to_vec();
+    let mut wr = vec![0.0; n];
+    let mut wi = vec![0.0; n];
+    let mut anorm = 0.0;
+    for (i, row) in a.iter().enumerate() {
+        for v in &row[i.saturating_sub(1)..] {
+            anorm += v.abs();
+        }
+    }
+    let mut nn = n as isize - 1;
+    let mut t = 0.0;
+    while nn >= 0 {
+        let mut its = 0;
+        loop {
+            let u = nn as usize;
+            let mut l = nn;
+            while l >= 1 {
+                let k = l as usize;
+                let mut s = a[k - 1][k - 1].abs() + a[k][k].abs();
+                if s == 0.0 {
+                    s = anorm;
+                }
+                if a[k][k - 1].abs() + s == s {
+                    a[k][k - 1] = 0.0;
+                    break;
+                }
+                l -= 1;
+            }
+            let mut x = a[u][u];
+            if l == nn {
+                wr[u] = x + t;
+                wi[u] = 0.0;
+                nn -= 1;
+            } else {
+                let mut y = a[u - 1][u - 1];
+                let mut w = a[u][u - 1] * a[u - 1][u];
+                if l == nn - 1 {
+                    let p = 0.5 * (y - x);
+                    let q = p * p + w;
+                    let z = q.abs().sqrt();
+                    x += t;
+                    if q >= 0.0 {
+                        let z = p + z.copysign(p);
+                        wr[u - 1] = x + z;
+                        wr[u] = if z != 0.0 { x - w / z } else { x + z };
+                        wi[u - 1] = 0.0;
+                        wi[u] = 0.0;
+                    } else {
+                        wr[u - 1] = x + p;
+                        wr[u] = x + p;
+                        wi[u - 1] = -z;
+                        wi[u] = z;
+                    }
+                    nn -= 2;
+                } else {
+                    if its == 60 {
+                        for k in 0..(u + 1) {
+                            wr[k] = f64::NAN;
+                            wi[k] = f64::NAN;
+                        }
+                        return wr.into_iter().zip(wi).collect();
+                    }
+                    // 例外シフト
+                    if its == 10 || its == 20 {
+                        t += x;
+                        for (i, row) in a.iter_mut().enumerate().take(u + 1) {
+                            row[i] -= x;
+                        }
+                        let s = a[u][u - 1].abs() + a[u - 1][u - 2].abs();
+                        x = 0.75 * s;
+                        y = x;
+                        w = -0.4375 * s * s;
+                    }
+                    its += 1;
+                    let l = l as usize;
+                    let mut m = u - 2;
+                    let (mut p, mut q, mut r);
+                    loop {
+                        let z = a[m][m];
+                        let rr = x - z;
+                        let ss = y - z;
+                        p = (rr * ss - w) / a[m + 1][m] + a[m][m + 1];
+                        q = a[m + 1][m + 1] - z - rr - ss;
+                        r = a[m + 2][m + 1];
+                        let s = p.abs() + q.abs() + r.abs();
+                        p /= s;
+                        q /= s;
+                        r /= s;
+                        if m == l {
+                            break;
+                        }
+                        let uu = a[m][m - 1].abs() * (q.abs() + r.abs());
+                        let vv =
+                            p.abs() * (a[m - 1][m - 1].abs() + z.abs() + a[m + 1][m + 1].abs());
+                        if uu + vv == vv {
+                            break;
+                        }
+                        m -= 1;
+                    }
+                    for i in (m + 2)..(u + 1) {
+                        a[i][i - 2] = 0.0;
+                        if i != m + 2 {
+                            a[i][i - 3] = 0.0;
+                        }
+                    }
+                    let mut xx = 0.0;
+                    for k in m..u {
+                        if k != m {
+                            p = a[k][k - 1];
+                            q = a[k + 1][k - 1];
+                            r = if k != u - 1 { a[k + 2][k - 1] } else { 0.0 };
+                            xx = p.abs() + q.abs() + r.abs();
+                            if xx != 0.0 {
+                                p /= xx;
+                                q /= xx;
+                                r /= xx;
+                            }
+                        }
+                        let s = (p * p + q * q + r * r).sqrt().copysign(p);
+                        if s != 0.0 {
+                            if k == m {
+                                if l != m {
+                                    a[k][k - 1] = -a[k][k - 1];
+                                }
+                            } else {
+                                a[k][k - 1] = -s * xx;
+                            }
+                            p += s;
+                            let (x1, y1, z1) = (p / s, q / s, r / s);
+                            q /= p;
+                            r /= p;
+                            let (upper, lower) = a.split_at_mut(k + 1);
+                            let (row_1, rest) = lower.split_at_mut(1);
+                            for j in k..(u + 1) {
+                                let mut pp = upper[k][j] + q * row_1[0][j];
+                                if k != u - 1 {
+                                    pp += r * rest[0][j];
+                                    rest[0][j] -= pp * z1;
+                                }
+                                row_1[0][j] -= pp * y1;
+                                upper[k][j] -= pp * x1;
+                            }
+                            let mmin = if u < k + 3 { u } else { k + 3 };
+                            for row in a.iter_mut().take(mmin + 1).skip(l) {
+                                let mut pp = x1 * row[k] + y1 * row[k + 1];
+                                if k != u - 1 {
+                                    pp += z1 * row[k + 2];
+                                    row[k + 2] -= pp * r;
+                                }
+                                row[k + 1] -= pp * q;
+                                row[k] -= pp;
+                            }
+                        }
+                    }
+                }
+            }
+            if nn < 0 || l >= nn - 1 {
+                break;
+            }
+        }
+    }
+    wr.into_iter().zip(wi).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solve_3x3() {
+        let a = vec![
+            vec![0.0, 2.0, 1.0],
+            vec![1.0, 1.0, 0.0],
+            vec![2.0, 0.0, 3.0],
+        ];
+        let x = solve(&a, &[7.0, 3.0, 11.0]).unwrap();
+        assert!((x[0] - 1.0).abs() < 1.0e-14);
+        assert!((x[1] - 2.0).abs() < 1.0e-14);
+        assert!((x[2] - 3.0).abs() < 1.0e-14);
+    }
+
+    #[test]
+    fn solve_singular() {
+        let a = vec![vec![1.0, 2.0], vec![2.0, 4.0]];
+        assert_eq!(solve(&a, &[1.0, 2.0]), None);
+    }
+
+    #[test]
+    fn eigenvalues_3x3() {
+        let a = vec![
+            vec![2.0, -1.0, 0.0],
+            vec![-1.0, 2.0, -1.0],
+            vec![0.0, -1.0, 2.0],
+        ];
+        let lambda = symmetric_eigenvalues(&a);
+        let r2 = 2.0_f64.sqrt();
+        for (l, e) in lambda.iter().zip([2.0 - r2, 2.0, 2.0 + r2]) {
+            assert!((l - e).abs() < 1.0e-13, "{} {}", l, e);
+        }
+    }
+
+    fn sorted(mut lambda: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+        lambda.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        lambda
+    }
+
+    #[test]
+    fn general_eigenvalues() {
+        let rotation = vec![vec![0.0, -2.0], vec![2.0, 0.0]];
+        let lambda = sorted(eigenvalues(&rotation));
+        assert!((lambda[0].1 + 2.0).abs() < 1.0e-14 && lambda[0].0.abs() < 1.0e-14);
+        assert!((lambda[1].1 - 2.0).abs() < 1.0e-14);
+        // (x - 1)(x - 2)(x - 3)(x^2 + 1) = x^5 - 6x^4 + 12x^3 - 12x^2 + 11x - 6 の同伴行列
+        let c = [-6.0, 11.0, -12.0, 12.0, -6.0];
+        let mut companion = vec![vec![0.0; 5]; 5];
+        for i in 1..5 {
+            companion[i][i - 1] = 1.0;
+        }
+        for i in 0..5 {
+            companion[i][4] = -c[i];
+        }
+        let lambda = sorted(eigenvalues(&companion));
+        let expected = [(0.0, -1.0), (0.0, 1.0), (1.0, 0.0), (2.0, 0.0), (3.0, 0.0)];
+        for (l, e) in lambda.iter().zip(expected.iter()) {
+            assert!(
+                (l.0 - e.0).abs() < 1.0e-10 && (l.1 - e.1).abs() < 1.0e-10,
+                "{:?}",
+                lambda
+            );
+        }
+        // 対称行列では Jacobi 法と一致する
+        let a = vec![
+            vec![4.0, 1.0, -2.0, 2.0],
+            vec![1.0, 2.0, 0.0, 1.0],
+            vec![-2.0, 0.0, 3.0, -2.0],
+            vec![2.0, 1.0, -2.0, -1.0],
+        ];
+        let lambda = sorted(eigenvalues(&a));
+        for (l, e) in lambda.iter().zip(symmetric_eigenvalues(&a).iter()) {
+            assert!((l.0 - e).abs() < 1.0e-12 && l.1 == 0.0, "{:?}", lambda);
+        }
+    }
+}
@@ -0,0 +1,369 @@
+use crate::domain::Domain;
+use crate::kd_tree;
+use crate::kd_tree::{Grid2D, Points2D};
+use crate::meshfree::MeshfreeNodes;
+use crate::stencil;
+use crate::stencil::DiffOperator;
+use crate::wave_eqation::WaveEq;
+use std::io;
+
+// 節点ごとの求積の重み。外接矩形上の格子点を最も近い節点に割り当てた Voronoi 面積を、
+// 合計が領域の面積になるよう正規化する
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quadrature {
+    pub weights: Vec<f64>,
+}
+
+impl Quadrature {
+    #[allow(dead_code)]
+    pub fn voronoi(domain: &Domain, nodes: &Points2D, resolution: usize) -> Self {
+        let tree = kd_tree::KDTree::construct_kd_tree(nodes);
+        let (x_min, y_min, x_max, y_max) = domain.bounding_box();
+        let m = resolution.max(1);
+        let mut count = vec![0usize; nodes.points.len()];
+        for i in 0..m {
+            for j in 0..m {
+                // セルの中心で数える
+                let x = x_min + (x_max - x_min) * (i as f64 + 0.5) / m as f64;
+                let y = y_min + (y_max - y_min) * (j as f64 + 0.5) / m as f64;
+                if domain.sdf(x, y) <= 0.0 {
+                    count[tree.nearest_neighbors(&Grid2D::new(x, y), 1)[0]] += 1;
+                }
+            }
+        }
+        let total: usize = count.iter().sum();
+        let area = domain.area();
+        Quadrature {
+            weights: count
+                .iter()
+                .map(|c| area * *c as f64 / total.max(1) as f64)
+                .collect(),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn integrate(&self, f: &[f64]) -> f64 {
+        self.weights.iter().zip(f.iter()).map(|(w, v)| w * v).sum()
+    }
+}
+
+// 時間発展の監視の設定
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostics {
+    pub wave_speed: f64,    // 位置エネルギー c^2 |∇u|^2 / 2 の c
+    pub blow_up: f64,       // max |u| がこれを超えたら打ち切る
+    pub use_velocity: bool, // u_t に velocity を使う (Runge-Kutta)。false なら後退差分
+    pub resolution: usize,  // 求積の重みを作る格子の一辺の分割数
+    pub degree: usize,      // 勾配のステンシル
+    pub num_neighbor: usize,
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Diagnostics {
+            wave_speed: 1.0,
+            blow_up: 1.0e6,
+            use_velocity: false,
+            resolution: 200,
+            degree: 2,
+            num_neighbor: 12,
+        }
+    }
+}
+
+// 1 ステップ分の積分量。熱方程式では mass と l2 を、波動方程式では energy を見る
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiagnosticRecord {
+    pub step: usize,
+    pub time: f64,
+    pub mass: f64,      // ∫ u
+    pub l2: f64,        // ∫ u^2 / 2
+    pub kinetic: f64,   // ∫ u_t^2 / 2
+    pub potential: f64, // ∫ c^2 |∇u|^2 / 2
+    pub max_abs: f64,
+}
+
+impl DiagnosticRecord {
+    #[allow(dead_code)]
+    pub fn energy(&self) -> f64 {
+        self.kinetic + self.potential
+    }
+}
+
+type Row = Vec<(usize, f64)>;
+
+// 節点集合を固定して各ステップの積分量を記録する
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct DiagnosticMonitor {
+    pub config: Diagnostics,
+    pub quadrature: Quadrature, // 内部点の後に境界点
+    pub records: Vec<DiagnosticRecord>,
+    gradient: Vec<(Row, Row)>,    // 全節点での (∂x, ∂y) の重み
+    nearest_interior: Vec<usize>, // 境界点ごとに最も近い内部点
+}
+
+impl DiagnosticMonitor {
+    #[allow(dead_code)]
+    pub fn new(wave: &WaveEq, config: Diagnostics) -> Result<Self, String> {
+        DiagnosticMonitor::from_nodes(&wave.domain, &wave.interior, &wave.boundary, config)
+    }
+
+    #[allow(dead_code)]
+    pub fn from_nodes(
+        domain: &Domain,
+        interior: &Points2D,
+        boundary: &Points2D,
+        config: Diagnostics,
+    ) -> Result<Self, String> {
+        let mesh = MeshfreeNodes::new(interior, boundary, config.degree, config.num_neighbor);
+        let quadrature = Quadrature::voronoi(domain, &mesh.nodes, config.resolution);
+        let stencils = mesh.stencils.iter().chain(mesh.boundary_stencils.iter());
+        let gradient = stencils
+            .enumerate()
+            .map(|(i, s)| {
+                let neighbors: Vec<Grid2D> =
+                    s.iter().map(|j| mesh.nodes.points[*j].clone()).collect();
+                let center = &mesh.nodes.points[i];
+                let row = |op: &DiffOperator| -> Result<Row, String> {
+                    let w = stencil::weights(center, &neighbors, mesh.degree, op)
+                        .ok_or_else(|| format!("degenerate gradient stencil at node {}", i))?;
+                    Ok(s.iter().cloned().zip(w).collect())
+                };
+                Ok((row(&DiffOperator::d_x())?, row(&DiffOperator::d_y())?))
+            })
+            .collect::<Result<_, String>>()?;
+        let tree = kd_tree::KDTree::construct_kd_tree(interior);
+        let nearest_interior = boundary
+            .points
+            .iter()
+            .map(|p| tree.nearest_neighbors(p, 1)[0])
+            .collect();
+        Ok(DiagnosticMonitor {
+            config,
+            quadrature,
+            records: vec![],
+            gradient,
+            nearest_interior,
+        })
+    }
+
+    // 積分を inside が真になる節点 (内部点の後に境界点の順) の分だけにする。吸収層の内側の
+    // エネルギーを測るときなどに使う
+    #[allow(dead_code)]
+    pub fn restrict_to(&mut self, nodes: &Points2D, inside: impl Fn(f64, f64) -> bool) {
+        for (w, p) in self.quadrature.weights.iter_mut().zip(nodes.points.iter()) {
+            if !inside(p.x, p.y) {
+                *w = 0.0;
+            }
+        }
+    }
+
+    // 内部点の値に境界値を続けた全節点の値。Dirichlet 以外の境界点は最も近い内部点の値で代用する
+    fn node_values(&self, wave: &WaveEq, interior: &[f64], t: f64) -> Vec<f64> {
+        let mut u = interior.to_vec();
+        for (k, g) in wave.boundary_values(t).iter().enumerate() {
+            u.push(if g.is_finite() {
+                *g
+            } else {
+                interior[self.nearest_interior[k]]
+            });
+        }
+        u
+    }
+
+    // 現在の value_1 (最新の解) について積分量を記録する。NaN や max |u| > blow_up なら Err
+    #[allow(dead_code)]
+    pub fn record(&mut self, wave: &WaveEq, dt: f64) -> Result<DiagnosticRecord, String> {
+        let t = wave.time;
+        let u = self.node_values(wave, &wave.value_1, t);
+        let u_t: Vec<f64> = if self.config.use_velocity && wave.velocity.len() == wave.value_1.len()
+        {
+            // Dirichlet 境界は g の後退差分、それ以外は最も近い内部点の速度
+            let n = wave.velocity.len();
+            let previous = wave.boundary_values(t - dt);
+            let mut v = wave.velocity.clone();
+            for (k, g) in previous.iter().enumerate() {
+                v.push(if g.is_finite() {
+                    (u[n + k] - g) / dt
+                } else {
+                    wave.velocity[self.nearest_interior[k]]
+                });
+            }
+            v
+        } else {
+            let previous = self.node_values(wave, &wave.value_2, t - dt);
+            u.iter()
+                .zip(previous.iter())
+                .map(|(a, b)| (a - b) / dt)
+                .collect()
+        };
+        self.record_values(t, &u, &u_t)
+    }
+
+    // 全節点 (内部点の後に境界点) の u と u_t から記録する
+    #[allow(dead_code)]
+    pub fn record_values(
+        &mut self,
+        t: f64,
+        u: &[f64],
+        u_t: &[f64],
+    ) -> Result<DiagnosticRecord, String> {
+        let max_abs = u.iter().fold(0.0_f64, |m, v| m.max(v.abs()));
+        if let Some(i) = u.iter().position(|v| !v.is_finite()) {
+            return Err(format!("non-finite value at node {} at t = {}", i, t));
+        }
+        if max_abs > self.config.blow_up {
+            return Err(format!(
+                "max |u| = {:e} exceeds {:e} at t = {}",
+                max_abs, self.config.blow_up, t
+            ));
+        }
+        let grad2: Vec<f64> = self
+            .gradient
+            .iter()
+            .map(|(dx, dy)| {
+                let ux: f64 = dx.iter().map(|(j, w)| w * u[*j]).sum();
+                let uy: f64 = dy.iter().map(|(j, w)| w * u[*j]).sum();
+                ux * ux + uy * uy
+            })
+            .collect();
+        let c2 = self.config.wave_speed * self.config.wave_speed;
+        let square = |f: &[f64]| f.iter().map(|v| v * v).collect::<Vec<f64>>();
+        let record = DiagnosticRecord {
+            step: self.records.len(),
+            time: t,
+            mass: self.quadrature.integrate(u),
+            l2: 0.5 * self.quadrature.integrate(&square(u)),
+            kinetic: 0.5 * self.quadrature.integrate(&square(u_t)),
+            potential: 0.5 * c2 * self.quadrature.integrate(&grad2),
+            max_abs,
+        };
+        self.records.push(record);
+        Ok(record)
+    }
+
+    // 最初の記録からのエネルギーの相対変化
+    #[allow(dead_code)]
+    pub fn energy_drift(&self) -> f64 {
+        match (self.records.first(), self.records.last()) {
+            (Some(first), Some(last)) if first.energy() != 0.0 => {
+                (last.energy() - first.energy()) / first.energy()
+            }
+            _ => 0.0,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn to_csv(&self) -> String {
+        let mut text = String::from("step,time,mass,l2,kinetic,potential,energy,max_abs\n");
+        for r in &self.records {
+            text.push_str(&format!(
+                "{},{:e},{:e},{:e},{:e},{:e},{:e},{:e}\n",
+                r.step,
+                r.time,
+                r.mass,
+                r.l2,
+                r.kinetic,
+                r.potential,
+                r.energy(),
+                r.max_abs
+            ));
+        }
+        text
+    }
+
+    #[allow(dead_code)]
+    pub fn save_csv(&self, path: &str) -> io::Result<()> {
+        std::fs::write(path, self.to_csv())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bessel;
+    use crate::node_generator::{NodeGenerator, NodeStrategy, Spacing};
+
+    fn disk(h: f64) -> WaveEq {
+        let mut wave = WaveEq::new();
+        wave.create_with(&NodeGenerator::new(
+            Domain::unit_circle(),
+            Spacing::Uniform(h),
+            NodeStrategy::HexLattice,
+        ));
+        wave
+    }
+
+    #[test]
+    fn quadrature_integrates_polynomials() {
+        let wave = disk(0.05);
+        let mut nodes = wave.interior.clone();
+        nodes.points.extend(wave.boundary.points.iter().cloned());
+        let q = Quadrature::voronoi(&wave.domain, &nodes, 400);
+        let pi = std::f64::consts::PI;
+        assert!((q.integrate(&vec![1.0; nodes.points.len()]) - pi).abs() < 1.0e-12);
+        let r2: Vec<f64> = nodes.points.iter().map(|p| p.x * p.x + p.y * p.y).collect();
+        assert!(
+            (q.integrate(&r2) - 0.5 * pi).abs() < 0.01 * pi,
+            "{}",
+            q.integrate(&r2)
+        );
+        let x: Vec<f64> = nodes.points.iter().map(|p| p.x).collect();
+        assert!(q.integrate(&x).abs() < 1.0e-3);
+    }
+
+    // 定在波 J_0(j r) cos(j t) のエネルギーは時間によらず π j^2 J_1(j)^2 / 2
+    #[test]
+    fn standing_wave_energy_is_constant() {
+        let mut wave = disk(0.04);
+        let j = bessel::bessel_zero(0, 1);
+        let dt = 1.0e-4;
+        let mode = |p: &Grid2D, t: f64| bessel::bessel_j(0, j * p.x.hypot(p.y)) * (j * t).cos();
+        let mut monitor = DiagnosticMonitor::new(&wave, Diagnostics::default()).unwrap();
+        let exact = 0.5 * std::f64::consts::PI * (j * bessel::bessel_j(1, j)).powi(2);
+        for t in [0.3, 0.7, 1.1] {
+            wave.time = t;
+            wave.value_1 = wave.interior.points.iter().map(|p| mode(p, t)).collect();
+            wave.value_2 = wave
+                .interior
+                .points
+                .iter()
+                .map(|p| mode(p, t - dt))
+                .collect();
+            let r = monitor.record(&wave, dt).unwrap();
+            assert!(
+                (r.energy() - exact).abs() < 0.02 * exact,
+                "{:?} {}",
+                r,
+                exact
+            );
+            assert!(r.max_abs <= 1.0);
+        }
+        assert!(monitor.energy_drift().abs() < 0.01);
+        let csv = monitor.to_csv();
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.starts_with("step,time,mass"));
+    }
+
+    #[test]
+    fn detects_blow_up() {
+        let mut wave = disk(0.1);
+        let n = wave.interior.points.len();
+        wave.value_1 = vec![1.0; n];
+        wave.value_2 = vec![1.0; n];
+        let mut monitor = DiagnosticMonitor::new(&wave, Diagnostics::default()).unwrap();
+        let r = monitor.record(&wave, 0.1).unwrap();
+        // 境界は 0 なので質量は面積より小さい
+        assert!(r.mass > 0.0 && r.mass < std::f64::consts::PI);
+        assert_eq!(r.kinetic, 0.0);
+        wave.value_1[3] = 1.0e7;
+        assert!(monitor.record(&wave, 0.1).unwrap_err().contains("exceeds"));
+        wave.value_1[3] = f64::NAN;
+        assert!(monitor.record(&wave, 0.1).unwrap_err().contains("node 3"));
+        assert_eq!(monitor.records.len(), 1);
+    }
+}
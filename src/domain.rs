@@ -0,0 +1,544 @@
+use crate::kd_tree::Grid2D;
+
+// 2 次元の計算領域。符号付き距離関数 sdf は内部で負
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Domain {
+    Circle {
+        cx: f64,
+        cy: f64,
+        r: f64,
+    },
+    Ellipse {
+        cx: f64,
+        cy: f64,
+        a: f64,
+        b: f64,
+    },
+    Rectangle {
+        x_min: f64,
+        y_min: f64,
+        x_max: f64,
+        y_max: f64,
+    },
+    Polygon {
+        vertices: Vec<Grid2D>, // 反時計回り
+    },
+    Union(Box<Domain>, Box<Domain>),
+    Difference(Box<Domain>, Box<Domain>),
+}
+
+impl Domain {
+    #[allow(dead_code)]
+    pub fn unit_circle() -> Self {
+        Domain::Circle {
+            cx: 0.0,
+            cy: 0.0,
+            r: 1.0,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn union(a: Domain, b: Domain) -> Self {
+        Domain::Union(Box::new(a), Box::new(b))
+    }
+
+    #[allow(dead_code)]
+    pub fn difference(a: Domain, b: Domain) -> Self {
+        Domain::Difference(Box::new(a), Box::new(b))
+    }
+
+    // 穴あき領域 (多重連結)
+    #[allow(dead_code)]
+    pub fn with_holes(outer: Domain, holes: Vec<Domain>) -> Self {
+        holes.into_iter().fold(outer, Domain::difference)
+    }
+
+    // Circle, Rectangle, Polygon 以外は近似 (符号と零点集合は正しい)
+    #[allow(dead_code)]
+    pub fn sdf(&self, x: f64, y: f64) -> f64 {
+        match self {
+            Domain::Circle { cx, cy, r } => ((x - cx) * (x - cx) + (y - cy) * (y - cy)).sqrt() - r,
+            Domain::Ellipse { cx, cy, a, b } => {
+                let u = (x - cx) / a;
+                let v = (y - cy) / b;
+                ((u * u + v * v).sqrt() - 1.0) * a.min(*b)
+            }
+            Domain::Rectangle {
+                x_min,
+                y_min,
+                x_max,
+                y_max,
+            } => {
+                let dx = (x_min - x).max(x - x_max);
+                let dy = (y_min - y).max(y - y_max);
+                let outside = (dx.max(0.0) * dx.max(0.0) + dy.max(0.0) * dy.max(0.0)).sqrt();
+                outside + dx.max(dy).min(0.0)
+            }
+            Domain::Polygon { vertices } => {
+                let n = vertices.len();
+                let mut d2 = f64::INFINITY;
+                let mut inside = false;
+                for i in 0..n {
+                    let p = &vertices[i];
+                    let q = &vertices[(i + 1) % n];
+                    let ex = q.x - p.x;
+                    let ey = q.y - p.y;
+                    let s =
+                        (((x - p.x) * ex + (y - p.y) * ey) / (ex * ex + ey * ey)).clamp(0.0, 1.0);
+                    let dx = x - (p.x + s * ex);
+                    let dy = y - (p.y + s * ey);
+                    d2 = d2.min(dx * dx + dy * dy);
+                    if (p.y > y) != (q.y > y) && x < p.x + (y - p.y) / (q.y - p.y) * ex {
+                        inside = !inside;
+                    }
+                }
+                if inside {
+                    -d2.sqrt()
+                } else {
+                    d2.sqrt()
+                }
+            }
+            Domain::Union(a, b) => a.sdf(x, y).min(b.sdf(x, y)),
+            Domain::Difference(a, b) => a.sdf(x, y).max(-b.sdf(x, y)),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        match self {
+            Domain::Circle { cx, cy, r } => (x - cx) * (x - cx) + (y - cy) * (y - cy) < r * r,
+            _ => self.sdf(x, y) < 0.0,
+        }
+    }
+
+    // 外向き単位法線 (sdf の勾配)
+    #[allow(dead_code)]
+    pub fn normal(&self, x: f64, y: f64) -> Grid2D {
+        let (nx, ny) = match self {
+            Domain::Circle { cx, cy, .. } => (x - cx, y - cy),
+            _ => {
+                let (x_min, y_min, x_max, y_max) = self.bounding_box();
+                let eps = 1.0e-7 * (x_max - x_min).max(y_max - y_min);
+                (
+                    self.sdf(x + eps, y) - self.sdf(x - eps, y),
+                    self.sdf(x, y + eps) - self.sdf(x, y - eps),
+                )
+            }
+        };
+        let norm = (nx * nx + ny * ny).sqrt();
+        if norm == 0.0 {
+            Grid2D::new(0.0, 0.0)
+        } else {
+            Grid2D::new(nx / norm, ny / norm)
+        }
+    }
+
+    // (x_min, y_min, x_max, y_max)
+    #[allow(dead_code)]
+    pub fn bounding_box(&self) -> (f64, f64, f64, f64) {
+        match self {
+            Domain::Circle { cx, cy, r } => (cx - r, cy - r, cx + r, cy + r),
+            Domain::Ellipse { cx, cy, a, b } => (cx - a, cy - b, cx + a, cy + b),
+            Domain::Rectangle {
+                x_min,
+                y_min,
+                x_max,
+                y_max,
+            } => (*x_min, *y_min, *x_max, *y_max),
+            Domain::Polygon { vertices } => vertices.iter().fold(
+                (f64::INFINITY, f64::INFINITY, -f64::INFINITY, -f64::INFINITY),
+                |b, p| (b.0.min(p.x), b.1.min(p.y), b.2.max(p.x), b.3.max(p.y)),
+            ),
+            Domain::Union(a, b) => {
+                let ba = a.bounding_box();
+                let bb = b.bounding_box();
+                (
+                    ba.0.min(bb.0),
+                    ba.1.min(bb.1),
+                    ba.2.max(bb.2),
+                    ba.3.max(bb.3),
+                )
+            }
+            Domain::Difference(a, _) => a.bounding_box(),
+        }
+    }
+
+    // 外接矩形の中心と半幅 (cx, cy, hx, hy)
+    #[allow(dead_code)]
+    pub fn center_and_half_width(&self) -> (f64, f64, f64, f64) {
+        match self {
+            Domain::Circle { cx, cy, r } => (*cx, *cy, *r, *r),
+            _ => {
+                let (x_min, y_min, x_max, y_max) = self.bounding_box();
+                (
+                    0.5 * (x_min + x_max),
+                    0.5 * (y_min + y_max),
+                    0.5 * (x_max - x_min),
+                    0.5 * (y_max - y_min),
+                )
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn area(&self) -> f64 {
+        match self {
+            Domain::Circle { r, .. } => std::f64::consts::PI * r * r,
+            Domain::Ellipse { a, b, .. } => std::f64::consts::PI * a * b,
+            Domain::Rectangle {
+                x_min,
+                y_min,
+                x_max,
+                y_max,
+            } => (x_max - x_min) * (y_max - y_min),
+            Domain::Polygon { vertices } => {
+                let n = vertices.len();
+                let mut s = 0.0;
+                for i in 0..n {
+                    let p = &vertices[i];
+                    let q = &vertices[(i + 1) % n];
+                    s += p.x * q.y - q.x * p.y;
+                }
+                0.5 * s.abs()
+            }
+            _ => {
+                // 外接矩形上の格子で数える
+                let m = 400;
+                let (x_min, y_min, x_max, y_max) = self.bounding_box();
+                let hx = (x_max - x_min) / m as f64;
+                let hy = (y_max - y_min) / m as f64;
+                let mut count = 0;
+                for i in 0..m {
+                    for j in 0..m {
+                        let x = x_min + (i as f64 + 0.5) * hx;
+                        let y = y_min + (j as f64 + 0.5) * hy;
+                        if self.contains(x, y) {
+                            count += 1;
+                        }
+                    }
+                }
+                count as f64 * hx * hy
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn perimeter(&self) -> f64 {
+        match self {
+            Domain::Circle { r, .. } => 2.0 * std::f64::consts::PI * r,
+            Domain::Ellipse { a, b, .. } => {
+                // Ramanujan の近似
+                let h = (a - b) * (a - b) / ((a + b) * (a + b));
+                std::f64::consts::PI * (a + b) * (1.0 + 3.0 * h / (10.0 + (4.0 - 3.0 * h).sqrt()))
+            }
+            Domain::Rectangle {
+                x_min,
+                y_min,
+                x_max,
+                y_max,
+            } => 2.0 * ((x_max - x_min) + (y_max - y_min)),
+            Domain::Polygon { vertices } => {
+                let n = vertices.len();
+                (0..n)
+                    .map(|i| vertices[i].distance_square(&vertices[(i + 1) % n]).sqrt())
+                    .sum()
+            }
+            _ => {
+                let pts = self.boundary_samples(4000);
+                let total = self.children_perimeter();
+                total * pts.len() as f64 / self.children_samples(4000) as f64
+            }
+        }
+    }
+
+    fn children_perimeter(&self) -> f64 {
+        match self {
+            Domain::Union(a, b) | Domain::Difference(a, b) => {
+                a.children_perimeter() + b.children_perimeter()
+            }
+            _ => self.perimeter(),
+        }
+    }
+
+    fn children_samples(&self, n: usize) -> usize {
+        match self {
+            Domain::Union(a, b) | Domain::Difference(a, b) => {
+                let (na, nb) = self.split_count(a, b, n);
+                a.children_samples(na) + b.children_samples(nb)
+            }
+            _ => n,
+        }
+    }
+
+    fn split_count(&self, a: &Domain, b: &Domain, n: usize) -> (usize, usize) {
+        let pa = a.children_perimeter();
+        let pb = b.children_perimeter();
+        let na = ((n as f64) * pa / (pa + pb)).round() as usize;
+        (na, n - na)
+    }
+
+    // 境界のパラメータ表示 s ∈ [0, 1) (単純な図形のみ)
+    #[allow(dead_code)]
+    pub fn boundary_point(&self, s: f64) -> Option<Grid2D> {
+        match self {
+            Domain::Union(_, _) | Domain::Difference(_, _) => None,
+            Domain::Circle { .. } | Domain::Ellipse { .. } => {
+                let theta = 2.0 * std::f64::consts::PI * s;
+                Some(self.ellipse_point(theta))
+            }
+            _ => {
+                let vertices = self.vertices();
+                let n = vertices.len();
+                let total = self.perimeter();
+                let mut target = s.rem_euclid(1.0) * total;
+                for i in 0..n {
+                    let p = &vertices[i];
+                    let q = &vertices[(i + 1) % n];
+                    let len = p.distance_square(q).sqrt();
+                    if target <= len || i == n - 1 {
+                        let t = (target / len).min(1.0);
+                        return Some(Grid2D::new(p.x + t * (q.x - p.x), p.y + t * (q.y - p.y)));
+                    }
+                    target -= len;
+                }
+                None
+            }
+        }
+    }
+
+    fn ellipse_point(&self, theta: f64) -> Grid2D {
+        match self {
+            Domain::Circle { cx, cy, r } => Grid2D::new(cx + r * theta.cos(), cy + r * theta.sin()),
+            Domain::Ellipse { cx, cy, a, b } => {
+                Grid2D::new(cx + a * theta.cos(), cy + b * theta.sin())
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn vertices(&self) -> Vec<Grid2D> {
+        match self {
+            Domain::Rectangle {
+                x_min,
+                y_min,
+                x_max,
+                y_max,
+            } => vec![
+                Grid2D::new(*x_min, *y_min),
+                Grid2D::new(*x_max, *y_min),
+                Grid2D::new(*x_max, *y_max),
+                Grid2D::new(*x_min, *y_max),
+            ],
+            Domain::Polygon { vertices } => vertices.clone(),
+            _ => vec![],
+        }
+    }
+
+    // 境界上のおよそ n 点。CSG では合成後の境界に残る点だけを返す
+    #[allow(dead_code)]
+    pub fn boundary_samples(&self, n: usize) -> Vec<Grid2D> {
+        match self {
+            Domain::Circle { .. } | Domain::Ellipse { .. } => (0..n)
+                .map(|i| self.ellipse_point(2.0 * std::f64::consts::PI * i as f64 / n as f64))
+                .collect(),
+            Domain::Rectangle { .. } | Domain::Polygon { .. } => (0..n)
+                .filter_map(|i| self.boundary_point(i as f64 / n as f64))
+                .collect(),
+            Domain::Union(a, b) => {
+                let (na, nb) = self.split_count(a, b, n);
+                let mut pts: Vec<Grid2D> = a
+                    .boundary_samples(na)
+                    .into_iter()
+                    .filter(|p| !b.contains(p.x, p.y) && b.sdf(p.x, p.y) > 1.0e-12)
+                    .collect();
+                pts.extend(
+                    b.boundary_samples(nb)
+                        .into_iter()
+                        .filter(|p| !a.contains(p.x, p.y) && a.sdf(p.x, p.y) > 1.0e-12),
+                );
+                pts
+            }
+            Domain::Difference(a, b) => {
+                let (na, nb) = self.split_count(a, b, n);
+                let mut pts: Vec<Grid2D> = a
+                    .boundary_samples(na)
+                    .into_iter()
+                    .filter(|p| b.sdf(p.x, p.y) > 1.0e-12)
+                    .collect();
+                pts.extend(
+                    b.boundary_samples(nb)
+                        .into_iter()
+                        .filter(|p| a.sdf(p.x, p.y) < -1.0e-12),
+                );
+                pts
+            }
+        }
+    }
+
+    // 外に出た点を領域内へ戻す
+    #[allow(dead_code)]
+    pub fn pull_inside(&self, x: f64, y: f64) -> (f64, f64) {
+        match self {
+            Domain::Circle { cx, cy, .. } => {
+                // 中心まわりに 90 度回して半分に縮める
+                let mut x = x;
+                let mut y = y;
+                loop {
+                    let tmp = x;
+                    x = cx - 0.5 * (y - cy);
+                    y = cy + 0.5 * (tmp - cx);
+                    if self.contains(x, y) {
+                        return (x, y);
+                    }
+                }
+            }
+            _ => {
+                let (x_min, y_min, x_max, y_max) = self.bounding_box();
+                let margin = 1.0e-3 * (x_max - x_min).max(y_max - y_min);
+                let mut x = x;
+                let mut y = y;
+                for _ in 0..20 {
+                    let d = self.sdf(x, y);
+                    if d < 0.0 {
+                        break;
+                    }
+                    let n = self.normal(x, y);
+                    x -= (d + margin) * n.x;
+                    y -= (d + margin) * n.y;
+                }
+                (x, y)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shapes() -> Vec<Domain> {
+        vec![
+            Domain::unit_circle(),
+            Domain::Ellipse {
+                cx: 0.1,
+                cy: -0.2,
+                a: 1.5,
+                b: 0.7,
+            },
+            Domain::Rectangle {
+                x_min: -1.0,
+                y_min: -0.5,
+                x_max: 2.0,
+                y_max: 0.5,
+            },
+            Domain::Polygon {
+                vertices: vec![
+                    Grid2D::new(0.0, 0.0),
+                    Grid2D::new(2.0, 0.0),
+                    Grid2D::new(2.0, 1.0),
+                    Grid2D::new(1.0, 2.0),
+                    Grid2D::new(0.0, 1.0),
+                ],
+            },
+            Domain::with_holes(
+                Domain::unit_circle(),
+                vec![Domain::Circle {
+                    cx: 0.0,
+                    cy: 0.0,
+                    r: 0.4,
+                }],
+            ),
+            Domain::union(
+                Domain::Rectangle {
+                    x_min: -1.0,
+                    y_min: -0.25,
+                    x_max: 1.0,
+                    y_max: 0.25,
+                },
+                Domain::Circle {
+                    cx: 1.0,
+                    cy: 0.0,
+                    r: 0.5,
+                },
+            ),
+        ]
+    }
+
+    #[test]
+    fn sdf_signs() {
+        let c = Domain::unit_circle();
+        assert_eq!(c.sdf(0.0, 0.0), -1.0);
+        assert_eq!(c.sdf(2.0, 0.0), 1.0);
+        let r = &shapes()[2];
+        assert_eq!(r.sdf(0.0, 0.0), -0.5);
+        assert_eq!(r.sdf(3.0, 0.0), 1.0);
+        let p = &shapes()[3];
+        assert!(p.contains(1.0, 1.5));
+        assert!(!p.contains(0.2, 1.9));
+        assert!((p.sdf(1.0, -0.5) - 0.5).abs() < 1.0e-14);
+        let annulus = &shapes()[4];
+        assert!(!annulus.contains(0.0, 0.0));
+        assert!(annulus.contains(0.7, 0.0));
+        assert!(!annulus.contains(1.1, 0.0));
+    }
+
+    #[test]
+    fn boundary_samples_lie_on_boundary() {
+        for d in shapes() {
+            let pts = d.boundary_samples(200);
+            assert!(pts.len() > 50, "{:?}", d);
+            for p in pts {
+                assert!(d.sdf(p.x, p.y).abs() < 1.0e-9, "{:?} {:?}", d, p);
+            }
+        }
+    }
+
+    #[test]
+    fn normals_point_outward() {
+        for d in shapes() {
+            for p in d.boundary_samples(100) {
+                let n = d.normal(p.x, p.y);
+                assert!((n.x * n.x + n.y * n.y - 1.0).abs() < 1.0e-9);
+                assert!(!d.contains(p.x + 1.0e-3 * n.x, p.y + 1.0e-3 * n.y));
+                assert!(
+                    d.contains(p.x - 1.0e-3 * n.x, p.y - 1.0e-3 * n.y),
+                    "{:?} {:?}",
+                    d,
+                    p
+                );
+            }
+        }
+        // 穴の境界では穴の中心向き
+        let n = shapes()[4].normal(0.4, 0.0);
+        assert!((n.x + 1.0).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn area_and_perimeter() {
+        let d = shapes();
+        assert!((d[0].area() - std::f64::consts::PI).abs() < 1.0e-14);
+        assert_eq!(d[2].area(), 3.0);
+        assert_eq!(d[3].area(), 3.0);
+        let annulus = std::f64::consts::PI * (1.0 - 0.16);
+        assert!((d[4].area() - annulus).abs() < 1.0e-2);
+        let perimeter = 2.0 * std::f64::consts::PI * 1.4;
+        assert!((d[4].perimeter() - perimeter).abs() < 1.0e-2);
+        assert_eq!(d[2].perimeter(), 8.0);
+    }
+
+    #[test]
+    fn pull_inside() {
+        for d in shapes() {
+            let (x_min, y_min, x_max, y_max) = d.bounding_box();
+            for (x, y) in [
+                (x_max + 0.1, 0.0),
+                (0.0, y_min - 0.2),
+                (x_min - 0.05, y_max),
+            ] {
+                let (px, py) = d.pull_inside(x, y);
+                assert!(d.contains(px, py), "{:?} {} {}", d, px, py);
+            }
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use crate::kd_tree_3d::Points3D;
+use crate::point::Point3;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+// 3 次元の計算領域。符号付き距離関数 sdf は内部で負
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Domain3D {
+    Ball {
+        cx: f64,
+        cy: f64,
+        cz: f64,
+        r: f64,
+    },
+    Cuboid {
+        x_min: f64,
+        y_min: f64,
+        z_min: f64,
+        x_max: f64,
+        y_max: f64,
+        z_max: f64,
+    },
+    // z 軸に平行な円柱
+    Cylinder {
+        cx: f64,
+        cy: f64,
+        r: f64,
+        z_min: f64,
+        z_max: f64,
+    },
+    Union(Box<Domain3D>, Box<Domain3D>),
+    Difference(Box<Domain3D>, Box<Domain3D>),
+}
+
+impl Domain3D {
+    #[allow(dead_code)]
+    pub fn unit_ball() -> Self {
+        Domain3D::Ball {
+            cx: 0.0,
+            cy: 0.0,
+            cz: 0.0,
+            r: 1.0,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn union(a: Domain3D, b: Domain3D) -> Self {
+        Domain3D::Union(Box::new(a), Box::new(b))
+    }
+
+    #[allow(dead_code)]
+    pub fn difference(a: Domain3D, b: Domain3D) -> Self {
+        Domain3D::Difference(Box::new(a), Box::new(b))
+    }
+
+    // Ball, Cuboid, Cylinder は厳密。Union, Difference は符号と零点集合が正しい近似
+    #[allow(dead_code)]
+    pub fn sdf(&self, x: f64, y: f64, z: f64) -> f64 {
+        match self {
+            Domain3D::Ball { cx, cy, cz, r } => {
+                ((x - cx) * (x - cx) + (y - cy) * (y - cy) + (z - cz) * (z - cz)).sqrt() - r
+            }
+            Domain3D::Cuboid {
+                x_min,
+                y_min,
+                z_min,
+                x_max,
+                y_max,
+                z_max,
+            } => {
+                let d = [
+                    (x_min - x).max(x - x_max),
+                    (y_min - y).max(y - y_max),
+                    (z_min - z).max(z - z_max),
+                ];
+                let outside = d.iter().map(|v| v.max(0.0).powi(2)).sum::<f64>().sqrt();
+                outside + d[0].max(d[1]).max(d[2]).min(0.0)
+            }
+            Domain3D::Cylinder {
+                cx,
+                cy,
+                r,
+                z_min,
+                z_max,
+            } => {
+                let dr = ((x - cx) * (x - cx) + (y - cy) * (y - cy)).sqrt() - r;
+                let dz = (z_min - z).max(z - z_max);
+                let outside = (dr.max(0.0).powi(2) + dz.max(0.0).powi(2)).sqrt();
+                outside + dr.max(dz).min(0.0)
+            }
+            Domain3D::Union(a, b) => a.sdf(x, y, z).min(b.sdf(x, y, z)),
+            Domain3D::Difference(a, b) => a.sdf(x, y, z).max(-b.sdf(x, y, z)),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn contains(&self, x: f64, y: f64, z: f64) -> bool {
+        self.sdf(x, y, z) < 0.0
+    }
+
+    // 外向き単位法線 (sdf の勾配)
+    #[allow(dead_code)]
+    pub fn normal(&self, x: f64, y: f64, z: f64) -> Point3 {
+        let (nx, ny, nz) = match self {
+            Domain3D::Ball { cx, cy, cz, .. } => (x - cx, y - cy, z - cz),
+            _ => {
+                let eps = 1.0e-7 * self.diameter();
+                (
+                    self.sdf(x + eps, y, z) - self.sdf(x - eps, y, z),
+                    self.sdf(x, y + eps, z) - self.sdf(x, y - eps, z),
+                    self.sdf(x, y, z + eps) - self.sdf(x, y, z - eps),
+                )
+            }
+        };
+        let norm = (nx * nx + ny * ny + nz * nz).sqrt();
+        if norm == 0.0 {
+            Point3::new(0.0, 0.0, 0.0)
+        } else {
+            Point3::new(nx / norm, ny / norm, nz / norm)
+        }
+    }
+
+    // (最小の角, 最大の角)
+    #[allow(dead_code)]
+    pub fn bounding_box(&self) -> (Point3, Point3) {
+        match self {
+            Domain3D::Ball { cx, cy, cz, r } => (
+                Point3::new(cx - r, cy - r, cz - r),
+                Point3::new(cx + r, cy + r, cz + r),
+            ),
+            Domain3D::Cuboid {
+                x_min,
+                y_min,
+                z_min,
+                x_max,
+                y_max,
+                z_max,
+            } => (
+                Point3::new(*x_min, *y_min, *z_min),
+                Point3::new(*x_max, *y_max, *z_max),
+            ),
+            Domain3D::Cylinder {
+                cx,
+                cy,
+                r,
+                z_min,
+                z_max,
+            } => (
+                Point3::new(cx - r, cy - r, *z_min),
+                Point3::new(cx + r, cy + r, *z_max),
+            ),
+            Domain3D::Union(a, b) => {
+                let (a_min, a_max) = a.bounding_box();
+                let (b_min, b_max) = b.bounding_box();
+                (
+                    Point3::new(
+                        a_min.x.min(b_min.x),
+                        a_min.y.min(b_min.y),
+                        a_min.z.min(b_min.z),
+                    ),
+                    Point3::new(
+                        a_max.x.max(b_max.x),
+                        a_max.y.max(b_max.y),
+                        a_max.z.max(b_max.z),
+                    ),
+                )
+            }
+            Domain3D::Difference(a, _) => a.bounding_box(),
+        }
+    }
+
+    fn diameter(&self) -> f64 {
+        let (lo, hi) = self.bounding_box();
+        (hi.x - lo.x).max(hi.y - lo.y).max(hi.z - lo.z)
+    }
+
+    // 基本形状は厳密、組み合わせは格子点の数え上げ
+    #[allow(dead_code)]
+    pub fn volume(&self) -> f64 {
+        match self {
+            Domain3D::Ball { r, .. } => 4.0 / 3.0 * PI * r * r * r,
+            Domain3D::Cuboid {
+                x_min,
+                y_min,
+                z_min,
+                x_max,
+                y_max,
+                z_max,
+            } => (x_max - x_min) * (y_max - y_min) * (z_max - z_min),
+            Domain3D::Cylinder {
+                r, z_min, z_max, ..
+            } => PI * r * r * (z_max - z_min),
+            _ => {
+                let h = self.diameter() / 100.0;
+                lattice(self, h).len() as f64 * h * h * h
+            }
+        }
+    }
+
+    // 間隔 h の節点 (boundary, interior)。
+    // 球面は Fibonacci 点、それ以外は境界近くの格子点を sdf で面に射影してから間引く。
+    // 内部点は境界から 0.5 h 以上離れた立方格子点
+    #[allow(dead_code)]
+    pub fn generate_nodes(&self, h: f64) -> (Points3D, Points3D) {
+        let boundary = match self {
+            Domain3D::Ball { cx, cy, cz, r } => {
+                // 六方格子と同じ面密度
+                let n = (4.0 * PI * r * r / (h * h) * 2.0 / 3.0_f64.sqrt()).round() as usize;
+                fibonacci_sphere(n)
+                    .into_iter()
+                    .map(|p| Point3::new(cx + r * p.x, cy + r * p.y, cz + r * p.z))
+                    .collect()
+            }
+            _ => self.project_to_surface(h),
+        };
+        let interior = lattice(self, h)
+            .into_iter()
+            .filter(|p| self.sdf(p.x, p.y, p.z) < -0.5 * h)
+            .collect();
+        (Points3D { points: boundary }, Points3D { points: interior })
+    }
+
+    fn project_to_surface(&self, h: f64) -> Vec<Point3> {
+        let fine = 0.5 * h;
+        let mut projected = vec![];
+        for p in lattice_in_box(&self.bounding_box(), fine, fine) {
+            if self.sdf(p.x, p.y, p.z).abs() >= fine {
+                continue;
+            }
+            let mut q = p;
+            for _ in 0..5 {
+                let d = self.sdf(q.x, q.y, q.z);
+                let n = self.normal(q.x, q.y, q.z);
+                q = Point3::new(q.x - d * n.x, q.y - d * n.y, q.z - d * n.z);
+            }
+            if self.sdf(q.x, q.y, q.z).abs() < 1.0e-3 * h {
+                projected.push(q);
+            }
+        }
+        thin(projected, 0.8 * h)
+    }
+}
+
+// 単位球面上のほぼ等間隔な n 点
+#[allow(dead_code)]
+pub fn fibonacci_sphere(n: usize) -> Vec<Point3> {
+    let golden = PI * (3.0 - 5.0_f64.sqrt());
+    (0..n)
+        .map(|k| {
+            let z = 1.0 - (2.0 * k as f64 + 1.0) / n as f64;
+            let s = (1.0 - z * z).sqrt();
+            let phi = golden * k as f64;
+            Point3::new(s * phi.cos(), s * phi.sin(), z)
+        })
+        .collect()
+}
+
+// 領域内の間隔 h の立方格子点
+fn lattice(domain: &Domain3D, h: f64) -> Vec<Point3> {
+    lattice_in_box(&domain.bounding_box(), h, 0.0)
+        .into_iter()
+        .filter(|p| domain.contains(p.x, p.y, p.z))
+        .collect()
+}
+
+fn lattice_in_box(bbox: &(Point3, Point3), h: f64, margin: f64) -> Vec<Point3> {
+    let (lo, hi) = bbox;
+    let count = |a: f64, b: f64| ((b - a + 2.0 * margin) / h).floor() as usize + 1;
+    let (nx, ny, nz) = (count(lo.x, hi.x), count(lo.y, hi.y), count(lo.z, hi.z));
+    let mut points = Vec::with_capacity(nx * ny * nz);
+    for i in 0..nx {
+        for j in 0..ny {
+            for k in 0..nz {
+                points.push(Point3::new(
+                    lo.x - margin + i as f64 * h,
+                    lo.y - margin + j as f64 * h,
+                    lo.z - margin + k as f64 * h,
+                ));
+            }
+        }
+    }
+    points
+}
+
+// 先に採用した点から min_distance 未満の点を捨てる (セル幅 min_distance の空間ハッシュ)
+fn thin(points: Vec<Point3>, min_distance: f64) -> Vec<Point3> {
+    let cell = |p: &Point3| {
+        (
+            (p.x / min_distance).floor() as i64,
+            (p.y / min_distance).floor() as i64,
+            (p.z / min_distance).floor() as i64,
+        )
+    };
+    let mut cells: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+    let mut kept: Vec<Point3> = vec![];
+    for p in points {
+        let (ci, cj, ck) = cell(&p);
+        let mut too_close = false;
+        'search: for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+                    if let Some(list) = cells.get(&(ci + di, cj + dj, ck + dk)) {
+                        if list
+                            .iter()
+                            .any(|q| kept[*q].distance_square(&p) < min_distance * min_distance)
+                        {
+                            too_close = true;
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+        if !too_close {
+            cells.entry((ci, cj, ck)).or_default().push(kept.len());
+            kept.push(p);
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sdf_of_solids() {
+        let ball = Domain3D::unit_ball();
+        assert!((ball.sdf(0.0, 0.0, 0.5) + 0.5).abs() < 1.0e-14);
+        let cube = Domain3D::Cuboid {
+            x_min: -1.0,
+            y_min: -1.0,
+            z_min: -1.0,
+            x_max: 1.0,
+            y_max: 1.0,
+            z_max: 1.0,
+        };
+        assert!((cube.sdf(2.0, 2.0, 0.0) - 2.0_f64.sqrt()).abs() < 1.0e-14);
+        assert!((cube.sdf(0.5, 0.0, 0.0) + 0.5).abs() < 1.0e-14);
+        let n = cube.normal(0.3, 0.99, 0.1);
+        assert!((n.y - 1.0).abs() < 1.0e-6);
+        let hollow = Domain3D::difference(
+            cube,
+            Domain3D::Ball {
+                cx: 0.0,
+                cy: 0.0,
+                cz: 0.0,
+                r: 0.5,
+            },
+        );
+        assert!(!hollow.contains(0.0, 0.0, 0.0));
+        assert!(hollow.contains(0.8, 0.0, 0.0));
+        let exact = 8.0 - 4.0 / 3.0 * PI * 0.125;
+        assert!((hollow.volume() - exact).abs() < 0.05 * exact);
+    }
+
+    #[test]
+    fn nodes_of_ball_and_hollow_cube() {
+        let h = 0.2;
+        let ball = Domain3D::unit_ball();
+        let (boundary, interior) = ball.generate_nodes(h);
+        for p in &boundary.points {
+            assert!(ball.sdf(p.x, p.y, p.z).abs() < 1.0e-12);
+        }
+        for p in &interior.points {
+            assert!(ball.sdf(p.x, p.y, p.z) < -0.5 * h);
+        }
+        // 内部点の数はほぼ体積 / h^3
+        let ratio = interior.points.len() as f64 * h * h * h / ball.volume();
+        assert!(ratio > 0.6 && ratio < 1.1, "{}", ratio);
+
+        let hollow = Domain3D::difference(
+            Domain3D::Cuboid {
+                x_min: -1.0,
+                y_min: -1.0,
+                z_min: -1.0,
+                x_max: 1.0,
+                y_max: 1.0,
+                z_max: 1.0,
+            },
+            Domain3D::Ball {
+                cx: 0.0,
+                cy: 0.0,
+                cz: 0.0,
+                r: 0.5,
+            },
+        );
+        let (boundary, _) = hollow.generate_nodes(h);
+        let mut on_sphere = 0;
+        for (i, p) in boundary.points.iter().enumerate() {
+            assert!(hollow.sdf(p.x, p.y, p.z).abs() < 1.0e-3 * h);
+            for q in &boundary.points[i + 1..] {
+                assert!(p.distance_square(q) >= 0.64 * h * h - 1.0e-12);
+            }
+            if (p.distance_square(&Point3::new(0.0, 0.0, 0.0)) - 0.25).abs() < 1.0e-6 {
+                on_sphere += 1;
+            }
+        }
+        // 内側の球面にも節点がある
+        assert!(on_sphere > 20, "{}", on_sphere);
+    }
+}
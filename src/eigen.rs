@@ -0,0 +1,529 @@
+use crate::bessel;
+use crate::dense_matrix;
+use crate::krylov;
+use crate::krylov::KrylovMethod;
+use crate::meshfree::MeshfreeNodes;
+use crate::preconditioner::{Preconditioner, PreconditionerType};
+use crate::sparse_matrix::CsrMatrix;
+use crate::stencil::DiffOperator;
+use std::fmt;
+
+// スペクトル変換。部分空間反復は絶対値の大きい固有値から収束するので、欲しい固有値を大きく写す
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+    ShiftInvert(f64), // (A - σI)^{-1}: σ に近い固有値
+    Cayley(f64),      // (A - σI)^{-1} (A + σI), σ > 0: |μ| > 1 と Re λ > 0 が同値
+}
+
+impl Transform {
+    fn shift(&self) -> f64 {
+        match self {
+            Transform::ShiftInvert(sigma) | Transform::Cayley(sigma) => *sigma,
+        }
+    }
+
+    // 変換後の固有値 θ から A の固有値 λ に戻す
+    fn to_eigenvalue(self, re: f64, im: f64) -> (f64, f64) {
+        match self {
+            // λ = σ + 1 / θ
+            Transform::ShiftInvert(sigma) => {
+                let d = re * re + im * im;
+                (sigma + re / d, -im / d)
+            }
+            // λ = σ (θ + 1) / (θ - 1)
+            Transform::Cayley(sigma) => {
+                let d = (re - 1.0) * (re - 1.0) + im * im;
+                (
+                    sigma * ((re + 1.0) * (re - 1.0) + im * im) / d,
+                    sigma * (-2.0 * im) / d,
+                )
+            }
+        }
+    }
+}
+
+// 固有値 re + i im と、|x| = 1 に正規化した固有ベクトル vector + i vector_im
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Eigenpair {
+    pub re: f64,
+    pub im: f64,
+    pub vector: Vec<f64>,
+    pub vector_im: Vec<f64>,
+    pub residual: f64, // |A x - λ x|
+}
+
+// 部分空間を Krylov 列で広げ、Rayleigh-Ritz で近似固有対を取り出し、求める Ritz ベクトルを残して再出発する
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArnoldiSolver {
+    pub num_eigen: usize,
+    pub krylov_dim: usize, // 部分空間の最大次元
+    pub max_restart: usize,
+    pub tol: f64, // |A x - λ x| <= tol max(|λ|, 1) で収束
+    pub linear_method: KrylovMethod,
+    pub preconditioner: PreconditionerType,
+    pub linear_tol: f64,
+    pub linear_max_iter: usize,
+}
+
+impl ArnoldiSolver {
+    #[allow(dead_code)]
+    pub fn new(num_eigen: usize) -> Self {
+        ArnoldiSolver {
+            num_eigen,
+            krylov_dim: (2 * num_eigen + 20).max(40),
+            max_restart: 30,
+            tol: 1.0e-8,
+            linear_method: KrylovMethod::Gmres(50),
+            preconditioner: PreconditionerType::Ilu0,
+            linear_tol: 1.0e-12,
+            linear_max_iter: 2000,
+        }
+    }
+
+    // 変換後の絶対値の大きい順 (ShiftInvert なら σ に近い順) に num_eigen 個
+    #[allow(dead_code)]
+    pub fn solve(&self, a: &CsrMatrix, transform: Transform) -> Result<Vec<Eigenpair>, String> {
+        let n = a.nrows;
+        if n == 0 || self.num_eigen == 0 {
+            return Ok(vec![]);
+        }
+        let m = self.krylov_dim.min(n).max(self.num_eigen.min(n));
+        let sigma = transform.shift();
+        let shifted = CsrMatrix::linear_combination(1.0, a, -sigma, &CsrMatrix::identity(n));
+        let precond = Preconditioner::new(self.preconditioner, &shifted);
+        let apply = |v: &[f64]| -> Result<Vec<f64>, String> {
+            let rhs = match transform {
+                Transform::ShiftInvert(_) => v.to_vec(),
+                Transform::Cayley(_) => {
+                    let mut av = a.mul_vec(v);
+                    krylov::axpy(sigma, v, &mut av);
+                    av
+                }
+            };
+            let result = krylov::solve(
+                self.linear_method,
+                &shifted,
+                &rhs,
+                &vec![0.0; n],
+                &precond,
+                self.linear_tol,
+                self.linear_max_iter,
+            );
+            if result.converged {
+                Ok(result.x)
+            } else {
+                Err(format!(
+                    "eigen: inner solve with shift {} did not converge (residual {:.3e})",
+                    sigma,
+                    result.residual_history.last().cloned().unwrap_or(f64::NAN)
+                ))
+            }
+        };
+
+        // v: 正規直交基底, w: 各基底ベクトルの像
+        let mut v: Vec<Vec<f64>> = vec![];
+        let mut w: Vec<Vec<f64>> = vec![];
+        let mut next = start_vector(n, 0);
+        let mut pairs = vec![];
+        for restart in 0..(self.max_restart + 1) {
+            while v.len() < m {
+                let Some(q) = orthonormalize(&v, next.clone()) else {
+                    // 部分空間が不変になったら別の方向を足す
+                    next = start_vector(n, v.len() + 1);
+                    if v.len() + 1 >= n {
+                        break;
+                    }
+                    continue;
+                };
+                let image = apply(&q)?;
+                next = image.clone();
+                v.push(q);
+                w.push(image);
+            }
+
+            // Rayleigh-Ritz: G = V^T B V
+            let k = v.len();
+            let g: Vec<Vec<f64>> = (0..k)
+                .map(|i| (0..k).map(|j| krylov::dot(&v[i], &w[j])).collect())
+                .collect();
+            let mut theta = dense_matrix::eigenvalues(&g);
+            if theta.iter().any(|t| !(t.0.is_finite() && t.1.is_finite())) {
+                return Err("eigen: QR iteration on the projected matrix failed".to_string());
+            }
+            theta.sort_by(|x, y| {
+                let ax = x.0.hypot(x.1);
+                let ay = y.0.hypot(y.1);
+                ay.total_cmp(&ax).then(y.1.total_cmp(&x.1))
+            });
+            let wanted = take_with_conjugates(&theta, self.num_eigen.min(k));
+
+            pairs = vec![];
+            let mut basis = vec![];
+            let mut converged = true;
+            for (re, im) in wanted.iter().filter(|t| t.1 >= 0.0) {
+                let (yr, yi) = ritz_coefficients(&g, *re, *im)?;
+                let mut xr = combine(&v, &yr);
+                let mut xi = combine(&v, &yi);
+                let scale = (krylov::dot(&xr, &xr) + krylov::dot(&xi, &xi)).sqrt();
+                xr.iter_mut().chain(xi.iter_mut()).for_each(|x| *x /= scale);
+                let (l_re, l_im) = transform.to_eigenvalue(*re, *im);
+                let residual = complex_residual(a, l_re, l_im, &xr, &xi);
+                if residual > self.tol * l_re.hypot(l_im).max(1.0) {
+                    converged = false;
+                }
+                basis.push(yr.clone());
+                if *im != 0.0 {
+                    basis.push(yi.clone());
+                    pairs.push(Eigenpair {
+                        re: l_re,
+                        im: -l_im,
+                        vector: xr.clone(),
+                        vector_im: xi.iter().map(|x| -x).collect(),
+                        residual,
+                    });
+                }
+                pairs.push(Eigenpair {
+                    re: l_re,
+                    im: l_im,
+                    vector: xr,
+                    vector_im: xi,
+                    residual,
+                });
+            }
+            if converged || restart == self.max_restart || k == n {
+                break;
+            }
+
+            // 求める Ritz ベクトル (複素なら実部と虚部) を残して再出発
+            let mut kept_v: Vec<Vec<f64>> = vec![];
+            let mut kept_w: Vec<Vec<f64>> = vec![];
+            for y in basis {
+                let mut y = y;
+                // 係数空間で直交化すれば V y も直交する
+                for c in &kept_v {
+                    let p = krylov::dot(c, &y);
+                    krylov::axpy(-p, c, &mut y);
+                }
+                let norm = krylov::norm(&y);
+                if norm > 1.0e-10 {
+                    y.iter_mut().for_each(|x| *x /= norm);
+                    kept_v.push(y);
+                }
+            }
+            kept_w.extend(kept_v.iter().map(|y| combine(&w, y)));
+            let kept: Vec<Vec<f64>> = kept_v.iter().map(|y| combine(&v, y)).collect();
+            next = kept_w
+                .last()
+                .cloned()
+                .unwrap_or_else(|| start_vector(n, restart + 1));
+            v = kept;
+            w = kept_w;
+        }
+        Ok(pairs)
+    }
+}
+
+// 決まった擬似乱数の出発ベクトル
+fn start_vector(n: usize, seed: usize) -> Vec<f64> {
+    (0..n)
+        .map(|i| 1.0 + 0.5 * ((i * 7 + seed * 13 + 1) as f64 * 0.618034).sin())
+        .collect()
+}
+
+// 基底に対して 2 回 Gram-Schmidt を掛ける。ほぼ従属なら None
+fn orthonormalize(basis: &[Vec<f64>], mut x: Vec<f64>) -> Option<Vec<f64>> {
+    let original = krylov::norm(&x);
+    if original == 0.0 || !original.is_finite() {
+        return None;
+    }
+    for _ in 0..2 {
+        for q in basis {
+            let p = krylov::dot(q, &x);
+            krylov::axpy(-p, q, &mut x);
+        }
+    }
+    let norm = krylov::norm(&x);
+    if norm < 1.0e-12 * original {
+        return None;
+    }
+    x.iter_mut().for_each(|v| *v /= norm);
+    Some(x)
+}
+
+fn combine(basis: &[Vec<f64>], y: &[f64]) -> Vec<f64> {
+    let mut x = vec![0.0; basis[0].len()];
+    for (q, c) in basis.iter().zip(y.iter()) {
+        krylov::axpy(*c, q, &mut x);
+    }
+    x
+}
+
+// 先頭から count 個。境目で複素共役の組を分けない
+fn take_with_conjugates(theta: &[(f64, f64)], count: usize) -> Vec<(f64, f64)> {
+    let mut wanted: Vec<(f64, f64)> = theta.iter().take(count).cloned().collect();
+    if let Some(last) = wanted.last() {
+        if last.1 > 0.0 && count < theta.len() {
+            wanted.push(theta[count]);
+        }
+    }
+    wanted
+}
+
+// (G - θ I) y = 0 の y を逆反復で求める。複素 θ は実 2k 次元の系に直す
+fn ritz_coefficients(g: &[Vec<f64>], re: f64, im: f64) -> Result<(Vec<f64>, Vec<f64>), String> {
+    let k = g.len();
+    let delta = 1.0e-8 * re.hypot(im).max(f64::MIN_POSITIVE);
+    let shift = re + delta;
+    let size = if im == 0.0 { k } else { 2 * k };
+    let mut m = vec![vec![0.0; size]; size];
+    for i in 0..k {
+        for j in 0..k {
+            let d = if i == j { shift } else { 0.0 };
+            m[i][j] = g[i][j] - d;
+            if im != 0.0 {
+                m[k + i][k + j] = g[i][j] - d;
+            }
+        }
+        if im != 0.0 {
+            m[i][k + i] = im;
+            m[k + i][i] = -im;
+        }
+    }
+    let mut y = start_vector(size, 3);
+    for _ in 0..3 {
+        y = dense_matrix::solve(&m, &y)
+            .ok_or_else(|| "eigen: singular shifted projected matrix".to_string())?;
+        let norm = krylov::norm(&y);
+        y.iter_mut().for_each(|v| *v /= norm);
+    }
+    if im == 0.0 {
+        Ok((y, vec![0.0; k]))
+    } else {
+        Ok((y[0..k].to_vec(), y[k..].to_vec()))
+    }
+}
+
+// |A (xr + i xi) - (re + i im)(xr + i xi)|
+fn complex_residual(a: &CsrMatrix, re: f64, im: f64, xr: &[f64], xi: &[f64]) -> f64 {
+    let ar = a.mul_vec(xr);
+    let ai = a.mul_vec(xi);
+    let mut sum = 0.0;
+    for i in 0..xr.len() {
+        let r = ar[i] - re * xr[i] + im * xi[i];
+        let s = ai[i] - re * xi[i] - im * xr[i];
+        sum += r * r + s * s;
+    }
+    sum.sqrt()
+}
+
+// 内部点の Dirichlet Laplacian (境界値を 0 とした A_II)
+#[allow(dead_code)]
+pub fn dirichlet_laplacian(mesh: &MeshfreeNodes) -> Result<CsrMatrix, String> {
+    Ok(mesh.interior_system(&DiffOperator::laplacian())?.0)
+}
+
+// 絶対値の小さい順に k 個 (Laplacian なら最も滑らかなモード)
+#[allow(dead_code)]
+pub fn lowest_eigenpairs(a: &CsrMatrix, k: usize) -> Result<Vec<Eigenpair>, String> {
+    let mut pairs = ArnoldiSolver::new(k).solve(a, Transform::ShiftInvert(0.0))?;
+    if pairs
+        .iter()
+        .any(|p| !(p.re.is_finite() && p.im.is_finite()))
+    {
+        return Err("eigen: non-finite eigenvalue".to_string());
+    }
+    pairs.sort_by(|x, y| {
+        x.re.hypot(x.im)
+            .total_cmp(&y.re.hypot(y.im))
+            .then(x.im.total_cmp(&y.im))
+    });
+    pairs.truncate(k);
+    Ok(pairs)
+}
+
+// 実部が正の固有値。陽的な時間発展はこれらのモードで指数的に増大する
+// Cayley 変換の絶対値の大きい k 個を調べるので、σ は疑わしい固有値の大きさ程度にとる
+#[allow(dead_code)]
+pub fn unstable_eigenpairs(a: &CsrMatrix, k: usize, sigma: f64) -> Result<Vec<Eigenpair>, String> {
+    assert!(sigma > 0.0, "Cayley shift must be positive");
+    let pairs = ArnoldiSolver::new(k).solve(a, Transform::Cayley(sigma))?;
+    Ok(pairs.into_iter().filter(|p| p.re > 0.0).collect())
+}
+
+// 小さい行列ならすべての固有値を密行列の QR 法で求められる
+#[allow(dead_code)]
+pub fn dense_spectrum(a: &CsrMatrix) -> Vec<(f64, f64)> {
+    let mut dense = vec![vec![0.0; a.ncols]; a.nrows];
+    for (i, row) in a.to_rows().iter().enumerate() {
+        for (j, v) in row {
+            dense[i][*j] += v;
+        }
+    }
+    dense_matrix::eigenvalues(&dense)
+}
+
+// 単位円板の -Δ の Dirichlet 固有値 j_{m,s}^2 を小さい順に count 個。m > 0 は cos と sin の 2 重
+#[allow(dead_code)]
+pub fn disk_eigenvalues(count: usize) -> Result<Vec<(f64, usize, usize)>, String> {
+    let mut values = vec![];
+    for m in 0..count {
+        for s in 1..(count + 1) {
+            let j = bessel::bessel_zero(m, s);
+            if !j.is_finite() {
+                return Err(format!("eigen: Bessel zero j_{},{} not found", m, s));
+            }
+            let multiplicity = if m == 0 { 1 } else { 2 };
+            for _ in 0..multiplicity {
+                values.push((j * j, m, s));
+            }
+        }
+    }
+    values.sort_by(|a, b| a.0.total_cmp(&b.0));
+    values.truncate(count);
+    Ok(values)
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct SpectrumReport {
+    pub lowest: Vec<Eigenpair>,
+    pub exact: Vec<(f64, usize, usize)>, // (j_{m,s}^2, m, s)
+    pub spectral_radius: f64,
+    pub unstable: Vec<Eigenpair>,
+}
+
+impl SpectrumReport {
+    // 単位円板上の節点の Dirichlet Laplacian について、低いモードと不安定な固有値を調べる
+    #[allow(dead_code)]
+    pub fn unit_disk(mesh: &MeshfreeNodes, k: usize) -> Result<Self, String> {
+        let a = dirichlet_laplacian(mesh)?;
+        let lowest = lowest_eigenpairs(&a, k)?;
+        let spectral_radius = a.spectral_radius(200);
+        let unstable = unstable_eigenpairs(&a, k, 0.25 * spectral_radius)?;
+        Ok(SpectrumReport {
+            lowest,
+            exact: disk_eigenvalues(k)?,
+            spectral_radius,
+            unstable,
+        })
+    }
+
+    // |(-λ) - j^2| / j^2
+    #[allow(dead_code)]
+    pub fn relative_errors(&self) -> Vec<f64> {
+        self.lowest
+            .iter()
+            .zip(self.exact.iter())
+            .map(|(p, e)| (-p.re - e.0).hypot(p.im) / e.0)
+            .collect()
+    }
+}
+
+impl fmt::Display for SpectrumReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "lowest eigenvalues of -Δ (Dirichlet):")?;
+        for ((p, e), err) in self
+            .lowest
+            .iter()
+            .zip(self.exact.iter())
+            .zip(self.relative_errors())
+        {
+            writeln!(
+                f,
+                "  {:>12.6} {:+.2e}i  j({},{})^2 = {:>12.6}  rel. error {:.3e}  residual {:.1e}",
+                -p.re, -p.im, e.1, e.2, e.0, err, p.residual
+            )?;
+        }
+        writeln!(f, "spectral radius estimate: {:.4e}", self.spectral_radius)?;
+        if self.unstable.is_empty() {
+            write!(f, "no eigenvalues with positive real part found")
+        } else {
+            write!(f, "eigenvalues with positive real part:")?;
+            for p in &self.unstable {
+                write!(f, "\n  {:.6e} {:+.6e}i", p.re, p.im)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Domain;
+    use crate::node_generator::{NodeGenerator, NodeStrategy, Spacing};
+
+    fn disk_mesh(h: f64, degree: usize, num_neighbor: usize) -> MeshfreeNodes {
+        let generator = NodeGenerator::new(
+            Domain::unit_circle(),
+            Spacing::Uniform(h),
+            NodeStrategy::HexLattice,
+        );
+        let (boundary, interior) = generator.generate();
+        MeshfreeNodes::new(&interior, &boundary, degree, num_neighbor)
+    }
+
+    // 既知の固有値をもつ非対称行列: 対角に -1..-n、右上に正の実部の 2x2 ブロック
+    fn test_matrix(n: usize) -> CsrMatrix {
+        let mut rows: Vec<Vec<(usize, f64)>> = (0..n)
+            .map(|i| {
+                let mut row = vec![(i, -(i as f64 + 1.0))];
+                if i + 1 < n - 2 {
+                    row.push((i + 1, 0.3));
+                }
+                row
+            })
+            .collect();
+        // 固有値 0.5 ± 2i
+        rows[n - 2] = vec![(n - 2, 0.5), (n - 1, 2.0)];
+        rows[n - 1] = vec![(n - 2, -2.0), (n - 1, 0.5)];
+        CsrMatrix::from_rows(n, &rows)
+    }
+
+    #[test]
+    fn shift_invert_and_cayley_on_known_matrix() {
+        let a = test_matrix(60);
+        let lowest = lowest_eigenpairs(&a, 3).unwrap();
+        assert!((lowest[0].re + 1.0).abs() < 1.0e-8, "{:?}", lowest[0].re);
+        assert!((lowest[1].re + 2.0).abs() < 1.0e-8);
+        // 0.5 ± 2i (|λ| = 2.06) は -2 と -3 の間に入る
+        assert!((lowest[2].re - 0.5).abs() < 1.0e-8 && (lowest[2].im + 2.0).abs() < 1.0e-8);
+        for p in &lowest {
+            assert!(p.residual < 1.0e-6, "{}", p.residual);
+        }
+
+        let unstable = unstable_eigenpairs(&a, 4, 2.0).unwrap();
+        assert_eq!(unstable.len(), 2);
+        for p in &unstable {
+            assert!((p.re - 0.5).abs() < 1.0e-8 && (p.im.abs() - 2.0).abs() < 1.0e-8);
+        }
+        let dense: Vec<(f64, f64)> = dense_spectrum(&a)
+            .into_iter()
+            .filter(|l| l.0 > 0.0)
+            .collect();
+        assert_eq!(dense.len(), 2);
+    }
+
+    #[test]
+    fn disk_laplacian_matches_bessel_zeros() {
+        let exact = disk_eigenvalues(6).unwrap();
+        assert!((exact[0].0 - 5.783185962946784).abs() < 1.0e-10);
+        assert_eq!((exact[1].1, exact[2].1, exact[3].1), (1, 1, 2));
+        assert!((exact[5].0 - 30.471262343662087).abs() < 1.0e-9);
+
+        let mesh = disk_mesh(0.06, 2, 12);
+        let report = SpectrumReport::unit_disk(&mesh, 6).unwrap();
+        assert_eq!(report.lowest.len(), 6);
+        for (p, err) in report.lowest.iter().zip(report.relative_errors()) {
+            assert!(err < 0.02, "{}", report);
+            assert!(p.residual < 1.0e-4 * p.re.abs(), "{}", report);
+        }
+        // 基本モードは符号を変えない
+        let v = &report.lowest[0].vector;
+        assert!(v.iter().all(|x| *x >= -1.0e-8) || v.iter().all(|x| *x <= 1.0e-8));
+        assert!(report.unstable.iter().all(|p| p.re > 0.0));
+    }
+}
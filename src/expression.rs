@@ -0,0 +1,285 @@
+// 初期条件やソース項を文字列で与えるための簡単な数式
+// 変数 x, y, t, r (= sqrt(x^2 + y^2))、定数 pi, e、+ - * / ^、
+// 関数 sin cos tan exp log sqrt abs tanh が使える
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variable {
+    X,
+    Y,
+    T,
+    R,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Exp,
+    Log,
+    Sqrt,
+    Abs,
+    Tanh,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(f64),
+    Variable(Variable),
+    Neg(Box<Expression>),
+    Add(Box<Expression>, Box<Expression>),
+    Sub(Box<Expression>, Box<Expression>),
+    Mul(Box<Expression>, Box<Expression>),
+    Div(Box<Expression>, Box<Expression>),
+    Pow(Box<Expression>, Box<Expression>),
+    Call(Function, Box<Expression>),
+}
+
+impl Expression {
+    #[allow(dead_code)]
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            pos: 0,
+        };
+        let e = parser.expr()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.chars.len() {
+            return Err(format!(
+                "unexpected '{}' at {} in \"{}\"",
+                parser.chars[parser.pos], parser.pos, text
+            ));
+        }
+        Ok(e)
+    }
+
+    #[allow(dead_code)]
+    pub fn eval(&self, x: f64, y: f64, t: f64) -> f64 {
+        match self {
+            Expression::Number(v) => *v,
+            Expression::Variable(v) => match v {
+                Variable::X => x,
+                Variable::Y => y,
+                Variable::T => t,
+                Variable::R => (x * x + y * y).sqrt(),
+            },
+            Expression::Neg(a) => -a.eval(x, y, t),
+            Expression::Add(a, b) => a.eval(x, y, t) + b.eval(x, y, t),
+            Expression::Sub(a, b) => a.eval(x, y, t) - b.eval(x, y, t),
+            Expression::Mul(a, b) => a.eval(x, y, t) * b.eval(x, y, t),
+            Expression::Div(a, b) => a.eval(x, y, t) / b.eval(x, y, t),
+            Expression::Pow(a, b) => a.eval(x, y, t).powf(b.eval(x, y, t)),
+            Expression::Call(f, a) => {
+                let v = a.eval(x, y, t);
+                match f {
+                    Function::Sin => v.sin(),
+                    Function::Cos => v.cos(),
+                    Function::Tan => v.tan(),
+                    Function::Exp => v.exp(),
+                    Function::Log => v.ln(),
+                    Function::Sqrt => v.sqrt(),
+                    Function::Abs => v.abs(),
+                    Function::Tanh => v.tanh(),
+                }
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected '{}' at {}", c, self.pos))
+        }
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Expression, String> {
+        let mut e = self.term()?;
+        loop {
+            match self.peek() {
+                Some('+') => {
+                    self.pos += 1;
+                    e = Expression::Add(Box::new(e), Box::new(self.term()?));
+                }
+                Some('-') => {
+                    self.pos += 1;
+                    e = Expression::Sub(Box::new(e), Box::new(self.term()?));
+                }
+                _ => return Ok(e),
+            }
+        }
+    }
+
+    // term := unary (('*' | '/') unary)*
+    fn term(&mut self) -> Result<Expression, String> {
+        let mut e = self.unary()?;
+        loop {
+            match self.peek() {
+                Some('*') => {
+                    self.pos += 1;
+                    e = Expression::Mul(Box::new(e), Box::new(self.unary()?));
+                }
+                Some('/') => {
+                    self.pos += 1;
+                    e = Expression::Div(Box::new(e), Box::new(self.unary()?));
+                }
+                _ => return Ok(e),
+            }
+        }
+    }
+
+    // unary := '-' unary | power
+    fn unary(&mut self) -> Result<Expression, String> {
+        if self.peek() == Some('-') {
+            self.pos += 1;
+            return Ok(Expression::Neg(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    // power := atom ('^' unary)?  (右結合)
+    fn power(&mut self) -> Result<Expression, String> {
+        let base = self.atom()?;
+        if self.peek() == Some('^') {
+            self.pos += 1;
+            return Ok(Expression::Pow(Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Expression, String> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let e = self.expr()?;
+                self.expect(')')?;
+                Ok(e)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let start = self.pos;
+                while self.pos < self.chars.len() && self.chars[self.pos].is_ascii_alphanumeric() {
+                    self.pos += 1;
+                }
+                let name: String = self.chars[start..self.pos].iter().collect();
+                let function = match name.as_str() {
+                    "sin" => Some(Function::Sin),
+                    "cos" => Some(Function::Cos),
+                    "tan" => Some(Function::Tan),
+                    "exp" => Some(Function::Exp),
+                    "log" => Some(Function::Log),
+                    "sqrt" => Some(Function::Sqrt),
+                    "abs" => Some(Function::Abs),
+                    "tanh" => Some(Function::Tanh),
+                    _ => None,
+                };
+                if let Some(f) = function {
+                    self.expect('(')?;
+                    let e = self.expr()?;
+                    self.expect(')')?;
+                    return Ok(Expression::Call(f, Box::new(e)));
+                }
+                match name.as_str() {
+                    "x" => Ok(Expression::Variable(Variable::X)),
+                    "y" => Ok(Expression::Variable(Variable::Y)),
+                    "t" => Ok(Expression::Variable(Variable::T)),
+                    "r" => Ok(Expression::Variable(Variable::R)),
+                    "pi" => Ok(Expression::Number(std::f64::consts::PI)),
+                    "e" => Ok(Expression::Number(std::f64::consts::E)),
+                    _ => Err(format!("unknown name '{}' at {}", name, start)),
+                }
+            }
+            Some(c) => Err(format!("unexpected '{}' at {}", c, self.pos)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+
+    fn number(&mut self) -> Result<Expression, String> {
+        let start = self.pos;
+        while self.pos < self.chars.len()
+            && (self.chars[self.pos].is_ascii_digit() || self.chars[self.pos] == '.')
+        {
+            self.pos += 1;
+        }
+        // 指数表記 1.0e-3
+        if self.pos < self.chars.len()
+            && (self.chars[self.pos] == 'e' || self.chars[self.pos] == 'E')
+        {
+            let mut end = self.pos + 1;
+            if end < self.chars.len() && (self.chars[end] == '+' || self.chars[end] == '-') {
+                end += 1;
+            }
+            if end < self.chars.len() && self.chars[end].is_ascii_digit() {
+                self.pos = end;
+                while self.pos < self.chars.len() && self.chars[self.pos].is_ascii_digit() {
+                    self.pos += 1;
+                }
+            }
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse::<f64>()
+            .map(Expression::Number)
+            .map_err(|_| format!("invalid number '{}' at {}", text, start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precedence() {
+        let e = Expression::parse("1 + 2 * 3 ^ 2 - -4 / 2").unwrap();
+        assert_eq!(e.eval(0.0, 0.0, 0.0), 21.0);
+        let e = Expression::parse("-2^2").unwrap();
+        assert_eq!(e.eval(0.0, 0.0, 0.0), -4.0);
+        let e = Expression::parse("2^3^2").unwrap();
+        assert_eq!(e.eval(0.0, 0.0, 0.0), 512.0);
+    }
+
+    #[test]
+    fn variables_and_functions() {
+        let e = Expression::parse("exp(-10 * (x^2 + y^2)) * cos(pi * t)").unwrap();
+        let (x, y, t): (f64, f64, f64) = (0.3, -0.2, 0.25);
+        let expected = (-10.0 * (x * x + y * y)).exp() * (std::f64::consts::PI * t).cos();
+        assert!((e.eval(x, y, t) - expected).abs() < 1.0e-15);
+        let e = Expression::parse("sqrt(abs(-r)) + 1.5e-1 * tanh(x)").unwrap();
+        assert!((e.eval(3.0, 4.0, 0.0) - (5.0_f64.sqrt() + 0.15 * 3.0_f64.tanh())).abs() < 1.0e-15);
+        assert_eq!(
+            Expression::parse("2e").unwrap_err(),
+            "unexpected 'e' at 1 in \"2e\""
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(Expression::parse("").is_err());
+        assert!(Expression::parse("(x + 1").is_err());
+        assert!(Expression::parse("foo(x)").is_err());
+        assert!(Expression::parse("x y").is_err());
+        assert!(Expression::parse("sin x").is_err());
+    }
+}
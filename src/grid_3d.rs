@@ -1,15 +1,61 @@
 use super::point;
 use super::two_variable_polynomial;
 
+// poly_fitting_by_euler_with_tol の打ち切り条件
+const MAX_FITTING_ITER: usize = 1_000_000;
+const MIN_RELATIVE_DECREASE: f64 = 1.0e-12;
+
 #[derive(Debug)]
 pub struct Grid3D {
     pub points_3d: Vec<point::Point3>,
+    pub constraints: Vec<Constraint>,
+}
+
+// (x, y) での制約 α p + β (n・∇p) = r (Neumann, Robin 境界)
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constraint {
+    pub x: f64,
+    pub y: f64,
+    pub nx: f64,
+    pub ny: f64,
+    pub alpha: f64,
+    pub beta: f64,
+    pub r: f64,
+}
+
+impl Constraint {
+    #[allow(dead_code)]
+    pub fn residual(&self, poly: &two_variable_polynomial::TwoPolynomial) -> f64 {
+        let p_n = self.nx * poly.eval_deriv(self.x, self.y, 1, 0)
+            + self.ny * poly.eval_deriv(self.x, self.y, 0, 1);
+        self.alpha * poly.eval_xy(self.x, self.y) + self.beta * p_n - self.r
+    }
+
+    // 係数 x^a y^b に関する residual の偏微分
+    fn residual_deriv(&self, x_deg: usize, y_deg: usize) -> f64 {
+        let m = self.x.powi(x_deg as i32) * self.y.powi(y_deg as i32);
+        let m_x = if x_deg == 0 {
+            0.0
+        } else {
+            x_deg as f64 * self.x.powi(x_deg as i32 - 1) * self.y.powi(y_deg as i32)
+        };
+        let m_y = if y_deg == 0 {
+            0.0
+        } else {
+            y_deg as f64 * self.x.powi(x_deg as i32) * self.y.powi(y_deg as i32 - 1)
+        };
+        self.alpha * m + self.beta * (self.nx * m_x + self.ny * m_y)
+    }
 }
 
 impl Grid3D {
     #[allow(dead_code)]
     pub fn new() -> Self {
-        Grid3D { points_3d: vec![] }
+        Grid3D {
+            points_3d: vec![],
+            constraints: vec![],
+        }
     }
 
     #[allow(dead_code)]
@@ -22,13 +68,20 @@ impl Grid3D {
     }
 
     #[allow(dead_code)]
+    pub fn push_constraint(&mut self, constraint: Constraint) {
+        self.constraints.push(constraint);
+    }
+
+    // 制約が互いに矛盾するなどで potential が tol まで下がらないときは、
+    // 減少が止まるか MAX_FITTING_ITER 回で打ち切ってその時点の係数を返す
+    #[allow(dead_code, clippy::needless_borrow)]
     pub fn poly_fitting_by_euler_with_tol(
         &mut self,
         poly: &mut two_variable_polynomial::TwoPolynomial,
         tol: f64,
     ) -> Vec<f64> {
         let mut dt = 1.0e-3;
-        loop {
+        for _ in 0..MAX_FITTING_ITER {
             let pre: f64 = self.potential(&poly);
             let mut tmp = poly.clone();
             let tmp_a: two_variable_polynomial::TwoPolynomial = self.euler_step(&mut tmp, dt);
@@ -38,7 +91,13 @@ impl Grid3D {
             if pre > post {
                 dt = (1.01 * dt).min(1.0e3);
                 *poly = tmp;
-            } else if post > pre {
+                if pre - post <= MIN_RELATIVE_DECREASE * pre {
+                    break;
+                }
+            } else if dt <= 1.0e-5 {
+                // 最小の刻みでも減少しない
+                break;
+            } else {
                 dt = (0.9 * dt).max(1.0e-5);
             }
             if post < tol || pre < tol {
@@ -48,7 +107,7 @@ impl Grid3D {
         poly.two_poly.to_vec()
     }
 
-    #[allow(dead_code)]
+    #[allow(dead_code, clippy::assign_op_pattern, clippy::needless_borrow)]
     pub fn euler_step(
         &mut self,
         poly: &mut two_variable_polynomial::TwoPolynomial,
@@ -79,6 +138,14 @@ impl Grid3D {
                 }
             }
         }
+        for c in &self.constraints {
+            let res = c.residual(poly);
+            for m in 0..(n + 1) {
+                for (i, d) in du.iter_mut().enumerate().skip(m * (n + 1)).take(n - m + 1) {
+                    *d += c.residual_deriv(i / (n + 1), i % (n + 1)) * res;
+                }
+            }
+        }
         du
     }
 
@@ -90,7 +157,10 @@ impl Grid3D {
                 - self.poly_eval(poly, self.points_3d[i].x, self.points_3d[i].y))
             .powf(2.0);
         }
-        u / self.points_3d.len() as f64
+        for c in &self.constraints {
+            u += c.residual(poly).powf(2.0);
+        }
+        u / (self.points_3d.len() + self.constraints.len()) as f64
     }
 
     #[allow(dead_code)]
@@ -310,4 +380,46 @@ mod tests {
         assert_eq!(coef[4], 0.0);
         assert_eq!(coef[5], 0.0);
     }
+
+    #[test]
+    fn fitting_with_neumann_constraint() {
+        // p = a + b x の形に制限されるよう y 方向の点を置き、x = 1 で p_x = 2 を課す
+        let mut poly = two_variable_polynomial::TwoPolynomial::new(1);
+        let mut test = Grid3D::new();
+        test.push(point::Point3::new(0.0, 0.0, 1.0));
+        test.push(point::Point3::new(0.0, 1.0, 1.0));
+        test.push_constraint(Constraint {
+            x: 1.0,
+            y: 0.0,
+            nx: 1.0,
+            ny: 0.0,
+            alpha: 0.0,
+            beta: 1.0,
+            r: 2.0,
+        });
+        test.poly_fitting_by_euler_with_tol(&mut poly, 1.0e-12);
+        assert!((poly.eval_xy(0.5, 0.5) - 2.0).abs() < 1.0e-4);
+        assert!((poly.eval_deriv(0.3, 0.2, 1, 0) - 2.0).abs() < 1.0e-4);
+        assert!(test.constraints[0].residual(&poly).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn fitting_stops_on_inconsistent_constraints() {
+        // 同じ点で p = 0 と p = 1 を課すと potential は 0 にならない。最小二乗の妥協 p = 1/2 で止まる
+        let mut poly = two_variable_polynomial::TwoPolynomial::new(1);
+        let mut test = Grid3D::new();
+        for value in [0.0, 1.0] {
+            test.push_constraint(Constraint {
+                x: 0.5,
+                y: 0.5,
+                nx: 1.0,
+                ny: 0.0,
+                alpha: 1.0,
+                beta: 0.0,
+                r: value,
+            });
+        }
+        test.poly_fitting_by_euler_with_tol(&mut poly, 1.0e-12);
+        assert!((poly.eval_xy(0.5, 0.5) - 0.5).abs() < 1.0e-4);
+    }
 }
@@ -0,0 +1,330 @@
+use crate::krylov;
+use crate::krylov::KrylovMethod;
+use crate::meshfree::MeshfreeNodes;
+use crate::preconditioner::{Preconditioner, PreconditionerType};
+use crate::sparse_matrix::CsrMatrix;
+use crate::stencil::DiffOperator;
+
+// 半離散系 du/dt = A u + F(t) (+ N(u, t)) の陰的時間積分
+// F(t) は境界値の持ち上げ A_IB g(t) やソース項を含む
+
+// (I - c A) x = b を解く。c が変わらない限り行列と前処理を使い回す。収束しなければ Err
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct ShiftedSolver {
+    pub a: CsrMatrix,
+    pub method: KrylovMethod,
+    pub preconditioner: PreconditionerType,
+    pub tol: f64,
+    pub max_iter: usize,
+    cache: Option<(f64, CsrMatrix, Preconditioner)>,
+}
+
+impl ShiftedSolver {
+    #[allow(dead_code)]
+    pub fn new(a: CsrMatrix) -> Self {
+        ShiftedSolver {
+            a,
+            method: KrylovMethod::BiCgStab,
+            preconditioner: PreconditionerType::Ilu0,
+            tol: 1.0e-12,
+            max_iter: 1000,
+            cache: None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn solve(&mut self, c: f64, b: &[f64], x0: &[f64]) -> Result<Vec<f64>, String> {
+        let rebuild = match &self.cache {
+            Some((c_old, _, _)) => *c_old != c,
+            None => true,
+        };
+        if rebuild {
+            let m =
+                CsrMatrix::linear_combination(1.0, &CsrMatrix::identity(self.a.nrows), -c, &self.a);
+            let p = Preconditioner::new(self.preconditioner, &m);
+            self.cache = Some((c, m, p));
+        }
+        let (_, m, p) = self.cache.as_ref().unwrap();
+        let res = krylov::solve(self.method, m, b, x0, p, self.tol, self.max_iter);
+        if !res.converged {
+            return Err(format!(
+                "implicit solve did not converge: residual {:e}",
+                res.residual_history[res.residual_history.len() - 1]
+            ));
+        }
+        Ok(res.x)
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImplicitScheme {
+    BackwardEuler,
+    CrankNicolson,
+    Bdf2, // 初回は後退 Euler
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct ImplicitStepper {
+    pub solver: ShiftedSolver,
+    pub scheme: ImplicitScheme,
+    previous: Option<(f64, Vec<f64>)>, // BDF2 用 (dt, u^{n-1})
+}
+
+impl ImplicitStepper {
+    #[allow(dead_code)]
+    pub fn new(a: CsrMatrix, scheme: ImplicitScheme) -> Self {
+        ImplicitStepper {
+            solver: ShiftedSolver::new(a),
+            scheme,
+            previous: None,
+        }
+    }
+
+    // 同次 Dirichlet 境界での du/dt = L u
+    #[allow(dead_code)]
+    pub fn from_mesh(
+        mesh: &MeshfreeNodes,
+        op: &DiffOperator,
+        scheme: ImplicitScheme,
+    ) -> Result<Self, String> {
+        Ok(ImplicitStepper::new(mesh.interior_system(op)?.0, scheme))
+    }
+
+    // 線形ソルバーが収束しなければ Err (BDF2 の履歴も更新しない)
+    #[allow(dead_code)]
+    pub fn step(
+        &mut self,
+        u: &[f64],
+        t: f64,
+        dt: f64,
+        forcing: &dyn Fn(f64) -> Vec<f64>,
+    ) -> Result<Vec<f64>, String> {
+        let f_new = forcing(t + dt);
+        let next = match (self.scheme, &self.previous) {
+            (ImplicitScheme::CrankNicolson, _) => {
+                let au = self.solver.a.mul_vec(u);
+                let f_old = forcing(t);
+                let b: Vec<f64> = (0..u.len())
+                    .map(|i| u[i] + 0.5 * dt * (au[i] + f_old[i] + f_new[i]))
+                    .collect();
+                self.solver.solve(0.5 * dt, &b, u)?
+            }
+            (ImplicitScheme::Bdf2, Some((dt_old, u_old))) if *dt_old == dt => {
+                let b: Vec<f64> = (0..u.len())
+                    .map(|i| (4.0 * u[i] - u_old[i]) / 3.0 + 2.0 / 3.0 * dt * f_new[i])
+                    .collect();
+                self.solver.solve(2.0 / 3.0 * dt, &b, u)?
+            }
+            _ => {
+                let b: Vec<f64> = (0..u.len()).map(|i| u[i] + dt * f_new[i]).collect();
+                self.solver.solve(dt, &b, u)?
+            }
+        };
+        if self.scheme == ImplicitScheme::Bdf2 {
+            self.previous = Some((dt, u.to_vec()));
+        }
+        Ok(next)
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImexScheme {
+    Euler,
+    Ars222, // Ascher-Ruuth-Spiteri (2,2,2)
+}
+
+// A u + F(t) を陰的に、N(u, t) を陽的に扱う
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct ImexStepper {
+    pub solver: ShiftedSolver,
+    pub scheme: ImexScheme,
+}
+
+impl ImexStepper {
+    #[allow(dead_code)]
+    pub fn new(a: CsrMatrix, scheme: ImexScheme) -> Self {
+        ImexStepper {
+            solver: ShiftedSolver::new(a),
+            scheme,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn step(
+        &mut self,
+        u: &[f64],
+        t: f64,
+        dt: f64,
+        forcing: &dyn Fn(f64) -> Vec<f64>,
+        explicit: &dyn Fn(&[f64], f64) -> Vec<f64>,
+    ) -> Result<Vec<f64>, String> {
+        let n = u.len();
+        let k1 = explicit(u, t);
+        match self.scheme {
+            ImexScheme::Euler => {
+                let f = forcing(t + dt);
+                let b: Vec<f64> = (0..n).map(|i| u[i] + dt * (k1[i] + f[i])).collect();
+                self.solver.solve(dt, &b, u)
+            }
+            ImexScheme::Ars222 => {
+                let gamma = 1.0 - 0.5_f64.sqrt();
+                let delta = 1.0 - 1.0 / (2.0 * gamma);
+                let f2 = forcing(t + gamma * dt);
+                let b2: Vec<f64> = (0..n)
+                    .map(|i| u[i] + gamma * dt * (k1[i] + f2[i]))
+                    .collect();
+                let u2 = self.solver.solve(gamma * dt, &b2, u)?;
+                let au2 = self.solver.a.mul_vec(&u2);
+                let k2 = explicit(&u2, t + gamma * dt);
+                let f3 = forcing(t + dt);
+                let b3: Vec<f64> = (0..n)
+                    .map(|i| {
+                        u[i] + dt
+                            * (delta * k1[i]
+                                + (1.0 - delta) * k2[i]
+                                + (1.0 - gamma) * (au2[i] + f2[i])
+                                + gamma * f3[i])
+                    })
+                    .collect();
+                self.solver.solve(gamma * dt, &b3, &u2)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wave_eqation::WaveEq;
+
+    // u = e^{-t} (x^2 + y^2) は u_t = Δu + F, F = -e^{-t} (x^2 + y^2 + 4) を満たす
+    fn heat_error(scheme: ImplicitScheme, dt: f64) -> f64 {
+        let mut wave = WaveEq::new();
+        wave.create(30);
+        let mesh = MeshfreeNodes::from_wave(&wave, 2, 12);
+        let (a_ii, a_ib) = mesh.interior_system(&DiffOperator::laplacian()).unwrap();
+        let n = mesh.num_interior;
+        let exact = |x: f64, y: f64, t: f64| (-t).exp() * (x * x + y * y);
+        let forcing = |t: f64| -> Vec<f64> {
+            let g: Vec<f64> = mesh.nodes.points[n..]
+                .iter()
+                .map(|p| exact(p.x, p.y, t))
+                .collect();
+            let lift = a_ib.mul_vec(&g);
+            (0..n)
+                .map(|i| {
+                    let p = mesh.point(i);
+                    lift[i] - (-t).exp() * (p.x * p.x + p.y * p.y + 4.0)
+                })
+                .collect()
+        };
+        let mut stepper = ImplicitStepper::new(a_ii, scheme);
+        let mut u: Vec<f64> = (0..n)
+            .map(|i| exact(mesh.point(i).x, mesh.point(i).y, 0.0))
+            .collect();
+        let steps = (0.5 / dt).round() as usize;
+        for k in 0..steps {
+            u = stepper.step(&u, k as f64 * dt, dt, &forcing).unwrap();
+        }
+        (0..n)
+            .map(|i| (u[i] - exact(mesh.point(i).x, mesh.point(i).y, 0.5)).abs())
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn backward_euler_first_order() {
+        let e1 = heat_error(ImplicitScheme::BackwardEuler, 0.05);
+        let e2 = heat_error(ImplicitScheme::BackwardEuler, 0.025);
+        let rate = (e1 / e2).log2();
+        assert!((rate - 1.0).abs() < 0.2, "rate {}", rate);
+    }
+
+    #[test]
+    fn crank_nicolson_second_order() {
+        let e1 = heat_error(ImplicitScheme::CrankNicolson, 0.05);
+        let e2 = heat_error(ImplicitScheme::CrankNicolson, 0.025);
+        let rate = (e1 / e2).log2();
+        assert!((rate - 2.0).abs() < 0.2, "rate {}", rate);
+    }
+
+    #[test]
+    fn bdf2_second_order() {
+        let e1 = heat_error(ImplicitScheme::Bdf2, 0.05);
+        let e2 = heat_error(ImplicitScheme::Bdf2, 0.025);
+        let rate = (e1 / e2).log2();
+        assert!(rate > 1.7, "rate {}", rate);
+    }
+
+    #[test]
+    fn large_step_is_stable() {
+        // 陽的 Euler では到底不安定な dt
+        assert!(heat_error(ImplicitScheme::BackwardEuler, 0.25) < 0.1);
+    }
+
+    // u = e^{-t} (x^2 + y^2): u_t = Δu - u + F, F = -4 e^{-t}, -u を陽的に扱う
+    fn imex_error(scheme: ImexScheme, dt: f64) -> f64 {
+        let mut wave = WaveEq::new();
+        wave.create(30);
+        let mesh = MeshfreeNodes::from_wave(&wave, 2, 12);
+        let (a_ii, a_ib) = mesh.interior_system(&DiffOperator::laplacian()).unwrap();
+        let n = mesh.num_interior;
+        let exact = |x: f64, y: f64, t: f64| (-t).exp() * (x * x + y * y);
+        let forcing = |t: f64| -> Vec<f64> {
+            let g: Vec<f64> = mesh.nodes.points[n..]
+                .iter()
+                .map(|p| exact(p.x, p.y, t))
+                .collect();
+            a_ib.mul_vec(&g)
+                .iter()
+                .map(|l| l - 4.0 * (-t).exp())
+                .collect()
+        };
+        let explicit = |u: &[f64], _t: f64| -> Vec<f64> { u.iter().map(|v| -v).collect() };
+        let mut stepper = ImexStepper::new(a_ii, scheme);
+        let mut u: Vec<f64> = (0..n)
+            .map(|i| exact(mesh.point(i).x, mesh.point(i).y, 0.0))
+            .collect();
+        let steps = (0.5 / dt).round() as usize;
+        for k in 0..steps {
+            u = stepper
+                .step(&u, k as f64 * dt, dt, &forcing, &explicit)
+                .unwrap();
+        }
+        (0..n)
+            .map(|i| (u[i] - exact(mesh.point(i).x, mesh.point(i).y, 0.5)).abs())
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn imex_orders() {
+        let rate_euler =
+            (imex_error(ImexScheme::Euler, 0.05) / imex_error(ImexScheme::Euler, 0.025)).log2();
+        let rate_ars =
+            (imex_error(ImexScheme::Ars222, 0.05) / imex_error(ImexScheme::Ars222, 0.025)).log2();
+        assert!((rate_euler - 1.0).abs() < 0.2, "rate {}", rate_euler);
+        assert!(rate_ars > 1.7, "rate {}", rate_ars);
+    }
+
+    #[test]
+    fn unconverged_solve_is_an_error() {
+        let mut wave = WaveEq::new();
+        wave.create(30);
+        let mesh = MeshfreeNodes::from_wave(&wave, 2, 12);
+        let mut stepper =
+            ImplicitStepper::from_mesh(&mesh, &DiffOperator::laplacian(), ImplicitScheme::Bdf2)
+                .unwrap();
+        stepper.solver.max_iter = 1;
+        let u = vec![1.0; mesh.num_interior];
+        let zero = vec![0.0; mesh.num_interior];
+        assert!(stepper.step(&u, 0.0, 0.1, &|_| zero.clone()).is_err());
+        // 失敗したステップは BDF2 の履歴に残らない
+        assert!(stepper.previous.is_none());
+        stepper.solver.max_iter = 1000;
+        assert!(stepper.step(&u, 0.0, 0.1, &|_| zero.clone()).is_ok());
+    }
+}
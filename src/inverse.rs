@@ -0,0 +1,678 @@
+use crate::implicit::ShiftedSolver;
+use crate::meshfree::MeshfreeNodes;
+use crate::pde_model::Source;
+use crate::sparse_matrix::CsrMatrix;
+use crate::stencil::DiffOperator;
+use std::fmt;
+
+// 観測データから推定する量
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Parameter {
+    Diffusivity,      // 一定の κ (パラメータは 1 個)
+    DiffusivityField, // 節点ごとの κ (全節点)
+    InitialCondition, // 内部点の u(x, 0)
+}
+
+// 正則化項
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Regularization {
+    None,
+    Tikhonov { weight: f64, prior: f64 }, // weight / 2 Σ (θ - prior)^2
+    Smoothness(f64),                      // weight / 2 Σ_i |∇θ(x_i)|^2 (内部点)
+}
+
+// 時刻ステップ step での節点 node (内部点) の観測値
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Observation {
+    pub node: usize,
+    pub step: usize,
+    pub value: f64,
+}
+
+// 勾配流 θ' = -∇J の離散化。J が減れば刻みを growth 倍、増えれば棄却して shrink 倍
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GradientFlowOptions {
+    pub max_iter: usize,
+    pub tol: f64,          // J の相対減少がこれを下回ったら止める
+    pub initial_step: f64, // 最初の更新の最大ノルム
+    pub growth: f64,
+    pub shrink: f64,
+    pub lower_bound: f64, // κ を推定するときの下限
+}
+
+impl Default for GradientFlowOptions {
+    fn default() -> Self {
+        GradientFlowOptions {
+            max_iter: 200,
+            tol: 1.0e-10,
+            initial_step: 0.1,
+            growth: 1.5,
+            shrink: 0.5,
+            lower_bound: 1.0e-3,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct InverseReport {
+    pub parameters: Vec<f64>,
+    pub objective_history: Vec<f64>,
+    pub iterations: usize,
+}
+
+// u_t = ∇・(κ∇u), u = g (境界) を後退 Euler で num_steps 進める順問題と、その観測誤差
+// J(θ) = 1/2 Σ (u_node^step - value)^2 + 正則化 の最小化。
+// κ は節点値で持ち、∇・(κ∇u) = κ Δu + ∇κ・∇u の形で離散化する (κ について線形なので微分が厳密)
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct HeatInverseProblem {
+    pub mesh: MeshfreeNodes,
+    pub dt: f64,
+    pub num_steps: usize,
+    pub boundary_value: Source,
+    pub diffusivity: Vec<f64>, // 全節点
+    pub initial: Vec<f64>,     // 内部点
+    pub observations: Vec<Observation>,
+    pub regularization: Regularization,
+    laplacian: CsrMatrix, // 内部点の行 x 全節点
+    d_x: CsrMatrix,
+    d_y: CsrMatrix,
+}
+
+impl HeatInverseProblem {
+    #[allow(dead_code)]
+    pub fn new(mesh: MeshfreeNodes, dt: f64, num_steps: usize) -> Result<Self, String> {
+        let matrix = |op: &DiffOperator| -> Result<CsrMatrix, String> {
+            Ok(CsrMatrix::from_rows(
+                mesh.num_nodes(),
+                &mesh.operator_rows(op)?,
+            ))
+        };
+        let laplacian = matrix(&DiffOperator::laplacian())?;
+        let d_x = matrix(&DiffOperator::d_x())?;
+        let d_y = matrix(&DiffOperator::d_y())?;
+        Ok(HeatInverseProblem {
+            diffusivity: vec![1.0; mesh.num_nodes()],
+            initial: vec![0.0; mesh.num_interior],
+            mesh,
+            dt,
+            num_steps,
+            boundary_value: Source::new(|_, _, _| 0.0),
+            observations: vec![],
+            regularization: Regularization::None,
+            laplacian,
+            d_x,
+            d_y,
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn set_initial_condition(&mut self, u0: &dyn Fn(f64, f64) -> f64) {
+        self.initial = self.mesh.nodes.points[0..self.mesh.num_interior]
+            .iter()
+            .map(|p| u0(p.x, p.y))
+            .collect();
+    }
+
+    #[allow(dead_code)]
+    pub fn set_diffusivity(&mut self, kappa: &dyn Fn(f64, f64) -> f64) {
+        self.diffusivity = self
+            .mesh
+            .nodes
+            .points
+            .iter()
+            .map(|p| kappa(p.x, p.y))
+            .collect();
+    }
+
+    // (x, y) に最も近い内部点 (センサーの位置)。内部点がなければ None
+    #[allow(dead_code)]
+    pub fn nearest_interior_node(&self, x: f64, y: f64) -> Option<usize> {
+        let d2 = |i: usize| {
+            let p = self.mesh.point(i);
+            (p.x - x) * (p.x - x) + (p.y - y) * (p.y - y)
+        };
+        (0..self.mesh.num_interior).min_by(|a, b| d2(*a).total_cmp(&d2(*b)))
+    }
+
+    // 現在のパラメータで順問題を解き、nodes x steps の観測値を返す
+    #[allow(dead_code)]
+    pub fn observe(&self, nodes: &[usize], steps: &[usize]) -> Result<Vec<Observation>, String> {
+        let states = self.forward()?;
+        Ok(steps
+            .iter()
+            .flat_map(|step| {
+                let states = &states;
+                nodes.iter().map(move |node| Observation {
+                    node: *node,
+                    step: *step,
+                    value: states[*step][*node],
+                })
+            })
+            .collect())
+    }
+
+    #[allow(dead_code)]
+    pub fn parameters(&self, kind: Parameter) -> Vec<f64> {
+        match kind {
+            Parameter::Diffusivity => {
+                vec![self.diffusivity.iter().sum::<f64>() / self.diffusivity.len() as f64]
+            }
+            Parameter::DiffusivityField => self.diffusivity.clone(),
+            Parameter::InitialCondition => self.initial.clone(),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn set_parameters(&mut self, kind: Parameter, theta: &[f64]) {
+        match kind {
+            Parameter::Diffusivity => self.diffusivity = vec![theta[0]; self.mesh.num_nodes()],
+            Parameter::DiffusivityField => self.diffusivity = theta.to_vec(),
+            Parameter::InitialCondition => self.initial = theta.to_vec(),
+        }
+    }
+
+    // κ に対する ∇・(κ∇u) の行列 (内部点の行 x 全節点)
+    fn operator(&self, kappa: &[f64]) -> CsrMatrix {
+        let kappa_x = self.d_x.mul_vec(kappa);
+        let kappa_y = self.d_y.mul_vec(kappa);
+        let (lap, dx, dy) = (
+            self.laplacian.to_rows(),
+            self.d_x.to_rows(),
+            self.d_y.to_rows(),
+        );
+        let rows: Vec<Vec<(usize, f64)>> = (0..self.mesh.num_interior)
+            .map(|i| {
+                let mut row: Vec<(usize, f64)> =
+                    lap[i].iter().map(|(j, w)| (*j, kappa[i] * w)).collect();
+                row.extend(dx[i].iter().map(|(j, w)| (*j, kappa_x[i] * w)));
+                row.extend(dy[i].iter().map(|(j, w)| (*j, kappa_y[i] * w)));
+                row
+            })
+            .collect();
+        CsrMatrix::from_rows(self.mesh.num_nodes(), &rows)
+    }
+
+    fn boundary_values(&self, step: usize) -> Vec<f64> {
+        let t = step as f64 * self.dt;
+        self.mesh.nodes.points[self.mesh.num_interior..]
+            .iter()
+            .map(|p| self.boundary_value.eval(p.x, p.y, t))
+            .collect()
+    }
+
+    // 境界値を付け足した全節点のベクトル
+    fn full_state(&self, interior: &[f64], step: usize) -> Vec<f64> {
+        let mut u = interior.to_vec();
+        u.extend(self.boundary_values(step));
+        u
+    }
+
+    // (I - dt A_II) u^{n+1} = u^n + dt A_IB g^{n+1}。内部点の値 u^0, ..., u^N
+    // 以下、線形ソルバーが収束しなければ Err を返す
+    #[allow(dead_code)]
+    pub fn forward(&self) -> Result<Vec<Vec<f64>>, String> {
+        let n = self.mesh.num_interior;
+        let (a_ii, a_ib) = self
+            .mesh
+            .split_rows(&self.operator(&self.diffusivity).to_rows());
+        let mut solver = ShiftedSolver::new(a_ii);
+        let mut states = vec![self.initial.clone()];
+        for step in 1..(self.num_steps + 1) {
+            let lift = a_ib.mul_vec(&self.boundary_values(step));
+            let prev = &states[step - 1];
+            let b: Vec<f64> = (0..n).map(|i| prev[i] + self.dt * lift[i]).collect();
+            let next = solver.solve(self.dt, &b, prev)?;
+            states.push(next);
+        }
+        Ok(states)
+    }
+
+    // ステップごとの観測残差 ∂J/∂u^n
+    fn residuals(&self, states: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        let mut r = vec![vec![0.0; self.mesh.num_interior]; self.num_steps + 1];
+        for o in &self.observations {
+            assert!(
+                o.node < self.mesh.num_interior,
+                "sensor must be an interior node"
+            );
+            r[o.step][o.node] += states[o.step][o.node] - o.value;
+        }
+        r
+    }
+
+    // 正則化を掛ける全節点上の場 (初期値は境界に g(x, 0) を付け足す)
+    fn regularized_field(&self, kind: Parameter, theta: &[f64]) -> Vec<f64> {
+        match kind {
+            Parameter::InitialCondition => self.full_state(theta, 0),
+            _ => theta.to_vec(),
+        }
+    }
+
+    fn regularization_value(&self, kind: Parameter, theta: &[f64]) -> f64 {
+        match self.regularization {
+            Regularization::None => 0.0,
+            Regularization::Tikhonov { weight, prior } => {
+                0.5 * weight * theta.iter().map(|v| (v - prior) * (v - prior)).sum::<f64>()
+            }
+            Regularization::Smoothness(weight) => {
+                if kind == Parameter::Diffusivity {
+                    return 0.0;
+                }
+                let field = self.regularized_field(kind, theta);
+                let gx = self.d_x.mul_vec(&field);
+                let gy = self.d_y.mul_vec(&field);
+                0.5 * weight * gx.iter().chain(gy.iter()).map(|v| v * v).sum::<f64>()
+            }
+        }
+    }
+
+    fn regularization_gradient(&self, kind: Parameter, theta: &[f64]) -> Vec<f64> {
+        match self.regularization {
+            Regularization::None => vec![0.0; theta.len()],
+            Regularization::Tikhonov { weight, prior } => {
+                theta.iter().map(|v| weight * (v - prior)).collect()
+            }
+            Regularization::Smoothness(weight) => {
+                if kind == Parameter::Diffusivity {
+                    return vec![0.0; theta.len()];
+                }
+                let field = self.regularized_field(kind, theta);
+                let gx = self.d_x.transpose().mul_vec(&self.d_x.mul_vec(&field));
+                let gy = self.d_y.transpose().mul_vec(&self.d_y.mul_vec(&field));
+                (0..theta.len()).map(|i| weight * (gx[i] + gy[i])).collect()
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn misfit(&self, states: &[Vec<f64>]) -> f64 {
+        self.observations
+            .iter()
+            .map(|o| 0.5 * (states[o.step][o.node] - o.value).powi(2))
+            .sum()
+    }
+
+    // 現在のパラメータでの J
+    #[allow(dead_code)]
+    pub fn objective(&self, kind: Parameter) -> Result<f64, String> {
+        Ok(self.misfit(&self.forward()?) + self.regularization_value(kind, &self.parameters(kind)))
+    }
+
+    // 随伴方程式 (I - dt A_II)^T λ^n = λ^{n+1} + ∂J/∂u^n を n = N, ..., 1 と遡って解き、
+    // dJ/dθ = -Σ λ^n・∂R^n/∂θ (R^n は n ステップ目の後退 Euler の残差) を組み立てる
+    #[allow(dead_code)]
+    pub fn gradient(&self, kind: Parameter) -> Result<Vec<f64>, String> {
+        let n = self.mesh.num_interior;
+        let states = self.forward()?;
+        let residuals = self.residuals(&states);
+        let (a_ii, _) = self
+            .mesh
+            .split_rows(&self.operator(&self.diffusivity).to_rows());
+        let mut adjoint_solver = ShiftedSolver::new(a_ii.transpose());
+        let mut lambda = vec![0.0; n];
+        let mut grad_kappa = vec![0.0; self.mesh.num_nodes()];
+        let (d_x_t, d_y_t) = (self.d_x.transpose(), self.d_y.transpose());
+        for step in (1..(self.num_steps + 1)).rev() {
+            let b: Vec<f64> = (0..n).map(|i| lambda[i] + residuals[step][i]).collect();
+            lambda = adjoint_solver.solve(self.dt, &b, &lambda)?;
+            if kind == Parameter::InitialCondition {
+                continue;
+            }
+            // ∂(A(κ) u)_i/∂κ_m = δ_im Δu_i + (D_x)_im u_x,i + (D_y)_im u_y,i
+            let u = self.full_state(&states[step], step);
+            let lap_u = self.laplacian.mul_vec(&u);
+            let u_x = self.d_x.mul_vec(&u);
+            let u_y = self.d_y.mul_vec(&u);
+            let lx: Vec<f64> = (0..n).map(|i| lambda[i] * u_x[i]).collect();
+            let ly: Vec<f64> = (0..n).map(|i| lambda[i] * u_y[i]).collect();
+            let (gx, gy) = (d_x_t.mul_vec(&lx), d_y_t.mul_vec(&ly));
+            for m in 0..self.mesh.num_nodes() {
+                let local = if m < n { lambda[m] * lap_u[m] } else { 0.0 };
+                grad_kappa[m] += self.dt * (local + gx[m] + gy[m]);
+            }
+        }
+        let theta = self.parameters(kind);
+        let mut grad = match kind {
+            Parameter::Diffusivity => vec![grad_kappa.iter().sum()],
+            Parameter::DiffusivityField => grad_kappa,
+            Parameter::InitialCondition => (0..n).map(|i| lambda[i] + residuals[0][i]).collect(),
+        };
+        for (g, r) in grad
+            .iter_mut()
+            .zip(self.regularization_gradient(kind, &theta))
+        {
+            *g += r;
+        }
+        Ok(grad)
+    }
+
+    // 接線形モデル (I - dt A_II) δu^n = δu^{n-1} + dt A(δκ) u^n を前向きに解いた方向微分 dJ/dθ・δθ
+    #[allow(dead_code)]
+    pub fn directional_derivative(
+        &self,
+        kind: Parameter,
+        direction: &[f64],
+    ) -> Result<f64, String> {
+        let n = self.mesh.num_interior;
+        let states = self.forward()?;
+        let residuals = self.residuals(&states);
+        let (a_ii, _) = self
+            .mesh
+            .split_rows(&self.operator(&self.diffusivity).to_rows());
+        let mut solver = ShiftedSolver::new(a_ii);
+        let delta_kappa = match kind {
+            Parameter::Diffusivity => vec![direction[0]; self.mesh.num_nodes()],
+            Parameter::DiffusivityField => direction.to_vec(),
+            Parameter::InitialCondition => vec![0.0; self.mesh.num_nodes()],
+        };
+        let delta_operator = self.operator(&delta_kappa);
+        let mut du = match kind {
+            Parameter::InitialCondition => direction.to_vec(),
+            _ => vec![0.0; n],
+        };
+        let mut dj: f64 = (0..n).map(|i| residuals[0][i] * du[i]).sum();
+        for step in 1..(self.num_steps + 1) {
+            let forcing = delta_operator.mul_vec(&self.full_state(&states[step], step));
+            let b: Vec<f64> = (0..n).map(|i| du[i] + self.dt * forcing[i]).collect();
+            du = solver.solve(self.dt, &b, &du)?;
+            dj += (0..n).map(|i| residuals[step][i] * du[i]).sum::<f64>();
+        }
+        let theta = self.parameters(kind);
+        Ok(dj
+            + self
+                .regularization_gradient(kind, &theta)
+                .iter()
+                .zip(direction.iter())
+                .map(|(g, d)| g * d)
+                .sum::<f64>())
+    }
+
+    // 勾配流で J を最小化し、推定したパラメータを問題に設定する。
+    // 途中で線形ソルバーが収束しなければ、それまでに受理したパラメータを設定して Err を返す
+    #[allow(dead_code)]
+    pub fn calibrate(
+        &mut self,
+        kind: Parameter,
+        options: &GradientFlowOptions,
+    ) -> Result<InverseReport, String> {
+        let project = |theta: &mut Vec<f64>| {
+            if kind != Parameter::InitialCondition {
+                for v in theta.iter_mut() {
+                    *v = v.max(options.lower_bound);
+                }
+            }
+        };
+        let mut theta = self.parameters(kind);
+        let mut objective = self.objective(kind)?;
+        let mut history = vec![objective];
+        let mut tau = 0.0;
+        let mut iterations = 0;
+        'flow: while iterations < options.max_iter {
+            iterations += 1;
+            let grad = self.gradient(kind)?;
+            let g_max = grad.iter().fold(0.0_f64, |m, g| m.max(g.abs()));
+            if g_max == 0.0 {
+                break;
+            }
+            if tau == 0.0 {
+                tau = options.initial_step / g_max;
+            }
+            let theta_max = theta.iter().fold(0.0_f64, |m, t| m.max(t.abs()));
+            // 減少するまで刻みを縮める
+            loop {
+                let mut trial: Vec<f64> = theta
+                    .iter()
+                    .zip(grad.iter())
+                    .map(|(t, g)| t - tau * g)
+                    .collect();
+                project(&mut trial);
+                self.set_parameters(kind, &trial);
+                let value = match self.objective(kind) {
+                    Ok(value) => value,
+                    Err(e) => {
+                        self.set_parameters(kind, &theta);
+                        return Err(e);
+                    }
+                };
+                if value < objective {
+                    let decrease = (objective - value) / objective;
+                    theta = trial;
+                    objective = value;
+                    history.push(objective);
+                    tau *= options.growth;
+                    if decrease < options.tol {
+                        break 'flow;
+                    }
+                    break;
+                }
+                tau *= options.shrink;
+                if tau * g_max < 1.0e-14 * (1.0 + theta_max) {
+                    break 'flow;
+                }
+            }
+        }
+        self.set_parameters(kind, &theta);
+        Ok(InverseReport {
+            parameters: theta,
+            objective_history: history,
+            iterations,
+        })
+    }
+}
+
+impl fmt::Debug for HeatInverseProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HeatInverseProblem")
+            .field("num_nodes", &self.mesh.num_nodes())
+            .field("dt", &self.dt)
+            .field("num_steps", &self.num_steps)
+            .field("observations", &self.observations.len())
+            .field("regularization", &self.regularization)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Domain;
+    use crate::node_generator::{NodeGenerator, NodeStrategy, Spacing};
+    use rand::prelude::*;
+    use std::f64::consts::PI;
+
+    fn square(h: f64, dt: f64, num_steps: usize) -> HeatInverseProblem {
+        let domain = Domain::Rectangle {
+            x_min: 0.0,
+            y_min: 0.0,
+            x_max: 1.0,
+            y_max: 1.0,
+        };
+        let generator = NodeGenerator::new(domain, Spacing::Uniform(h), NodeStrategy::HexLattice);
+        let (boundary, interior) = generator.generate();
+        HeatInverseProblem::new(
+            MeshfreeNodes::new(&interior, &boundary, 2, 12),
+            dt,
+            num_steps,
+        )
+        .unwrap()
+    }
+
+    // 格子状に並べたセンサーで全ステップを観測し、相対 noise の雑音を加える
+    fn synthetic_data(problem: &mut HeatInverseProblem, sensors: usize, noise: f64) {
+        let s = (sensors + 1) as f64;
+        let mut nodes = vec![];
+        for i in 1..(sensors + 1) {
+            for j in 1..(sensors + 1) {
+                nodes.push(
+                    problem
+                        .nearest_interior_node(i as f64 / s, j as f64 / s)
+                        .unwrap(),
+                );
+            }
+        }
+        let steps: Vec<usize> = (1..(problem.num_steps + 1)).collect();
+        let mut rng = StdRng::seed_from_u64(7);
+        problem.observations = problem
+            .observe(&nodes, &steps)
+            .unwrap()
+            .into_iter()
+            .map(|o| Observation {
+                value: o.value * (1.0 + noise * (2.0 * rng.gen::<f64>() - 1.0)),
+                ..o
+            })
+            .collect();
+    }
+
+    fn hot_spot(x: f64, y: f64) -> f64 {
+        (PI * x).sin() * (PI * y).sin() + 0.5 * (2.0 * PI * x).sin() * (PI * y).sin()
+    }
+
+    #[test]
+    fn adjoint_matches_tangent_linear_and_finite_differences() {
+        let mut base = square(0.125, 0.01, 5);
+        base.set_initial_condition(&hot_spot);
+        base.set_diffusivity(&|x, y| 1.0 + 0.5 * x * y);
+        synthetic_data(&mut base, 3, 0.0);
+        // 観測後にパラメータをずらし、残差が 0 でない点で比べる
+        base.set_diffusivity(&|x, _| 0.8 + 0.3 * x);
+        base.set_initial_condition(&|x, y| hot_spot(x, y) + 0.2 * x * y);
+        base.regularization = Regularization::Smoothness(1.0e-3);
+        let mut rng = StdRng::seed_from_u64(3);
+        for kind in [
+            Parameter::Diffusivity,
+            Parameter::DiffusivityField,
+            Parameter::InitialCondition,
+        ] {
+            let mut problem = base.clone();
+            if kind == Parameter::Diffusivity {
+                problem.set_parameters(kind, &[0.8]);
+            }
+            let theta = problem.parameters(kind);
+            let direction: Vec<f64> = theta.iter().map(|_| rng.gen::<f64>() - 0.5).collect();
+            let adjoint: f64 = problem
+                .gradient(kind)
+                .unwrap()
+                .iter()
+                .zip(direction.iter())
+                .map(|(g, d)| g * d)
+                .sum();
+            let tangent = problem.directional_derivative(kind, &direction).unwrap();
+            let eps = 1.0e-6;
+            let shifted = |s: f64| {
+                let mut p = problem.parameters(kind);
+                for (t, d) in p.iter_mut().zip(direction.iter()) {
+                    *t += s * d;
+                }
+                let mut copy = problem.clone();
+                copy.set_parameters(kind, &p);
+                copy.objective(kind).unwrap()
+            };
+            let fd = (shifted(eps) - shifted(-eps)) / (2.0 * eps);
+            let scale = adjoint.abs().max(1.0e-12);
+            assert!(
+                (adjoint - tangent).abs() < 1.0e-7 * scale,
+                "{:?} {} {}",
+                kind,
+                adjoint,
+                tangent
+            );
+            assert!(
+                (adjoint - fd).abs() < 1.0e-5 * scale,
+                "{:?} {} {}",
+                kind,
+                adjoint,
+                fd
+            );
+        }
+    }
+
+    #[test]
+    fn nearest_interior_node_finds_sensor_position() {
+        let problem = square(0.1, 0.005, 1);
+        let n = problem.mesh.num_interior;
+        for i in [0, n / 2, n - 1] {
+            let p = problem.mesh.point(i);
+            assert_eq!(problem.nearest_interior_node(p.x, p.y), Some(i));
+            assert_eq!(
+                problem.nearest_interior_node(p.x + 1.0e-3, p.y - 1.0e-3),
+                Some(i)
+            );
+        }
+        assert!(problem.nearest_interior_node(f64::NAN, 0.5).is_some());
+    }
+
+    #[test]
+    fn recovers_constant_diffusivity() {
+        let mut problem = square(0.1, 0.005, 20);
+        problem.set_initial_condition(&hot_spot);
+        problem.set_parameters(Parameter::Diffusivity, &[0.5]);
+        synthetic_data(&mut problem, 4, 0.01);
+        problem.set_parameters(Parameter::Diffusivity, &[1.0]);
+        let report = problem
+            .calibrate(Parameter::Diffusivity, &GradientFlowOptions::default())
+            .unwrap();
+        let history = &report.objective_history;
+        assert!(
+            (report.parameters[0] - 0.5).abs() < 0.01,
+            "{:?}",
+            report.parameters
+        );
+        assert!(history.windows(2).all(|w| w[1] < w[0]));
+    }
+
+    #[test]
+    fn recovers_diffusivity_field() {
+        let kappa = |x: f64, _y: f64| 1.0 + 0.5 * x;
+        let mut problem = square(0.1, 0.005, 20);
+        problem.set_initial_condition(&hot_spot);
+        problem.set_diffusivity(&kappa);
+        synthetic_data(&mut problem, 6, 0.0);
+        problem.set_parameters(Parameter::Diffusivity, &[1.0]);
+        problem.regularization = Regularization::Smoothness(1.0e-6);
+        let error = |problem: &HeatInverseProblem| {
+            (0..problem.mesh.num_interior)
+                .map(|i| {
+                    let p = problem.mesh.point(i);
+                    (problem.diffusivity[i] - kappa(p.x, p.y)).abs()
+                })
+                .sum::<f64>()
+                / problem.mesh.num_interior as f64
+        };
+        let before = error(&problem);
+        let report = problem
+            .calibrate(Parameter::DiffusivityField, &GradientFlowOptions::default())
+            .unwrap();
+        let history = &report.objective_history;
+        let after = error(&problem);
+        assert!(history[history.len() - 1] < 1.0e-3 * history[0]);
+        assert!(after < 0.35 * before, "{} {}", before, after);
+    }
+
+    #[test]
+    fn recovers_initial_condition() {
+        let mut problem = square(0.1, 0.005, 20);
+        problem.set_initial_condition(&hot_spot);
+        synthetic_data(&mut problem, 6, 0.01);
+        problem.set_initial_condition(&|_, _| 0.0);
+        problem.regularization = Regularization::Smoothness(1.0e-4);
+        let report = problem
+            .calibrate(Parameter::InitialCondition, &GradientFlowOptions::default())
+            .unwrap();
+        let history = &report.objective_history;
+        // 相対 L2 誤差
+        let (e2, u2) = (0..problem.mesh.num_interior).fold((0.0, 0.0), |(e2, u2), i| {
+            let p = problem.mesh.point(i);
+            let u = hot_spot(p.x, p.y);
+            (e2 + (problem.initial[i] - u).powi(2), u2 + u * u)
+        });
+        let error = (e2 / u2).sqrt();
+        assert!(history[history.len() - 1] < 1.0e-2 * history[0]);
+        assert!(error < 0.15, "{}", error);
+    }
+}
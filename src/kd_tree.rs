@@ -4,7 +4,7 @@ pub struct Grid2D {
     pub y: f64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Points2D {
     pub points: Vec<Grid2D>,
 }
@@ -84,7 +84,45 @@ impl KDTree {
         near.clone()
     }
 
+    // 近い順に k 個
+    #[allow(dead_code)]
+    pub fn nearest_neighbors(&self, x: &Grid2D, k: usize) -> Vec<usize> {
+        let mut best = Vec::<(f64, usize)>::new();
+        self.search_nearest(x, k, &mut best, 0);
+        best.iter().map(|b| b.1).collect()
+    }
+
     #[allow(dead_code)]
+    fn search_nearest(&self, x: &Grid2D, k: usize, best: &mut Vec<(f64, usize)>, depth: i32) {
+        if k == 0 {
+            return;
+        }
+        let r2 = self.position.distance_square(x);
+        if best.len() < k || r2 < best[best.len() - 1].0 {
+            let pos = best.partition_point(|b| b.0 <= r2);
+            best.insert(pos, (r2, self.id));
+            best.truncate(k);
+        }
+        let diff = match depth % 2 {
+            0 => x.x - self.position.x,
+            _ => x.y - self.position.y,
+        };
+        let (near, far) = if diff < 0.0 {
+            (&self.left, &self.right)
+        } else {
+            (&self.right, &self.left)
+        };
+        if let Some(near_node) = near {
+            near_node.search_nearest(x, k, best, depth + 1);
+        }
+        if best.len() < k || diff * diff < best[best.len() - 1].0 {
+            if let Some(far_node) = far {
+                far_node.search_nearest(x, k, best, depth + 1);
+            }
+        }
+    }
+
+    #[allow(dead_code, clippy::single_match)]
     fn search_points_id(&self, x: &Grid2D, radius: f64, near: &mut Vec<usize>, mut depth: i32) {
         let axis = depth % 2;
         let r_self = self.position.distance_square(x).sqrt();
@@ -263,6 +301,12 @@ impl KDTree {
 }
 
 #[cfg(test)]
+#[allow(
+    clippy::unnecessary_mut_passed,
+    clippy::unnecessary_cast,
+    clippy::duration_subsec,
+    clippy::assign_op_pattern
+)]
 mod tests {
     use super::*;
 
@@ -336,6 +380,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn nearest_neighbors() {
+        use rand::prelude::*;
+        let seed: [u8; 32] = [1; 32];
+        let mut rng: rand::rngs::StdRng = rand::SeedableRng::from_seed(seed);
+        let num_point: usize = 500;
+
+        let mut vec = Points2D::new();
+
+        for _ in 0..num_point {
+            let x_r = 2.0 * (rng.gen::<f64>() - 0.5);
+            let y_r = 2.0 * (rng.gen::<f64>() - 0.5);
+            vec.push(x_r, y_r);
+        }
+
+        let tree = KDTree::construct_kd_tree(&vec);
+        let center = Grid2D { x: 0.1, y: -0.2 };
+        let near = tree.nearest_neighbors(&center, 7);
+
+        let mut brute: Vec<usize> = (0..num_point).collect();
+        brute.sort_by(|a, b| {
+            vec.points[*a]
+                .distance_square(&center)
+                .partial_cmp(&vec.points[*b].distance_square(&center))
+                .unwrap()
+        });
+        assert_eq!(near, brute[0..7].to_vec());
+    }
+
     #[test]
     fn count_leaves() {
         use rand::prelude::*;
@@ -0,0 +1,179 @@
+use crate::point::Point3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Points3D {
+    pub points: Vec<Point3>,
+}
+
+impl Points3D {
+    #[allow(dead_code)]
+    pub fn new() -> Self {
+        Points3D { points: vec![] }
+    }
+
+    #[allow(dead_code)]
+    pub fn push(&mut self, x: f64, y: f64, z: f64) {
+        self.points.push(Point3::new(x, y, z));
+    }
+}
+
+// 3 次元の k-d 木。格子状の節点を順に挿入すると偏るので、中央値で分割して作る
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct KDTree3D {
+    id: usize,
+    position: Point3,
+    axis: usize,
+    left: Option<Box<KDTree3D>>,
+    right: Option<Box<KDTree3D>>,
+}
+
+impl KDTree3D {
+    #[allow(dead_code)]
+    pub fn construct_kd_tree(points: &Points3D) -> KDTree3D {
+        assert!(!points.points.is_empty());
+        let mut ids: Vec<usize> = (0..points.points.len()).collect();
+        *Self::build(points, &mut ids, 0).unwrap()
+    }
+
+    fn build(points: &Points3D, ids: &mut [usize], depth: usize) -> Option<Box<KDTree3D>> {
+        if ids.is_empty() {
+            return None;
+        }
+        let axis = depth % 3;
+        let mid = ids.len() / 2;
+        ids.select_nth_unstable_by(mid, |a, b| {
+            points.points[*a]
+                .coord(axis)
+                .total_cmp(&points.points[*b].coord(axis))
+        });
+        let id = ids[mid];
+        let (left, rest) = ids.split_at_mut(mid);
+        Some(Box::new(KDTree3D {
+            id,
+            position: points.points[id].clone(),
+            axis,
+            left: Self::build(points, left, depth + 1),
+            right: Self::build(points, &mut rest[1..], depth + 1),
+        }))
+    }
+
+    // 近い順に k 個
+    #[allow(dead_code)]
+    pub fn nearest_neighbors(&self, x: &Point3, k: usize) -> Vec<usize> {
+        let mut best = Vec::<(f64, usize)>::new();
+        self.search_nearest(x, k, &mut best);
+        best.iter().map(|b| b.1).collect()
+    }
+
+    fn search_nearest(&self, x: &Point3, k: usize, best: &mut Vec<(f64, usize)>) {
+        if k == 0 {
+            return;
+        }
+        let r2 = self.position.distance_square(x);
+        if best.len() < k || r2 < best[best.len() - 1].0 {
+            let pos = best.partition_point(|b| b.0 <= r2);
+            best.insert(pos, (r2, self.id));
+            best.truncate(k);
+        }
+        let diff = x.coord(self.axis) - self.position.coord(self.axis);
+        let (near, far) = if diff < 0.0 {
+            (&self.left, &self.right)
+        } else {
+            (&self.right, &self.left)
+        };
+        if let Some(near_node) = near {
+            near_node.search_nearest(x, k, best);
+        }
+        if best.len() < k || diff * diff < best[best.len() - 1].0 {
+            if let Some(far_node) = far {
+                far_node.search_nearest(x, k, best);
+            }
+        }
+    }
+
+    // 半径 radius 未満の点
+    #[allow(dead_code)]
+    pub fn neighbor_search(&self, x: &Point3, radius: f64) -> Vec<usize> {
+        let mut near = vec![];
+        self.search_radius(x, radius, &mut near);
+        near
+    }
+
+    fn search_radius(&self, x: &Point3, radius: f64, near: &mut Vec<usize>) {
+        if self.position.distance_square(x) < radius * radius {
+            near.push(self.id);
+        }
+        let diff = x.coord(self.axis) - self.position.coord(self.axis);
+        if diff < radius {
+            if let Some(left) = &self.left {
+                left.search_radius(x, radius, near);
+            }
+        }
+        if -diff <= radius {
+            if let Some(right) = &self.right {
+                right.search_radius(x, radius, near);
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn depth(&self) -> usize {
+        let left = self.left.as_ref().map_or(0, |n| 1 + n.depth());
+        let right = self.right.as_ref().map_or(0, |n| 1 + n.depth());
+        left.max(right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_generator::halton;
+
+    #[test]
+    fn matches_brute_force() {
+        let mut points = Points3D::new();
+        for k in 0..2000 {
+            points.push(halton(k + 1, 2), halton(k + 1, 3), halton(k + 1, 5));
+        }
+        // 格子状に並んだ点でも平衡する
+        for i in 0..10 {
+            for j in 0..10 {
+                points.push(i as f64 * 0.1, j as f64 * 0.1, 0.5);
+            }
+        }
+        let tree = KDTree3D::construct_kd_tree(&points);
+        assert!(tree.depth() <= 12);
+        let x = Point3::new(0.3, 0.55, 0.5);
+        let mut brute: Vec<(f64, usize)> = points
+            .points
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (p.distance_square(&x), i))
+            .collect();
+        brute.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let near = tree.nearest_neighbors(&x, 20);
+        let expected: Vec<usize> = brute.iter().take(20).map(|b| b.1).collect();
+        assert_eq!(near, expected);
+        let mut within = tree.neighbor_search(&x, 0.1);
+        within.sort();
+        let mut expected: Vec<usize> = brute.iter().filter(|b| b.0 < 0.01).map(|b| b.1).collect();
+        expected.sort();
+        assert_eq!(within, expected);
+    }
+
+    #[test]
+    fn build_with_nan_point() {
+        // NaN の座標があっても構築で panic しない
+        let mut points = Points3D::new();
+        for k in 0..50 {
+            points.push(halton(k + 1, 2), halton(k + 1, 3), halton(k + 1, 5));
+        }
+        points.push(f64::NAN, 0.5, 0.5);
+        let tree = KDTree3D::construct_kd_tree(&points);
+        assert_eq!(
+            tree.nearest_neighbors(&Point3::new(0.5, 0.5, 0.5), 5).len(),
+            5
+        );
+    }
+}
@@ -0,0 +1,367 @@
+use crate::preconditioner::Preconditioner;
+use crate::sparse_matrix::CsrMatrix;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KrylovMethod {
+    Cg,
+    BiCgStab,
+    Gmres(usize), // リスタート長 m
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct KrylovResult {
+    pub x: Vec<f64>,
+    pub residual_history: Vec<f64>,
+    pub converged: bool,
+}
+
+#[allow(dead_code)]
+pub fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+}
+
+#[allow(dead_code)]
+pub fn norm(a: &[f64]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+// y += alpha x
+#[allow(dead_code)]
+pub fn axpy(alpha: f64, x: &[f64], y: &mut [f64]) {
+    for (yi, xi) in y.iter_mut().zip(x.iter()) {
+        *yi += alpha * xi;
+    }
+}
+
+#[allow(dead_code)]
+pub fn solve(
+    method: KrylovMethod,
+    a: &CsrMatrix,
+    b: &[f64],
+    x0: &[f64],
+    precond: &Preconditioner,
+    tol: f64,
+    max_iter: usize,
+) -> KrylovResult {
+    match method {
+        KrylovMethod::Cg => cg(a, b, x0, precond, tol, max_iter),
+        KrylovMethod::BiCgStab => bicgstab(a, b, x0, precond, tol, max_iter),
+        KrylovMethod::Gmres(m) => gmres(a, b, x0, precond, m, tol, max_iter),
+    }
+}
+
+fn residual(a: &CsrMatrix, b: &[f64], x: &[f64]) -> Vec<f64> {
+    let ax = a.mul_vec(x);
+    b.iter().zip(ax.iter()).map(|(bi, ai)| bi - ai).collect()
+}
+
+// 相対残差 |b - Ax| / |b| が tol 未満で収束
+#[allow(dead_code)]
+pub fn cg(
+    a: &CsrMatrix,
+    b: &[f64],
+    x0: &[f64],
+    precond: &Preconditioner,
+    tol: f64,
+    max_iter: usize,
+) -> KrylovResult {
+    let mut x = x0.to_vec();
+    let b_norm = norm(b).max(f64::MIN_POSITIVE);
+    let mut r = residual(a, b, &x);
+    let mut z = precond.apply(&r);
+    let mut p = z.clone();
+    let mut rz = dot(&r, &z);
+    let mut history = vec![norm(&r) / b_norm];
+    let mut converged = history[0] < tol;
+    for _ in 0..max_iter {
+        if converged {
+            break;
+        }
+        let ap = a.mul_vec(&p);
+        let alpha = rz / dot(&p, &ap);
+        axpy(alpha, &p, &mut x);
+        axpy(-alpha, &ap, &mut r);
+        history.push(norm(&r) / b_norm);
+        converged = history[history.len() - 1] < tol;
+        z = precond.apply(&r);
+        let rz_new = dot(&r, &z);
+        let beta = rz_new / rz;
+        for i in 0..p.len() {
+            p[i] = z[i] + beta * p[i];
+        }
+        rz = rz_new;
+    }
+    KrylovResult {
+        x,
+        residual_history: history,
+        converged,
+    }
+}
+
+// 右前処理付き
+#[allow(dead_code)]
+pub fn bicgstab(
+    a: &CsrMatrix,
+    b: &[f64],
+    x0: &[f64],
+    precond: &Preconditioner,
+    tol: f64,
+    max_iter: usize,
+) -> KrylovResult {
+    let n = b.len();
+    let mut x = x0.to_vec();
+    let b_norm = norm(b).max(f64::MIN_POSITIVE);
+    let mut r = residual(a, b, &x);
+    let r_hat = r.clone();
+    let mut rho = 1.0;
+    let mut alpha = 1.0;
+    let mut omega = 1.0;
+    let mut v = vec![0.0; n];
+    let mut p = vec![0.0; n];
+    let mut history = vec![norm(&r) / b_norm];
+    let mut converged = history[0] < tol;
+    for _ in 0..max_iter {
+        if converged {
+            break;
+        }
+        let rho_new = dot(&r_hat, &r);
+        if rho_new == 0.0 {
+            break;
+        }
+        let beta = (rho_new / rho) * (alpha / omega);
+        for i in 0..n {
+            p[i] = r[i] + beta * (p[i] - omega * v[i]);
+        }
+        let p_hat = precond.apply(&p);
+        v = a.mul_vec(&p_hat);
+        alpha = rho_new / dot(&r_hat, &v);
+        let mut s = r.clone();
+        axpy(-alpha, &v, &mut s);
+        if norm(&s) / b_norm < tol {
+            axpy(alpha, &p_hat, &mut x);
+            history.push(norm(&s) / b_norm);
+            converged = true;
+            break;
+        }
+        let s_hat = precond.apply(&s);
+        let t = a.mul_vec(&s_hat);
+        omega = dot(&t, &s) / dot(&t, &t);
+        axpy(alpha, &p_hat, &mut x);
+        axpy(omega, &s_hat, &mut x);
+        r = s;
+        axpy(-omega, &t, &mut r);
+        rho = rho_new;
+        history.push(norm(&r) / b_norm);
+        converged = history[history.len() - 1] < tol;
+        if omega == 0.0 {
+            break;
+        }
+    }
+    KrylovResult {
+        x,
+        residual_history: history,
+        converged,
+    }
+}
+
+// GMRES(m)、右前処理付き。max_iter は内部反復の総数
+#[allow(dead_code)]
+pub fn gmres(
+    a: &CsrMatrix,
+    b: &[f64],
+    x0: &[f64],
+    precond: &Preconditioner,
+    m: usize,
+    tol: f64,
+    max_iter: usize,
+) -> KrylovResult {
+    let m = m.max(1);
+    let mut x = x0.to_vec();
+    let b_norm = norm(b).max(f64::MIN_POSITIVE);
+    let mut r = residual(a, b, &x);
+    let mut history = vec![norm(&r) / b_norm];
+    let mut converged = history[0] < tol;
+    let mut iter = 0;
+    while !converged && iter < max_iter {
+        let beta = norm(&r);
+        let mut basis: Vec<Vec<f64>> = vec![r.iter().map(|ri| ri / beta).collect()];
+        let mut z_basis: Vec<Vec<f64>> = vec![];
+        let mut h = vec![vec![0.0; m]; m + 1];
+        let mut cs = vec![0.0; m];
+        let mut sn = vec![0.0; m];
+        let mut g = vec![0.0; m + 1];
+        g[0] = beta;
+        let mut k_used = 0;
+        for k in 0..m {
+            if iter >= max_iter {
+                break;
+            }
+            iter += 1;
+            let z = precond.apply(&basis[k]);
+            let mut w = a.mul_vec(&z);
+            z_basis.push(z);
+            // 修正 Gram-Schmidt
+            for j in 0..(k + 1) {
+                h[j][k] = dot(&w, &basis[j]);
+                axpy(-h[j][k], &basis[j], &mut w);
+            }
+            h[k + 1][k] = norm(&w);
+            for j in 0..k {
+                let t = cs[j] * h[j][k] + sn[j] * h[j + 1][k];
+                h[j + 1][k] = -sn[j] * h[j][k] + cs[j] * h[j + 1][k];
+                h[j][k] = t;
+            }
+            let d = (h[k][k] * h[k][k] + h[k + 1][k] * h[k + 1][k]).sqrt();
+            cs[k] = h[k][k] / d;
+            sn[k] = h[k + 1][k] / d;
+            h[k][k] = d;
+            h[k + 1][k] = 0.0;
+            g[k + 1] = -sn[k] * g[k];
+            g[k] *= cs[k];
+            k_used = k + 1;
+            history.push(g[k + 1].abs() / b_norm);
+            converged = history[history.len() - 1] < tol;
+            let w_norm = norm(&w);
+            if converged || w_norm == 0.0 {
+                break;
+            }
+            basis.push(w.iter().map(|wi| wi / w_norm).collect());
+        }
+        let mut y = vec![0.0; k_used];
+        for i in (0..k_used).rev() {
+            let mut t = g[i];
+            for j in (i + 1)..k_used {
+                t -= h[i][j] * y[j];
+            }
+            y[i] = t / h[i][i];
+        }
+        for (yi, zi) in y.iter().zip(z_basis.iter()) {
+            axpy(*yi, zi, &mut x);
+        }
+        r = residual(a, b, &x);
+        let last = history.len() - 1;
+        history[last] = norm(&r) / b_norm;
+        converged = history[last] < tol;
+        if k_used == 0 {
+            break;
+        }
+    }
+    KrylovResult {
+        x,
+        residual_history: history,
+        converged,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preconditioner::PreconditionerType;
+
+    // 1 次元ラプラシアン (SPD)
+    fn laplace_1d(n: usize) -> CsrMatrix {
+        let rows: Vec<Vec<(usize, f64)>> = (0..n)
+            .map(|i| {
+                let mut row = vec![(i, 2.0)];
+                if i > 0 {
+                    row.push((i - 1, -1.0));
+                }
+                if i + 1 < n {
+                    row.push((i + 1, -1.0));
+                }
+                row
+            })
+            .collect();
+        CsrMatrix::from_rows(n, &rows)
+    }
+
+    #[test]
+    fn cg_laplace() {
+        let a = laplace_1d(50);
+        let exact: Vec<f64> = (0..50).map(|i| (i as f64 * 0.1).sin()).collect();
+        let b = a.mul_vec(&exact);
+        let res = cg(
+            &a,
+            &b,
+            &vec![0.0; 50],
+            &Preconditioner::Identity,
+            1.0e-12,
+            200,
+        );
+        assert!(res.converged);
+        let pre = Preconditioner::new(PreconditionerType::Jacobi, &a);
+        let res_jacobi = cg(&a, &b, &vec![0.0; 50], &pre, 1.0e-12, 200);
+        assert!(res_jacobi.converged);
+        for (x, e) in res.x.iter().zip(exact.iter()) {
+            assert!((x - e).abs() < 1.0e-9);
+        }
+    }
+
+    fn nonsymmetric(n: usize) -> CsrMatrix {
+        let rows: Vec<Vec<(usize, f64)>> = (0..n)
+            .map(|i| {
+                let mut row = vec![(i, 3.0)];
+                if i > 0 {
+                    row.push((i - 1, -1.5));
+                }
+                if i + 1 < n {
+                    row.push((i + 1, -0.5));
+                }
+                row
+            })
+            .collect();
+        CsrMatrix::from_rows(n, &rows)
+    }
+
+    #[test]
+    fn nonsymmetric_all_methods() {
+        let n = 40;
+        let a = nonsymmetric(n);
+        let exact: Vec<f64> = (0..n).map(|i| 1.0 + i as f64).collect();
+        let b = a.mul_vec(&exact);
+        for method in [KrylovMethod::BiCgStab, KrylovMethod::Gmres(10)] {
+            for kind in [
+                PreconditionerType::Identity,
+                PreconditionerType::Jacobi,
+                PreconditionerType::Ilu0,
+            ] {
+                let pre = Preconditioner::new(kind, &a);
+                let res = solve(method, &a, &b, &vec![0.0; n], &pre, 1.0e-12, 400);
+                assert!(res.converged, "{:?} {:?}", method, kind);
+                assert!(res.residual_history.len() > 1);
+                for (x, e) in res.x.iter().zip(exact.iter()) {
+                    assert!((x - e).abs() < 1.0e-8);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ilu0_reduces_gmres_iterations() {
+        let n = 200;
+        let a = nonsymmetric(n);
+        let b = vec![1.0; n];
+        let plain = gmres(
+            &a,
+            &b,
+            &vec![0.0; n],
+            &Preconditioner::Identity,
+            20,
+            1.0e-10,
+            1000,
+        );
+        let ilu = gmres(
+            &a,
+            &b,
+            &vec![0.0; n],
+            &Preconditioner::ilu0(&a),
+            20,
+            1.0e-10,
+            1000,
+        );
+        assert!(plain.converged && ilu.converged);
+        assert!(ilu.residual_history.len() < plain.residual_history.len());
+    }
+}
@@ -1,26 +1,207 @@
+mod absorbing;
+mod bessel;
+mod boundary_condition;
+mod cell_list;
+mod checkpoint;
+mod convergence;
+mod dense_matrix;
+mod diagnostics;
+mod domain;
+mod domain_3d;
+mod eigen;
+mod expression;
 mod grid_3d;
+mod implicit;
+mod inverse;
 mod kd_tree;
+mod kd_tree_3d;
+mod krylov;
+mod meshfree;
+mod meshfree_3d;
+mod node_generator;
+mod node_quality;
+mod nonlinear;
+mod pde_model;
 mod point;
+mod preconditioner;
+mod refinement;
+mod runge_kutta;
+mod sparse_matrix;
+mod steady;
+mod stencil;
+mod stencil_3d;
+mod surface;
+mod system;
+mod transport;
 mod two_variable_polynomial;
 mod visualization;
 mod wave_eqation;
 
+// コマンドライン引数。引数の順序で結果が変わらないよう、すべて読んでから実行する
+//   --convergence=結果.csv  製造解の Poisson 問題の収束試験だけを行う (グラフは .png)
+//   --restart=パス          保存した状態から続ける (節点の生成と多項式の当てはめを省く)
+//   --initial=式 --velocity=式 | --initial-file=パス  初期条件 (式では x, y, t, r が使える)
+//   --source=式             右辺に加える f(x, y, t)
+//   --quality               節点集合の品質を表示する
+//   --eigen=k               Dirichlet Laplacian の低い k 個の固有値を表示する
+//   --diagnostics=パス.csv  各ステップの質量・エネルギー・max |u| を書き出し、発散したら止める
+//   --checkpoint=パス       10 ステップごとに状態を保存する
+const USAGE: &str = "usage: interpolation-in-regression [--convergence=PATH.csv] \
+[--restart=PATH | --initial=EXPR [--velocity=EXPR] | --initial-file=PATH] [--source=EXPR] \
+[--quality] [--eigen=K] [--diagnostics=PATH.csv] [--checkpoint=PATH]";
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    convergence: Option<String>,
+    restart: Option<String>,
+    initial: Option<String>,
+    velocity: Option<String>,
+    initial_file: Option<String>,
+    source: Option<String>,
+    quality: bool,
+    eigen: Option<usize>,
+    diagnostics: Option<String>,
+    checkpoint: Option<String>,
+}
+
+impl Options {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+        for arg in args {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, value.to_string()),
+                None if arg == "--quality" => {
+                    options.quality = true;
+                    continue;
+                }
+                None => return Err(format!("unknown argument {}", arg)),
+            };
+            let slot = match name {
+                "--convergence" => &mut options.convergence,
+                "--restart" => &mut options.restart,
+                "--initial" => &mut options.initial,
+                "--velocity" => &mut options.velocity,
+                "--initial-file" => &mut options.initial_file,
+                "--source" => &mut options.source,
+                "--diagnostics" => &mut options.diagnostics,
+                "--checkpoint" => &mut options.checkpoint,
+                "--eigen" => {
+                    let k = value
+                        .parse()
+                        .map_err(|_| format!("--eigen needs a count, got {}", value))?;
+                    options.eigen = Some(k);
+                    continue;
+                }
+                _ => return Err(format!("unknown argument {}", arg)),
+            };
+            if slot.replace(value).is_some() {
+                return Err(format!("{} given more than once", name));
+            }
+        }
+        let initial_sources = [
+            options.restart.is_some(),
+            options.initial.is_some() || options.velocity.is_some(),
+            options.initial_file.is_some(),
+        ];
+        if initial_sources.iter().filter(|given| **given).count() > 1 {
+            return Err(
+                "--restart, --initial/--velocity and --initial-file cannot be combined".to_string(),
+            );
+        }
+        let convergence_only = Options {
+            convergence: options.convergence.clone(),
+            ..Options::default()
+        };
+        if options.convergence.is_some() && options != convergence_only {
+            return Err("--convergence cannot be combined with other arguments".to_string());
+        }
+        Ok(options)
+    }
+}
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("{}\n{}", msg, USAGE);
+    std::process::exit(2);
+}
+
 fn main() {
+    let options = Options::parse(std::env::args().skip(1)).unwrap_or_else(|msg| usage_error(&msg));
+    if let Some(path) = &options.convergence {
+        let mut study =
+            convergence::ConvergenceStudy::new(convergence::Problem::manufactured_poisson());
+        study.stencil_sizes = vec![12, 20, 30];
+        study.degrees = vec![2, 3, 4];
+        let records = study.run();
+        convergence::save_csv(path, &records).unwrap_or_else(|e| panic!("{}", e));
+        visualization::draw_convergence(
+            &records,
+            &(path.trim_end_matches(".csv").to_string() + ".png"),
+        );
+        for rate in convergence::observed_rates(&records) {
+            println!("{:?}", rate);
+        }
+        return;
+    }
+
     let dt = 1.0e-3;
     let tol = 1.0e-4;
     let degree = 2;
     let num_poiunt = 70;
     let num_neighbor = 5;
-    let mut wave: wave_eqation::WaveEq = wave_eqation::WaveEq::make(num_poiunt, degree, tol, num_neighbor);
+    let (mut wave, start_step) = match &options.restart {
+        Some(path) => {
+            let saved = checkpoint::Checkpoint::load(path).unwrap_or_else(|e| panic!("{}", e));
+            (saved.wave, saved.step)
+        }
+        None => (
+            wave_eqation::WaveEq::make(num_poiunt, degree, tol, num_neighbor),
+            0,
+        ),
+    };
+    if let Some(e) = &options.source {
+        wave.source =
+            Some(pde_model::Source::from_expression(e).unwrap_or_else(|msg| usage_error(&msg)));
+    }
+    if let Some(path) = &options.initial_file {
+        wave.load_initial_condition(path, dt)
+            .unwrap_or_else(|e| panic!("{}", e));
+    } else if options.restart.is_none() {
+        let initial = options
+            .initial
+            .as_deref()
+            .unwrap_or("exp(-10 * (x * x + y * y))");
+        let velocity = options.velocity.as_deref().unwrap_or("0");
+        wave.set_initial_condition_from_expression(initial, velocity, dt)
+            .unwrap_or_else(|msg| usage_error(&msg));
+    }
+    if options.quality {
+        let report = node_quality::NodeQuality::default().report(
+            &wave.domain,
+            &wave.boundary,
+            &wave.interior,
+        );
+        println!("{}", report);
+    }
+    if let Some(k) = options.eigen {
+        let mesh = meshfree::MeshfreeNodes::from_wave(&wave, degree, 12);
+        let report = eigen::SpectrumReport::unit_disk(&mesh, k).unwrap_or_else(|e| panic!("{}", e));
+        println!("{}", report);
+    }
+    let diagnostics_path = options.diagnostics;
+    let checkpoint_path = options.checkpoint;
 
-    for t in 0..1000 {
+    let mut monitor = diagnostics_path.as_ref().map(|_| {
+        diagnostics::DiagnosticMonitor::new(&wave, diagnostics::Diagnostics::default())
+            .unwrap_or_else(|e| panic!("{}", e))
+    });
+    'time: for t in (start_step as i32 / 10)..1000 {
         let n = 25;
         let mut vec = grid_3d::Grid3D::new();
         for i in -n..n {
             for j in -n..n {
                 let x = i as f64 / n as f64;
                 let y = j as f64 / n as f64;
-                if x * x + y * y < 1.0 {
+                if wave.domain.contains(x, y) {
                     let p = point::Point3::new(x, y, wave.poly_eval(x, y));
                     vec.push(p);
                 }
@@ -37,6 +218,55 @@ fn main() {
         for j in 0..10 {
             println!("{} {}", t, j);
             wave.step(tol, dt);
+            if let Some(monitor) = monitor.as_mut() {
+                if let Err(msg) = monitor.record(&wave, dt) {
+                    println!("aborted: {}", msg);
+                    break 'time;
+                }
+            }
         }
+        if let Some(path) = &checkpoint_path {
+            checkpoint::Checkpoint::new(&wave, 10 * (t as usize + 1))
+                .save(path)
+                .unwrap_or_else(|e| panic!("{}", e));
+        }
+    }
+    if let (Some(monitor), Some(path)) = (monitor, diagnostics_path) {
+        monitor.save_csv(&path).unwrap_or_else(|e| panic!("{}", e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn options_do_not_depend_on_order() {
+        let a = parse(&["--initial=x * y", "--velocity=1", "--quality", "--eigen=3"]).unwrap();
+        let b = parse(&["--eigen=3", "--velocity=1", "--quality", "--initial=x * y"]).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.initial.as_deref(), Some("x * y"));
+        assert_eq!(a.eigen, Some(3));
+        assert!(a.quality);
+        assert_eq!(parse(&[]).unwrap(), Options::default());
+    }
+
+    #[test]
+    fn rejects_conflicts_and_unknown_arguments() {
+        assert!(parse(&["--initial-file=u.txt", "--initial=x"]).is_err());
+        assert!(parse(&["--initial=x", "--initial-file=u.txt"]).is_err());
+        assert!(parse(&["--initial-file=u.txt", "--velocity=1"]).is_err());
+        assert!(parse(&["--restart=s.txt", "--initial=x"]).is_err());
+        assert!(parse(&["--initial=x", "--initial=y"]).is_err());
+        assert!(parse(&["--convergence=c.csv", "--quality"]).is_err());
+        assert!(parse(&["--eigen=many"]).is_err());
+        assert!(parse(&["--unknown"]).is_err());
+        assert!(parse(&["--unknown=1"]).is_err());
+        assert!(parse(&["--convergence=c.csv"]).is_ok());
+        assert!(parse(&["--restart=s.txt", "--source=t", "--checkpoint=s.txt"]).is_ok());
     }
 }
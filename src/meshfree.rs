@@ -0,0 +1,343 @@
+use crate::kd_tree;
+use crate::kd_tree::{Grid2D, Points2D};
+use crate::sparse_matrix::CsrMatrix;
+use crate::stencil;
+use crate::stencil::DiffOperator;
+use crate::wave_eqation::WaveEq;
+
+// divergence_rows で層の界面とみなす、ステンシル内の κ の最大 / 最小
+const JUMP_RATIO: f64 = 2.0;
+
+// 内部点と境界点をまとめた節点集合と、内部点ごとの近傍（ステンシル）
+// 節点番号は内部点 0..num_interior の後に境界点が続く
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct MeshfreeNodes {
+    pub nodes: Points2D,
+    pub num_interior: usize,
+    pub degree: usize,
+    pub stencils: Vec<Vec<usize>>,
+    pub boundary_stencils: Vec<Vec<usize>>, // Neumann / Robin 境界の行用
+    pub normals: Vec<Grid2D>,               // 境界点の外向き単位法線
+}
+
+impl MeshfreeNodes {
+    #[allow(dead_code)]
+    pub fn new(
+        interior: &Points2D,
+        boundary: &Points2D,
+        degree: usize,
+        num_neighbor: usize,
+    ) -> Self {
+        let mut nodes = interior.clone();
+        nodes.points.extend(boundary.points.iter().cloned());
+        let tree = kd_tree::KDTree::construct_kd_tree(&nodes);
+        let stencils = interior
+            .points
+            .iter()
+            .map(|p| tree.nearest_neighbors(p, num_neighbor))
+            .collect();
+        let boundary_stencils = boundary
+            .points
+            .iter()
+            .map(|p| tree.nearest_neighbors(p, num_neighbor))
+            .collect();
+        MeshfreeNodes {
+            nodes,
+            num_interior: interior.points.len(),
+            degree,
+            stencils,
+            boundary_stencils,
+            normals: vec![],
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn from_wave(wave: &WaveEq, degree: usize, num_neighbor: usize) -> Self {
+        let mut mesh = MeshfreeNodes::new(&wave.interior, &wave.boundary, degree, num_neighbor);
+        mesh.normals = (0..wave.boundary.points.len())
+            .map(|k| wave.boundary_normal(k))
+            .collect();
+        mesh
+    }
+
+    #[allow(dead_code)]
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.points.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn num_boundary(&self) -> usize {
+        self.num_nodes() - self.num_interior
+    }
+
+    #[allow(dead_code)]
+    pub fn point(&self, index: usize) -> &Grid2D {
+        &self.nodes.points[index]
+    }
+
+    // 内部点 i で (L u)(x_i) ≈ Σ w_j u_j となる (j, w_j)。点が集まりすぎたり一直線に並んだりして
+    // 重みが決まらなければ Err
+    #[allow(dead_code)]
+    pub fn stencil_weights(
+        &self,
+        i: usize,
+        op: &DiffOperator,
+    ) -> Result<Vec<(usize, f64)>, String> {
+        let neighbors: Vec<Grid2D> = self.stencils[i]
+            .iter()
+            .map(|j| self.nodes.points[*j].clone())
+            .collect();
+        let w = stencil::weights(&self.nodes.points[i], &neighbors, self.degree, op)
+            .ok_or_else(|| format!("degenerate stencil at interior node {}", i))?;
+        Ok(self.stencils[i].iter().cloned().zip(w).collect())
+    }
+
+    // 境界点 k (節点番号 num_interior + k) での α u + β ∂u/∂n の行
+    #[allow(dead_code)]
+    pub fn boundary_row(
+        &self,
+        k: usize,
+        alpha: f64,
+        beta: f64,
+    ) -> Result<Vec<(usize, f64)>, String> {
+        let index = self.num_interior + k;
+        if beta == 0.0 {
+            return Ok(vec![(index, alpha)]);
+        }
+        let n = self
+            .normals
+            .get(k)
+            .ok_or_else(|| format!("no normal for boundary node {}", k))?;
+        let op = DiffOperator::identity()
+            .scale(alpha)
+            .add(&DiffOperator::d_x().scale(beta * n.x))
+            .add(&DiffOperator::d_y().scale(beta * n.y));
+        let neighbors: Vec<Grid2D> = self.boundary_stencils[k]
+            .iter()
+            .map(|j| self.nodes.points[*j].clone())
+            .collect();
+        let w = stencil::weights(&self.nodes.points[index], &neighbors, self.degree, &op)
+            .ok_or_else(|| format!("degenerate stencil at boundary node {}", k))?;
+        Ok(self.boundary_stencils[k].iter().cloned().zip(w).collect())
+    }
+
+    // 内部点の行だけを持つ num_interior x num_nodes の行列
+    #[allow(dead_code)]
+    pub fn operator_rows(&self, op: &DiffOperator) -> Result<Vec<Vec<(usize, f64)>>, String> {
+        (0..self.num_interior)
+            .map(|i| self.stencil_weights(i, op))
+            .collect()
+    }
+
+    // 節点ごとに異なる作用素 (変数係数) の行
+    #[allow(dead_code)]
+    pub fn operator_rows_at(
+        &self,
+        op_at: &dyn Fn(usize) -> DiffOperator,
+    ) -> Result<Vec<Vec<(usize, f64)>>, String> {
+        (0..self.num_interior)
+            .map(|i| self.stencil_weights(i, &op_at(i)))
+            .collect()
+    }
+
+    // 境界点の行も含めた num_nodes x num_nodes の行列 (境界での勾配など)
+    #[allow(dead_code)]
+    pub fn node_rows(&self, op: &DiffOperator) -> Result<Vec<Vec<(usize, f64)>>, String> {
+        let mut rows = self.operator_rows(op)?;
+        for (k, s) in self.boundary_stencils.iter().enumerate() {
+            let neighbors: Vec<Grid2D> = s.iter().map(|j| self.point(*j).clone()).collect();
+            let w = stencil::weights(
+                self.point(self.num_interior + k),
+                &neighbors,
+                self.degree,
+                op,
+            )
+            .ok_or_else(|| format!("degenerate stencil at boundary node {}", k))?;
+            rows.push(s.iter().cloned().zip(w).collect());
+        }
+        Ok(rows)
+    }
+
+    // ∇・(κ ∇u) の内部点の行。κ は節点場 kappa[j] = [κ_xx, κ_xy, κ_yy] (全節点) で与える。
+    // ステンシル内で κ が滑らかな節点では非保存形 κ : ∇∇u + (∇・κ)・∇u を fitted 重みで近似し、
+    // κ の対角成分が JUMP_RATIO 倍以上変わる (層の界面をまたぐ) 節点では
+    // Σ_j (κ_ij : W_ij) (u_j - u_i) とする。W_ij は 2 階微分の重み、κ_ij は対角成分が調和平均、
+    // 非対角成分が算術平均で、直列の層を通る流束の平均になる
+    #[allow(dead_code)]
+    pub fn divergence_rows(&self, kappa: &[[f64; 3]]) -> Result<Vec<Vec<(usize, f64)>>, String> {
+        assert_eq!(kappa.len(), self.num_nodes());
+        (0..self.num_interior)
+            .map(|i| {
+                let diagonal = |c: usize| {
+                    self.stencils[i]
+                        .iter()
+                        .map(|j| kappa[*j][c])
+                        .fold((f64::MAX, f64::MIN), |(lo, hi), k| (lo.min(k), hi.max(k)))
+                };
+                let jump = [diagonal(0), diagonal(2)]
+                    .iter()
+                    .any(|(lo, hi)| *hi > JUMP_RATIO * *lo);
+                if jump {
+                    self.interface_row(i, kappa)
+                } else {
+                    self.stencil_weights(i, &self.divergence_operator(i, kappa)?)
+                }
+            })
+            .collect()
+    }
+
+    // 節点 i での κ : ∇∇ + (∇・κ)・∇ 。∇・κ は節点場に 1 階微分の重みを掛けて求める
+    fn divergence_operator(&self, i: usize, kappa: &[[f64; 3]]) -> Result<DiffOperator, String> {
+        let w_x = self.stencil_weights(i, &DiffOperator::d_x())?;
+        let w_y = self.stencil_weights(i, &DiffOperator::d_y())?;
+        let (mut div_x, mut div_y) = (0.0, 0.0);
+        for (&(j, a), &(_, b)) in w_x.iter().zip(w_y.iter()) {
+            div_x += a * kappa[j][0] + b * kappa[j][1];
+            div_y += a * kappa[j][1] + b * kappa[j][2];
+        }
+        let [k_xx, k_xy, k_yy] = kappa[i];
+        Ok(DiffOperator {
+            c: 0.0,
+            c_x: div_x,
+            c_y: div_y,
+            c_xx: k_xx,
+            c_xy: 2.0 * k_xy,
+            c_yy: k_yy,
+        })
+    }
+
+    // 界面をまたぐ節点の行 Σ_j (κ_ij : W_ij) (u_j - u_i)
+    fn interface_row(&self, i: usize, kappa: &[[f64; 3]]) -> Result<Vec<(usize, f64)>, String> {
+        let second = |c_xx: f64, c_xy: f64, c_yy: f64| DiffOperator {
+            c: 0.0,
+            c_x: 0.0,
+            c_y: 0.0,
+            c_xx,
+            c_xy,
+            c_yy,
+        };
+        let harmonic = |a: f64, b: f64| {
+            if a + b == 0.0 {
+                0.0
+            } else {
+                2.0 * a * b / (a + b)
+            }
+        };
+        let w_xx = self.stencil_weights(i, &second(1.0, 0.0, 0.0))?;
+        let w_xy = self.stencil_weights(i, &second(0.0, 1.0, 0.0))?;
+        let w_yy = self.stencil_weights(i, &second(0.0, 0.0, 1.0))?;
+        let mut row = vec![];
+        let mut center = 0.0;
+        for ((&(j, a), &(_, b)), &(_, c)) in w_xx.iter().zip(w_xy.iter()).zip(w_yy.iter()) {
+            if j == i {
+                continue;
+            }
+            let w = harmonic(kappa[i][0], kappa[j][0]) * a
+                + (kappa[i][1] + kappa[j][1]) * b
+                + harmonic(kappa[i][2], kappa[j][2]) * c;
+            row.push((j, w));
+            center -= w;
+        }
+        row.push((i, center));
+        Ok(row)
+    }
+
+    // 行を内部点の列 A_II と境界点の列 A_IB に分ける
+    #[allow(dead_code)]
+    pub fn split_rows(&self, rows: &[Vec<(usize, f64)>]) -> (CsrMatrix, CsrMatrix) {
+        let n = self.num_interior;
+        let mut interior_rows = vec![];
+        let mut boundary_rows = vec![];
+        for row in rows {
+            interior_rows.push(row.iter().filter(|e| e.0 < n).cloned().collect::<Vec<_>>());
+            boundary_rows.push(
+                row.iter()
+                    .filter(|e| e.0 >= n)
+                    .map(|e| (e.0 - n, e.1))
+                    .collect::<Vec<_>>(),
+            );
+        }
+        (
+            CsrMatrix::from_rows(n, &interior_rows),
+            CsrMatrix::from_rows(self.num_boundary(), &boundary_rows),
+        )
+    }
+
+    #[allow(dead_code)]
+    pub fn interior_system(&self, op: &DiffOperator) -> Result<(CsrMatrix, CsrMatrix), String> {
+        Ok(self.split_rows(&self.operator_rows(op)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn laplacian_of_quadratic() {
+        let mut wave = WaveEq::new();
+        wave.create(25);
+        let mesh = MeshfreeNodes::from_wave(&wave, 2, 12);
+        assert_eq!(mesh.num_nodes(), 74);
+        assert_eq!(mesh.num_boundary(), 25);
+        let u: Vec<f64> = mesh
+            .nodes
+            .points
+            .iter()
+            .map(|p| p.x * p.x + 3.0 * p.y * p.y - p.x * p.y)
+            .collect();
+        let (a_ii, a_ib) = mesh.interior_system(&DiffOperator::laplacian()).unwrap();
+        let lu_i = a_ii.mul_vec(&u[0..mesh.num_interior]);
+        let lu_b = a_ib.mul_vec(&u[mesh.num_interior..]);
+        for i in 0..mesh.num_interior {
+            assert!((lu_i[i] + lu_b[i] - 8.0).abs() < 1.0e-8);
+        }
+    }
+
+    #[test]
+    fn divergence_of_variable_flux() {
+        use crate::domain::Domain;
+        use crate::node_generator::{NodeGenerator, NodeStrategy, Spacing};
+        let generator = NodeGenerator::new(
+            Domain::unit_circle(),
+            Spacing::Uniform(0.05),
+            NodeStrategy::HexLattice,
+        );
+        let (boundary, interior) = generator.generate();
+        let mesh = MeshfreeNodes::new(&interior, &boundary, 2, 12);
+        // κ = 2 + x, u = x^2 + y^2: ∇・(κ ∇u) = 8 + 6x
+        let kappa: Vec<[f64; 3]> = mesh
+            .nodes
+            .points
+            .iter()
+            .map(|p| [2.0 + p.x, 0.0, 2.0 + p.x])
+            .collect();
+        let u: Vec<f64> = mesh
+            .nodes
+            .points
+            .iter()
+            .map(|p| p.x * p.x + p.y * p.y)
+            .collect();
+        let a = CsrMatrix::from_rows(mesh.num_nodes(), &mesh.divergence_rows(&kappa).unwrap());
+        let lu = a.mul_vec(&u);
+        let error = lu
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (v - 8.0 - 6.0 * mesh.point(i).x).abs())
+            .fold(0.0, f64::max);
+        assert!(error < 1.0e-7, "{}", error);
+        // 一定の異方性テンソルでは 2 次式に対して厳密
+        let kappa = vec![[2.0, 0.5, 1.0]; mesh.num_nodes()];
+        let u: Vec<f64> = mesh
+            .nodes
+            .points
+            .iter()
+            .map(|p| p.x * p.x + 3.0 * p.y * p.y - p.x * p.y)
+            .collect();
+        let a = CsrMatrix::from_rows(mesh.num_nodes(), &mesh.divergence_rows(&kappa).unwrap());
+        for v in a.mul_vec(&u) {
+            assert!((v - 9.0).abs() < 1.0e-8, "{}", v);
+        }
+    }
+}
@@ -0,0 +1,447 @@
+use crate::kd_tree_3d::{KDTree3D, Points3D};
+use crate::krylov;
+use crate::krylov::KrylovMethod;
+use crate::point::Point3;
+use crate::preconditioner::{Preconditioner, PreconditionerType};
+use crate::runge_kutta::{rk_step, RkScheme};
+use crate::sparse_matrix::CsrMatrix;
+use crate::steady::SteadySolution;
+use crate::stencil_3d;
+use crate::stencil_3d::DiffOperator3D;
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+
+// MeshfreeNodes の 3 次元版。節点番号は内部点の後に境界点が続く
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct MeshfreeNodes3D {
+    pub nodes: Points3D,
+    pub num_interior: usize,
+    pub degree: usize,
+    pub stencils: Vec<Vec<usize>>,
+}
+
+impl MeshfreeNodes3D {
+    // 2 次の多項式 (10 項) には 30 点程度の近傍が要る
+    #[allow(dead_code)]
+    pub fn new(
+        interior: &Points3D,
+        boundary: &Points3D,
+        degree: usize,
+        num_neighbor: usize,
+    ) -> Self {
+        let mut nodes = interior.clone();
+        nodes.points.extend(boundary.points.iter().cloned());
+        let tree = KDTree3D::construct_kd_tree(&nodes);
+        let stencils = interior
+            .points
+            .iter()
+            .map(|p| tree.nearest_neighbors(p, num_neighbor))
+            .collect();
+        MeshfreeNodes3D {
+            nodes,
+            num_interior: interior.points.len(),
+            degree,
+            stencils,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.points.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn num_boundary(&self) -> usize {
+        self.num_nodes() - self.num_interior
+    }
+
+    #[allow(dead_code)]
+    pub fn point(&self, index: usize) -> &Point3 {
+        &self.nodes.points[index]
+    }
+
+    // 重みが決まらない退化したステンシルなら Err
+    #[allow(dead_code)]
+    pub fn stencil_weights(
+        &self,
+        i: usize,
+        op: &DiffOperator3D,
+    ) -> Result<Vec<(usize, f64)>, String> {
+        let neighbors: Vec<Point3> = self.stencils[i]
+            .iter()
+            .map(|j| self.nodes.points[*j].clone())
+            .collect();
+        let w = stencil_3d::weights(&self.nodes.points[i], &neighbors, self.degree, op)
+            .ok_or_else(|| format!("degenerate stencil at interior node {}", i))?;
+        Ok(self.stencils[i].iter().cloned().zip(w).collect())
+    }
+
+    // 内部点の行だけを持つ num_interior x num_nodes の行列
+    #[allow(dead_code)]
+    pub fn operator_rows(&self, op: &DiffOperator3D) -> Result<Vec<Vec<(usize, f64)>>, String> {
+        (0..self.num_interior)
+            .map(|i| self.stencil_weights(i, op))
+            .collect()
+    }
+
+    // 行を内部点の列 A_II と境界点の列 A_IB に分ける
+    #[allow(dead_code)]
+    pub fn split_rows(&self, rows: &[Vec<(usize, f64)>]) -> (CsrMatrix, CsrMatrix) {
+        let n = self.num_interior;
+        let mut interior_rows = vec![];
+        let mut boundary_rows = vec![];
+        for row in rows {
+            interior_rows.push(row.iter().filter(|e| e.0 < n).cloned().collect::<Vec<_>>());
+            boundary_rows.push(
+                row.iter()
+                    .filter(|e| e.0 >= n)
+                    .map(|e| (e.0 - n, e.1))
+                    .collect::<Vec<_>>(),
+            );
+        }
+        (
+            CsrMatrix::from_rows(n, &interior_rows),
+            CsrMatrix::from_rows(self.num_boundary(), &boundary_rows),
+        )
+    }
+
+    #[allow(dead_code)]
+    pub fn interior_system(&self, op: &DiffOperator3D) -> Result<(CsrMatrix, CsrMatrix), String> {
+        Ok(self.split_rows(&self.operator_rows(op)?))
+    }
+
+    // L u = f (内部), u = g (境界) を GMRES + ILU(0) で解く
+    #[allow(dead_code)]
+    pub fn solve_steady(
+        &self,
+        op: &DiffOperator3D,
+        f: &dyn Fn(f64, f64, f64) -> f64,
+        g: &dyn Fn(f64, f64, f64) -> f64,
+        tol: f64,
+        max_iter: usize,
+    ) -> Result<SteadySolution, String> {
+        let n = self.num_interior;
+        let (a_ii, a_ib) = self.interior_system(op)?;
+        let g_b: Vec<f64> = self.nodes.points[n..]
+            .iter()
+            .map(|p| g(p.x, p.y, p.z))
+            .collect();
+        let lift = a_ib.mul_vec(&g_b);
+        let b: Vec<f64> = (0..n)
+            .map(|i| {
+                let p = self.point(i);
+                f(p.x, p.y, p.z) - lift[i]
+            })
+            .collect();
+        let precond = Preconditioner::new(PreconditionerType::Ilu0, &a_ii);
+        let res = krylov::solve(
+            KrylovMethod::Gmres(50),
+            &a_ii,
+            &b,
+            &vec![0.0; n],
+            &precond,
+            tol,
+            max_iter,
+        );
+        let mut value = res.x;
+        value.extend(g_b);
+        Ok(SteadySolution {
+            value,
+            residual_history: res.residual_history,
+            converged: res.converged,
+        })
+    }
+}
+
+// 境界値 g(x, y, z, t)
+pub type BoundaryFn3D = Rc<dyn Fn(f64, f64, f64, f64) -> f64>;
+
+// u_t = κ Δu (order 1) または u_tt = c^2 Δu (order 2) を RK4 で進める。境界は Dirichlet
+#[allow(dead_code)]
+pub struct Evolution3D {
+    pub mesh: MeshfreeNodes3D,
+    pub order: usize,
+    pub coefficient: f64, // κ または c^2
+    pub boundary_value: BoundaryFn3D,
+    pub value: Vec<f64>,    // 内部点、境界点の順
+    pub velocity: Vec<f64>, // order 2 のときの u_t (内部点)
+    pub time: f64,
+    operator: CsrMatrix, // 内部点の行 x 全節点
+}
+
+impl Evolution3D {
+    #[allow(dead_code)]
+    pub fn heat(mesh: MeshfreeNodes3D, diffusivity: f64) -> Result<Self, String> {
+        Self::with_order(mesh, 1, diffusivity)
+    }
+
+    #[allow(dead_code)]
+    pub fn wave(mesh: MeshfreeNodes3D, speed: f64) -> Result<Self, String> {
+        Self::with_order(mesh, 2, speed * speed)
+    }
+
+    fn with_order(mesh: MeshfreeNodes3D, order: usize, coefficient: f64) -> Result<Self, String> {
+        let rows = mesh.operator_rows(&DiffOperator3D::laplacian().scale(coefficient))?;
+        let operator = CsrMatrix::from_rows(mesh.num_nodes(), &rows);
+        Ok(Evolution3D {
+            value: vec![0.0; mesh.num_nodes()],
+            velocity: vec![0.0; mesh.num_interior],
+            mesh,
+            order,
+            coefficient,
+            boundary_value: Rc::new(|_, _, _, _| 0.0),
+            time: 0.0,
+            operator,
+        })
+    }
+
+    // u(x, 0) = u0, u_t(x, 0) = u1 (order 1 では u1 は使わない)
+    #[allow(dead_code)]
+    pub fn set_initial_condition(
+        &mut self,
+        u0: &dyn Fn(f64, f64, f64) -> f64,
+        u1: &dyn Fn(f64, f64, f64) -> f64,
+    ) {
+        self.value = self
+            .mesh
+            .nodes
+            .points
+            .iter()
+            .map(|p| u0(p.x, p.y, p.z))
+            .collect();
+        self.velocity = self.mesh.nodes.points[0..self.mesh.num_interior]
+            .iter()
+            .map(|p| u1(p.x, p.y, p.z))
+            .collect();
+        self.apply_boundary();
+    }
+
+    #[allow(dead_code)]
+    pub fn interior_matrix(&self) -> CsrMatrix {
+        self.mesh.split_rows(&self.operator.to_rows()).0
+    }
+
+    #[allow(dead_code)]
+    pub fn step(&mut self, dt: f64) {
+        let n = self.mesh.num_interior;
+        let tableau = RkScheme::Rk4.tableau();
+        let mut state = self.value[0..n].to_vec();
+        if self.order == 2 {
+            state.extend_from_slice(&self.velocity);
+        }
+        let (next, _) = rk_step(&tableau, &state, self.time, dt, &mut |s, t| {
+            let mut u = s[0..n].to_vec();
+            u.extend(self.boundary_values(t));
+            let lu = self.operator.mul_vec(&u);
+            if self.order == 2 {
+                let mut ds = s[n..].to_vec();
+                ds.extend(lu);
+                ds
+            } else {
+                lu
+            }
+        });
+        self.value[0..n].copy_from_slice(&next[0..n]);
+        if self.order == 2 {
+            self.velocity.copy_from_slice(&next[n..]);
+        }
+        self.time += dt;
+        self.apply_boundary();
+    }
+
+    // order 1 は実軸、order 2 は虚軸 (固有値 ±i√λ) の RK4 安定限界から決める
+    #[allow(dead_code)]
+    pub fn cfl_dt(&self, safety: f64) -> f64 {
+        let scheme = RkScheme::Rk4;
+        let rho = self.interior_matrix().spectral_radius(200);
+        if self.order == 2 {
+            safety * scheme.imaginary_stability_bound() / rho.sqrt()
+        } else {
+            safety * scheme.real_stability_bound() / rho
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn save_point_cloud(&self, path: &str) -> io::Result<()> {
+        save_point_cloud(&self.mesh.nodes, &self.value, path)
+    }
+
+    #[allow(dead_code)]
+    pub fn save_vtk(&self, path: &str) -> io::Result<()> {
+        save_vtk(&self.mesh.nodes, &self.value, path)
+    }
+
+    fn boundary_values(&self, t: f64) -> Vec<f64> {
+        self.mesh.nodes.points[self.mesh.num_interior..]
+            .iter()
+            .map(|p| (self.boundary_value)(p.x, p.y, p.z, t))
+            .collect()
+    }
+
+    fn apply_boundary(&mut self) {
+        let n = self.mesh.num_interior;
+        let g = self.boundary_values(self.time);
+        self.value[n..].copy_from_slice(&g);
+    }
+}
+
+impl fmt::Debug for Evolution3D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Evolution3D")
+            .field("num_nodes", &self.mesh.num_nodes())
+            .field("order", &self.order)
+            .field("coefficient", &self.coefficient)
+            .field("time", &self.time)
+            .finish()
+    }
+}
+
+// 点群の CSV (x,y,z,u)
+#[allow(dead_code)]
+pub fn save_point_cloud(nodes: &Points3D, value: &[f64], path: &str) -> io::Result<()> {
+    let mut text = String::from("x,y,z,u\n");
+    for (p, u) in nodes.points.iter().zip(value.iter()) {
+        text.push_str(&format!("{},{},{},{}\n", p.x, p.y, p.z, u));
+    }
+    std::fs::write(path, text)
+}
+
+// ParaView などで読める legacy VTK の POLYDATA (頂点と点データ u)
+#[allow(dead_code)]
+pub fn save_vtk(nodes: &Points3D, value: &[f64], path: &str) -> io::Result<()> {
+    let n = nodes.points.len();
+    let mut text = String::from("# vtk DataFile Version 3.0\nmeshfree 3d\nASCII\n");
+    text.push_str(&format!("DATASET POLYDATA\nPOINTS {} double\n", n));
+    for p in &nodes.points {
+        text.push_str(&format!("{} {} {}\n", p.x, p.y, p.z));
+    }
+    text.push_str(&format!("VERTICES {} {}\n", n, 2 * n));
+    for i in 0..n {
+        text.push_str(&format!("1 {}\n", i));
+    }
+    text.push_str(&format!(
+        "POINT_DATA {}\nSCALARS u double 1\nLOOKUP_TABLE default\n",
+        n
+    ));
+    for u in value {
+        text.push_str(&format!("{}\n", u));
+    }
+    std::fs::write(path, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain_3d::Domain3D;
+    use std::f64::consts::PI;
+
+    fn ball_mesh(h: f64) -> MeshfreeNodes3D {
+        let (boundary, interior) = Domain3D::unit_ball().generate_nodes(h);
+        MeshfreeNodes3D::new(&interior, &boundary, 2, 30)
+    }
+
+    // 球対称な Dirichlet 固有関数 sin(πr) / (πr) (固有値 π^2)
+    fn radial_mode(x: f64, y: f64, z: f64) -> f64 {
+        let r = (x * x + y * y + z * z).sqrt();
+        if r < 1.0e-12 {
+            1.0
+        } else {
+            (PI * r).sin() / (PI * r)
+        }
+    }
+
+    fn max_error(evolution: &Evolution3D, exact: &dyn Fn(f64, f64, f64) -> f64) -> f64 {
+        evolution
+            .mesh
+            .nodes
+            .points
+            .iter()
+            .zip(evolution.value.iter())
+            .map(|(p, v)| (v - exact(p.x, p.y, p.z)).abs())
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn heat_in_unit_ball() {
+        let mut heat = Evolution3D::heat(ball_mesh(0.1), 1.0).unwrap();
+        heat.set_initial_condition(&radial_mode, &|_, _, _| 0.0);
+        let t_end = 0.1;
+        let steps = (t_end / heat.cfl_dt(0.5)).ceil() as usize;
+        for _ in 0..steps {
+            heat.step(t_end / steps as f64);
+        }
+        let decay = (-PI * PI * t_end).exp();
+        let error = max_error(&heat, &|x, y, z| decay * radial_mode(x, y, z));
+        assert!(error < 0.01, "{}", error);
+    }
+
+    #[test]
+    fn wave_in_unit_ball() {
+        let mut wave = Evolution3D::wave(ball_mesh(0.1), 1.0).unwrap();
+        wave.set_initial_condition(&radial_mode, &|_, _, _| 0.0);
+        let t_end = 0.5;
+        let steps = (t_end / wave.cfl_dt(0.5)).ceil() as usize;
+        for _ in 0..steps {
+            wave.step(t_end / steps as f64);
+        }
+        let phase = (PI * t_end).cos();
+        let error = max_error(&wave, &|x, y, z| phase * radial_mode(x, y, z));
+        assert!(error < 0.02, "{}", error);
+    }
+
+    #[test]
+    fn poisson_on_hollow_cube() {
+        // 立方体から球をくり抜いた領域で 2 次式は厳密に再現される
+        let domain = Domain3D::difference(
+            Domain3D::Cuboid {
+                x_min: -1.0,
+                y_min: -1.0,
+                z_min: -1.0,
+                x_max: 1.0,
+                y_max: 1.0,
+                z_max: 1.0,
+            },
+            Domain3D::Ball {
+                cx: 0.0,
+                cy: 0.0,
+                cz: 0.0,
+                r: 0.5,
+            },
+        );
+        let (boundary, interior) = domain.generate_nodes(0.2);
+        let mesh = MeshfreeNodes3D::new(&interior, &boundary, 2, 30);
+        let exact = |x: f64, y: f64, z: f64| x * x + 2.0 * y * y - z * z + x * y + z;
+        let solution = mesh
+            .solve_steady(
+                &DiffOperator3D::laplacian(),
+                &|_, _, _| 4.0,
+                &exact,
+                1.0e-12,
+                2000,
+            )
+            .unwrap();
+        assert!(solution.converged);
+        for (p, v) in mesh.nodes.points.iter().zip(solution.value.iter()) {
+            assert!((v - exact(p.x, p.y, p.z)).abs() < 1.0e-7);
+        }
+    }
+
+    #[test]
+    fn writes_point_cloud() {
+        let mut heat = Evolution3D::heat(ball_mesh(0.3), 1.0).unwrap();
+        heat.set_initial_condition(&radial_mode, &|_, _, _| 0.0);
+        let dir = std::env::temp_dir();
+        let csv = dir.join("meshfree_3d_test.csv");
+        let vtk = dir.join("meshfree_3d_test.vtk");
+        heat.save_point_cloud(csv.to_str().unwrap()).unwrap();
+        heat.save_vtk(vtk.to_str().unwrap()).unwrap();
+        let text = std::fs::read_to_string(&csv).unwrap();
+        assert_eq!(text.lines().count(), heat.mesh.num_nodes() + 1);
+        let text = std::fs::read_to_string(&vtk).unwrap();
+        assert!(text.contains(&format!("POINTS {} double", heat.mesh.num_nodes())));
+        std::fs::remove_file(csv).unwrap();
+        std::fs::remove_file(vtk).unwrap();
+    }
+}
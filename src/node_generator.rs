@@ -0,0 +1,876 @@
+use crate::cell_list::VerletList;
+use crate::domain::Domain;
+use crate::kd_tree;
+use crate::kd_tree::{Grid2D, Points2D};
+use rand::prelude::*;
+use std::fmt;
+use std::rc::Rc;
+
+// 節点間隔 h(x, y)
+#[allow(dead_code)]
+#[derive(Clone)]
+pub enum Spacing {
+    Uniform(f64),
+    Variable(Rc<dyn Fn(f64, f64) -> f64>),
+}
+
+impl Spacing {
+    #[allow(dead_code)]
+    pub fn variable(h: impl Fn(f64, f64) -> f64 + 'static) -> Self {
+        Spacing::Variable(Rc::new(h))
+    }
+
+    #[allow(dead_code)]
+    pub fn at(&self, x: f64, y: f64) -> f64 {
+        match self {
+            Spacing::Uniform(h) => *h,
+            Spacing::Variable(h) => h(x, y),
+        }
+    }
+}
+
+impl fmt::Debug for Spacing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Spacing::Uniform(h) => write!(f, "Uniform({})", h),
+            Spacing::Variable(_) => write!(f, "Variable"),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeStrategy {
+    Random, // 一様乱数による棄却法
+    PoissonDisk,
+    Halton,
+    Sobol,
+    HexLattice,
+}
+
+// Lennard-Jones ポテンシャルの勾配流による内部点の再配置
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LennardJones {
+    pub epsilon: f64,
+    pub sigma: Option<f64>,  // None なら点数と領域の面積から決める
+    pub cutoff: Option<f64>, // 相互作用を打ち切る距離 (σ の倍数)。None なら全点 O(N^2)
+    pub skin: f64,           // 打ち切りありのときの近傍リストの余裕 (σ の倍数)
+    pub dt: f64,
+    pub max_move: f64, // 1 ステップの移動量の上限 (σ の倍数)
+    pub max_steps: usize,
+    pub tol: f64, // 1 ステップの最大移動量がこれ未満になれば止める
+}
+
+impl Default for LennardJones {
+    fn default() -> Self {
+        LennardJones {
+            epsilon: 1.0,
+            sigma: None,
+            cutoff: None,
+            skin: 0.5,
+            dt: 1.0e-3,
+            max_move: 0.5,
+            max_steps: 1000,
+            tol: 0.0,
+        }
+    }
+}
+
+impl LennardJones {
+    // 平衡距離が平均点間隔程度になるように領域の面積で合わせる
+    #[allow(dead_code)]
+    pub fn sigma_for(&self, domain: &Domain, num_interior: usize) -> f64 {
+        match self.sigma {
+            Some(sigma) => sigma,
+            None => {
+                let scale = (domain.area() / std::f64::consts::PI).sqrt();
+                0.9 * (2.0_f64).powf(-1.0 / 6.0) * scale / (num_interior as f64).sqrt()
+            }
+        }
+    }
+
+    // 内部点 index に働く力 -∇V。scale があれば点 i, j 間の σ を (s_i + s_j) / 2 倍する
+    #[allow(dead_code)]
+    pub fn force(
+        &self,
+        index: usize,
+        interior: &Points2D,
+        boundary: &Points2D,
+        sigma: f64,
+        scale: Option<(&[f64], &[f64])>,
+    ) -> Grid2D {
+        let n = interior.points.len();
+        let others = (0..n + boundary.points.len()).filter(|k| *k != index);
+        self.force_from(index, others, interior, boundary, sigma, scale)
+    }
+
+    // others (内部点 0..n の後に境界点が続く番号) からの力だけを足す
+    #[allow(dead_code)]
+    pub fn force_from(
+        &self,
+        index: usize,
+        others: impl Iterator<Item = usize>,
+        interior: &Points2D,
+        boundary: &Points2D,
+        sigma: f64,
+        scale: Option<(&[f64], &[f64])>,
+    ) -> Grid2D {
+        let n = interior.points.len();
+        let p = &interior.points[index];
+        let mut f_x = 0.0;
+        let mut f_y = 0.0;
+        for k in others {
+            let (q, s) = if k < n {
+                let s = scale.map_or(1.0, |(si, _)| 0.5 * (si[index] + si[k]));
+                (&interior.points[k], s)
+            } else {
+                let s = scale.map_or(1.0, |(si, sb)| 0.5 * (si[index] + sb[k - n]));
+                (&boundary.points[k - n], s)
+            };
+            let sigma = sigma * s;
+            let dx = p.x - q.x;
+            let dy = p.y - q.y;
+            let r = (dx * dx + dy * dy).sqrt();
+            if let Some(cutoff) = self.cutoff {
+                if r > cutoff * sigma {
+                    continue;
+                }
+            }
+            let c = self.pair_coefficient(sigma, r);
+            f_x += c * dx;
+            f_y += c * dy;
+        }
+        Grid2D::new(f_x, f_y)
+    }
+
+    // -dV/dr / r
+    fn pair_coefficient(&self, sigma: f64, r: f64) -> f64 {
+        let sigma2 = sigma * sigma;
+        let sigma4 = sigma2 * sigma2;
+        let sigma8 = sigma4 * sigma4;
+        let sigma6 = sigma2 * sigma4;
+        let sigma12 = sigma8 * sigma4;
+        let r2 = r * r;
+        let r4 = r2 * r2;
+        let r8 = r4 * r4;
+        let r14 = r8 * r4 * r2;
+        4.0 * self.epsilon * (12.0 * sigma12 / r14 - 6.0 * sigma6 / r8)
+    }
+
+    // 全内部点を 1 回動かし、最大移動量を返す
+    #[allow(dead_code)]
+    pub fn sweep(
+        &self,
+        domain: &Domain,
+        interior: &mut Points2D,
+        boundary: &Points2D,
+        sigma: f64,
+        scale: Option<(&[f64], &[f64])>,
+    ) -> f64 {
+        self.sweep_with(domain, interior, boundary, sigma, scale, None)
+    }
+
+    // neighbors があればその近傍リストの点からの力だけを使う
+    #[allow(dead_code)]
+    pub fn sweep_with(
+        &self,
+        domain: &Domain,
+        interior: &mut Points2D,
+        boundary: &Points2D,
+        sigma: f64,
+        scale: Option<(&[f64], &[f64])>,
+        neighbors: Option<&VerletList>,
+    ) -> f64 {
+        let mut max_move = 0.0_f64;
+        for i in 0..interior.points.len() {
+            let f = match neighbors {
+                Some(list) => self.force_from(
+                    i,
+                    list.lists[i].iter().cloned(),
+                    interior,
+                    boundary,
+                    sigma,
+                    scale,
+                ),
+                None => self.force(i, interior, boundary, sigma, scale),
+            };
+            let mut d_x = self.dt * f.x;
+            let mut d_y = self.dt * f.y;
+            let d = (d_x * d_x + d_y * d_y).sqrt();
+            if d > self.max_move * sigma {
+                d_x *= self.max_move * sigma / d;
+                d_y *= self.max_move * sigma / d;
+            }
+            // 力 -∇V の向きに動かす
+            let x = interior.points[i].x + d_x;
+            let y = interior.points[i].y + d_y;
+            // 円以外では外に出る移動を棄却する (射影すると境界点に近づきすぎて発散する)
+            let (x, y) = if !(x.is_finite() && y.is_finite()) {
+                (interior.points[i].x, interior.points[i].y)
+            } else if domain.contains(x, y) {
+                (x, y)
+            } else if let Domain::Circle { .. } = domain {
+                domain.pull_inside(x, y)
+            } else {
+                (interior.points[i].x, interior.points[i].y)
+            };
+            let moved = Grid2D::new(x, y)
+                .distance_square(&interior.points[i])
+                .sqrt();
+            max_move = max_move.max(moved);
+            interior.points[i].x = x;
+            interior.points[i].y = y;
+        }
+        max_move
+    }
+
+    // 停止までのステップ数を返す
+    #[allow(dead_code)]
+    pub fn relax(
+        &self,
+        domain: &Domain,
+        spacing: &Spacing,
+        interior: &mut Points2D,
+        boundary: &Points2D,
+    ) -> usize {
+        if interior.points.is_empty() {
+            return 0;
+        }
+        let sigma = self.sigma_for(domain, interior.points.len());
+        let scale_b: Vec<f64> = match spacing {
+            Spacing::Uniform(_) => vec![],
+            Spacing::Variable(_) => {
+                let h_ref = interior
+                    .points
+                    .iter()
+                    .map(|p| spacing.at(p.x, p.y))
+                    .sum::<f64>()
+                    / interior.points.len() as f64;
+                boundary
+                    .points
+                    .iter()
+                    .map(|p| spacing.at(p.x, p.y) / h_ref)
+                    .collect()
+            }
+        };
+        let mut neighbors: Option<VerletList> = None;
+        for step in 0..self.max_steps {
+            let scale_i: Vec<f64> = match spacing {
+                Spacing::Uniform(_) => vec![],
+                Spacing::Variable(_) => {
+                    let h: Vec<f64> = interior
+                        .points
+                        .iter()
+                        .map(|p| spacing.at(p.x, p.y))
+                        .collect();
+                    let h_ref = h.iter().sum::<f64>() / h.len() as f64;
+                    h.iter().map(|v| v / h_ref).collect()
+                }
+            };
+            let scale = match spacing {
+                Spacing::Uniform(_) => None,
+                Spacing::Variable(_) => Some((&scale_i[..], &scale_b[..])),
+            };
+            if let Some(cutoff) = self.cutoff {
+                match neighbors.as_mut() {
+                    Some(list) => {
+                        list.update(interior, boundary);
+                    }
+                    None => {
+                        let s_max = scale_i
+                            .iter()
+                            .chain(scale_b.iter())
+                            .fold(1.0, |m: f64, v| m.max(*v));
+                        neighbors = Some(VerletList::new(
+                            interior,
+                            boundary,
+                            cutoff * sigma * s_max,
+                            self.skin * sigma,
+                        ));
+                    }
+                }
+            }
+            let max_move =
+                self.sweep_with(domain, interior, boundary, sigma, scale, neighbors.as_ref());
+            if max_move < self.tol {
+                return step + 1;
+            }
+        }
+        self.max_steps
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct NodeGenerator {
+    pub domain: Domain,
+    pub spacing: Spacing,
+    pub strategy: NodeStrategy,
+    pub relaxation: Option<LennardJones>,
+    pub boundary_margin: f64, // 境界から margin h 以内の内部点を捨てる
+    pub seed: [u8; 32],
+}
+
+impl NodeGenerator {
+    #[allow(dead_code)]
+    pub fn new(domain: Domain, spacing: Spacing, strategy: NodeStrategy) -> Self {
+        NodeGenerator {
+            domain,
+            spacing,
+            strategy,
+            relaxation: None,
+            boundary_margin: 0.5,
+            seed: [1; 32],
+        }
+    }
+
+    // 境界は等間隔 (境界に沿った h の平均)
+    #[allow(dead_code)]
+    pub fn boundary_nodes(&self) -> Points2D {
+        let n = match &self.spacing {
+            Spacing::Uniform(h) => (self.domain.perimeter() / h).round() as usize,
+            Spacing::Variable(_) => {
+                let samples = self.domain.boundary_samples(1000);
+                let mean_inverse = samples
+                    .iter()
+                    .map(|p| 1.0 / self.spacing.at(p.x, p.y))
+                    .sum::<f64>()
+                    / samples.len() as f64;
+                (self.domain.perimeter() * mean_inverse).round() as usize
+            }
+        };
+        let mut boundary = Points2D::new();
+        for p in self.domain.boundary_samples(n.max(3)) {
+            boundary.push(p.x, p.y);
+        }
+        boundary
+    }
+
+    // 内部点数の目安 ∫ 1 / h^2 dA
+    #[allow(dead_code)]
+    pub fn expected_count(&self) -> usize {
+        match &self.spacing {
+            Spacing::Uniform(h) => (self.domain.area() / (h * h)) as usize,
+            Spacing::Variable(_) => {
+                let m = 200;
+                let (x_min, y_min, x_max, y_max) = self.domain.bounding_box();
+                let hx = (x_max - x_min) / m as f64;
+                let hy = (y_max - y_min) / m as f64;
+                let mut sum = 0.0;
+                for i in 0..m {
+                    for j in 0..m {
+                        let x = x_min + (i as f64 + 0.5) * hx;
+                        let y = y_min + (j as f64 + 0.5) * hy;
+                        if self.domain.contains(x, y) {
+                            let h = self.spacing.at(x, y);
+                            sum += hx * hy / (h * h);
+                        }
+                    }
+                }
+                sum as usize
+            }
+        }
+    }
+
+    // 領域内の h の最小値 (間引きの基準)
+    fn min_spacing(&self) -> f64 {
+        match &self.spacing {
+            Spacing::Uniform(h) => *h,
+            Spacing::Variable(_) => {
+                let m = 100;
+                let (x_min, y_min, x_max, y_max) = self.domain.bounding_box();
+                let mut h_min = f64::INFINITY;
+                for i in 0..=m {
+                    for j in 0..=m {
+                        let x = x_min + (x_max - x_min) * i as f64 / m as f64;
+                        let y = y_min + (y_max - y_min) * j as f64 / m as f64;
+                        if self.domain.contains(x, y) {
+                            h_min = h_min.min(self.spacing.at(x, y));
+                        }
+                    }
+                }
+                h_min
+            }
+        }
+    }
+
+    fn accept(&self, x: f64, y: f64) -> bool {
+        self.domain.contains(x, y)
+            && (self.boundary_margin == 0.0
+                || self.domain.sdf(x, y) < -self.boundary_margin * self.spacing.at(x, y))
+    }
+
+    // h(x, y) が大きいところほど点を間引く (u は [0, 1) の乱数)
+    fn keep(&self, x: f64, y: f64, h_min: f64, u: f64) -> bool {
+        match self.spacing {
+            Spacing::Uniform(_) => true,
+            Spacing::Variable(_) => {
+                let h = self.spacing.at(x, y);
+                u < (h_min / h) * (h_min / h)
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn interior_nodes(&self, boundary: &Points2D) -> Points2D {
+        let mut interior = match self.strategy {
+            NodeStrategy::Random => self.random(),
+            NodeStrategy::Halton => self.quasi_random(|k| (halton(k, 2), halton(k, 3))),
+            NodeStrategy::Sobol => {
+                let mut sobol = Sobol2D::new();
+                self.quasi_random(move |_| sobol.next())
+            }
+            NodeStrategy::HexLattice => self.hex_lattice(),
+            NodeStrategy::PoissonDisk => self.poisson_disk(),
+        };
+        if let Some(lj) = &self.relaxation {
+            lj.relax(&self.domain, &self.spacing, &mut interior, boundary);
+        }
+        interior
+    }
+
+    // (境界点, 内部点)
+    #[allow(dead_code)]
+    pub fn generate(&self) -> (Points2D, Points2D) {
+        let boundary = self.boundary_nodes();
+        let interior = self.interior_nodes(&boundary);
+        (boundary, interior)
+    }
+
+    fn to_box(&self, u: f64, v: f64) -> (f64, f64) {
+        let (cx, cy, hx, hy) = self.domain.center_and_half_width();
+        (cx + hx * (2.0 * (u - 0.5)), cy + hy * (2.0 * (v - 0.5)))
+    }
+
+    fn random(&self) -> Points2D {
+        let count = self.expected_count();
+        let h_min = self.min_spacing();
+        let mut rng: rand::rngs::StdRng = rand::SeedableRng::from_seed(self.seed);
+        let mut interior = Points2D::new();
+        // 領域が空に近いときの無限ループ避け
+        let max_candidates = 1000 * (count + 1);
+        let mut k = 0;
+        while interior.points.len() < count && k < max_candidates {
+            let (x, y) = self.to_box(rng.gen::<f64>(), rng.gen::<f64>());
+            let keep = match self.spacing {
+                Spacing::Uniform(_) => true,
+                Spacing::Variable(_) => self.keep(x, y, h_min, rng.gen::<f64>()),
+            };
+            if self.accept(x, y) && keep {
+                interior.push(x, y);
+            }
+            k += 1;
+        }
+        interior
+    }
+
+    fn quasi_random(&self, mut point: impl FnMut(usize) -> (f64, f64)) -> Points2D {
+        let count = self.expected_count();
+        let h_min = self.min_spacing();
+        let mut interior = Points2D::new();
+        let mut k = 1;
+        // 領域が空に近いときの無限ループ避け
+        let max_candidates = 1000 * (count + 1);
+        while interior.points.len() < count && k < max_candidates {
+            let (u, v) = point(k);
+            let (x, y) = self.to_box(u, v);
+            if self.accept(x, y) && self.keep(x, y, h_min, halton(k, 5)) {
+                interior.push(x, y);
+            }
+            k += 1;
+        }
+        interior
+    }
+
+    // 1 点あたりの面積が h^2 になる正三角形格子
+    fn hex_lattice(&self) -> Points2D {
+        let h_min = self.min_spacing();
+        let a = h_min * (2.0 / 3.0_f64.sqrt()).sqrt();
+        let row = a * 3.0_f64.sqrt() / 2.0;
+        let (x_min, y_min, x_max, y_max) = self.domain.bounding_box();
+        let mut interior = Points2D::new();
+        let mut k = 1;
+        let mut j = 0;
+        loop {
+            let y = y_min + j as f64 * row;
+            if y > y_max {
+                break;
+            }
+            let offset = if j % 2 == 0 { 0.0 } else { 0.5 * a };
+            let mut i = 0;
+            loop {
+                let x = x_min + offset + i as f64 * a;
+                if x > x_max {
+                    break;
+                }
+                if self.accept(x, y) {
+                    if self.keep(x, y, h_min, halton(k, 2)) {
+                        interior.push(x, y);
+                    }
+                    k += 1;
+                }
+                i += 1;
+            }
+            j += 1;
+        }
+        interior
+    }
+
+    // Bridson の方法。点の間隔は h(x, y) 以上
+    fn poisson_disk(&self) -> Points2D {
+        let h_min = self.min_spacing();
+        let (x_min, y_min, x_max, y_max) = self.domain.bounding_box();
+        let cell = h_min / 2.0_f64.sqrt();
+        let nx = ((x_max - x_min) / cell).ceil() as usize + 1;
+        let ny = ((y_max - y_min) / cell).ceil() as usize + 1;
+        let mut grid: Vec<Vec<usize>> = vec![vec![]; nx * ny];
+        let cell_of = |x: f64, y: f64| {
+            (
+                (((x - x_min) / cell) as usize).min(nx - 1),
+                (((y - y_min) / cell) as usize).min(ny - 1),
+            )
+        };
+        let mut rng: rand::rngs::StdRng = rand::SeedableRng::from_seed(self.seed);
+        let mut interior = Points2D::new();
+        let mut active: Vec<usize> = vec![];
+        // 最初の点
+        for _ in 0..10000 {
+            let (x, y) = self.to_box(rng.gen::<f64>(), rng.gen::<f64>());
+            if self.accept(x, y) {
+                let (i, j) = cell_of(x, y);
+                grid[j * nx + i].push(0);
+                interior.push(x, y);
+                active.push(0);
+                break;
+            }
+        }
+        let candidates = 30;
+        while !active.is_empty() {
+            let a = rng.gen_range(0..active.len());
+            let p = interior.points[active[a]].clone();
+            let h = self.spacing.at(p.x, p.y);
+            let mut found = false;
+            for _ in 0..candidates {
+                let r = h * (1.0 + rng.gen::<f64>());
+                let theta = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+                let x = p.x + r * theta.cos();
+                let y = p.y + r * theta.sin();
+                if !self.accept(x, y) {
+                    continue;
+                }
+                let hq = self.spacing.at(x, y);
+                let reach = (hq.max(h) / cell).ceil() as usize + 1;
+                let (ci, cj) = cell_of(x, y);
+                let q = Grid2D::new(x, y);
+                let mut ok = true;
+                'search: for j in cj.saturating_sub(reach)..(cj + reach + 1).min(ny) {
+                    for i in ci.saturating_sub(reach)..(ci + reach + 1).min(nx) {
+                        for k in &grid[j * nx + i] {
+                            let o = &interior.points[*k];
+                            let h_pair = hq.min(self.spacing.at(o.x, o.y));
+                            if o.distance_square(&q) < h_pair * h_pair {
+                                ok = false;
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+                if ok {
+                    let index = interior.points.len();
+                    grid[cj * nx + ci].push(index);
+                    interior.push(x, y);
+                    active.push(index);
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                active.swap_remove(a);
+            }
+        }
+        interior
+    }
+}
+
+// 基数 base の van der Corput 列の k 番目
+#[allow(dead_code)]
+pub fn halton(k: usize, base: usize) -> f64 {
+    let mut k = k;
+    let mut f = 1.0;
+    let mut r = 0.0;
+    while k > 0 {
+        f /= base as f64;
+        r += f * (k % base) as f64;
+        k /= base;
+    }
+    r
+}
+
+// 2 次元 Sobol 列 (Gray code 順)
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Sobol2D {
+    index: u32,
+    x: u32,
+    y: u32,
+    v_x: [u32; 32],
+    v_y: [u32; 32],
+}
+
+impl Sobol2D {
+    #[allow(dead_code)]
+    pub fn new() -> Self {
+        let mut v_x = [0; 32];
+        let mut v_y = [0; 32];
+        for k in 0..32 {
+            v_x[k] = 1 << (31 - k);
+            // 原始多項式 x + 1, m_1 = 1
+            v_y[k] = if k == 0 {
+                1 << 31
+            } else {
+                v_y[k - 1] ^ (v_y[k - 1] >> 1)
+            };
+        }
+        Sobol2D {
+            index: 0,
+            x: 0,
+            y: 0,
+            v_x,
+            v_y,
+        }
+    }
+
+    // 先頭の (0, 0) は飛ばす
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> (f64, f64) {
+        let c = self.index.trailing_ones() as usize;
+        self.x ^= self.v_x[c];
+        self.y ^= self.v_y[c];
+        self.index += 1;
+        let scale = 1.0 / 4294967296.0;
+        (self.x as f64 * scale, self.y as f64 * scale)
+    }
+}
+
+// 最近接点距離の統計
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeStatistics {
+    pub num_nodes: usize,
+    pub min_spacing: f64,
+    pub mean_spacing: f64,
+    pub max_spacing: f64,
+    pub regularity: f64, // max / min
+}
+
+#[allow(dead_code)]
+pub fn node_statistics(points: &Points2D) -> NodeStatistics {
+    let tree = kd_tree::KDTree::construct_kd_tree(points);
+    let d: Vec<f64> = points
+        .points
+        .iter()
+        .map(|p| {
+            let near = tree.nearest_neighbors(p, 2);
+            points.points[near[near.len() - 1]]
+                .distance_square(p)
+                .sqrt()
+        })
+        .collect();
+    let min = d.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = d.iter().cloned().fold(0.0, f64::max);
+    NodeStatistics {
+        num_nodes: d.len(),
+        min_spacing: min,
+        mean_spacing: d.iter().sum::<f64>() / d.len() as f64,
+        max_spacing: max,
+        regularity: max / min,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square() -> Domain {
+        Domain::Rectangle {
+            x_min: 0.0,
+            y_min: 0.0,
+            x_max: 1.0,
+            y_max: 1.0,
+        }
+    }
+
+    #[test]
+    fn low_discrepancy_sequences() {
+        assert_eq!(halton(1, 2), 0.5);
+        assert_eq!(halton(6, 2), 0.375);
+        assert!((halton(5, 3) - 7.0 / 9.0).abs() < 1.0e-15);
+        let mut sobol = Sobol2D::new();
+        let first: Vec<(f64, f64)> = (0..4).map(|_| sobol.next()).collect();
+        assert_eq!(
+            first,
+            vec![(0.5, 0.5), (0.75, 0.25), (0.25, 0.75), (0.375, 0.375)]
+        );
+    }
+
+    #[test]
+    fn strategies_fill_domain() {
+        let h = 0.05;
+        for strategy in [
+            NodeStrategy::Random,
+            NodeStrategy::PoissonDisk,
+            NodeStrategy::Halton,
+            NodeStrategy::Sobol,
+            NodeStrategy::HexLattice,
+        ] {
+            let generator = NodeGenerator::new(square(), Spacing::Uniform(h), strategy);
+            let (boundary, interior) = generator.generate();
+            assert_eq!(boundary.points.len(), 80);
+            let n = interior.points.len() as f64;
+            assert!(n > 0.5 * 400.0 && n < 1.5 * 400.0, "{:?} {}", strategy, n);
+            for p in &interior.points {
+                assert!(generator.domain.sdf(p.x, p.y) < -0.5 * h);
+            }
+            let stats = node_statistics(&interior);
+            if strategy == NodeStrategy::PoissonDisk {
+                assert!(stats.min_spacing >= h);
+            }
+            if strategy == NodeStrategy::HexLattice {
+                assert!(stats.regularity < 1.0 + 1.0e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn thin_domain_returns() {
+        // 境界からの余白 0.5 h が半幅 0.05 より大きいので内部点を置ける場所がない
+        let thin = Domain::Rectangle {
+            x_min: 0.0,
+            y_min: 0.0,
+            x_max: 10.0,
+            y_max: 0.1,
+        };
+        for strategy in [
+            NodeStrategy::Random,
+            NodeStrategy::PoissonDisk,
+            NodeStrategy::Halton,
+            NodeStrategy::Sobol,
+            NodeStrategy::HexLattice,
+        ] {
+            let generator = NodeGenerator::new(thin.clone(), Spacing::Uniform(0.3), strategy);
+            let (boundary, interior) = generator.generate();
+            assert!(!boundary.points.is_empty());
+            assert!(interior.points.is_empty(), "{:?}", strategy);
+        }
+    }
+
+    #[test]
+    fn variable_spacing() {
+        // 左側を細かく
+        let spacing = Spacing::variable(|x, _| 0.03 + 0.07 * x);
+        for strategy in [NodeStrategy::Halton, NodeStrategy::PoissonDisk] {
+            let generator = NodeGenerator::new(square(), spacing.clone(), strategy);
+            let (_, interior) = generator.generate();
+            let left = interior.points.iter().filter(|p| p.x < 0.5).count();
+            let right = interior.points.len() - left;
+            assert!(left > 2 * right, "{:?} {} {}", strategy, left, right);
+        }
+    }
+
+    #[test]
+    fn relaxation_improves_regularity() {
+        let mut generator = NodeGenerator::new(
+            Domain::unit_circle(),
+            Spacing::Uniform(0.1),
+            NodeStrategy::Random,
+        );
+        let (boundary, before) = generator.generate();
+        generator.relaxation = Some(LennardJones {
+            cutoff: Some(3.0),
+            tol: 1.0e-6,
+            ..LennardJones::default()
+        });
+        let after = generator.interior_nodes(&boundary);
+        assert_eq!(before.points.len(), after.points.len());
+        assert!(node_statistics(&after).min_spacing > 2.0 * node_statistics(&before).min_spacing);
+        for p in &after.points {
+            assert!(generator.domain.contains(p.x, p.y));
+        }
+    }
+
+    #[test]
+    fn force_is_negative_potential_gradient() {
+        let generator = NodeGenerator::new(
+            Domain::unit_circle(),
+            Spacing::Uniform(0.2),
+            NodeStrategy::Halton,
+        );
+        let (boundary, mut interior) = generator.generate();
+        let lj = LennardJones::default();
+        let sigma = lj.sigma_for(&generator.domain, interior.points.len());
+        // 全点対の V = 4ε ((σ/r)^12 - (σ/r)^6) の和
+        let energy = |interior: &Points2D| {
+            let all: Vec<&Grid2D> = interior.points.iter().chain(&boundary.points).collect();
+            let mut v = 0.0;
+            for i in 0..interior.points.len() {
+                for (j, q) in all.iter().enumerate() {
+                    if j > i {
+                        let s6 = (sigma * sigma / all[i].distance_square(q)).powi(3);
+                        v += 4.0 * lj.epsilon * (s6 * s6 - s6);
+                    }
+                }
+            }
+            v
+        };
+        let i = interior.points.len() / 2;
+        let f = lj.force(i, &interior, &boundary, sigma, None);
+        let eps = 1.0e-7 * sigma;
+        let mut shifted = interior.clone();
+        shifted.points[i].x += eps;
+        let v_plus = energy(&shifted);
+        shifted.points[i].x -= 2.0 * eps;
+        let v_minus = energy(&shifted);
+        let grad_x = (v_plus - v_minus) / (2.0 * eps);
+        assert!(
+            (f.x + grad_x).abs() < 1.0e-4 * f.x.abs().max(1.0),
+            "{} {}",
+            f.x,
+            grad_x
+        );
+        // 1 回の掃引でエネルギーが下がる
+        let before = energy(&interior);
+        lj.sweep(&generator.domain, &mut interior, &boundary, sigma, None);
+        assert!(energy(&interior) < before);
+    }
+
+    #[test]
+    fn cutoff_forces_match_neighbor_list() {
+        let generator = NodeGenerator::new(
+            Domain::unit_circle(),
+            Spacing::Uniform(0.08),
+            NodeStrategy::Halton,
+        );
+        let (boundary, interior) = generator.generate();
+        let lj = LennardJones {
+            cutoff: Some(2.5),
+            ..LennardJones::default()
+        };
+        let sigma = lj.sigma_for(&generator.domain, interior.points.len());
+        let list = VerletList::new(&interior, &boundary, 2.5 * sigma, 0.5 * sigma);
+        for i in 0..interior.points.len() {
+            let brute = lj.force(i, &interior, &boundary, sigma, None);
+            let fast = lj.force_from(
+                i,
+                list.lists[i].iter().cloned(),
+                &interior,
+                &boundary,
+                sigma,
+                None,
+            );
+            let scale = brute.x.abs().max(brute.y.abs()).max(1.0);
+            assert!((brute.x - fast.x).abs() < 1.0e-12 * scale);
+            assert!((brute.y - fast.y).abs() < 1.0e-12 * scale);
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use crate::domain::Domain;
+use crate::kd_tree;
+use crate::kd_tree::{Grid2D, Points2D};
+use crate::stencil;
+use std::fmt;
+
+// 最小・平均・最大
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub min: f64,
+    pub mean: f64,
+    pub max: f64,
+}
+
+impl Summary {
+    #[allow(dead_code)]
+    pub fn of(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return Summary {
+                min: 0.0,
+                mean: 0.0,
+                max: 0.0,
+            };
+        }
+        Summary {
+            min: values.iter().cloned().fold(f64::INFINITY, f64::min),
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            max: values.iter().cloned().fold(-f64::INFINITY, f64::max),
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "min {:.4e}  mean {:.4e}  max {:.4e}",
+            self.min, self.mean, self.max
+        )
+    }
+}
+
+// 条件数のヒストグラムの区間数。区間 k は 10^k <= κ < 10^(k+1)、最後の区間はそれ以上と ∞
+pub const CONDITION_BINS: usize = 16;
+
+// 節点集合の品質評価の設定
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeQuality {
+    pub num_neighbor: usize,     // ステンシルの点数
+    pub degree: usize,           // ステンシルの多項式の次数
+    pub fill_resolution: usize,  // 充填距離を測る格子の一辺の分割数
+    pub boundary_tolerance: f64, // 境界までの距離が内部点の間隔のこの倍未満なら近すぎる
+}
+
+impl Default for NodeQuality {
+    fn default() -> Self {
+        NodeQuality {
+            num_neighbor: 12,
+            degree: 2,
+            fill_resolution: 200,
+            boundary_tolerance: 0.25,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityReport {
+    pub num_interior: usize,
+    pub num_boundary: usize,
+    pub nearest: Summary,          // 内部点から最も近い節点までの距離
+    pub local_regularity: Summary, // ステンシル内の最近接距離の最大 / 最小
+    pub fill_distance: f64,        // 領域内の点から最も近い節点までの距離の最大
+    pub separation_distance: f64,  // 節点間距離の最小の半分
+    pub boundary_spacing: Summary, // 境界点どうしの最近接距離
+    pub boundary_gap: Summary,     // 境界点から最も近い内部点までの距離
+    pub condition_histogram: Vec<usize>,
+    pub max_condition: f64,
+    pub singular_stencils: Vec<usize>, // 点が足りず最小二乗が組めない内部点
+    pub outside: Vec<usize>,           // 領域の外にある内部点
+    pub near_boundary: Vec<usize>,     // 境界に近すぎる内部点
+}
+
+impl QualityReport {
+    // 充填距離と分離距離の比。1 に近いほど一様
+    #[allow(dead_code)]
+    pub fn mesh_ratio(&self) -> f64 {
+        self.fill_distance / self.separation_distance
+    }
+
+    #[allow(dead_code)]
+    pub fn is_acceptable(&self) -> bool {
+        self.outside.is_empty()
+            && self.near_boundary.is_empty()
+            && self.singular_stencils.is_empty()
+            && self.max_condition.is_finite()
+    }
+}
+
+impl fmt::Display for QualityReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "nodes: {} interior, {} boundary",
+            self.num_interior, self.num_boundary
+        )?;
+        writeln!(f, "nearest neighbour distance: {}", self.nearest)?;
+        writeln!(f, "local regularity ratio:     {}", self.local_regularity)?;
+        writeln!(
+            f,
+            "fill distance {:.4e}, separation distance {:.4e}, mesh ratio {:.3}",
+            self.fill_distance,
+            self.separation_distance,
+            self.mesh_ratio()
+        )?;
+        writeln!(f, "boundary spacing:           {}", self.boundary_spacing)?;
+        writeln!(f, "boundary-interior gap:      {}", self.boundary_gap)?;
+        writeln!(
+            f,
+            "stencil condition numbers (max {:.3e}):",
+            self.max_condition
+        )?;
+        for (k, count) in self.condition_histogram.iter().enumerate() {
+            if *count > 0 {
+                writeln!(f, "  1e{:<2} .. : {}", k, count)?;
+            }
+        }
+        writeln!(f, "singular stencils: {:?}", self.singular_stencils)?;
+        writeln!(f, "outside the domain: {:?}", self.outside)?;
+        write!(f, "too close to the boundary: {:?}", self.near_boundary)
+    }
+}
+
+impl NodeQuality {
+    #[allow(dead_code)]
+    pub fn report(
+        &self,
+        domain: &Domain,
+        boundary: &Points2D,
+        interior: &Points2D,
+    ) -> QualityReport {
+        assert!(
+            !interior.points.is_empty() && !boundary.points.is_empty(),
+            "node quality needs both interior and boundary nodes"
+        );
+        let n = interior.points.len();
+        let mut nodes = interior.clone();
+        nodes.points.extend(boundary.points.iter().cloned());
+        let tree = kd_tree::KDTree::construct_kd_tree(&nodes);
+
+        // 全節点の最近接距離 (番号は内部点の後に境界点)
+        let nearest_all: Vec<f64> = nodes
+            .points
+            .iter()
+            .map(|p| nearest_distance(&tree, &nodes, p, 1))
+            .collect();
+
+        let mut local_regularity = Vec::with_capacity(n);
+        let mut condition_histogram = vec![0; CONDITION_BINS];
+        let mut max_condition = 0.0_f64;
+        let mut singular_stencils = vec![];
+        for (i, p) in interior.points.iter().enumerate() {
+            let near = tree.nearest_neighbors(p, self.num_neighbor);
+            let d: Vec<f64> = near.iter().map(|k| nearest_all[*k]).collect();
+            let s = Summary::of(&d);
+            local_regularity.push(s.max / s.min);
+            let stencil_nodes: Vec<Grid2D> =
+                near.iter().map(|k| nodes.points[*k].clone()).collect();
+            match stencil::condition_number(p, &stencil_nodes, self.degree) {
+                Some(kappa) => {
+                    let bin = if kappa.is_finite() {
+                        (kappa.log10().max(0.0) as usize).min(CONDITION_BINS - 1)
+                    } else {
+                        CONDITION_BINS - 1
+                    };
+                    condition_histogram[bin] += 1;
+                    max_condition = max_condition.max(kappa);
+                }
+                None => singular_stencils.push(i),
+            }
+        }
+
+        // 境界点そのものが近くにあると最近接距離が小さくなるので、内部点どうしの間隔と比べる
+        let interior_tree = kd_tree::KDTree::construct_kd_tree(interior);
+        let mut outside = vec![];
+        let mut near_boundary = vec![];
+        for (i, p) in interior.points.iter().enumerate() {
+            let h = nearest_distance(&interior_tree, interior, p, 1);
+            if !domain.contains(p.x, p.y) {
+                outside.push(i);
+            } else if -domain.sdf(p.x, p.y) < self.boundary_tolerance * h {
+                near_boundary.push(i);
+            }
+        }
+
+        let boundary_tree = kd_tree::KDTree::construct_kd_tree(boundary);
+        let boundary_spacing: Vec<f64> = boundary
+            .points
+            .iter()
+            .map(|p| nearest_distance(&boundary_tree, boundary, p, 1))
+            .collect();
+        let boundary_gap: Vec<f64> = boundary
+            .points
+            .iter()
+            .map(|p| nearest_distance(&interior_tree, interior, p, 0))
+            .collect();
+
+        QualityReport {
+            num_interior: n,
+            num_boundary: boundary.points.len(),
+            nearest: Summary::of(&nearest_all[0..n]),
+            local_regularity: Summary::of(&local_regularity),
+            fill_distance: self.fill_distance(domain, &tree, &nodes),
+            separation_distance: 0.5 * nearest_all.iter().cloned().fold(f64::INFINITY, f64::min),
+            boundary_spacing: Summary::of(&boundary_spacing),
+            boundary_gap: Summary::of(&boundary_gap),
+            condition_histogram,
+            max_condition,
+            singular_stencils,
+            outside,
+            near_boundary,
+        }
+    }
+
+    // 外接矩形上の格子点のうち領域内のものから最も近い節点までの距離の最大
+    fn fill_distance(&self, domain: &Domain, tree: &kd_tree::KDTree, nodes: &Points2D) -> f64 {
+        let (x_min, y_min, x_max, y_max) = domain.bounding_box();
+        let m = self.fill_resolution.max(1);
+        let mut fill = 0.0_f64;
+        for i in 0..(m + 1) {
+            for j in 0..(m + 1) {
+                let x = x_min + (x_max - x_min) * i as f64 / m as f64;
+                let y = y_min + (y_max - y_min) * j as f64 / m as f64;
+                if domain.sdf(x, y) <= 0.0 {
+                    fill = fill.max(nearest_distance(tree, nodes, &Grid2D::new(x, y), 0));
+                }
+            }
+        }
+        fill
+    }
+}
+
+// 近い順で skip 個目を除いた最も近い点までの距離 (skip = 1 で自分自身を除く)
+fn nearest_distance(tree: &kd_tree::KDTree, points: &Points2D, p: &Grid2D, skip: usize) -> f64 {
+    let near = tree.nearest_neighbors(p, skip + 1);
+    match near.get(skip) {
+        Some(k) => points.points[*k].distance_square(p).sqrt(),
+        None => f64::INFINITY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_generator::{NodeGenerator, NodeStrategy, Spacing};
+
+    #[test]
+    fn hex_lattice_quality() {
+        let domain = Domain::Rectangle {
+            x_min: 0.0,
+            y_min: 0.0,
+            x_max: 1.0,
+            y_max: 1.0,
+        };
+        let h = 0.05;
+        let generator = NodeGenerator::new(
+            domain.clone(),
+            Spacing::Uniform(h),
+            NodeStrategy::HexLattice,
+        );
+        let (boundary, interior) = generator.generate();
+        let report = NodeQuality::default().report(&domain, &boundary, &interior);
+        assert_eq!(report.num_interior, interior.points.len());
+        assert_eq!(report.num_boundary, 80);
+        assert!(report.nearest.min > 0.5 * h && report.nearest.max < 2.0 * h);
+        assert!(report.fill_distance > 0.25 * h && report.fill_distance < h);
+        assert!(report.separation_distance <= 0.5 * report.nearest.min);
+        assert!(report.mesh_ratio() < 5.0, "{}", report.mesh_ratio());
+        assert!((report.boundary_spacing.mean - h).abs() < 1.0e-9);
+        assert!(report.boundary_gap.max < 2.0 * h);
+        assert_eq!(
+            report.condition_histogram.iter().sum::<usize>(),
+            report.num_interior
+        );
+        assert!(report.max_condition < 1.0e4, "{}", report.max_condition);
+        assert!(report.is_acceptable(), "{}", report);
+    }
+
+    #[test]
+    fn detects_bad_points() {
+        let domain = Domain::unit_circle();
+        let mut boundary = Points2D::new();
+        for p in domain.boundary_samples(40) {
+            boundary.push(p.x, p.y);
+        }
+        let mut interior = Points2D::new();
+        for k in 1..=100 {
+            let r = 0.85 * (k as f64 / 100.0).sqrt();
+            let theta = 2.399963 * k as f64;
+            interior.push(r * theta.cos(), r * theta.sin());
+        }
+        interior.push(1.2, 0.0); // 外
+        interior.push(0.0, 0.999); // 境界に近すぎる
+        let report = NodeQuality::default().report(&domain, &boundary, &interior);
+        assert_eq!(report.outside, vec![100]);
+        assert_eq!(report.near_boundary, vec![101]);
+        assert!(!report.is_acceptable());
+
+        // 点が足りないとステンシルが組めない
+        let quality = NodeQuality {
+            num_neighbor: 4,
+            ..NodeQuality::default()
+        };
+        let report = quality.report(&domain, &boundary, &interior);
+        assert_eq!(report.singular_stencils.len(), interior.points.len());
+    }
+}
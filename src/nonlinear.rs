@@ -0,0 +1,678 @@
+use crate::implicit::ShiftedSolver;
+use crate::kd_tree::Points2D;
+use crate::krylov;
+use crate::krylov::KrylovMethod;
+use crate::meshfree::MeshfreeNodes;
+use crate::pde_model::Source;
+use crate::preconditioner::{Preconditioner, PreconditionerType};
+use crate::runge_kutta::{rk_step, RkScheme};
+use crate::sparse_matrix::CsrMatrix;
+use crate::stencil::DiffOperator;
+use std::fmt;
+
+// m 成分の系 ∂u_c/∂t = D_c Δu_c + R_c(u) - (u_0, u_1)・∇u_c (最後の項は self_advection のとき)
+// 反応項 R は節点ごとに成分の値だけで決まる
+pub trait NonlinearModel {
+    fn num_components(&self) -> usize;
+
+    fn diffusivity(&self, component: usize) -> f64;
+
+    fn reaction(&self, u: &[f64]) -> Vec<f64>;
+
+    // ∂R_c/∂u_d を行優先で m x m
+    fn reaction_jacobian(&self, u: &[f64]) -> Vec<f64>;
+
+    // 成分 0, 1 を速度とみなして移流させる (Burgers)
+    fn self_advection(&self) -> bool {
+        false
+    }
+}
+
+// u_t = ε^2 Δu + u - u^3
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct AllenCahn {
+    pub epsilon: f64,
+}
+
+impl NonlinearModel for AllenCahn {
+    fn num_components(&self) -> usize {
+        1
+    }
+
+    fn diffusivity(&self, _component: usize) -> f64 {
+        self.epsilon * self.epsilon
+    }
+
+    fn reaction(&self, u: &[f64]) -> Vec<f64> {
+        vec![u[0] - u[0].powi(3)]
+    }
+
+    fn reaction_jacobian(&self, u: &[f64]) -> Vec<f64> {
+        vec![1.0 - 3.0 * u[0] * u[0]]
+    }
+}
+
+// u_t = D Δu + r u (1 - u)
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct FisherKpp {
+    pub diffusivity: f64,
+    pub rate: f64,
+}
+
+impl NonlinearModel for FisherKpp {
+    fn num_components(&self) -> usize {
+        1
+    }
+
+    fn diffusivity(&self, _component: usize) -> f64 {
+        self.diffusivity
+    }
+
+    fn reaction(&self, u: &[f64]) -> Vec<f64> {
+        vec![self.rate * u[0] * (1.0 - u[0])]
+    }
+
+    fn reaction_jacobian(&self, u: &[f64]) -> Vec<f64> {
+        vec![self.rate * (1.0 - 2.0 * u[0])]
+    }
+}
+
+// u_t = D_u Δu - u v^2 + F (1 - u), v_t = D_v Δv + u v^2 - (F + k) v
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct GrayScott {
+    pub diffusivity_u: f64,
+    pub diffusivity_v: f64,
+    pub feed: f64,
+    pub kill: f64,
+}
+
+impl NonlinearModel for GrayScott {
+    fn num_components(&self) -> usize {
+        2
+    }
+
+    fn diffusivity(&self, component: usize) -> f64 {
+        if component == 0 {
+            self.diffusivity_u
+        } else {
+            self.diffusivity_v
+        }
+    }
+
+    fn reaction(&self, u: &[f64]) -> Vec<f64> {
+        let uvv = u[0] * u[1] * u[1];
+        vec![
+            -uvv + self.feed * (1.0 - u[0]),
+            uvv - (self.feed + self.kill) * u[1],
+        ]
+    }
+
+    fn reaction_jacobian(&self, u: &[f64]) -> Vec<f64> {
+        let (a, b) = (u[0], u[1]);
+        vec![
+            -b * b - self.feed,
+            -2.0 * a * b,
+            b * b,
+            2.0 * a * b - self.feed - self.kill,
+        ]
+    }
+}
+
+// 粘性 Burgers 方程式 u_t + (u・∇) u = ν Δu 。成分は速度の x, y
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Burgers {
+    pub viscosity: f64,
+}
+
+impl NonlinearModel for Burgers {
+    fn num_components(&self) -> usize {
+        2
+    }
+
+    fn diffusivity(&self, _component: usize) -> f64 {
+        self.viscosity
+    }
+
+    fn reaction(&self, _u: &[f64]) -> Vec<f64> {
+        vec![0.0, 0.0]
+    }
+
+    fn reaction_jacobian(&self, _u: &[f64]) -> Vec<f64> {
+        vec![0.0; 4]
+    }
+
+    fn self_advection(&self) -> bool {
+        true
+    }
+}
+
+// 陰的ステップの Newton 反復。線形系はヤコビアンを組み立てて Krylov 法で解く
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct NewtonOptions {
+    pub tol: f64, // 非線形残差の 2 ノルム
+    pub max_iter: usize,
+    pub linear_method: KrylovMethod,
+    pub preconditioner: PreconditionerType,
+    pub linear_tol: f64,
+    pub linear_max_iter: usize,
+}
+
+impl Default for NewtonOptions {
+    fn default() -> Self {
+        NewtonOptions {
+            tol: 1.0e-9,
+            max_iter: 20,
+            linear_method: KrylovMethod::Gmres(50),
+            preconditioner: PreconditionerType::Ilu0,
+            linear_tol: 1.0e-10,
+            linear_max_iter: 1000,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct NewtonReport {
+    pub iterations: usize,
+    pub residual_history: Vec<f64>,
+}
+
+// 節点ごとに m 個の未知数をもつ非線形系。境界は成分ごとの Dirichlet u_c = g_c(x, y, t)
+// value は成分ごとに全節点 (内部点、境界点の順) を並べる。時間発展の未知数は内部点だけの
+// U[c * num_interior + i]
+#[allow(dead_code)]
+pub struct NonlinearSystem {
+    pub mesh: MeshfreeNodes,
+    pub model: Box<dyn NonlinearModel>,
+    pub boundary_value: Vec<Source>,
+    pub value: Vec<f64>,
+    pub time: f64,
+    pub newton: NewtonOptions,
+    laplacian: CsrMatrix,                          // 内部点の行 x 全節点
+    laplacian_ii: CsrMatrix,                       // 内部点の列だけ
+    gradient: Option<[(CsrMatrix, CsrMatrix); 2]>, // self_advection のときの (x, y) の (全節点, 内部点)
+    imex: Vec<ShiftedSolver>,
+}
+
+impl NonlinearSystem {
+    #[allow(dead_code)]
+    pub fn new(
+        interior: &Points2D,
+        boundary: &Points2D,
+        degree: usize,
+        num_neighbor: usize,
+        model: impl NonlinearModel + 'static,
+    ) -> Result<Self, String> {
+        let mesh = MeshfreeNodes::new(interior, boundary, degree, num_neighbor);
+        let n_all = mesh.num_nodes();
+        let m = model.num_components();
+        let rows = mesh.operator_rows(&DiffOperator::laplacian())?;
+        let laplacian = CsrMatrix::from_rows(n_all, &rows);
+        let laplacian_ii = mesh.split_rows(&rows).0;
+        let gradient = if model.self_advection() {
+            assert!(m >= 2, "self advection needs two velocity components");
+            let pair = |op: DiffOperator| -> Result<(CsrMatrix, CsrMatrix), String> {
+                let rows = mesh.operator_rows(&op)?;
+                Ok((CsrMatrix::from_rows(n_all, &rows), mesh.split_rows(&rows).0))
+            };
+            Some([pair(DiffOperator::d_x())?, pair(DiffOperator::d_y())?])
+        } else {
+            None
+        };
+        let imex = (0..m)
+            .map(|_| ShiftedSolver::new(laplacian_ii.clone()))
+            .collect();
+        Ok(NonlinearSystem {
+            boundary_value: (0..m).map(|_| Source::new(|_, _, _| 0.0)).collect(),
+            value: vec![0.0; m * n_all],
+            mesh,
+            model: Box::new(model),
+            time: 0.0,
+            newton: NewtonOptions::default(),
+            laplacian,
+            laplacian_ii,
+            gradient,
+            imex,
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn num_components(&self) -> usize {
+        self.model.num_components()
+    }
+
+    // u0(c, x, y)
+    #[allow(dead_code)]
+    pub fn set_initial_condition(&mut self, u0: &dyn Fn(usize, f64, f64) -> f64) {
+        let n_all = self.mesh.num_nodes();
+        self.value = (0..self.num_components() * n_all)
+            .map(|k| {
+                let p = self.mesh.point(k % n_all);
+                u0(k / n_all, p.x, p.y)
+            })
+            .collect();
+        self.time = 0.0;
+        let state = self.interior_state();
+        self.value = self.full_state(&state, 0.0);
+    }
+
+    // 成分 c の全節点の値
+    #[allow(dead_code)]
+    pub fn component(&self, c: usize) -> &[f64] {
+        let n_all = self.mesh.num_nodes();
+        &self.value[c * n_all..(c + 1) * n_all]
+    }
+
+    #[allow(dead_code)]
+    pub fn interior_state(&self) -> Vec<f64> {
+        let n = self.mesh.num_interior;
+        (0..self.num_components())
+            .flat_map(|c| self.component(c)[0..n].iter().cloned())
+            .collect()
+    }
+
+    // 内部点の U に時刻 t の境界値を加えた全節点の値
+    fn full_state(&self, u: &[f64], t: f64) -> Vec<f64> {
+        let n = self.mesh.num_interior;
+        let mut full = vec![];
+        for c in 0..self.num_components() {
+            full.extend_from_slice(&u[c * n..(c + 1) * n]);
+            full.extend(
+                self.mesh.nodes.points[n..]
+                    .iter()
+                    .map(|p| self.boundary_value[c].eval(p.x, p.y, t)),
+            );
+        }
+        full
+    }
+
+    // 内部点での (D Δu, R(u) - (u_0, u_1)・∇u) 。前者は境界値の持ち上げを含む
+    fn split_rhs(&self, u: &[f64], t: f64) -> (Vec<f64>, Vec<f64>) {
+        let n = self.mesh.num_interior;
+        let n_all = self.mesh.num_nodes();
+        let m = self.num_components();
+        let full = self.full_state(u, t);
+        let mut diffusion = vec![];
+        let mut nonlinear = vec![0.0; m * n];
+        for c in 0..m {
+            let lap = self.laplacian.mul_vec(&full[c * n_all..(c + 1) * n_all]);
+            let d = self.model.diffusivity(c);
+            diffusion.extend(lap.iter().map(|v| d * v));
+        }
+        for i in 0..n {
+            let local: Vec<f64> = (0..m).map(|c| u[c * n + i]).collect();
+            for (c, r) in self.model.reaction(&local).into_iter().enumerate() {
+                nonlinear[c * n + i] = r;
+            }
+        }
+        if let Some([(gx, _), (gy, _)]) = &self.gradient {
+            for c in 0..m {
+                let field = &full[c * n_all..(c + 1) * n_all];
+                let (ux, uy) = (gx.mul_vec(field), gy.mul_vec(field));
+                for i in 0..n {
+                    nonlinear[c * n + i] -= u[i] * ux[i] + u[n + i] * uy[i];
+                }
+            }
+        }
+        (diffusion, nonlinear)
+    }
+
+    // 半離散系の右辺 F(U, t)
+    #[allow(dead_code)]
+    pub fn rhs(&self, u: &[f64], t: f64) -> Vec<f64> {
+        let (mut f, nonlinear) = self.split_rhs(u, t);
+        krylov::axpy(1.0, &nonlinear, &mut f);
+        f
+    }
+
+    // ∂F/∂U (m num_interior 次の正方行列)
+    #[allow(dead_code)]
+    pub fn jacobian(&self, u: &[f64], t: f64) -> CsrMatrix {
+        let n = self.mesh.num_interior;
+        let n_all = self.mesh.num_nodes();
+        let m = self.num_components();
+        let a = self.laplacian_ii.to_rows();
+        let mut rows: Vec<Vec<(usize, f64)>> = vec![vec![]; m * n];
+        for c in 0..m {
+            let d = self.model.diffusivity(c);
+            for i in 0..n {
+                rows[c * n + i].extend(a[i].iter().map(|(j, w)| (c * n + j, d * w)));
+            }
+        }
+        for i in 0..n {
+            let local: Vec<f64> = (0..m).map(|c| u[c * n + i]).collect();
+            let jac = self.model.reaction_jacobian(&local);
+            for c in 0..m {
+                for e in 0..m {
+                    rows[c * n + i].push((e * n + i, jac[c * m + e]));
+                }
+            }
+        }
+        if let Some([(gx, gx_ii), (gy, gy_ii)]) = &self.gradient {
+            let full = self.full_state(u, t);
+            let (gx_rows, gy_rows) = (gx_ii.to_rows(), gy_ii.to_rows());
+            for c in 0..m {
+                let field = &full[c * n_all..(c + 1) * n_all];
+                let (ux, uy) = (gx.mul_vec(field), gy.mul_vec(field));
+                for i in 0..n {
+                    let row = &mut rows[c * n + i];
+                    row.extend(gx_rows[i].iter().map(|(j, w)| (c * n + j, -u[i] * w)));
+                    row.extend(gy_rows[i].iter().map(|(j, w)| (c * n + j, -u[n + i] * w)));
+                    row.push((i, -ux[i]));
+                    row.push((n + i, -uy[i]));
+                }
+            }
+        }
+        CsrMatrix::from_rows(m * n, &rows)
+    }
+
+    // 非線形項も含めてすべて陽的に Runge-Kutta 4 次
+    #[allow(dead_code)]
+    pub fn step_explicit(&mut self, dt: f64) {
+        let u = self.interior_state();
+        let (next, _) = rk_step(&RkScheme::Rk4.tableau(), &u, self.time, dt, &mut |s, t| {
+            self.rhs(s, t)
+        });
+        self.time += dt;
+        self.value = self.full_state(&next, self.time);
+    }
+
+    // 拡散を陰的、非線形項を陽的に扱う IMEX Euler。
+    // 線形ソルバーが収束しなければ状態を変えずに Err を返す
+    #[allow(dead_code)]
+    pub fn step_imex(&mut self, dt: f64) -> Result<(), String> {
+        let n = self.mesh.num_interior;
+        let u = self.interior_state();
+        let (_, nonlinear) = self.split_rhs(&u, self.time);
+        // 新しい時刻の境界値の持ち上げ D_c A_IB g_c は、内部点を 0 とした D Δ から得る
+        let (lift, _) = self.split_rhs(&vec![0.0; u.len()], self.time + dt);
+        let mut next = vec![];
+        for c in 0..self.num_components() {
+            let d = self.model.diffusivity(c);
+            let range = c * n..(c + 1) * n;
+            let b: Vec<f64> = range
+                .clone()
+                .map(|k| u[k] + dt * (nonlinear[k] + lift[k]))
+                .collect();
+            next.extend(self.imex[c].solve(dt * d, &b, &u[range])?);
+        }
+        self.time += dt;
+        self.value = self.full_state(&next, self.time);
+        Ok(())
+    }
+
+    // 後退 Euler U - U^n - dt F(U, t + dt) = 0 を Newton-Krylov で解く。
+    // 収束しなければ状態を変えずに Err を返す
+    #[allow(dead_code)]
+    pub fn step_implicit(&mut self, dt: f64) -> Result<NewtonReport, String> {
+        let u_old = self.interior_state();
+        let t = self.time + dt;
+        let residual = |u: &[f64]| -> Vec<f64> {
+            let f = self.rhs(u, t);
+            (0..u.len()).map(|k| u[k] - u_old[k] - dt * f[k]).collect()
+        };
+        let mut u = u_old.clone();
+        let mut g = residual(&u);
+        let mut history = vec![krylov::norm(&g)];
+        while history[history.len() - 1] >= self.newton.tol {
+            if history.len() > self.newton.max_iter {
+                return Err(format!(
+                    "Newton did not converge at t = {}: residual {:e}",
+                    t,
+                    history[history.len() - 1]
+                ));
+            }
+            let j = CsrMatrix::linear_combination(
+                1.0,
+                &CsrMatrix::identity(u.len()),
+                -dt,
+                &self.jacobian(&u, t),
+            );
+            let precond = Preconditioner::new(self.newton.preconditioner, &j);
+            let minus_g: Vec<f64> = g.iter().map(|v| -v).collect();
+            let res = krylov::solve(
+                self.newton.linear_method,
+                &j,
+                &minus_g,
+                &vec![0.0; u.len()],
+                &precond,
+                self.newton.linear_tol,
+                self.newton.linear_max_iter,
+            );
+            krylov::axpy(1.0, &res.x, &mut u);
+            g = residual(&u);
+            let r = krylov::norm(&g);
+            if !r.is_finite() {
+                return Err(format!("Newton diverged at t = {}", t));
+            }
+            history.push(r);
+        }
+        self.time = t;
+        self.value = self.full_state(&u, t);
+        Ok(NewtonReport {
+            iterations: history.len() - 1,
+            residual_history: history,
+        })
+    }
+}
+
+impl fmt::Debug for NonlinearSystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NonlinearSystem")
+            .field("num_nodes", &self.mesh.num_nodes())
+            .field("num_components", &self.num_components())
+            .field("time", &self.time)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Domain;
+    use crate::node_generator::{NodeGenerator, NodeStrategy, Spacing};
+
+    fn rectangle(
+        x_min: f64,
+        x_max: f64,
+        y_max: f64,
+        h: f64,
+        model: impl NonlinearModel + 'static,
+    ) -> NonlinearSystem {
+        let domain = Domain::Rectangle {
+            x_min,
+            y_min: 0.0,
+            x_max,
+            y_max,
+        };
+        let generator = NodeGenerator::new(domain, Spacing::Uniform(h), NodeStrategy::HexLattice);
+        let (boundary, interior) = generator.generate();
+        NonlinearSystem::new(&interior, &boundary, 2, 12, model).unwrap()
+    }
+
+    fn max_error(system: &NonlinearSystem, exact: &dyn Fn(usize, f64, f64) -> f64) -> f64 {
+        let n_all = system.mesh.num_nodes();
+        system
+            .value
+            .iter()
+            .enumerate()
+            .map(|(k, v)| {
+                let p = system.mesh.point(k % n_all);
+                (v - exact(k / n_all, p.x, p.y)).abs()
+            })
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn jacobian_matches_finite_differences() {
+        let gray_scott = GrayScott {
+            diffusivity_u: 0.2,
+            diffusivity_v: 0.1,
+            feed: 0.04,
+            kill: 0.06,
+        };
+        for mut system in [
+            rectangle(0.0, 1.0, 1.0, 0.2, gray_scott),
+            rectangle(0.0, 1.0, 1.0, 0.2, Burgers { viscosity: 0.1 }),
+        ] {
+            system.boundary_value = vec![
+                Source::new(|x, y, _| 1.0 + x * y),
+                Source::new(|x, _, t| x - t),
+            ];
+            let len = 2 * system.mesh.num_interior;
+            let u: Vec<f64> = (0..len).map(|k| (k as f64 * 0.37).sin()).collect();
+            let j = system.jacobian(&u, 0.3);
+            let eps = 1.0e-6;
+            for k in (0..len).step_by(7) {
+                let mut up = u.clone();
+                let mut um = u.clone();
+                up[k] += eps;
+                um[k] -= eps;
+                let (fp, fm) = (system.rhs(&up, 0.3), system.rhs(&um, 0.3));
+                for r in 0..len {
+                    let fd = (fp[r] - fm[r]) / (2.0 * eps);
+                    assert!((fd - j.get(r, k)).abs() < 1.0e-5 * (1.0 + fd.abs()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fisher_kpp_travelling_wave() {
+        // Ablowitz-Zeppetella の厳密解 u = (1 + e^{(x - ct) / √6})^{-2}, c = 5 / √6
+        let exact = |t: f64| {
+            move |_c: usize, x: f64, _y: f64| {
+                (1.0 + ((x - 5.0 / 6.0_f64.sqrt() * t) / 6.0_f64.sqrt()).exp()).powi(-2)
+            }
+        };
+        let model = || FisherKpp {
+            diffusivity: 1.0,
+            rate: 1.0,
+        };
+        let mut errors = vec![];
+        for scheme in 0..3 {
+            let mut system = rectangle(-5.0, 5.0, 1.0, 0.2, model());
+            system.boundary_value = vec![Source::new(move |x, y, t| exact(t)(0, x, y))];
+            system.set_initial_condition(&exact(0.0));
+            let dt = if scheme == 0 { 0.005 } else { 0.02 };
+            for _ in 0..50 {
+                match scheme {
+                    0 => system.step_explicit(dt),
+                    1 => system.step_imex(dt).unwrap(),
+                    _ => {
+                        let report = system.step_implicit(dt).unwrap();
+                        assert!(report.iterations <= 4, "{:?}", report);
+                    }
+                }
+            }
+            errors.push(max_error(&system, &exact(system.time)));
+        }
+        assert!(errors[0] < 1.0e-4, "{:?}", errors);
+        assert!(errors[1] < 1.0e-3, "{:?}", errors);
+        assert!(errors[2] < 1.0e-3, "{:?}", errors);
+    }
+
+    #[test]
+    fn burgers_front() {
+        // 2 次元 Burgers の厳密解 (Fletcher)
+        let nu = 0.05;
+        let exact = move |t: f64| {
+            move |c: usize, x: f64, y: f64| {
+                let s = 0.25 / (1.0 + ((-4.0 * x + 4.0 * y - t) / (32.0 * nu)).exp());
+                if c == 0 {
+                    0.75 - s
+                } else {
+                    0.75 + s
+                }
+            }
+        };
+        for implicit in [false, true] {
+            let mut system = rectangle(0.0, 1.0, 1.0, 0.04, Burgers { viscosity: nu });
+            system.boundary_value = (0..2)
+                .map(|c| Source::new(move |x, y, t| exact(t)(c, x, y)))
+                .collect();
+            system.set_initial_condition(&exact(0.0));
+            for _ in 0..50 {
+                if implicit {
+                    system.step_implicit(0.01).unwrap();
+                } else {
+                    system.step_explicit(0.01);
+                }
+            }
+            let error = max_error(&system, &exact(system.time));
+            assert!(error < 5.0e-4, "implicit {} {}", implicit, error);
+        }
+    }
+
+    #[test]
+    fn allen_cahn_relaxes_with_large_steps() {
+        // tanh(x / (√2 ε)) は定常解。内部の摂動は大きな dt の後退 Euler でも消える
+        let epsilon = 0.1;
+        let profile = move |_c: usize, x: f64, _y: f64| (x / (2.0_f64.sqrt() * epsilon)).tanh();
+        let mut system = rectangle(-1.0, 1.0, 0.5, 0.04, AllenCahn { epsilon });
+        system.boundary_value = vec![Source::new(move |x, y, _| profile(0, x, y))];
+        system.set_initial_condition(&|c, x, y| {
+            profile(c, x, y) + 0.5 * (1.0 - x * x) * (std::f64::consts::PI * y / 0.5).sin()
+        });
+        for _ in 0..20 {
+            system.step_implicit(1.0).unwrap();
+        }
+        let error = max_error(&system, &profile);
+        assert!(error < 2.0e-2, "{}", error);
+    }
+
+    #[test]
+    fn gray_scott_schemes_agree() {
+        // 一様な u = 1, v = 0 の中央に置いた種で v が育つ。陽解法と dt を 5 倍にした IMEX・陰解法を比べる
+        let model = || GrayScott {
+            diffusivity_u: 0.01,
+            diffusivity_v: 0.005,
+            feed: 0.06,
+            kill: 0.04,
+        };
+        let seed = |c: usize, x: f64, y: f64| {
+            let inside = (x - 2.5).abs() < 1.0 && (y - 2.5).abs() < 1.0;
+            match (c, inside) {
+                (0, true) => 0.5,
+                (0, false) => 1.0,
+                (_, true) => 0.25,
+                (_, false) => 0.0,
+            }
+        };
+        let mut results = vec![];
+        for scheme in 0..3 {
+            let mut system = rectangle(0.0, 5.0, 5.0, 0.2, model());
+            system.boundary_value = vec![Source::new(|_, _, _| 1.0), Source::new(|_, _, _| 0.0)];
+            system.set_initial_condition(&seed);
+            let dt = if scheme == 0 { 0.05 } else { 0.25 };
+            while system.time < 50.0 - 1.0e-9 {
+                match scheme {
+                    0 => system.step_explicit(dt),
+                    1 => system.step_imex(dt).unwrap(),
+                    _ => {
+                        system.step_implicit(dt).unwrap();
+                    }
+                }
+            }
+            results.push(system.value.clone());
+        }
+        let difference = |a: &[f64], b: &[f64]| {
+            a.iter()
+                .zip(b.iter())
+                .map(|(x, y)| (x - y).abs())
+                .fold(0.0, f64::max)
+        };
+        let v_max = results[0][results[0].len() / 2..]
+            .iter()
+            .cloned()
+            .fold(0.0, f64::max);
+        assert!(v_max > 0.5, "{}", v_max);
+        assert!(difference(&results[0], &results[1]) < 0.05);
+        assert!(difference(&results[0], &results[2]) < 0.05);
+    }
+}
@@ -0,0 +1,406 @@
+use crate::expression::Expression;
+use crate::kd_tree::Points2D;
+use crate::stencil::DiffOperator;
+use crate::two_variable_polynomial::TwoPolynomial;
+use std::fmt;
+use std::rc::Rc;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LocalDerivatives {
+    pub u: f64,
+    pub u_x: f64,
+    pub u_y: f64,
+    pub u_xx: f64,
+    pub u_xy: f64,
+    pub u_yy: f64,
+}
+
+impl LocalDerivatives {
+    #[allow(dead_code)]
+    pub fn from_poly(poly: &TwoPolynomial, x: f64, y: f64) -> Self {
+        LocalDerivatives {
+            u: poly.eval_xy(x, y),
+            u_x: poly.eval_deriv(x, y, 1, 0),
+            u_y: poly.eval_deriv(x, y, 0, 1),
+            u_xx: poly.eval_deriv(x, y, 2, 0),
+            u_xy: poly.eval_deriv(x, y, 1, 1),
+            u_yy: poly.eval_deriv(x, y, 0, 2),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn laplacian(&self) -> f64 {
+        self.u_xx + self.u_yy
+    }
+}
+
+// 時間微分の階数が time_order の方程式 d^n u / dt^n = rhs(u, x, y, t)
+pub trait PdeModel {
+    fn time_order(&self) -> usize {
+        1
+    }
+
+    fn rhs(&self, d: &LocalDerivatives, x: f64, y: f64, t: f64) -> f64;
+}
+
+// u_t = kappa Δu
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct HeatModel {
+    pub diffusivity: f64,
+}
+
+impl HeatModel {
+    #[allow(dead_code)]
+    pub fn new(diffusivity: f64) -> Self {
+        HeatModel { diffusivity }
+    }
+}
+
+impl PdeModel for HeatModel {
+    fn rhs(&self, d: &LocalDerivatives, _x: f64, _y: f64, _t: f64) -> f64 {
+        self.diffusivity * d.laplacian()
+    }
+}
+
+// u_tt = c^2 Δu
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct WaveModel {
+    pub speed: f64,
+}
+
+impl WaveModel {
+    #[allow(dead_code)]
+    pub fn new(speed: f64) -> Self {
+        WaveModel { speed }
+    }
+}
+
+impl PdeModel for WaveModel {
+    fn time_order(&self) -> usize {
+        2
+    }
+
+    fn rhs(&self, d: &LocalDerivatives, _x: f64, _y: f64, _t: f64) -> f64 {
+        self.speed * self.speed * d.laplacian()
+    }
+}
+
+// u_t + v・∇u = nu Δu
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct AdvectionDiffusionModel {
+    pub velocity_x: f64,
+    pub velocity_y: f64,
+    pub diffusivity: f64,
+}
+
+impl AdvectionDiffusionModel {
+    #[allow(dead_code)]
+    pub fn new(velocity_x: f64, velocity_y: f64, diffusivity: f64) -> Self {
+        AdvectionDiffusionModel {
+            velocity_x,
+            velocity_y,
+            diffusivity,
+        }
+    }
+}
+
+impl PdeModel for AdvectionDiffusionModel {
+    fn rhs(&self, d: &LocalDerivatives, _x: f64, _y: f64, _t: f64) -> f64 {
+        self.diffusivity * d.laplacian() - self.velocity_x * d.u_x - self.velocity_y * d.u_y
+    }
+}
+
+// u_t = D Δu + R(u)
+#[allow(dead_code)]
+pub struct ReactionDiffusionModel {
+    pub diffusivity: f64,
+    pub reaction: Box<dyn Fn(f64) -> f64>,
+}
+
+impl ReactionDiffusionModel {
+    #[allow(dead_code)]
+    pub fn new(diffusivity: f64, reaction: impl Fn(f64) -> f64 + 'static) -> Self {
+        ReactionDiffusionModel {
+            diffusivity,
+            reaction: Box::new(reaction),
+        }
+    }
+}
+
+impl PdeModel for ReactionDiffusionModel {
+    fn rhs(&self, d: &LocalDerivatives, _x: f64, _y: f64, _t: f64) -> f64 {
+        self.diffusivity * d.laplacian() + (self.reaction)(d.u)
+    }
+}
+
+#[allow(dead_code)]
+pub type RhsFn = Box<dyn Fn(&LocalDerivatives, f64, f64, f64) -> f64>;
+
+// 右辺をクロージャで与える
+#[allow(dead_code)]
+pub struct UserModel {
+    pub order: usize,
+    pub rhs: RhsFn,
+}
+
+impl UserModel {
+    #[allow(dead_code)]
+    pub fn new(
+        order: usize,
+        rhs: impl Fn(&LocalDerivatives, f64, f64, f64) -> f64 + 'static,
+    ) -> Self {
+        UserModel {
+            order,
+            rhs: Box::new(rhs),
+        }
+    }
+}
+
+impl PdeModel for UserModel {
+    fn time_order(&self) -> usize {
+        self.order
+    }
+
+    fn rhs(&self, d: &LocalDerivatives, x: f64, y: f64, t: f64) -> f64 {
+        (self.rhs)(d, x, y, t)
+    }
+}
+
+// スカラー係数 a(x, y)
+#[allow(dead_code)]
+pub type CoefficientFn = Rc<dyn Fn(f64, f64) -> f64>;
+
+// 対称テンソル係数 [κ_xx, κ_xy, κ_yy]
+#[allow(dead_code)]
+pub type TensorFn = Rc<dyn Fn(f64, f64) -> [f64; 3]>;
+
+// ∇・(κ ∇u) の係数 κ(x, y)。スカラーなら等方、テンソルなら異方性の拡散
+#[allow(dead_code)]
+#[derive(Clone)]
+pub enum Conductivity {
+    Scalar(CoefficientFn),
+    Tensor(TensorFn),
+}
+
+impl Conductivity {
+    #[allow(dead_code)]
+    pub fn constant(kappa: f64) -> Self {
+        Conductivity::Scalar(Rc::new(move |_, _| kappa))
+    }
+
+    #[allow(dead_code)]
+    pub fn scalar(kappa: impl Fn(f64, f64) -> f64 + 'static) -> Self {
+        Conductivity::Scalar(Rc::new(kappa))
+    }
+
+    #[allow(dead_code)]
+    pub fn tensor(kappa: impl Fn(f64, f64) -> [f64; 3] + 'static) -> Self {
+        Conductivity::Tensor(Rc::new(kappa))
+    }
+
+    // 方向 (dx, dy) に積み重なった層。s = dx x + dy y が interfaces[k] を越えると
+    // values[k] から values[k + 1] に変わる (interfaces は昇順)
+    #[allow(dead_code)]
+    pub fn layered(direction: (f64, f64), interfaces: Vec<f64>, values: Vec<f64>) -> Self {
+        assert_eq!(interfaces.len() + 1, values.len());
+        Conductivity::scalar(move |x, y| {
+            let s = direction.0 * x + direction.1 * y;
+            values[interfaces.iter().take_while(|c| s > **c).count()]
+        })
+    }
+
+    // [κ_xx, κ_xy, κ_yy]
+    #[allow(dead_code)]
+    pub fn at(&self, x: f64, y: f64) -> [f64; 3] {
+        match self {
+            Conductivity::Scalar(k) => {
+                let k = k(x, y);
+                [k, 0.0, k]
+            }
+            Conductivity::Tensor(k) => k(x, y),
+        }
+    }
+
+    // ∇・κ = (∂x κ_xx + ∂y κ_xy, ∂x κ_xy + ∂y κ_yy) を中心差分で
+    #[allow(dead_code)]
+    pub fn divergence(&self, x: f64, y: f64) -> (f64, f64) {
+        let h = 1.0e-5;
+        let (xp, xm) = (self.at(x + h, y), self.at(x - h, y));
+        let (yp, ym) = (self.at(x, y + h), self.at(x, y - h));
+        (
+            (xp[0] - xm[0] + yp[1] - ym[1]) / (2.0 * h),
+            (xp[1] - xm[1] + yp[2] - ym[2]) / (2.0 * h),
+        )
+    }
+
+    // 局所多項式に作用させる非保存形 ∇・(κ ∇u) = κ : ∇∇u + (∇・κ)・∇u 。
+    // κ が不連続な層状材料では界面で微分が壊れるので MeshfreeNodes::divergence_rows を使う
+    #[allow(dead_code)]
+    pub fn operator_at(&self, x: f64, y: f64) -> DiffOperator {
+        let [k_xx, k_xy, k_yy] = self.at(x, y);
+        let (d_x, d_y) = self.divergence(x, y);
+        DiffOperator {
+            c: 0.0,
+            c_x: d_x,
+            c_y: d_y,
+            c_xx: k_xx,
+            c_xy: 2.0 * k_xy,
+            c_yy: k_yy,
+        }
+    }
+
+    // 節点ごとの値 (divergence_rows に渡す節点場)
+    #[allow(dead_code)]
+    pub fn node_field(&self, points: &Points2D) -> Vec<[f64; 3]> {
+        points.points.iter().map(|p| self.at(p.x, p.y)).collect()
+    }
+}
+
+impl fmt::Debug for Conductivity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Conductivity::Scalar(_) => write!(f, "Conductivity::Scalar"),
+            Conductivity::Tensor(_) => write!(f, "Conductivity::Tensor"),
+        }
+    }
+}
+
+// u_t = ∇・(κ ∇u) (time_order 1)、または u_tt = ∇・(c^2 ∇u) (time_order 2)
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct VariableCoefficientModel {
+    pub conductivity: Conductivity,
+    pub order: usize,
+}
+
+impl VariableCoefficientModel {
+    #[allow(dead_code)]
+    pub fn heat(conductivity: Conductivity) -> Self {
+        VariableCoefficientModel {
+            conductivity,
+            order: 1,
+        }
+    }
+
+    // 波速 c(x, y) の波動方程式
+    #[allow(dead_code)]
+    pub fn wave(speed: impl Fn(f64, f64) -> f64 + 'static) -> Self {
+        VariableCoefficientModel {
+            conductivity: Conductivity::scalar(move |x, y| speed(x, y).powi(2)),
+            order: 2,
+        }
+    }
+}
+
+impl PdeModel for VariableCoefficientModel {
+    fn time_order(&self) -> usize {
+        self.order
+    }
+
+    fn rhs(&self, d: &LocalDerivatives, x: f64, y: f64, _t: f64) -> f64 {
+        self.conductivity.operator_at(x, y).apply(d)
+    }
+}
+
+// 右辺に加えるソース項 f(x, y, t)
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct Source(pub Rc<dyn Fn(f64, f64, f64) -> f64>);
+
+impl Source {
+    #[allow(dead_code)]
+    pub fn new(f: impl Fn(f64, f64, f64) -> f64 + 'static) -> Self {
+        Source(Rc::new(f))
+    }
+
+    #[allow(dead_code)]
+    pub fn from_expression(text: &str) -> Result<Self, String> {
+        let e = Expression::parse(text)?;
+        Ok(Source::new(move |x, y, t| e.eval(x, y, t)))
+    }
+
+    #[allow(dead_code)]
+    pub fn eval(&self, x: f64, y: f64, t: f64) -> f64 {
+        (self.0)(x, y, t)
+    }
+}
+
+impl fmt::Debug for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Source")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quadratic() -> TwoPolynomial {
+        // 1 + 2y + 3y^2 + 4x + 5xy + 7x^2
+        let mut poly = TwoPolynomial::new(2);
+        poly.two_poly = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0].to_vec();
+        poly
+    }
+
+    #[test]
+    fn local_derivatives() {
+        let d = LocalDerivatives::from_poly(&quadratic(), 0.0, 0.0);
+        assert_eq!(d.u, 1.0);
+        assert_eq!(d.u_x, 4.0);
+        assert_eq!(d.u_y, 2.0);
+        assert_eq!(d.u_xx, 14.0);
+        assert_eq!(d.u_xy, 5.0);
+        assert_eq!(d.u_yy, 6.0);
+        assert_eq!(d.laplacian(), 20.0);
+    }
+
+    #[test]
+    fn model_rhs() {
+        let d = LocalDerivatives::from_poly(&quadratic(), 0.0, 0.0);
+        assert_eq!(HeatModel::new(0.5).rhs(&d, 0.0, 0.0, 0.0), 10.0);
+        assert_eq!(WaveModel::new(2.0).rhs(&d, 0.0, 0.0, 0.0), 80.0);
+        assert_eq!(WaveModel::new(2.0).time_order(), 2);
+        assert_eq!(
+            AdvectionDiffusionModel::new(1.0, 1.0, 1.0).rhs(&d, 0.0, 0.0, 0.0),
+            14.0
+        );
+        let fisher = ReactionDiffusionModel::new(1.0, |u| u * (1.0 - u));
+        assert_eq!(fisher.rhs(&d, 0.0, 0.0, 0.0), 20.0);
+        let user = UserModel::new(1, |d, x, _y, t| d.u_xy + x + t);
+        assert_eq!(user.rhs(&d, 1.0, 0.0, 2.0), 8.0);
+    }
+
+    #[test]
+    fn variable_coefficient_rhs() {
+        // u = x^2 + y^2, κ = 1 + x: ∇・(κ ∇u) = 2x + 4(1 + x) = 4 + 6x
+        let mut poly = TwoPolynomial::new(2);
+        poly.two_poly = [0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0].to_vec();
+        let heat = VariableCoefficientModel::heat(Conductivity::scalar(|x, _| 1.0 + x));
+        for x in [-0.5, 0.0, 0.3] {
+            let d = LocalDerivatives::from_poly(&poly, x, 0.2);
+            assert!((heat.rhs(&d, x, 0.2, 0.0) - (4.0 + 6.0 * x)).abs() < 1.0e-8);
+        }
+        // 定数テンソル: 2 u_xx + 2 * 0.5 u_xy + u_yy
+        let d = LocalDerivatives::from_poly(&quadratic(), 0.0, 0.0);
+        let aniso = VariableCoefficientModel::heat(Conductivity::tensor(|_, _| [2.0, 0.5, 1.0]));
+        assert!((aniso.rhs(&d, 0.0, 0.0, 0.0) - 39.0).abs() < 1.0e-8);
+        let wave = VariableCoefficientModel::wave(|_, _| 2.0);
+        assert_eq!(wave.time_order(), 2);
+        assert!((wave.rhs(&d, 0.0, 0.0, 0.0) - 80.0).abs() < 1.0e-8);
+        let layers = Conductivity::layered((1.0, 0.0), vec![0.0, 1.0], vec![1.0, 5.0, 10.0]);
+        assert_eq!(layers.at(-1.0, 0.0), [1.0, 0.0, 1.0]);
+        assert_eq!(layers.at(0.5, 3.0)[2], 5.0);
+        assert_eq!(layers.at(2.0, 0.0)[0], 10.0);
+    }
+
+    #[test]
+    fn source_from_expression() {
+        let f = Source::from_expression("x * y + t").unwrap();
+        assert_eq!(f.eval(2.0, 3.0, 0.5), 6.5);
+        assert!(Source::from_expression("x +").is_err());
+    }
+}
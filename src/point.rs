@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Point3 {
     pub x: f64,
     pub y: f64,
@@ -14,4 +14,22 @@ impl Point3 {
             z: z_,
         }
     }
+
+    #[allow(dead_code)]
+    pub fn distance_square(&self, point: &Point3) -> f64 {
+        let dx = self.x - point.x;
+        let dy = self.y - point.y;
+        let dz = self.z - point.z;
+        dx * dx + dy * dy + dz * dz
+    }
+
+    // 軸 0, 1, 2 の座標
+    #[allow(dead_code)]
+    pub fn coord(&self, axis: usize) -> f64 {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
 }
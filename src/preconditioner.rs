@@ -0,0 +1,160 @@
+use crate::sparse_matrix::CsrMatrix;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PreconditionerType {
+    Identity,
+    Jacobi,
+    Ilu0,
+}
+
+// M^{-1} r を返す
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub enum Preconditioner {
+    Identity,
+    Jacobi(Vec<f64>),
+    Ilu0(CsrMatrix), // L (単位下三角) と U を同じパターンに格納
+}
+
+impl Preconditioner {
+    #[allow(dead_code)]
+    pub fn new(kind: PreconditionerType, a: &CsrMatrix) -> Self {
+        match kind {
+            PreconditionerType::Identity => Preconditioner::Identity,
+            PreconditionerType::Jacobi => Preconditioner::jacobi(a),
+            PreconditionerType::Ilu0 => Preconditioner::ilu0(a),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn jacobi(a: &CsrMatrix) -> Self {
+        Preconditioner::Jacobi(
+            a.diagonal()
+                .iter()
+                .map(|d| if *d != 0.0 { 1.0 / d } else { 1.0 })
+                .collect(),
+        )
+    }
+
+    #[allow(dead_code)]
+    pub fn ilu0(a: &CsrMatrix) -> Self {
+        let mut lu = a.clone();
+        let n = lu.nrows;
+        let diag_index: Vec<usize> = (0..n)
+            .map(|i| {
+                (lu.row_ptr[i]..lu.row_ptr[i + 1])
+                    .find(|k| lu.col_index[*k] == i)
+                    .unwrap_or_else(|| panic!("ILU(0): missing diagonal in row {}", i))
+            })
+            .collect();
+        for i in 1..n {
+            for kk in lu.row_ptr[i]..diag_index[i] {
+                let k = lu.col_index[kk];
+                lu.values[kk] /= lu.values[diag_index[k]];
+                let l_ik = lu.values[kk];
+                for jj in (kk + 1)..lu.row_ptr[i + 1] {
+                    let j = lu.col_index[jj];
+                    let u_kj = lu.get(k, j);
+                    lu.values[jj] -= l_ik * u_kj;
+                }
+            }
+        }
+        Preconditioner::Ilu0(lu)
+    }
+
+    #[allow(dead_code)]
+    pub fn apply(&self, r: &[f64]) -> Vec<f64> {
+        match self {
+            Preconditioner::Identity => r.to_vec(),
+            Preconditioner::Jacobi(inv_diag) => {
+                r.iter().zip(inv_diag.iter()).map(|(a, b)| a * b).collect()
+            }
+            Preconditioner::Ilu0(lu) => {
+                let n = lu.nrows;
+                let mut y = r.to_vec();
+                for i in 0..n {
+                    for k in lu.row_ptr[i]..lu.row_ptr[i + 1] {
+                        let j = lu.col_index[k];
+                        if j < i {
+                            y[i] -= lu.values[k] * y[j];
+                        }
+                    }
+                }
+                for i in (0..n).rev() {
+                    let mut diag = 1.0;
+                    for k in lu.row_ptr[i]..lu.row_ptr[i + 1] {
+                        let j = lu.col_index[k];
+                        if j > i {
+                            y[i] -= lu.values[k] * y[j];
+                        } else if j == i {
+                            diag = lu.values[k];
+                        }
+                    }
+                    y[i] /= diag;
+                }
+                y
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ilu0_tridiagonal_is_exact() {
+        // 三重対角では ILU(0) は完全 LU に一致する
+        let n = 10;
+        let rows: Vec<Vec<(usize, f64)>> = (0..n)
+            .map(|i| {
+                let mut row = vec![(i, 4.0)];
+                if i > 0 {
+                    row.push((i - 1, -1.0));
+                }
+                if i + 1 < n {
+                    row.push((i + 1, -2.0));
+                }
+                row
+            })
+            .collect();
+        let a = CsrMatrix::from_rows(n, &rows);
+        let x: Vec<f64> = (0..n).map(|i| (i as f64).cos()).collect();
+        let b = a.mul_vec(&x);
+        let y = Preconditioner::new(PreconditionerType::Ilu0, &a).apply(&b);
+        for (yi, xi) in y.iter().zip(x.iter()) {
+            assert!((yi - xi).abs() < 1.0e-12);
+        }
+    }
+
+    #[test]
+    fn ilu0_dense_pattern_is_exact() {
+        let n = 6;
+        let rows: Vec<Vec<(usize, f64)>> = (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| {
+                        (
+                            j,
+                            1.0 / (1.0 + i as f64 + 2.0 * j as f64) + (i == j) as usize as f64,
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+        let a = CsrMatrix::from_rows(n, &rows);
+        let x: Vec<f64> = (0..n).map(|i| 1.0 - 0.3 * i as f64).collect();
+        let y = Preconditioner::ilu0(&a).apply(&a.mul_vec(&x));
+        for (yi, xi) in y.iter().zip(x.iter()) {
+            assert!((yi - xi).abs() < 1.0e-12);
+        }
+    }
+
+    #[test]
+    fn jacobi() {
+        let a = CsrMatrix::from_rows(2, &[vec![(0, 2.0), (1, 1.0)], vec![(1, 4.0)]]);
+        let z = Preconditioner::new(PreconditionerType::Jacobi, &a).apply(&[1.0, 1.0]);
+        assert_eq!(z, vec![0.5, 0.25]);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::kd_tree;
+use crate::kd_tree::Grid2D;
+use crate::wave_eqation::WaveEq;
+
+// 局所多項式から求める事後誤差指標
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorIndicator {
+    Residual, // 近傍の点での当てはめ残差
+    Jump,     // 隣り合う多項式の中点での食い違い
+}
+
+// 誤差指標による節点の追加と間引き
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Refinement {
+    pub indicator: ErrorIndicator,
+    pub refine_ratio: f64,  // 指標が最大値のこの倍以上の点の周りに足す
+    pub coarsen_ratio: f64, // 指標が最大値のこの倍以下の点を間引く
+    pub min_spacing: f64,   // これより既存の節点に近い位置には足さない
+    pub max_spacing: f64,   // 間引いた後の間隔がこれを超えそうなら間引かない
+    pub num_neighbor: usize,
+    pub tol: f64, // 当てはめ直しの許容誤差
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AdaptStats {
+    pub added: usize,
+    pub removed: usize,
+    pub max_indicator: f64,
+}
+
+impl Refinement {
+    #[allow(dead_code)]
+    pub fn new(indicator: ErrorIndicator, min_spacing: f64, max_spacing: f64) -> Self {
+        Refinement {
+            indicator,
+            refine_ratio: 0.5,
+            coarsen_ratio: 0.01,
+            min_spacing,
+            max_spacing,
+            num_neighbor: 4,
+            tol: 1.0e-6,
+        }
+    }
+
+    // 指標の大きい点とその近傍の内部点との中点
+    #[allow(dead_code)]
+    pub fn refine_candidates(&self, wave: &WaveEq, eta: &[f64]) -> Vec<Grid2D> {
+        let max = eta.iter().cloned().fold(0.0, f64::max);
+        let mut nodes = wave.interior.clone();
+        nodes.points.extend(wave.boundary.points.iter().cloned());
+        let tree = kd_tree::KDTree::construct_kd_tree(&nodes);
+        let h2 = self.min_spacing * self.min_spacing;
+        let mut added: Vec<Grid2D> = vec![];
+        for (i, e) in eta.iter().enumerate() {
+            if max == 0.0 || *e < self.refine_ratio * max {
+                continue;
+            }
+            let p = &wave.interior.points[i];
+            for j in &wave.near_points_interior[i] {
+                let q = &wave.interior.points[*j];
+                let m = Grid2D::new(0.5 * (p.x + q.x), 0.5 * (p.y + q.y));
+                // 境界に近すぎる点は当てはめを悪くする
+                if wave.domain.sdf(m.x, m.y) > -0.5 * self.min_spacing {
+                    continue;
+                }
+                let nearest = tree.nearest_neighbors(&m, 1)[0];
+                if nodes.points[nearest].distance_square(&m) < h2
+                    || added.iter().any(|a| a.distance_square(&m) < h2)
+                {
+                    continue;
+                }
+                added.push(m);
+            }
+        }
+        added
+    }
+
+    // 指標の小さい点のうち、隣どうしを続けて消さず間隔が max_spacing を超えないもの
+    #[allow(dead_code)]
+    pub fn coarsen_candidates(&self, wave: &WaveEq, eta: &[f64]) -> Vec<usize> {
+        let max = eta.iter().cloned().fold(0.0, f64::max);
+        let n = wave.interior.points.len();
+        let tree = kd_tree::KDTree::construct_kd_tree(&wave.interior);
+        let mut keep = vec![false; n];
+        for (i, e) in eta.iter().enumerate() {
+            if *e >= self.refine_ratio * max {
+                for j in &wave.near_points_interior[i] {
+                    keep[*j] = true;
+                }
+            }
+        }
+        let mut removed = vec![];
+        for (i, e) in eta.iter().enumerate() {
+            if max == 0.0 || *e > self.coarsen_ratio * max || keep[i] {
+                continue;
+            }
+            if n - removed.len() <= self.num_neighbor {
+                break;
+            }
+            let p = &wave.interior.points[i];
+            let near = tree.nearest_neighbors(p, 2);
+            let d = wave.interior.points[near[near.len() - 1]]
+                .distance_square(p)
+                .sqrt();
+            // 近傍リストは対称でないので両向きに確かめる
+            if 2.0 * d > self.max_spacing
+                || wave.near_points_interior[i]
+                    .iter()
+                    .any(|j| removed.contains(j))
+            {
+                continue;
+            }
+            removed.push(i);
+            for j in &wave.near_points_interior[i] {
+                keep[*j] = true;
+            }
+        }
+        removed
+    }
+
+    // 節点を足し引きして近傍リストと多項式を作り直す
+    #[allow(dead_code)]
+    pub fn adapt(&self, wave: &mut WaveEq) -> AdaptStats {
+        let eta = wave.error_indicators(self.indicator);
+        let added = self.refine_candidates(wave, &eta);
+        let removed = self.coarsen_candidates(wave, &eta);
+        // 末尾に足してから消すので消す点の番号は変わらない
+        wave.insert_interior_points(&added);
+        wave.remove_interior_points(&removed);
+        wave.rebuild_near_points(self.num_neighbor);
+        wave.set_poly(self.tol);
+        AdaptStats {
+            added: added.len(),
+            removed: removed.len(),
+            max_indicator: eta.iter().cloned().fold(0.0, f64::max),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn front() -> WaveEq {
+        let mut wave = WaveEq::new();
+        wave.create(22);
+        wave.set_initial_condition_with(&|x, _| (4.0 * x).tanh(), &|_, _| 0.0, 1.0e-3);
+        wave.set_dirichlet(|x, _, _| (4.0 * x).tanh());
+        wave.set_boundary_near_points();
+        wave.set_interior_near_points(4);
+        wave.set_interior_near_points_plus_boundary();
+        wave.init_poly(2);
+        wave.set_poly(1.0e-4);
+        wave
+    }
+
+    // 前線 (x = 0) 付近の平均が遠くの平均より大きい
+    #[test]
+    fn indicators_locate_front() {
+        let wave = front();
+        for indicator in [ErrorIndicator::Residual, ErrorIndicator::Jump] {
+            let eta = wave.error_indicators(indicator);
+            assert_eq!(eta.len(), wave.interior.points.len());
+            let mean = |near: bool| {
+                let v: Vec<f64> = wave
+                    .interior
+                    .points
+                    .iter()
+                    .zip(eta.iter())
+                    .filter(|(p, _)| (p.x.abs() < 0.3) == near)
+                    .map(|(_, e)| *e)
+                    .collect();
+                v.iter().sum::<f64>() / v.len() as f64
+            };
+            assert!(eta.iter().all(|e| e.is_finite() && *e >= 0.0));
+            assert!(mean(true) > mean(false), "{:?}", indicator);
+        }
+    }
+
+    #[test]
+    fn insert_and_remove_keep_fields_consistent() {
+        let mut wave = front();
+        let n = wave.interior.points.len();
+        let p = Grid2D::new(0.01, 0.02);
+        wave.insert_interior_points(std::slice::from_ref(&p));
+        assert_eq!(wave.interior.points.len(), n + 1);
+        for len in [
+            wave.value.len(),
+            wave.value_1.len(),
+            wave.value_2.len(),
+            wave.velocity.len(),
+            wave.poly.len(),
+        ] {
+            assert_eq!(len, n + 1);
+        }
+        let i = (0..n)
+            .min_by(|a, b| {
+                let da = wave.interior.points[*a].distance_square(&p);
+                let db = wave.interior.points[*b].distance_square(&p);
+                da.partial_cmp(&db).unwrap()
+            })
+            .unwrap();
+        assert_eq!(wave.value[n], wave.poly[i].eval_xy(p.x, p.y));
+        let last = wave.value[n - 1];
+        wave.remove_interior_points(&[0, n - 2, 0]);
+        assert_eq!(wave.interior.points.len(), n - 1);
+        assert_eq!(wave.value[n - 3], last);
+        assert_eq!(wave.interior.points[n - 2], p);
+        assert_eq!(wave.poly.len(), n - 1);
+    }
+
+    #[test]
+    fn coarsen_skips_neighbors() {
+        let wave = front();
+        let n = wave.interior.points.len();
+        let mut refinement = Refinement::new(ErrorIndicator::Residual, 0.05, 10.0);
+        refinement.refine_ratio = 2.0;
+        refinement.coarsen_ratio = 1.0;
+        let removed = refinement.coarsen_candidates(&wave, &vec![1.0; n]);
+        assert!(!removed.is_empty() && removed.len() < n);
+        for i in &removed {
+            for j in &wave.near_points_interior[*i] {
+                assert!(*j == *i || !removed.contains(j));
+            }
+        }
+        refinement.max_spacing = 0.0;
+        assert!(refinement
+            .coarsen_candidates(&wave, &vec![1.0; n])
+            .is_empty());
+    }
+
+    #[test]
+    fn adapt_refines_front() {
+        let mut wave = front();
+        let n = wave.interior.points.len();
+        let mut refinement = Refinement::new(ErrorIndicator::Residual, 0.05, 0.6);
+        refinement.tol = 1.0e-4;
+        let eta = wave.error_indicators(refinement.indicator);
+        let max = eta.iter().cloned().fold(0.0, f64::max);
+        let flagged: Vec<Grid2D> = (0..n)
+            .filter(|i| eta[*i] >= refinement.refine_ratio * max)
+            .map(|i| wave.interior.points[i].clone())
+            .collect();
+        let stats = refinement.adapt(&mut wave);
+        assert!(stats.added > 0);
+        assert_eq!(stats.max_indicator, max);
+        assert_eq!(wave.interior.points.len(), n + stats.added - stats.removed);
+        for len in [
+            wave.value.len(),
+            wave.value_1.len(),
+            wave.value_2.len(),
+            wave.poly.len(),
+            wave.near_points_interior.len(),
+            wave.near_points_boundary.len(),
+        ] {
+            assert_eq!(len, wave.interior.points.len());
+        }
+        assert_eq!(wave.near.len(), wave.boundary.points.len());
+        // 足した点は指標の大きい点の近くにあり、既存の点から min_spacing 以上離れている
+        let added = &wave.interior.points[(n - stats.removed)..];
+        for (k, p) in added.iter().enumerate() {
+            assert!(
+                flagged.iter().any(|q| q.distance_square(p) < 0.25),
+                "{:?}",
+                p
+            );
+            for q in wave.interior.points.iter().take(n - stats.removed) {
+                assert!(q.distance_square(p) >= 0.05 * 0.05);
+            }
+            for q in &added[..k] {
+                assert!(q.distance_square(p) >= 0.05 * 0.05);
+            }
+        }
+    }
+}
@@ -0,0 +1,267 @@
+// 陽的 Runge-Kutta 法 (du/dt = f(u, t))
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RkScheme {
+    Euler,
+    Rk2,    // Heun
+    SspRk3, // Shu-Osher
+    Rk4,
+    HeunEuler,       // 2(1) 埋め込み
+    BogackiShampine, // 3(2) 埋め込み
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct ButcherTableau {
+    pub a: Vec<Vec<f64>>,
+    pub b: Vec<f64>,
+    pub c: Vec<f64>,
+    pub b_hat: Option<Vec<f64>>, // 誤差推定用の低次の重み
+    pub order: usize,
+}
+
+impl RkScheme {
+    #[allow(dead_code)]
+    pub fn tableau(&self) -> ButcherTableau {
+        match self {
+            RkScheme::Euler => ButcherTableau {
+                a: vec![vec![]],
+                b: vec![1.0],
+                c: vec![0.0],
+                b_hat: None,
+                order: 1,
+            },
+            RkScheme::Rk2 => ButcherTableau {
+                a: vec![vec![], vec![1.0]],
+                b: vec![0.5, 0.5],
+                c: vec![0.0, 1.0],
+                b_hat: None,
+                order: 2,
+            },
+            RkScheme::SspRk3 => ButcherTableau {
+                a: vec![vec![], vec![1.0], vec![0.25, 0.25]],
+                b: vec![1.0 / 6.0, 1.0 / 6.0, 2.0 / 3.0],
+                c: vec![0.0, 1.0, 0.5],
+                b_hat: None,
+                order: 3,
+            },
+            RkScheme::Rk4 => ButcherTableau {
+                a: vec![vec![], vec![0.5], vec![0.0, 0.5], vec![0.0, 0.0, 1.0]],
+                b: vec![1.0 / 6.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 6.0],
+                c: vec![0.0, 0.5, 0.5, 1.0],
+                b_hat: None,
+                order: 4,
+            },
+            RkScheme::HeunEuler => ButcherTableau {
+                a: vec![vec![], vec![1.0]],
+                b: vec![0.5, 0.5],
+                c: vec![0.0, 1.0],
+                b_hat: Some(vec![1.0, 0.0]),
+                order: 2,
+            },
+            RkScheme::BogackiShampine => ButcherTableau {
+                a: vec![
+                    vec![],
+                    vec![0.5],
+                    vec![0.0, 0.75],
+                    vec![2.0 / 9.0, 1.0 / 3.0, 4.0 / 9.0],
+                ],
+                b: vec![2.0 / 9.0, 1.0 / 3.0, 4.0 / 9.0, 0.0],
+                c: vec![0.0, 0.5, 0.75, 1.0],
+                b_hat: Some(vec![7.0 / 24.0, 0.25, 1.0 / 3.0, 0.125]),
+                order: 3,
+            },
+        }
+    }
+
+    // 安定領域の負の実軸上の大きさ (拡散型 λ < 0 に対して dt |λ| <= この値)
+    #[allow(dead_code)]
+    pub fn real_stability_bound(&self) -> f64 {
+        match self {
+            RkScheme::Euler | RkScheme::Rk2 | RkScheme::HeunEuler => 2.0,
+            RkScheme::SspRk3 | RkScheme::BogackiShampine => 2.5127,
+            RkScheme::Rk4 => 2.7853,
+        }
+    }
+
+    // 安定領域の虚軸上の大きさ (波動型 λ = ±iω に対して dt ω <= この値)
+    #[allow(dead_code)]
+    pub fn imaginary_stability_bound(&self) -> f64 {
+        match self {
+            RkScheme::Euler | RkScheme::Rk2 | RkScheme::HeunEuler => 0.0,
+            RkScheme::SspRk3 | RkScheme::BogackiShampine => 3.0_f64.sqrt(),
+            RkScheme::Rk4 => 2.0 * 2.0_f64.sqrt(),
+        }
+    }
+}
+
+// 1 ステップ進めた値と、埋め込み公式があれば誤差推定を返す
+#[allow(dead_code)]
+pub fn rk_step(
+    tableau: &ButcherTableau,
+    u: &[f64],
+    t: f64,
+    dt: f64,
+    f: &mut dyn FnMut(&[f64], f64) -> Vec<f64>,
+) -> (Vec<f64>, Option<Vec<f64>>) {
+    let n = u.len();
+    let mut k: Vec<Vec<f64>> = vec![];
+    for s in 0..tableau.b.len() {
+        let mut stage = u.to_vec();
+        for (j, a) in tableau.a[s].iter().enumerate() {
+            if *a != 0.0 {
+                for i in 0..n {
+                    stage[i] += dt * a * k[j][i];
+                }
+            }
+        }
+        k.push(f(&stage, t + tableau.c[s] * dt));
+    }
+    let combine = |weights: &[f64]| -> Vec<f64> {
+        let mut v = u.to_vec();
+        for (j, w) in weights.iter().enumerate() {
+            if *w != 0.0 {
+                for i in 0..n {
+                    v[i] += dt * w * k[j][i];
+                }
+            }
+        }
+        v
+    };
+    let next = combine(&tableau.b);
+    let error = tableau.b_hat.as_ref().map(|b_hat| {
+        let low = combine(b_hat);
+        next.iter().zip(low.iter()).map(|(a, b)| a - b).collect()
+    });
+    (next, error)
+}
+
+// 埋め込み公式による dt の自動制御
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct AdaptiveStepper {
+    pub scheme: RkScheme,
+    pub atol: f64,
+    pub rtol: f64,
+    pub safety: f64,
+    pub dt_min: f64,
+    pub dt_max: f64,
+    pub rejected: usize,
+}
+
+impl AdaptiveStepper {
+    #[allow(dead_code)]
+    pub fn new(scheme: RkScheme, atol: f64, rtol: f64) -> Self {
+        assert!(
+            scheme.tableau().b_hat.is_some(),
+            "{:?} has no embedded error estimate",
+            scheme
+        );
+        AdaptiveStepper {
+            scheme,
+            atol,
+            rtol,
+            safety: 0.9,
+            dt_min: 1.0e-10,
+            dt_max: f64::INFINITY,
+            rejected: 0,
+        }
+    }
+
+    // 受理された (u, dt_used, dt_next) を返す
+    #[allow(dead_code)]
+    pub fn step(
+        &mut self,
+        u: &[f64],
+        t: f64,
+        dt: f64,
+        f: &mut dyn FnMut(&[f64], f64) -> Vec<f64>,
+    ) -> (Vec<f64>, f64, f64) {
+        let tableau = self.scheme.tableau();
+        let exponent = 1.0 / tableau.order as f64;
+        let mut dt = dt.min(self.dt_max);
+        loop {
+            let (next, error) = rk_step(&tableau, u, t, dt, f);
+            let error = error.unwrap();
+            let mut err = 0.0_f64;
+            for i in 0..u.len() {
+                let scale = self.atol + self.rtol * u[i].abs().max(next[i].abs());
+                err = err.max(error[i].abs() / scale);
+            }
+            let factor = if err == 0.0 {
+                5.0
+            } else {
+                (self.safety * err.powf(-exponent)).clamp(0.2, 5.0)
+            };
+            if err <= 1.0 || dt <= self.dt_min {
+                let dt_next = (dt * factor).clamp(self.dt_min, self.dt_max);
+                return (next, dt, dt_next);
+            }
+            self.rejected += 1;
+            dt = (dt * factor).max(self.dt_min);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // du/dt = -u + sin(t)
+    fn error(scheme: RkScheme, dt: f64) -> f64 {
+        let mut f = |u: &[f64], t: f64| vec![-u[0] + t.sin()];
+        let exact = |t: f64| 1.5 * (-t).exp() + 0.5 * (t.sin() - t.cos());
+        let tab = scheme.tableau();
+        let mut u = vec![1.0];
+        let steps = (1.0 / dt).round() as usize;
+        for k in 0..steps {
+            u = rk_step(&tab, &u, k as f64 * dt, dt, &mut f).0;
+        }
+        (u[0] - exact(1.0)).abs()
+    }
+
+    #[test]
+    fn orders() {
+        for scheme in [
+            RkScheme::Euler,
+            RkScheme::Rk2,
+            RkScheme::SspRk3,
+            RkScheme::Rk4,
+            RkScheme::BogackiShampine,
+        ] {
+            let rate = (error(scheme, 0.05) / error(scheme, 0.025)).log2();
+            let order = scheme.tableau().order as f64;
+            assert!((rate - order).abs() < 0.25, "{:?} {}", scheme, rate);
+        }
+    }
+
+    #[test]
+    fn embedded_error_estimate() {
+        let mut f = |u: &[f64], _t: f64| vec![u[0]];
+        let (next, err) = rk_step(&RkScheme::HeunEuler.tableau(), &[1.0], 0.0, 0.1, &mut f);
+        assert!((next[0] - 1.105).abs() < 1.0e-12);
+        assert!((err.unwrap()[0] - 0.005).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn adaptive_reaches_tolerance() {
+        let mut f = |u: &[f64], t: f64| vec![-u[0] + t.sin()];
+        let exact = |t: f64| 1.5 * (-t).exp() + 0.5 * (t.sin() - t.cos());
+        let mut stepper = AdaptiveStepper::new(RkScheme::BogackiShampine, 1.0e-6, 1.0e-6);
+        let mut u = vec![1.0];
+        let mut t = 0.0;
+        let mut dt: f64 = 0.5;
+        let mut steps = 0;
+        while t < 5.0 {
+            dt = dt.min(5.0 - t);
+            let (next, used, dt_next) = stepper.step(&u, t, dt, &mut f);
+            u = next;
+            t += used;
+            dt = dt_next;
+            steps += 1;
+        }
+        assert!((u[0] - exact(5.0)).abs() < 1.0e-5);
+        assert!(steps < 500);
+    }
+}
@@ -0,0 +1,249 @@
+// 座標 (COO) 形式。組み立て用
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CooMatrix {
+    pub nrows: usize,
+    pub ncols: usize,
+    pub row: Vec<usize>,
+    pub col: Vec<usize>,
+    pub values: Vec<f64>,
+}
+
+impl CooMatrix {
+    #[allow(dead_code)]
+    pub fn new(nrows: usize, ncols: usize) -> Self {
+        CooMatrix {
+            nrows,
+            ncols,
+            row: vec![],
+            col: vec![],
+            values: vec![],
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn push(&mut self, i: usize, j: usize, v: f64) {
+        assert!(i < self.nrows && j < self.ncols);
+        self.row.push(i);
+        self.col.push(j);
+        self.values.push(v);
+    }
+
+    #[allow(dead_code)]
+    pub fn to_csr(&self) -> CsrMatrix {
+        let mut rows = vec![Vec::<(usize, f64)>::new(); self.nrows];
+        for k in 0..self.values.len() {
+            rows[self.row[k]].push((self.col[k], self.values[k]));
+        }
+        CsrMatrix::from_rows(self.ncols, &rows)
+    }
+}
+
+// CSR 形式の疎行列
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsrMatrix {
+    pub nrows: usize,
+    pub ncols: usize,
+    pub row_ptr: Vec<usize>,
+    pub col_index: Vec<usize>,
+    pub values: Vec<f64>,
+}
+
+impl CsrMatrix {
+    // 各行の (列, 値) から組み立てる。同じ列は足し合わせる
+    #[allow(dead_code)]
+    pub fn from_rows(ncols: usize, rows: &[Vec<(usize, f64)>]) -> Self {
+        let mut row_ptr = vec![0];
+        let mut col_index = vec![];
+        let mut values = vec![];
+        for row in rows {
+            let mut entries = row.clone();
+            entries.sort_by_key(|e| e.0);
+            for (j, v) in entries {
+                assert!(j < ncols);
+                if col_index.len() > row_ptr[row_ptr.len() - 1]
+                    && col_index[col_index.len() - 1] == j
+                {
+                    let last = values.len() - 1;
+                    values[last] += v;
+                } else {
+                    col_index.push(j);
+                    values.push(v);
+                }
+            }
+            row_ptr.push(col_index.len());
+        }
+        CsrMatrix {
+            nrows: rows.len(),
+            ncols,
+            row_ptr,
+            col_index,
+            values,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn identity(n: usize) -> Self {
+        CsrMatrix {
+            nrows: n,
+            ncols: n,
+            row_ptr: (0..(n + 1)).collect(),
+            col_index: (0..n).collect(),
+            values: vec![1.0; n],
+        }
+    }
+
+    // alpha A + beta B
+    #[allow(dead_code)]
+    pub fn linear_combination(alpha: f64, a: &CsrMatrix, beta: f64, b: &CsrMatrix) -> Self {
+        assert!(a.nrows == b.nrows && a.ncols == b.ncols);
+        let rows: Vec<Vec<(usize, f64)>> = a
+            .to_rows()
+            .into_iter()
+            .zip(b.to_rows())
+            .map(|(ra, rb)| {
+                ra.into_iter()
+                    .map(|(j, v)| (j, alpha * v))
+                    .chain(rb.into_iter().map(|(j, v)| (j, beta * v)))
+                    .collect()
+            })
+            .collect();
+        CsrMatrix::from_rows(a.ncols, &rows)
+    }
+
+    #[allow(dead_code)]
+    pub fn to_rows(&self) -> Vec<Vec<(usize, f64)>> {
+        (0..self.nrows)
+            .map(|i| {
+                (self.row_ptr[i]..self.row_ptr[i + 1])
+                    .map(|k| (self.col_index[k], self.values[k]))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[allow(dead_code)]
+    pub fn transpose(&self) -> Self {
+        let mut coo = CooMatrix::new(self.ncols, self.nrows);
+        for i in 0..self.nrows {
+            for k in self.row_ptr[i]..self.row_ptr[i + 1] {
+                coo.push(self.col_index[k], i, self.values[k]);
+            }
+        }
+        coo.to_csr()
+    }
+
+    #[allow(dead_code)]
+    pub fn mul_vec(&self, x: &[f64]) -> Vec<f64> {
+        let mut y = vec![0.0; self.nrows];
+        for (i, yi) in y.iter_mut().enumerate() {
+            let mut t = 0.0;
+            for k in self.row_ptr[i]..self.row_ptr[i + 1] {
+                t += self.values[k] * x[self.col_index[k]];
+            }
+            *yi = t;
+        }
+        y
+    }
+
+    // べき乗法による |λ|max の推定
+    #[allow(dead_code)]
+    pub fn spectral_radius(&self, max_iter: usize) -> f64 {
+        let n = self.nrows;
+        let mut v: Vec<f64> = (0..n).map(|i| 1.0 + (i as f64 * 0.618).fract()).collect();
+        let mut rho = 0.0;
+        for _ in 0..max_iter {
+            let norm_v = v.iter().map(|a| a * a).sum::<f64>().sqrt();
+            if norm_v == 0.0 {
+                return 0.0;
+            }
+            let w = self.mul_vec(&v);
+            let norm_w = w.iter().map(|a| a * a).sum::<f64>().sqrt();
+            let rho_new = norm_w / norm_v;
+            v = w
+                .iter()
+                .map(|a| a / norm_w.max(f64::MIN_POSITIVE))
+                .collect();
+            if (rho_new - rho).abs() < 1.0e-8 * rho_new {
+                return rho_new;
+            }
+            rho = rho_new;
+        }
+        rho
+    }
+
+    #[allow(dead_code)]
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        for k in self.row_ptr[i]..self.row_ptr[i + 1] {
+            if self.col_index[k] == j {
+                return self.values[k];
+            }
+        }
+        0.0
+    }
+
+    #[allow(dead_code)]
+    pub fn diagonal(&self) -> Vec<f64> {
+        (0..self.nrows).map(|i| self.get(i, i)).collect()
+    }
+
+    #[allow(dead_code)]
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csr_from_rows() {
+        // [[2, 0, 1], [0, 3, 0]]
+        let a = CsrMatrix::from_rows(3, &[vec![(2, 1.0), (0, 1.5), (0, 0.5)], vec![(1, 3.0)]]);
+        assert_eq!(a.row_ptr, vec![0, 2, 3]);
+        assert_eq!(a.col_index, vec![0, 2, 1]);
+        assert_eq!(a.values, vec![2.0, 1.0, 3.0]);
+        assert_eq!(a.get(0, 0), 2.0);
+        assert_eq!(a.get(1, 2), 0.0);
+        assert_eq!(a.mul_vec(&[1.0, 2.0, 3.0]), vec![5.0, 6.0]);
+        assert_eq!(a.diagonal(), vec![2.0, 3.0]);
+    }
+
+    #[test]
+    fn spectral_radius() {
+        let a = CsrMatrix::from_rows(
+            3,
+            &[
+                vec![(0, -4.0), (1, 1.0)],
+                vec![(0, 1.0), (1, -3.0)],
+                vec![(2, 1.0)],
+            ],
+        );
+        // 固有値 (-7 ± √5) / 2, 1
+        let expected = (7.0 + 5.0_f64.sqrt()) / 2.0;
+        assert!((a.spectral_radius(1000) - expected).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn coo_to_csr_and_transpose() {
+        let mut coo = CooMatrix::new(2, 3);
+        coo.push(1, 1, 3.0);
+        coo.push(0, 2, 1.0);
+        coo.push(0, 0, 2.0);
+        let a = coo.to_csr();
+        assert_eq!(a.mul_vec(&[1.0, 2.0, 3.0]), vec![5.0, 6.0]);
+        let at = a.transpose();
+        assert_eq!(at.nrows, 3);
+        assert_eq!(at.ncols, 2);
+        assert_eq!(at.mul_vec(&[1.0, 2.0]), vec![2.0, 6.0, 1.0]);
+        assert_eq!(at.transpose(), a);
+        assert_eq!(
+            CsrMatrix::identity(3).mul_vec(&[1.0, 2.0, 3.0]),
+            vec![1.0, 2.0, 3.0]
+        );
+        let c = CsrMatrix::linear_combination(2.0, &at, -1.0, &at);
+        assert_eq!(c, at);
+    }
+}
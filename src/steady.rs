@@ -0,0 +1,324 @@
+use crate::boundary_condition::BoundaryConditions;
+use crate::krylov;
+use crate::krylov::KrylovMethod;
+use crate::meshfree::MeshfreeNodes;
+use crate::pde_model::Conductivity;
+use crate::preconditioner::{Preconditioner, PreconditionerType};
+use crate::sparse_matrix::CsrMatrix;
+use crate::stencil::DiffOperator;
+
+// 定常問題 L u = f (内部), u = g (境界, Dirichlet)
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct SteadySolution {
+    pub value: Vec<f64>, // 内部点、境界点の順
+    pub residual_history: Vec<f64>,
+    pub converged: bool,
+}
+
+#[allow(dead_code)]
+pub struct SteadySolver {
+    pub mesh: MeshfreeNodes,
+    pub method: KrylovMethod,
+    pub preconditioner: PreconditionerType,
+    pub tol: f64,
+    pub max_iter: usize,
+}
+
+impl SteadySolver {
+    #[allow(dead_code)]
+    pub fn new(mesh: MeshfreeNodes) -> Self {
+        SteadySolver {
+            mesh,
+            method: KrylovMethod::BiCgStab,
+            preconditioner: PreconditionerType::Ilu0,
+            tol: 1.0e-10,
+            max_iter: 1000,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn solve(
+        &self,
+        op: &DiffOperator,
+        f: &dyn Fn(f64, f64) -> f64,
+        g: &dyn Fn(f64, f64) -> f64,
+    ) -> Result<SteadySolution, String> {
+        Ok(self.solve_rows(&self.mesh.operator_rows(op)?, f, g))
+    }
+
+    // 内部点の行 (num_interior x num_nodes) を直接与える
+    #[allow(dead_code)]
+    pub fn solve_rows(
+        &self,
+        rows: &[Vec<(usize, f64)>],
+        f: &dyn Fn(f64, f64) -> f64,
+        g: &dyn Fn(f64, f64) -> f64,
+    ) -> SteadySolution {
+        let n = self.mesh.num_interior;
+        let (a_ii, a_ib) = self.mesh.split_rows(rows);
+        let g_b: Vec<f64> = self.mesh.nodes.points[n..]
+            .iter()
+            .map(|p| g(p.x, p.y))
+            .collect();
+        let lift = a_ib.mul_vec(&g_b);
+        let b: Vec<f64> = (0..n)
+            .map(|i| {
+                let p = self.mesh.point(i);
+                f(p.x, p.y) - lift[i]
+            })
+            .collect();
+        let precond = Preconditioner::new(self.preconditioner, &a_ii);
+        let res = krylov::solve(
+            self.method,
+            &a_ii,
+            &b,
+            &vec![0.0; n],
+            &precond,
+            self.tol,
+            self.max_iter,
+        );
+        let mut value = res.x;
+        value.extend(g_b);
+        SteadySolution {
+            value,
+            residual_history: res.residual_history,
+            converged: res.converged,
+        }
+    }
+
+    // 境界点ごとに Dirichlet / Neumann / Robin を課す。Dirichlet 以外の境界値も未知数になるので
+    // 境界の行を含めた全節点の系を解く
+    #[allow(dead_code)]
+    pub fn solve_with_conditions(
+        &self,
+        op: &DiffOperator,
+        f: &dyn Fn(f64, f64) -> f64,
+        bc: &BoundaryConditions,
+    ) -> Result<SteadySolution, String> {
+        let n = self.mesh.num_interior;
+        let mut rows = self.mesh.operator_rows(op)?;
+        let mut b: Vec<f64> = (0..n)
+            .map(|i| {
+                let p = self.mesh.point(i);
+                f(p.x, p.y)
+            })
+            .collect();
+        for k in 0..self.mesh.num_boundary() {
+            let p = self.mesh.point(n + k);
+            let (alpha, beta, r) = bc.condition_at(p.x, p.y).coefficients(p.x, p.y, 0.0);
+            rows.push(self.mesh.boundary_row(k, alpha, beta)?);
+            b.push(r);
+        }
+        let a = CsrMatrix::from_rows(self.mesh.num_nodes(), &rows);
+        let precond = Preconditioner::new(self.preconditioner, &a);
+        let res = krylov::solve(
+            self.method,
+            &a,
+            &b,
+            &vec![0.0; a.nrows],
+            &precond,
+            self.tol,
+            self.max_iter,
+        );
+        Ok(SteadySolution {
+            value: res.x,
+            residual_history: res.residual_history,
+            converged: res.converged,
+        })
+    }
+
+    // -Δu = f
+    #[allow(dead_code)]
+    pub fn solve_poisson(
+        &self,
+        f: &dyn Fn(f64, f64) -> f64,
+        g: &dyn Fn(f64, f64) -> f64,
+    ) -> Result<SteadySolution, String> {
+        self.solve(&DiffOperator::laplacian().scale(-1.0), f, g)
+    }
+
+    // -∇・(κ ∇u) = f 。保存形の行なので層状の不連続な κ も扱える
+    #[allow(dead_code)]
+    pub fn solve_diffusion(
+        &self,
+        conductivity: &Conductivity,
+        f: &dyn Fn(f64, f64) -> f64,
+        g: &dyn Fn(f64, f64) -> f64,
+    ) -> Result<SteadySolution, String> {
+        let kappa = conductivity.node_field(&self.mesh.nodes);
+        let rows: Vec<Vec<(usize, f64)>> = self
+            .mesh
+            .divergence_rows(&kappa)?
+            .into_iter()
+            .map(|row| row.into_iter().map(|(j, w)| (j, -w)).collect())
+            .collect();
+        Ok(self.solve_rows(&rows, f, g))
+    }
+
+    // Δu + k^2 u = f
+    #[allow(dead_code)]
+    pub fn solve_helmholtz(
+        &self,
+        k: f64,
+        f: &dyn Fn(f64, f64) -> f64,
+        g: &dyn Fn(f64, f64) -> f64,
+    ) -> Result<SteadySolution, String> {
+        let op = DiffOperator::laplacian().add(&DiffOperator::identity().scale(k * k));
+        self.solve(&op, f, g)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wave_eqation::WaveEq;
+
+    fn solver() -> SteadySolver {
+        let mut wave = WaveEq::new();
+        wave.create(40);
+        SteadySolver::new(MeshfreeNodes::from_wave(&wave, 2, 12))
+    }
+
+    #[test]
+    fn poisson_quadratic() {
+        let solver = solver();
+        let u = |x: f64, y: f64| x * x + y * y + 0.5 * x;
+        let sol = solver.solve_poisson(&|_, _| -4.0, &u).unwrap();
+        assert!(sol.converged);
+        for (p, v) in solver.mesh.nodes.points.iter().zip(sol.value.iter()) {
+            assert!((u(p.x, p.y) - v).abs() < 1.0e-7);
+        }
+    }
+
+    #[test]
+    fn poisson_gmres_jacobi() {
+        let mut solver = solver();
+        solver.method = KrylovMethod::Gmres(30);
+        solver.preconditioner = PreconditionerType::Jacobi;
+        let u = |x: f64, y: f64| 2.0 * x * y - y * y;
+        let sol = solver.solve_poisson(&|_, _| 2.0, &u).unwrap();
+        assert!(sol.converged);
+        for (p, v) in solver.mesh.nodes.points.iter().zip(sol.value.iter()) {
+            assert!((u(p.x, p.y) - v).abs() < 1.0e-7);
+        }
+    }
+
+    #[test]
+    fn helmholtz_quadratic() {
+        let solver = solver();
+        let k = 2.0;
+        let u = |x: f64, y: f64| x * x - y * y + x * y + 1.0;
+        let sol = solver
+            .solve_helmholtz(k, &|x, y| k * k * u(x, y), &u)
+            .unwrap();
+        assert!(sol.converged);
+        assert!(sol.residual_history[sol.residual_history.len() - 1] < 1.0e-10);
+        for (p, v) in solver.mesh.nodes.points.iter().zip(sol.value.iter()) {
+            assert!((u(p.x, p.y) - v).abs() < 1.0e-7);
+        }
+    }
+
+    #[test]
+    fn poisson_mixed_neumann_dirichlet() {
+        use crate::boundary_condition::BoundaryCondition;
+        use std::rc::Rc;
+        let solver = solver();
+        // 単位円上で ∂u/∂n = x u_x + y u_y
+        let u = |x: f64, y: f64| x * x + y * y + 0.5 * x;
+        let mut bc =
+            BoundaryConditions::new(BoundaryCondition::Dirichlet(Rc::new(move |x, y, _| {
+                u(x, y)
+            })));
+        bc.add_segment(
+            |_, y| y > 0.0,
+            BoundaryCondition::Neumann(Rc::new(|x, y, _| 2.0 * (x * x + y * y) + 0.5 * x)),
+        );
+        let op = DiffOperator::laplacian().scale(-1.0);
+        let sol = solver
+            .solve_with_conditions(&op, &|_, _| -4.0, &bc)
+            .unwrap();
+        assert!(sol.converged);
+        for (p, v) in solver.mesh.nodes.points.iter().zip(sol.value.iter()) {
+            assert!((u(p.x, p.y) - v).abs() < 1.0e-6);
+        }
+    }
+
+    #[test]
+    fn poisson_robin() {
+        use crate::boundary_condition::BoundaryCondition;
+        use std::rc::Rc;
+        let solver = solver();
+        let u = |x: f64, y: f64| x * y - y * y + 1.0;
+        // u + ∂u/∂n = r, ∂u/∂n = 2xy - 2y^2
+        let bc = BoundaryConditions::new(BoundaryCondition::Robin {
+            alpha: 1.0,
+            beta: 1.0,
+            r: Rc::new(move |x, y, _| u(x, y) + 2.0 * x * y - 2.0 * y * y),
+        });
+        let op = DiffOperator::laplacian().scale(-1.0);
+        let sol = solver.solve_with_conditions(&op, &|_, _| 2.0, &bc).unwrap();
+        assert!(sol.converged);
+        for (p, v) in solver.mesh.nodes.points.iter().zip(sol.value.iter()) {
+            assert!((u(p.x, p.y) - v).abs() < 1.0e-6);
+        }
+    }
+
+    #[test]
+    fn layered_diffusion() {
+        use crate::domain::Domain;
+        use crate::node_generator::{NodeGenerator, NodeStrategy, Spacing};
+        let domain = Domain::Rectangle {
+            x_min: 0.0,
+            y_min: 0.0,
+            x_max: 1.0,
+            y_max: 1.0,
+        };
+        let generator =
+            NodeGenerator::new(domain, Spacing::Uniform(0.04), NodeStrategy::HexLattice);
+        let (boundary, interior) = generator.generate();
+        let mut solver = SteadySolver::new(MeshfreeNodes::new(&interior, &boundary, 2, 12));
+        solver.method = KrylovMethod::Gmres(50);
+        // x = 0.5 で κ が 1 から 10 に跳ぶ。流束 q = κ u_x は一定で u は折れ線
+        let kappa = Conductivity::layered((1.0, 0.0), vec![0.5], vec![1.0, 10.0]);
+        let q = 1.0 / (0.5 / 1.0 + 0.5 / 10.0);
+        let u = move |x: f64, _y: f64| {
+            if x < 0.5 {
+                q * x
+            } else {
+                q * 0.5 + q * (x - 0.5) / 10.0
+            }
+        };
+        let sol = solver.solve_diffusion(&kappa, &|_, _| 0.0, &u).unwrap();
+        assert!(sol.converged);
+        let error = solver
+            .mesh
+            .nodes
+            .points
+            .iter()
+            .zip(sol.value.iter())
+            .map(|(p, v)| (u(p.x, p.y) - v).abs())
+            .fold(0.0, f64::max);
+        assert!(error < 0.02, "{}", error);
+    }
+
+    #[test]
+    fn degenerate_stencil_is_err() {
+        use crate::kd_tree::Points2D;
+        // 一直線に並んだ点では 2 次の重みが決まらない
+        let mut interior = Points2D::new();
+        let mut boundary = Points2D::new();
+        for k in 1..20 {
+            interior.push(0.05 * k as f64, 0.0);
+        }
+        boundary.push(0.0, 0.0);
+        boundary.push(1.0, 0.0);
+        let solver = SteadySolver::new(MeshfreeNodes::new(&interior, &boundary, 2, 12));
+        let e = solver.solve_poisson(&|_, _| 0.0, &|_, _| 0.0).unwrap_err();
+        assert!(e.contains("degenerate stencil"), "{}", e);
+        assert!(solver
+            .mesh
+            .interior_system(&DiffOperator::laplacian())
+            .is_err());
+    }
+}
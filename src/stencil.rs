@@ -0,0 +1,364 @@
+use crate::dense_matrix;
+use crate::kd_tree::Grid2D;
+use crate::pde_model::{LocalDerivatives, PdeModel};
+
+// L u = c u + c_x u_x + c_y u_y + c_xx u_xx + c_xy u_xy + c_yy u_yy
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DiffOperator {
+    pub c: f64,
+    pub c_x: f64,
+    pub c_y: f64,
+    pub c_xx: f64,
+    pub c_xy: f64,
+    pub c_yy: f64,
+}
+
+impl DiffOperator {
+    #[allow(dead_code)]
+    pub fn identity() -> Self {
+        DiffOperator {
+            c: 1.0,
+            ..Default::default()
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn d_x() -> Self {
+        DiffOperator {
+            c_x: 1.0,
+            ..Default::default()
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn d_y() -> Self {
+        DiffOperator {
+            c_y: 1.0,
+            ..Default::default()
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn laplacian() -> Self {
+        DiffOperator {
+            c_xx: 1.0,
+            c_yy: 1.0,
+            ..Default::default()
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn scale(&self, a: f64) -> Self {
+        DiffOperator {
+            c: a * self.c,
+            c_x: a * self.c_x,
+            c_y: a * self.c_y,
+            c_xx: a * self.c_xx,
+            c_xy: a * self.c_xy,
+            c_yy: a * self.c_yy,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn add(&self, other: &DiffOperator) -> Self {
+        DiffOperator {
+            c: self.c + other.c,
+            c_x: self.c_x + other.c_x,
+            c_y: self.c_y + other.c_y,
+            c_xx: self.c_xx + other.c_xx,
+            c_xy: self.c_xy + other.c_xy,
+            c_yy: self.c_yy + other.c_yy,
+        }
+    }
+
+    // 点 (x, y) での model の右辺の線形部分 (右辺が局所微分についてアフィンなら厳密)
+    #[allow(dead_code)]
+    pub fn linearize(model: &dyn PdeModel, x: f64, y: f64, t: f64) -> Self {
+        let zero = LocalDerivatives::default();
+        let f0 = model.rhs(&zero, x, y, t);
+        let probe = |d: LocalDerivatives| model.rhs(&d, x, y, t) - f0;
+        DiffOperator {
+            c: probe(LocalDerivatives { u: 1.0, ..zero }),
+            c_x: probe(LocalDerivatives { u_x: 1.0, ..zero }),
+            c_y: probe(LocalDerivatives { u_y: 1.0, ..zero }),
+            c_xx: probe(LocalDerivatives { u_xx: 1.0, ..zero }),
+            c_xy: probe(LocalDerivatives { u_xy: 1.0, ..zero }),
+            c_yy: probe(LocalDerivatives { u_yy: 1.0, ..zero }),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn apply(&self, d: &LocalDerivatives) -> f64 {
+        self.c * d.u
+            + self.c_x * d.u_x
+            + self.c_y * d.u_y
+            + self.c_xx * d.u_xx
+            + self.c_xy * d.u_xy
+            + self.c_yy * d.u_yy
+    }
+
+    // 局所座標 (xi, eta) = (x - x_c, y - y_c) / h での単項式 xi^a eta^b に作用させた原点での値
+    fn apply_monomial(&self, a: usize, b: usize, h: f64) -> f64 {
+        match (a, b) {
+            (0, 0) => self.c,
+            (1, 0) => self.c_x / h,
+            (0, 1) => self.c_y / h,
+            (2, 0) => 2.0 * self.c_xx / (h * h),
+            (1, 1) => self.c_xy / (h * h),
+            (0, 2) => 2.0 * self.c_yy / (h * h),
+            _ => 0.0,
+        }
+    }
+}
+
+// 全次数 degree 以下の単項式の指数 (a, b)
+#[allow(dead_code)]
+pub fn monomial_exponents(degree: usize) -> Vec<(usize, usize)> {
+    let mut exps = vec![];
+    for total in 0..(degree + 1) {
+        for a in (0..(total + 1)).rev() {
+            exps.push((a, total - a));
+        }
+    }
+    exps
+}
+
+// 最小二乗の各行 (近傍点) に掛ける重み ω
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Weighting {
+    // ω = 1。近傍点をすべて同等に扱う
+    Uniform,
+    // ω = exp(-4 r^2 / h^2) (h は最遠の近傍点までの距離)。最遠点の重みは e^-4 ≈ 0.018 まで下がり、
+    // 多項式はほぼ中心付近の点で決まる。ラプラシアンのステンシルは中心係数の比重が大きくなり
+    // 組み立てた行列が対角優位に近づくので、Jacobi/ILU(0) 前処理の効きがよくなる
+    Gaussian,
+}
+
+impl Weighting {
+    fn omega(&self, r2: f64, h: f64) -> f64 {
+        match self {
+            Weighting::Uniform => 1.0,
+            Weighting::Gaussian => (-4.0 * r2 / (h * h)).exp(),
+        }
+    }
+}
+
+// 重み付き最小二乗の局所系: (h, Vandermonde 行, 重み ω, Gram 行列 V^T W V)
+type LocalSystem = (f64, Vec<Vec<f64>>, Vec<f64>, Vec<Vec<f64>>);
+
+fn local_system(
+    center: &Grid2D,
+    nodes: &[Grid2D],
+    degree: usize,
+    weighting: Weighting,
+) -> Option<LocalSystem> {
+    let exps = monomial_exponents(degree);
+    let m = exps.len();
+    if nodes.len() < m {
+        return None;
+    }
+    let h = nodes
+        .iter()
+        .fold(0.0_f64, |s, p| s.max(p.distance_square(center).sqrt()));
+    if h == 0.0 {
+        return None;
+    }
+    let vander: Vec<Vec<f64>> = nodes
+        .iter()
+        .map(|p| {
+            let xi = (p.x - center.x) / h;
+            let eta = (p.y - center.y) / h;
+            exps.iter()
+                .map(|(a, b)| xi.powi(*a as i32) * eta.powi(*b as i32))
+                .collect()
+        })
+        .collect();
+    let omega: Vec<f64> = nodes
+        .iter()
+        .map(|p| weighting.omega(p.distance_square(center), h))
+        .collect();
+    let mut gram = vec![vec![0.0; m]; m];
+    for (row, o) in vander.iter().zip(omega.iter()) {
+        for i in 0..m {
+            for j in 0..m {
+                gram[i][j] += o * row[i] * row[j];
+            }
+        }
+    }
+    Some((h, vander, omega, gram))
+}
+
+// 近傍点での値 u_j から最小二乗多項式を経由して (L u)(center) ≈ Σ w_j u_j となる重み w を返す
+// 最小二乗は Weighting::Gaussian で重み付けする
+#[allow(dead_code)]
+pub fn weights(
+    center: &Grid2D,
+    nodes: &[Grid2D],
+    degree: usize,
+    op: &DiffOperator,
+) -> Option<Vec<f64>> {
+    weights_with(center, nodes, degree, op, Weighting::Gaussian)
+}
+
+#[allow(dead_code)]
+pub fn weights_with(
+    center: &Grid2D,
+    nodes: &[Grid2D],
+    degree: usize,
+    op: &DiffOperator,
+    weighting: Weighting,
+) -> Option<Vec<f64>> {
+    let (h, vander, omega, gram) = local_system(center, nodes, degree, weighting)?;
+    let l: Vec<f64> = monomial_exponents(degree)
+        .iter()
+        .map(|(a, b)| op.apply_monomial(*a, *b, h))
+        .collect();
+    let z = dense_matrix::solve(&gram, &l)?;
+    Some(
+        vander
+            .iter()
+            .zip(omega.iter())
+            .map(|(row, o)| o * row.iter().zip(z.iter()).map(|(v, zi)| v * zi).sum::<f64>())
+            .collect(),
+    )
+}
+
+// 重み付き Vandermonde 行列 W^(1/2) V の 2 ノルム条件数。点が足りなければ None、
+// 退化しているか Gram 行列に NaN や無限大があれば ∞
+#[allow(dead_code)]
+pub fn condition_number(center: &Grid2D, nodes: &[Grid2D], degree: usize) -> Option<f64> {
+    let (_, _, _, gram) = local_system(center, nodes, degree, Weighting::Gaussian)?;
+    if gram.iter().flatten().any(|v| !v.is_finite()) {
+        return Some(f64::INFINITY);
+    }
+    let lambda = dense_matrix::symmetric_eigenvalues(&gram);
+    let (min, max) = (lambda[0], lambda[lambda.len() - 1]);
+    if !(min.is_finite() && max.is_finite()) || min <= max * 1.0e-300 {
+        return Some(f64::INFINITY);
+    }
+    Some((max / min).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cloud() -> Vec<Grid2D> {
+        let mut nodes = vec![];
+        for i in -2..3 {
+            for j in -2..3 {
+                nodes.push(Grid2D::new(
+                    0.3 + 0.1 * i as f64 + 0.01 * j as f64,
+                    -0.2 + 0.1 * j as f64 - 0.013 * i as f64,
+                ));
+            }
+        }
+        nodes
+    }
+
+    #[test]
+    fn exponents() {
+        assert_eq!(
+            monomial_exponents(2),
+            vec![(0, 0), (1, 0), (0, 1), (2, 0), (1, 1), (0, 2)]
+        );
+    }
+
+    #[test]
+    fn reproduce_quadratic() {
+        let nodes = cloud();
+        let center = Grid2D::new(0.31, -0.19);
+        let u = |x: f64, y: f64| 1.0 + 2.0 * x - y + 3.0 * x * x + 0.5 * x * y - 2.0 * y * y;
+        let values: Vec<f64> = nodes.iter().map(|p| u(p.x, p.y)).collect();
+        let apply = |op: &DiffOperator| -> f64 {
+            let w = weights(&center, &nodes, 2, op).unwrap();
+            w.iter().zip(values.iter()).map(|(a, b)| a * b).sum()
+        };
+        assert!((apply(&DiffOperator::identity()) - u(0.31, -0.19)).abs() < 1.0e-10);
+        assert!((apply(&DiffOperator::d_x()) - (2.0 + 6.0 * 0.31 - 0.5 * 0.19)).abs() < 1.0e-9);
+        assert!((apply(&DiffOperator::d_y()) - (-1.0 + 0.5 * 0.31 + 4.0 * 0.19)).abs() < 1.0e-9);
+        assert!((apply(&DiffOperator::laplacian()) - 2.0).abs() < 1.0e-8);
+    }
+
+    #[test]
+    fn linearize_model() {
+        use crate::pde_model::AdvectionDiffusionModel;
+        let op =
+            DiffOperator::linearize(&AdvectionDiffusionModel::new(1.0, -2.0, 0.5), 0.0, 0.0, 0.0);
+        assert_eq!(
+            op,
+            DiffOperator {
+                c: 0.0,
+                c_x: -1.0,
+                c_y: 2.0,
+                c_xx: 0.5,
+                c_xy: 0.0,
+                c_yy: 0.5
+            }
+        );
+    }
+
+    #[test]
+    fn gaussian_weighting_strengthens_center() {
+        let nodes = cloud();
+        let center = nodes[12].clone();
+        // 中心係数の大きさ / 他の係数の絶対値和
+        let dominance = |weighting: Weighting| {
+            let w =
+                weights_with(&center, &nodes, 2, &DiffOperator::laplacian(), weighting).unwrap();
+            let off: f64 = w
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != 12)
+                .map(|(_, a)| a.abs())
+                .sum();
+            -w[12] / off
+        };
+        let uniform = dominance(Weighting::Uniform);
+        let gaussian = dominance(Weighting::Gaussian);
+        assert!(gaussian > 2.0 * uniform, "{} {}", gaussian, uniform);
+        // どちらの重みでも 2 次式は厳密に再現する
+        let values: Vec<f64> = nodes.iter().map(|p| p.x * p.x + 3.0 * p.y * p.y).collect();
+        for weighting in [Weighting::Uniform, Weighting::Gaussian] {
+            let w =
+                weights_with(&center, &nodes, 2, &DiffOperator::laplacian(), weighting).unwrap();
+            let lap: f64 = w.iter().zip(values.iter()).map(|(a, b)| a * b).sum();
+            assert!((lap - 8.0).abs() < 1.0e-8, "{}", lap);
+        }
+    }
+
+    #[test]
+    fn too_few_nodes() {
+        let nodes = cloud()[0..4].to_vec();
+        assert_eq!(
+            weights(
+                &Grid2D::new(0.0, 0.0),
+                &nodes,
+                2,
+                &DiffOperator::laplacian()
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn stencil_condition_number() {
+        let nodes = cloud();
+        let center = Grid2D::new(0.31, -0.19);
+        let kappa = condition_number(&center, &nodes, 2).unwrap();
+        assert!(kappa > 1.0 && kappa < 1.0e3, "{}", kappa);
+        // 一直線上の点では 2 次の最小二乗は決まらない
+        let line: Vec<Grid2D> = (0..10)
+            .map(|i| Grid2D::new(0.1 * i as f64, 0.05 * i as f64))
+            .collect();
+        let kappa_line = condition_number(&Grid2D::new(0.0, 0.0), &line, 2).unwrap();
+        assert!(kappa_line > 1.0e6, "{}", kappa_line);
+        assert_eq!(condition_number(&center, &nodes[0..4], 2), None);
+        // 座標が NaN の点を含む
+        let mut broken = nodes.clone();
+        broken[3] = Grid2D::new(f64::NAN, -0.2);
+        assert_eq!(condition_number(&center, &broken, 2), Some(f64::INFINITY));
+    }
+}
@@ -0,0 +1,228 @@
+use crate::dense_matrix;
+use crate::point::Point3;
+
+// L u = c u + c_x u_x + c_y u_y + c_z u_z + c_xx u_xx + c_yy u_yy + c_zz u_zz
+//       + c_xy u_xy + c_xz u_xz + c_yz u_yz
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DiffOperator3D {
+    pub c: f64,
+    pub c_x: f64,
+    pub c_y: f64,
+    pub c_z: f64,
+    pub c_xx: f64,
+    pub c_yy: f64,
+    pub c_zz: f64,
+    pub c_xy: f64,
+    pub c_xz: f64,
+    pub c_yz: f64,
+}
+
+impl DiffOperator3D {
+    #[allow(dead_code)]
+    pub fn identity() -> Self {
+        DiffOperator3D {
+            c: 1.0,
+            ..Default::default()
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn d_x() -> Self {
+        DiffOperator3D {
+            c_x: 1.0,
+            ..Default::default()
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn d_y() -> Self {
+        DiffOperator3D {
+            c_y: 1.0,
+            ..Default::default()
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn d_z() -> Self {
+        DiffOperator3D {
+            c_z: 1.0,
+            ..Default::default()
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn laplacian() -> Self {
+        DiffOperator3D {
+            c_xx: 1.0,
+            c_yy: 1.0,
+            c_zz: 1.0,
+            ..Default::default()
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn scale(&self, a: f64) -> Self {
+        DiffOperator3D {
+            c: a * self.c,
+            c_x: a * self.c_x,
+            c_y: a * self.c_y,
+            c_z: a * self.c_z,
+            c_xx: a * self.c_xx,
+            c_yy: a * self.c_yy,
+            c_zz: a * self.c_zz,
+            c_xy: a * self.c_xy,
+            c_xz: a * self.c_xz,
+            c_yz: a * self.c_yz,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn add(&self, other: &DiffOperator3D) -> Self {
+        DiffOperator3D {
+            c: self.c + other.c,
+            c_x: self.c_x + other.c_x,
+            c_y: self.c_y + other.c_y,
+            c_z: self.c_z + other.c_z,
+            c_xx: self.c_xx + other.c_xx,
+            c_yy: self.c_yy + other.c_yy,
+            c_zz: self.c_zz + other.c_zz,
+            c_xy: self.c_xy + other.c_xy,
+            c_xz: self.c_xz + other.c_xz,
+            c_yz: self.c_yz + other.c_yz,
+        }
+    }
+
+    // 局所座標 (p - center) / h での単項式 xi^a eta^b zeta^c に作用させた原点での値
+    fn apply_monomial(&self, e: (usize, usize, usize), h: f64) -> f64 {
+        let h2 = h * h;
+        match e {
+            (0, 0, 0) => self.c,
+            (1, 0, 0) => self.c_x / h,
+            (0, 1, 0) => self.c_y / h,
+            (0, 0, 1) => self.c_z / h,
+            (2, 0, 0) => 2.0 * self.c_xx / h2,
+            (0, 2, 0) => 2.0 * self.c_yy / h2,
+            (0, 0, 2) => 2.0 * self.c_zz / h2,
+            (1, 1, 0) => self.c_xy / h2,
+            (1, 0, 1) => self.c_xz / h2,
+            (0, 1, 1) => self.c_yz / h2,
+            _ => 0.0,
+        }
+    }
+}
+
+// 全次数 degree 以下の 3 変数単項式の指数 (a, b, c)
+#[allow(dead_code)]
+pub fn monomial_exponents_3d(degree: usize) -> Vec<(usize, usize, usize)> {
+    let mut exps = vec![];
+    for total in 0..(degree + 1) {
+        for a in (0..(total + 1)).rev() {
+            for b in (0..(total - a + 1)).rev() {
+                exps.push((a, b, total - a - b));
+            }
+        }
+    }
+    exps
+}
+
+// stencil::weights の 3 次元版。重み付き最小二乗多項式を経由して (L u)(center) ≈ Σ w_j u_j
+#[allow(dead_code)]
+pub fn weights(
+    center: &Point3,
+    nodes: &[Point3],
+    degree: usize,
+    op: &DiffOperator3D,
+) -> Option<Vec<f64>> {
+    let exps = monomial_exponents_3d(degree);
+    let m = exps.len();
+    if nodes.len() < m {
+        return None;
+    }
+    let h = nodes
+        .iter()
+        .fold(0.0_f64, |s, p| s.max(p.distance_square(center).sqrt()));
+    if h == 0.0 {
+        return None;
+    }
+    let vander: Vec<Vec<f64>> = nodes
+        .iter()
+        .map(|p| {
+            let xi = (p.x - center.x) / h;
+            let eta = (p.y - center.y) / h;
+            let zeta = (p.z - center.z) / h;
+            exps.iter()
+                .map(|(a, b, c)| xi.powi(*a as i32) * eta.powi(*b as i32) * zeta.powi(*c as i32))
+                .collect()
+        })
+        .collect();
+    let omega: Vec<f64> = nodes
+        .iter()
+        .map(|p| (-4.0 * p.distance_square(center) / (h * h)).exp())
+        .collect();
+    let mut gram = vec![vec![0.0; m]; m];
+    for (row, o) in vander.iter().zip(omega.iter()) {
+        for i in 0..m {
+            for j in 0..m {
+                gram[i][j] += o * row[i] * row[j];
+            }
+        }
+    }
+    let l: Vec<f64> = exps.iter().map(|e| op.apply_monomial(*e, h)).collect();
+    let z = dense_matrix::solve(&gram, &l)?;
+    Some(
+        vander
+            .iter()
+            .zip(omega.iter())
+            .map(|(row, o)| o * row.iter().zip(z.iter()).map(|(v, zi)| v * zi).sum::<f64>())
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_generator::halton;
+
+    #[test]
+    fn exponents_3d() {
+        assert_eq!(monomial_exponents_3d(1).len(), 4);
+        assert_eq!(monomial_exponents_3d(2).len(), 10);
+        assert_eq!(monomial_exponents_3d(3).len(), 20);
+    }
+
+    #[test]
+    fn exact_for_quadratics() {
+        let center = Point3::new(0.1, -0.2, 0.3);
+        let nodes: Vec<Point3> = (0..30)
+            .map(|k| {
+                Point3::new(
+                    0.1 + 0.2 * (halton(k + 1, 2) - 0.5),
+                    -0.2 + 0.2 * (halton(k + 1, 3) - 0.5),
+                    0.3 + 0.2 * (halton(k + 1, 5) - 0.5),
+                )
+            })
+            .collect();
+        let u = |p: &Point3| {
+            1.0 + p.x - 2.0 * p.z + p.x * p.x + 2.0 * p.y * p.y + 3.0 * p.z * p.z + p.x * p.y
+                - p.y * p.z
+                + 4.0 * p.x * p.z
+        };
+        let values: Vec<f64> = nodes.iter().map(u).collect();
+        let eval = |op: &DiffOperator3D| {
+            let w = weights(&center, &nodes, 2, op).unwrap();
+            w.iter().zip(values.iter()).map(|(a, b)| a * b).sum::<f64>()
+        };
+        assert!((eval(&DiffOperator3D::laplacian()) - 12.0).abs() < 1.0e-8);
+        // u_z = -2 + 6z - y + 4x
+        let u_z = -2.0 + 6.0 * center.z - center.y + 4.0 * center.x;
+        assert!((eval(&DiffOperator3D::d_z()) - u_z).abs() < 1.0e-8);
+        let mixed = DiffOperator3D {
+            c_xz: 1.0,
+            c_yz: 1.0,
+            ..Default::default()
+        };
+        assert!((eval(&mixed) - 3.0).abs() < 1.0e-8);
+        assert!((eval(&DiffOperator3D::identity()) - u(&center)).abs() < 1.0e-10);
+    }
+}
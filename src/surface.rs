@@ -0,0 +1,461 @@
+use crate::dense_matrix;
+use crate::implicit::{ImplicitScheme, ImplicitStepper};
+use crate::kd_tree::Grid2D;
+use crate::kd_tree_3d::{KDTree3D, Points3D};
+use crate::meshfree_3d;
+use crate::point::Point3;
+use crate::runge_kutta::{rk_step, RkScheme};
+use crate::sparse_matrix::CsrMatrix;
+use crate::stencil;
+use crate::stencil::DiffOperator;
+use crate::two_variable_polynomial::TwoPolynomial;
+use std::fmt;
+use std::io;
+
+// 節点 p での接平面座標系。近傍点 q は (s, t, w) = ((q-p)・t1, (q-p)・t2, (q-p)・n) に写す
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TangentFrame {
+    pub normal: Point3,
+    pub t1: Point3,
+    pub t2: Point3,
+}
+
+impl TangentFrame {
+    // 近傍点の共分散行列の最小固有値の固有ベクトルを法線とする (主成分分析)
+    #[allow(dead_code)]
+    pub fn estimate(points: &[Point3]) -> Option<Self> {
+        let k = points.len() as f64;
+        let mean = points.iter().fold([0.0; 3], |m, p| {
+            [m[0] + p.x / k, m[1] + p.y / k, m[2] + p.z / k]
+        });
+        let mut cov = vec![vec![0.0; 3]; 3];
+        for p in points {
+            let d = [p.x - mean[0], p.y - mean[1], p.z - mean[2]];
+            for i in 0..3 {
+                for j in 0..3 {
+                    cov[i][j] += d[i] * d[j];
+                }
+            }
+        }
+        let lambda = dense_matrix::symmetric_eigenvalues(&cov)[0];
+        for (i, row) in cov.iter_mut().enumerate() {
+            row[i] -= lambda;
+        }
+        // C - λI の行はどれも法線に直交するので、行どうしの外積で一番長いものが法線の向き
+        let rows: Vec<Point3> = cov.iter().map(|r| Point3::new(r[0], r[1], r[2])).collect();
+        let normal = [(0, 1), (0, 2), (1, 2)]
+            .iter()
+            .map(|(a, b)| cross(&rows[*a], &rows[*b]))
+            .fold(Point3::new(0.0, 0.0, 0.0), |best, c| {
+                if norm(&c) > norm(&best) {
+                    c
+                } else {
+                    best
+                }
+            });
+        let normal = normalize(&normal)?;
+        // 法線と平行でない座標軸から接ベクトルを作る
+        let axis = if normal.x.abs() < 0.9 {
+            Point3::new(1.0, 0.0, 0.0)
+        } else {
+            Point3::new(0.0, 1.0, 0.0)
+        };
+        let t1 = normalize(&cross(&normal, &axis))?;
+        let t2 = cross(&normal, &t1);
+        Some(TangentFrame { normal, t1, t2 })
+    }
+
+    #[allow(dead_code)]
+    pub fn flip(&self) -> Self {
+        let minus = |p: &Point3| Point3::new(-p.x, -p.y, -p.z);
+        TangentFrame {
+            normal: minus(&self.normal),
+            t1: self.t2.clone(),
+            t2: self.t1.clone(),
+        }
+    }
+
+    // 原点 origin からの局所座標 (s, t, w)
+    #[allow(dead_code)]
+    pub fn local(&self, origin: &Point3, q: &Point3) -> (f64, f64, f64) {
+        let d = Point3::new(q.x - origin.x, q.y - origin.y, q.z - origin.z);
+        (dot(&d, &self.t1), dot(&d, &self.t2), dot(&d, &self.normal))
+    }
+}
+
+fn dot(a: &Point3, b: &Point3) -> f64 {
+    a.x * b.x + a.y * b.y + a.z * b.z
+}
+
+fn cross(a: &Point3, b: &Point3) -> Point3 {
+    Point3::new(
+        a.y * b.z - a.z * b.y,
+        a.z * b.x - a.x * b.z,
+        a.x * b.y - a.y * b.x,
+    )
+}
+
+fn norm(a: &Point3) -> f64 {
+    dot(a, a).sqrt()
+}
+
+fn normalize(a: &Point3) -> Option<Point3> {
+    let n = norm(a);
+    if n == 0.0 {
+        None
+    } else {
+        Some(Point3::new(a.x / n, a.y / n, a.z / n))
+    }
+}
+
+// 接平面座標 (s, t) 上の高さ w の重み付き最小二乗多項式。
+// 係数は TwoPolynomial の並び two_poly[a * (degree + 1) + b] (s^a t^b の係数)
+#[allow(dead_code)]
+pub fn fit_height(coords: &[Grid2D], heights: &[f64], degree: usize) -> Option<TwoPolynomial> {
+    let exps = stencil::monomial_exponents(degree);
+    let m = exps.len();
+    if coords.len() < m {
+        return None;
+    }
+    let origin = Grid2D::new(0.0, 0.0);
+    let h = coords
+        .iter()
+        .fold(0.0_f64, |s, p| s.max(p.distance_square(&origin).sqrt()));
+    if h == 0.0 {
+        return None;
+    }
+    let mut gram = vec![vec![0.0; m]; m];
+    let mut rhs = vec![0.0; m];
+    for (p, w) in coords.iter().zip(heights.iter()) {
+        let omega = (-4.0 * p.distance_square(&origin) / (h * h)).exp();
+        let row: Vec<f64> = exps
+            .iter()
+            .map(|(a, b)| (p.x / h).powi(*a as i32) * (p.y / h).powi(*b as i32))
+            .collect();
+        for i in 0..m {
+            rhs[i] += omega * row[i] * w;
+            for j in 0..m {
+                gram[i][j] += omega * row[i] * row[j];
+            }
+        }
+    }
+    let z = dense_matrix::solve(&gram, &rhs)?;
+    let mut poly = TwoPolynomial::new(degree);
+    for ((a, b), c) in exps.iter().zip(z.iter()) {
+        poly.two_poly[a * (degree + 1) + b] = c / h.powi((a + b) as i32);
+    }
+    Some(poly)
+}
+
+// 高さ関数 w = f(s, t) で表した曲面上の Laplace-Beltrami 作用素の原点での (s, t) 微分作用素。
+// g_ij = δ_ij + f_i f_j, G = det g とすると
+// Δ_Γ u = g^ij u_ij - (g^ij f_ij) (f_k u_k) / G,  g^ij = δ_ij - f_i f_j / G
+#[allow(dead_code)]
+pub fn laplace_beltrami_operator(height: &TwoPolynomial) -> DiffOperator {
+    let f_s = height.eval_deriv(0.0, 0.0, 1, 0);
+    let f_t = height.eval_deriv(0.0, 0.0, 0, 1);
+    let f_ss = height.eval_deriv(0.0, 0.0, 2, 0);
+    let f_st = height.eval_deriv(0.0, 0.0, 1, 1);
+    let f_tt = height.eval_deriv(0.0, 0.0, 0, 2);
+    let g = 1.0 + f_s * f_s + f_t * f_t;
+    let g_ss = 1.0 - f_s * f_s / g;
+    let g_st = -f_s * f_t / g;
+    let g_tt = 1.0 - f_t * f_t / g;
+    let mean = g_ss * f_ss + 2.0 * g_st * f_st + g_tt * f_tt;
+    DiffOperator {
+        c: 0.0,
+        c_x: -mean * f_s / g,
+        c_y: -mean * f_t / g,
+        c_xx: g_ss,
+        c_xy: 2.0 * g_st,
+        c_yy: g_tt,
+    }
+}
+
+// 曲面上の点群とその接平面、近傍
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct SurfaceNodes {
+    pub nodes: Points3D,
+    pub degree: usize,
+    pub stencils: Vec<Vec<usize>>,
+    pub frames: Vec<TangentFrame>,
+}
+
+impl SurfaceNodes {
+    // 法線は点群の重心から外向きにそろえる (星形の閉曲面を想定)。
+    // 近傍が一直線に並ぶなどして接平面が決まらなければ Err
+    #[allow(dead_code)]
+    pub fn new(nodes: &Points3D, degree: usize, num_neighbor: usize) -> Result<Self, String> {
+        let tree = KDTree3D::construct_kd_tree(nodes);
+        let n = nodes.points.len() as f64;
+        let centroid = nodes
+            .points
+            .iter()
+            .fold(Point3::new(0.0, 0.0, 0.0), |c, p| {
+                Point3::new(c.x + p.x / n, c.y + p.y / n, c.z + p.z / n)
+            });
+        let mut stencils = vec![];
+        let mut frames = vec![];
+        for (i, p) in nodes.points.iter().enumerate() {
+            let stencil = tree.nearest_neighbors(p, num_neighbor);
+            let neighbors: Vec<Point3> = stencil.iter().map(|j| nodes.points[*j].clone()).collect();
+            let frame = TangentFrame::estimate(&neighbors)
+                .ok_or_else(|| format!("degenerate neighbourhood at surface node {}", i))?;
+            let outward = Point3::new(p.x - centroid.x, p.y - centroid.y, p.z - centroid.z);
+            frames.push(if dot(&frame.normal, &outward) < 0.0 {
+                frame.flip()
+            } else {
+                frame
+            });
+            stencils.push(stencil);
+        }
+        Ok(SurfaceNodes {
+            nodes: nodes.clone(),
+            degree,
+            stencils,
+            frames,
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.points.len()
+    }
+
+    // 節点 i の近傍の接平面座標と高さ
+    fn local_coordinates(&self, i: usize) -> (Vec<Grid2D>, Vec<f64>) {
+        let p = &self.nodes.points[i];
+        self.stencils[i]
+            .iter()
+            .map(|j| {
+                let (s, t, w) = self.frames[i].local(p, &self.nodes.points[*j]);
+                (Grid2D::new(s, t), w)
+            })
+            .unzip()
+    }
+
+    // 節点 i での Δ_Γ u ≈ Σ w_j u_j となる (j, w_j)
+    #[allow(dead_code)]
+    pub fn laplace_beltrami_weights(&self, i: usize) -> Result<Vec<(usize, f64)>, String> {
+        let (coords, heights) = self.local_coordinates(i);
+        let height = fit_height(&coords, &heights, self.degree)
+            .ok_or_else(|| format!("degenerate height fit at surface node {}", i))?;
+        let op = laplace_beltrami_operator(&height);
+        let w = stencil::weights(&Grid2D::new(0.0, 0.0), &coords, self.degree, &op)
+            .ok_or_else(|| format!("degenerate stencil at surface node {}", i))?;
+        Ok(self.stencils[i].iter().cloned().zip(w).collect())
+    }
+
+    #[allow(dead_code)]
+    pub fn laplace_beltrami(&self) -> Result<CsrMatrix, String> {
+        let rows = (0..self.num_nodes())
+            .map(|i| self.laplace_beltrami_weights(i))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(CsrMatrix::from_rows(self.num_nodes(), &rows))
+    }
+}
+
+// 閉曲面上の拡散 u_t = κ Δ_Γ u。境界がないので全節点が未知数
+#[allow(dead_code)]
+pub struct SurfaceDiffusion {
+    pub mesh: SurfaceNodes,
+    pub diffusivity: f64,
+    pub value: Vec<f64>,
+    pub time: f64,
+    operator: CsrMatrix,
+    implicit: Option<ImplicitStepper>,
+}
+
+impl SurfaceDiffusion {
+    #[allow(dead_code)]
+    pub fn new(mesh: SurfaceNodes, diffusivity: f64) -> Result<Self, String> {
+        let rows = (0..mesh.num_nodes())
+            .map(|i| {
+                Ok(mesh
+                    .laplace_beltrami_weights(i)?
+                    .into_iter()
+                    .map(|(j, w)| (j, diffusivity * w))
+                    .collect())
+            })
+            .collect::<Result<Vec<Vec<(usize, f64)>>, String>>()?;
+        let operator = CsrMatrix::from_rows(mesh.num_nodes(), &rows);
+        Ok(SurfaceDiffusion {
+            value: vec![0.0; mesh.num_nodes()],
+            mesh,
+            diffusivity,
+            time: 0.0,
+            operator,
+            implicit: None,
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn set_initial_condition(&mut self, u0: &dyn Fn(f64, f64, f64) -> f64) {
+        self.value = self
+            .mesh
+            .nodes
+            .points
+            .iter()
+            .map(|p| u0(p.x, p.y, p.z))
+            .collect();
+    }
+
+    #[allow(dead_code)]
+    pub fn matrix(&self) -> &CsrMatrix {
+        &self.operator
+    }
+
+    // 陽的 RK4
+    #[allow(dead_code)]
+    pub fn step(&mut self, dt: f64) {
+        let tableau = RkScheme::Rk4.tableau();
+        let (next, _) = rk_step(&tableau, &self.value, self.time, dt, &mut |s, _| {
+            self.operator.mul_vec(s)
+        });
+        self.value = next;
+        self.time += dt;
+    }
+
+    // ImplicitStepper による陰的な 1 ステップ。scheme が変わったら作り直す。
+    // 線形ソルバーが収束しなければ状態を変えずに Err を返す
+    #[allow(dead_code)]
+    pub fn step_implicit(&mut self, scheme: ImplicitScheme, dt: f64) -> Result<(), String> {
+        if self.implicit.as_ref().map(|s| s.scheme) != Some(scheme) {
+            self.implicit = Some(ImplicitStepper::new(self.operator.clone(), scheme));
+        }
+        let n = self.mesh.num_nodes();
+        let stepper = self.implicit.as_mut().unwrap();
+        self.value = stepper.step(&self.value, self.time, dt, &|_| vec![0.0; n])?;
+        self.time += dt;
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    pub fn cfl_dt(&self, safety: f64) -> f64 {
+        safety * RkScheme::Rk4.real_stability_bound() / self.operator.spectral_radius(200)
+    }
+
+    #[allow(dead_code)]
+    pub fn save_point_cloud(&self, path: &str) -> io::Result<()> {
+        meshfree_3d::save_point_cloud(&self.mesh.nodes, &self.value, path)
+    }
+
+    #[allow(dead_code)]
+    pub fn save_vtk(&self, path: &str) -> io::Result<()> {
+        meshfree_3d::save_vtk(&self.mesh.nodes, &self.value, path)
+    }
+}
+
+impl fmt::Debug for SurfaceDiffusion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SurfaceDiffusion")
+            .field("num_nodes", &self.mesh.num_nodes())
+            .field("diffusivity", &self.diffusivity)
+            .field("time", &self.time)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain_3d::fibonacci_sphere;
+
+    fn sphere(n: usize) -> SurfaceNodes {
+        SurfaceNodes::new(
+            &Points3D {
+                points: fibonacci_sphere(n),
+            },
+            2,
+            20,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn tangent_frames_of_sphere() {
+        let mesh = sphere(500);
+        for (p, frame) in mesh.nodes.points.iter().zip(mesh.frames.iter()) {
+            // 単位球面の外向き法線は p 自身
+            assert!(dot(p, &frame.normal) > 0.999);
+            assert!(dot(&frame.t1, &frame.t2).abs() < 1.0e-12);
+            assert!(dot(&frame.t1, &frame.normal).abs() < 1.0e-12);
+        }
+        // 高さ関数は w ≈ -(s^2 + t^2) / 2
+        let (coords, heights) = mesh.local_coordinates(0);
+        let height = fit_height(&coords, &heights, 2).unwrap();
+        assert!((height.eval_deriv(0.0, 0.0, 2, 0) + 1.0).abs() < 0.05);
+        assert!((height.eval_deriv(0.0, 0.0, 0, 2) + 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn spherical_harmonics_are_eigenfunctions() {
+        // Δ_Γ Y_l = -l(l+1) Y_l
+        type Harmonic = fn(&Point3) -> f64;
+        let harmonics: [(usize, Harmonic); 3] = [
+            (1, |p| p.z),
+            (2, |p| p.x * p.y),
+            (2, |p| 3.0 * p.z * p.z - 1.0),
+        ];
+        let error = |n: usize| {
+            let mesh = sphere(n);
+            let lb = mesh.laplace_beltrami().unwrap();
+            harmonics
+                .iter()
+                .map(|(l, y)| {
+                    let u: Vec<f64> = mesh.nodes.points.iter().map(y).collect();
+                    let lu = lb.mul_vec(&u);
+                    let eigen = -((l * (l + 1)) as f64);
+                    lu.iter()
+                        .zip(u.iter())
+                        .map(|(a, b)| (a - eigen * b).abs())
+                        .fold(0.0, f64::max)
+                })
+                .fold(0.0, f64::max)
+        };
+        let coarse = error(800);
+        let fine = error(3200);
+        assert!(fine < 0.07, "{}", fine);
+        assert!(fine < 0.7 * coarse, "{} {}", coarse, fine);
+    }
+
+    #[test]
+    fn diffusion_on_sphere() {
+        // u = e^{-2t} z + e^{-6t} xy
+        let exact = |p: &Point3, t: f64| (-2.0 * t).exp() * p.z + (-6.0 * t).exp() * p.x * p.y;
+        let t_end = 0.2;
+        let max_error = |problem: &SurfaceDiffusion| {
+            problem
+                .mesh
+                .nodes
+                .points
+                .iter()
+                .zip(problem.value.iter())
+                .map(|(p, v)| (v - exact(p, t_end)).abs())
+                .fold(0.0, f64::max)
+        };
+
+        let mut explicit = SurfaceDiffusion::new(sphere(1500), 1.0).unwrap();
+        explicit.set_initial_condition(&|x, y, z| exact(&Point3::new(x, y, z), 0.0));
+        let steps = (t_end / explicit.cfl_dt(0.5)).ceil() as usize;
+        for _ in 0..steps {
+            explicit.step(t_end / steps as f64);
+        }
+        let error = max_error(&explicit);
+        assert!(error < 0.01, "{}", error);
+
+        // 陰的なら CFL よりずっと大きな dt で進められる
+        let mut implicit = SurfaceDiffusion::new(sphere(1500), 1.0).unwrap();
+        implicit.set_initial_condition(&|x, y, z| exact(&Point3::new(x, y, z), 0.0));
+        for _ in 0..5 {
+            implicit
+                .step_implicit(ImplicitScheme::Bdf2, t_end / 5.0)
+                .unwrap();
+        }
+        assert!(t_end / 5.0 > 4.0 * explicit.cfl_dt(0.5));
+        let error = max_error(&implicit);
+        assert!(error < 0.01, "{}", error);
+    }
+}
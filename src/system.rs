@@ -0,0 +1,726 @@
+use crate::kd_tree::Points2D;
+use crate::krylov;
+use crate::krylov::KrylovMethod;
+use crate::meshfree::MeshfreeNodes;
+use crate::pde_model::{LocalDerivatives, Source};
+use crate::preconditioner::{Preconditioner, PreconditionerType};
+use crate::runge_kutta::{rk_step, RkScheme};
+use crate::sparse_matrix::CsrMatrix;
+use crate::stencil::DiffOperator;
+use std::fmt;
+use std::rc::Rc;
+
+// 節点ごとに num_components 個の値をもつ場。成分ごとに全節点 (内部点、境界点の順) を並べる
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeField {
+    pub num_components: usize,
+    pub values: Vec<f64>,
+}
+
+impl NodeField {
+    #[allow(dead_code)]
+    pub fn new(num_components: usize, num_nodes: usize) -> Self {
+        NodeField {
+            num_components,
+            values: vec![0.0; num_components * num_nodes],
+        }
+    }
+
+    // f(c, x, y)
+    #[allow(dead_code)]
+    pub fn from_fn(
+        points: &Points2D,
+        num_components: usize,
+        f: &dyn Fn(usize, f64, f64) -> f64,
+    ) -> Self {
+        NodeField {
+            num_components,
+            values: (0..num_components)
+                .flat_map(|c| points.points.iter().map(move |p| f(c, p.x, p.y)))
+                .collect(),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn num_nodes(&self) -> usize {
+        self.values.len() / self.num_components
+    }
+
+    #[allow(dead_code)]
+    pub fn component(&self, c: usize) -> &[f64] {
+        let n = self.num_nodes();
+        &self.values[c * n..(c + 1) * n]
+    }
+
+    #[allow(dead_code)]
+    pub fn component_mut(&mut self, c: usize) -> &mut [f64] {
+        let n = self.num_nodes();
+        &mut self.values[c * n..(c + 1) * n]
+    }
+
+    // 節点 i の成分の値
+    #[allow(dead_code)]
+    pub fn node(&self, i: usize) -> Vec<f64> {
+        let n = self.num_nodes();
+        (0..self.num_components)
+            .map(|c| self.values[c * n + i])
+            .collect()
+    }
+}
+
+// 内部点での微分の行列。場の各成分に同じ行列を掛ける
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct FieldOperators {
+    pub d_x: CsrMatrix, // 内部点の行 x 全節点
+    pub d_y: CsrMatrix,
+    pub d_xx: CsrMatrix,
+    pub d_xy: CsrMatrix,
+    pub d_yy: CsrMatrix,
+}
+
+impl FieldOperators {
+    #[allow(dead_code)]
+    pub fn new(mesh: &MeshfreeNodes) -> Result<Self, String> {
+        let n_all = mesh.num_nodes();
+        let matrix = |op: DiffOperator| -> Result<CsrMatrix, String> {
+            Ok(CsrMatrix::from_rows(n_all, &mesh.operator_rows(&op)?))
+        };
+        let second = |c_xx: f64, c_xy: f64, c_yy: f64| DiffOperator {
+            c_xx,
+            c_xy,
+            c_yy,
+            ..Default::default()
+        };
+        Ok(FieldOperators {
+            d_x: matrix(DiffOperator::d_x())?,
+            d_y: matrix(DiffOperator::d_y())?,
+            d_xx: matrix(second(1.0, 0.0, 0.0))?,
+            d_xy: matrix(second(0.0, 1.0, 0.0))?,
+            d_yy: matrix(second(0.0, 0.0, 1.0))?,
+        })
+    }
+
+    // 成分 c に作用素を掛けた内部点の値
+    #[allow(dead_code)]
+    pub fn apply(&self, op: &DiffOperator, field: &NodeField, c: usize) -> Vec<f64> {
+        let u = field.component(c);
+        let mut result: Vec<f64> = u[0..self.d_x.nrows].iter().map(|v| op.c * v).collect();
+        for (a, m) in [
+            (op.c_x, &self.d_x),
+            (op.c_y, &self.d_y),
+            (op.c_xx, &self.d_xx),
+            (op.c_xy, &self.d_xy),
+            (op.c_yy, &self.d_yy),
+        ] {
+            if a != 0.0 {
+                krylov::axpy(a, &m.mul_vec(u), &mut result);
+            }
+        }
+        result
+    }
+
+    // 内部点 i ごとの各成分の局所微分 [i][c]
+    #[allow(dead_code)]
+    pub fn derivatives(&self, field: &NodeField) -> Vec<Vec<LocalDerivatives>> {
+        let n = self.d_x.nrows;
+        let per_component: Vec<[Vec<f64>; 5]> = (0..field.num_components)
+            .map(|c| {
+                let u = field.component(c);
+                [
+                    self.d_x.mul_vec(u),
+                    self.d_y.mul_vec(u),
+                    self.d_xx.mul_vec(u),
+                    self.d_xy.mul_vec(u),
+                    self.d_yy.mul_vec(u),
+                ]
+            })
+            .collect();
+        (0..n)
+            .map(|i| {
+                per_component
+                    .iter()
+                    .enumerate()
+                    .map(|(c, d)| LocalDerivatives {
+                        u: field.component(c)[i],
+                        u_x: d[0][i],
+                        u_y: d[1][i],
+                        u_xx: d[2][i],
+                        u_xy: d[3][i],
+                        u_yy: d[4][i],
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+// 連立系 d^n u_c / dt^n = rhs_c(u, x, y, t) 。d[d] は成分 d の局所微分
+pub trait SystemModel {
+    fn num_components(&self) -> usize;
+
+    fn time_order(&self) -> usize {
+        1
+    }
+
+    fn rhs(&self, d: &[LocalDerivatives], x: f64, y: f64, t: f64) -> Vec<f64>;
+}
+
+// 1 階の波動系 p_t = -c ∇・v, v_t = -c ∇p 。成分は (p, v_x, v_y)
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct AcousticWave {
+    pub speed: f64,
+}
+
+impl SystemModel for AcousticWave {
+    fn num_components(&self) -> usize {
+        3
+    }
+
+    fn rhs(&self, d: &[LocalDerivatives], _x: f64, _y: f64, _t: f64) -> Vec<f64> {
+        let c = self.speed;
+        vec![-c * (d[1].u_x + d[2].u_y), -c * d[0].u_x, -c * d[0].u_y]
+    }
+}
+
+// 浅水方程式の非保存形。成分は水深 h と流速 (u, v)
+// h_t = -∇・(h u), u_t = -(u・∇) u - g ∇h
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct ShallowWater {
+    pub gravity: f64,
+}
+
+impl SystemModel for ShallowWater {
+    fn num_components(&self) -> usize {
+        3
+    }
+
+    fn rhs(&self, d: &[LocalDerivatives], _x: f64, _y: f64, _t: f64) -> Vec<f64> {
+        let (h, u, v) = (&d[0], &d[1], &d[2]);
+        vec![
+            -(h.u_x * u.u + h.u * u.u_x + h.u_y * v.u + h.u * v.u_y),
+            -(u.u * u.u_x + v.u * u.u_y) - self.gravity * h.u_x,
+            -(u.u * v.u_x + v.u * v.u_y) - self.gravity * h.u_y,
+        ]
+    }
+}
+
+#[allow(dead_code)]
+pub type ForceFn = Rc<dyn Fn(f64, f64) -> (f64, f64)>;
+
+// 線形弾性体の変位 (u, v): ρ = 1 として u_tt = μ Δu + (λ + μ) ∇(∇・u) + f
+// 定常問題は CoupledSystem::solve_steady で右辺 = 0 を解く
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct LinearElasticity {
+    pub lambda: f64,
+    pub mu: f64,
+    pub body_force: ForceFn,
+}
+
+impl LinearElasticity {
+    #[allow(dead_code)]
+    pub fn new(
+        lambda: f64,
+        mu: f64,
+        body_force: impl Fn(f64, f64) -> (f64, f64) + 'static,
+    ) -> Self {
+        LinearElasticity {
+            lambda,
+            mu,
+            body_force: Rc::new(body_force),
+        }
+    }
+
+    // Young 率と Poisson 比から (平面ひずみ)
+    #[allow(dead_code)]
+    pub fn from_young(
+        young: f64,
+        poisson: f64,
+        body_force: impl Fn(f64, f64) -> (f64, f64) + 'static,
+    ) -> Self {
+        let lambda = young * poisson / ((1.0 + poisson) * (1.0 - 2.0 * poisson));
+        let mu = young / (2.0 * (1.0 + poisson));
+        LinearElasticity::new(lambda, mu, body_force)
+    }
+}
+
+impl fmt::Debug for LinearElasticity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LinearElasticity(λ = {}, μ = {})", self.lambda, self.mu)
+    }
+}
+
+impl SystemModel for LinearElasticity {
+    fn num_components(&self) -> usize {
+        2
+    }
+
+    fn time_order(&self) -> usize {
+        2
+    }
+
+    fn rhs(&self, d: &[LocalDerivatives], x: f64, y: f64, _t: f64) -> Vec<f64> {
+        let (u, v) = (&d[0], &d[1]);
+        let (f_x, f_y) = (self.body_force)(x, y);
+        let k = self.lambda + self.mu;
+        vec![
+            self.mu * u.laplacian() + k * (u.u_xx + v.u_xy) + f_x,
+            self.mu * v.laplacian() + k * (u.u_xy + v.u_yy) + f_y,
+        ]
+    }
+}
+
+// 点 (x, y) での右辺の線形部分。ops[c * m + d] は成分 d から成分 c への作用素、f0 は定数項
+// (右辺が局所微分についてアフィンなら厳密。DiffOperator::linearize の連立版)
+#[allow(dead_code)]
+pub fn linearize_system(
+    model: &dyn SystemModel,
+    x: f64,
+    y: f64,
+    t: f64,
+) -> (Vec<DiffOperator>, Vec<f64>) {
+    let m = model.num_components();
+    let zero = vec![LocalDerivatives::default(); m];
+    let f0 = model.rhs(&zero, x, y, t);
+    let mut ops = vec![DiffOperator::default(); m * m];
+    for d in 0..m {
+        let probe = |unit: LocalDerivatives| {
+            let mut local = zero.clone();
+            local[d] = unit;
+            let f = model.rhs(&local, x, y, t);
+            (0..m).map(|c| f[c] - f0[c]).collect::<Vec<f64>>()
+        };
+        let base = LocalDerivatives::default();
+        let columns = [
+            probe(LocalDerivatives { u: 1.0, ..base }),
+            probe(LocalDerivatives { u_x: 1.0, ..base }),
+            probe(LocalDerivatives { u_y: 1.0, ..base }),
+            probe(LocalDerivatives { u_xx: 1.0, ..base }),
+            probe(LocalDerivatives { u_xy: 1.0, ..base }),
+            probe(LocalDerivatives { u_yy: 1.0, ..base }),
+        ];
+        for c in 0..m {
+            ops[c * m + d] = DiffOperator {
+                c: columns[0][c],
+                c_x: columns[1][c],
+                c_y: columns[2][c],
+                c_xx: columns[3][c],
+                c_xy: columns[4][c],
+                c_yy: columns[5][c],
+            };
+        }
+    }
+    (ops, f0)
+}
+
+// 節点上の連立系。境界は成分ごとの Dirichlet u_c = g_c(x, y, t)
+// time_order が 2 なら velocity に u_t をもつ
+#[allow(dead_code)]
+pub struct CoupledSystem {
+    pub mesh: MeshfreeNodes,
+    pub model: Box<dyn SystemModel>,
+    pub boundary_value: Vec<Source>,
+    pub field: NodeField,
+    pub velocity: NodeField,
+    pub time: f64,
+    pub operators: FieldOperators,
+}
+
+impl CoupledSystem {
+    #[allow(dead_code)]
+    pub fn new(
+        interior: &Points2D,
+        boundary: &Points2D,
+        degree: usize,
+        num_neighbor: usize,
+        model: impl SystemModel + 'static,
+    ) -> Result<Self, String> {
+        let mesh = MeshfreeNodes::new(interior, boundary, degree, num_neighbor);
+        let m = model.num_components();
+        Ok(CoupledSystem {
+            operators: FieldOperators::new(&mesh)?,
+            field: NodeField::new(m, mesh.num_nodes()),
+            velocity: NodeField::new(m, mesh.num_nodes()),
+            boundary_value: (0..m).map(|_| Source::new(|_, _, _| 0.0)).collect(),
+            mesh,
+            model: Box::new(model),
+            time: 0.0,
+        })
+    }
+
+    // u0(c, x, y) と (2 階の系なら) u_t の初期値 u1(c, x, y)
+    #[allow(dead_code)]
+    pub fn set_initial_condition(
+        &mut self,
+        u0: &dyn Fn(usize, f64, f64) -> f64,
+        u1: &dyn Fn(usize, f64, f64) -> f64,
+    ) {
+        let m = self.model.num_components();
+        self.field = NodeField::from_fn(&self.mesh.nodes, m, u0);
+        self.velocity = NodeField::from_fn(&self.mesh.nodes, m, u1);
+        self.time = 0.0;
+        self.apply_boundary();
+    }
+
+    // 内部点での右辺 (成分ごとに内部点を並べる)
+    #[allow(dead_code)]
+    pub fn rhs(&self, field: &NodeField, t: f64) -> Vec<f64> {
+        let n = self.mesh.num_interior;
+        let m = field.num_components;
+        let mut f = vec![0.0; m * n];
+        for (i, d) in self.operators.derivatives(field).iter().enumerate() {
+            let p = self.mesh.point(i);
+            for (c, v) in self.model.rhs(d, p.x, p.y, t).into_iter().enumerate() {
+                f[c * n + i] = v;
+            }
+        }
+        f
+    }
+
+    // Runge-Kutta 4 次。2 階の系は (u, u_t) の 1 階系として進める
+    #[allow(dead_code)]
+    pub fn step(&mut self, dt: f64) {
+        let n = self.mesh.num_interior;
+        let m = self.model.num_components();
+        let second_order = self.model.time_order() == 2;
+        let mut state = self.interior_values(&self.field);
+        if second_order {
+            state.extend(self.interior_values(&self.velocity));
+        }
+        let (next, _) = rk_step(
+            &RkScheme::Rk4.tableau(),
+            &state,
+            self.time,
+            dt,
+            &mut |s, t| {
+                let field = self.with_boundary(&s[0..m * n], t);
+                let f = self.rhs(&field, t);
+                if second_order {
+                    let mut ds = s[m * n..].to_vec();
+                    ds.extend(f);
+                    ds
+                } else {
+                    f
+                }
+            },
+        );
+        self.time += dt;
+        self.field = self.with_boundary(&next[0..m * n], self.time);
+        if second_order {
+            self.velocity = self.with_boundary(&next[m * n..], self.time);
+            self.set_boundary_velocity();
+        }
+    }
+
+    // 右辺 = 0 の定常解。右辺は局所微分についてアフィンとして、節点ごとに linearize_system で
+    // 連立の作用素を取り出し、内部点の m num_interior 元の系を組み立てて解く
+    #[allow(dead_code)]
+    pub fn solve_steady(&mut self, tol: f64, max_iter: usize) -> Result<NodeField, String> {
+        let n = self.mesh.num_interior;
+        let m = self.model.num_components();
+        let g = self.with_boundary(&vec![0.0; m * n], self.time);
+        let mut rows: Vec<Vec<(usize, f64)>> = vec![vec![]; m * n];
+        let mut b = vec![0.0; m * n];
+        for i in 0..n {
+            let p = self.mesh.point(i);
+            let (ops, f0) = linearize_system(&*self.model, p.x, p.y, self.time);
+            for c in 0..m {
+                b[c * n + i] = -f0[c];
+                for d in 0..m {
+                    let op = &ops[c * m + d];
+                    if *op == DiffOperator::default() {
+                        continue;
+                    }
+                    for (j, w) in self.mesh.stencil_weights(i, op)? {
+                        if j < n {
+                            rows[c * n + i].push((d * n + j, w));
+                        } else {
+                            b[c * n + i] -= w * g.component(d)[j];
+                        }
+                    }
+                }
+            }
+        }
+        let a = CsrMatrix::from_rows(m * n, &rows);
+        let precond = Preconditioner::new(PreconditionerType::Ilu0, &a);
+        let res = krylov::solve(
+            KrylovMethod::Gmres(50),
+            &a,
+            &b,
+            &self.interior_values(&self.field),
+            &precond,
+            tol,
+            max_iter,
+        );
+        if !res.converged {
+            return Err(format!(
+                "steady solve did not converge: residual {:e}",
+                res.residual_history[res.residual_history.len() - 1]
+            ));
+        }
+        self.field = self.with_boundary(&res.x, self.time);
+        Ok(self.field.clone())
+    }
+
+    fn interior_values(&self, field: &NodeField) -> Vec<f64> {
+        let n = self.mesh.num_interior;
+        (0..field.num_components)
+            .flat_map(|c| field.component(c)[0..n].iter().cloned())
+            .collect()
+    }
+
+    // 内部点の値に時刻 t の境界値を加えた場
+    fn with_boundary(&self, interior: &[f64], t: f64) -> NodeField {
+        let n = self.mesh.num_interior;
+        let m = self.model.num_components();
+        let mut values = vec![];
+        for c in 0..m {
+            values.extend_from_slice(&interior[c * n..(c + 1) * n]);
+            values.extend(
+                self.mesh.nodes.points[n..]
+                    .iter()
+                    .map(|p| self.boundary_value[c].eval(p.x, p.y, t)),
+            );
+        }
+        NodeField {
+            num_components: m,
+            values,
+        }
+    }
+
+    fn apply_boundary(&mut self) {
+        let state = self.interior_values(&self.field);
+        self.field = self.with_boundary(&state, self.time);
+        if self.model.time_order() == 2 {
+            self.set_boundary_velocity();
+        }
+    }
+
+    // 境界点の u_t は g_t を中心差分で
+    fn set_boundary_velocity(&mut self) {
+        let n = self.mesh.num_interior;
+        let eps = 1.0e-6;
+        for c in 0..self.field.num_components {
+            let g = &self.boundary_value[c];
+            let points = &self.mesh.nodes.points[n..];
+            let values = &mut self.velocity.component_mut(c)[n..];
+            for (v, p) in values.iter_mut().zip(points.iter()) {
+                *v = (g.eval(p.x, p.y, self.time + eps) - g.eval(p.x, p.y, self.time - eps))
+                    / (2.0 * eps);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for CoupledSystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CoupledSystem")
+            .field("num_nodes", &self.mesh.num_nodes())
+            .field("num_components", &self.field.num_components)
+            .field("time", &self.time)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Domain;
+    use crate::node_generator::{NodeGenerator, NodeStrategy, Spacing};
+
+    fn unit_square(h: f64) -> (Points2D, Points2D) {
+        let domain = Domain::Rectangle {
+            x_min: 0.0,
+            y_min: 0.0,
+            x_max: 1.0,
+            y_max: 1.0,
+        };
+        NodeGenerator::new(domain, Spacing::Uniform(h), NodeStrategy::HexLattice).generate()
+    }
+
+    fn max_error(
+        field: &NodeField,
+        points: &Points2D,
+        exact: &dyn Fn(usize, f64, f64) -> f64,
+    ) -> f64 {
+        (0..field.num_components)
+            .flat_map(|c| {
+                field
+                    .component(c)
+                    .iter()
+                    .zip(points.points.iter())
+                    .map(move |(v, p)| (v - exact(c, p.x, p.y)).abs())
+            })
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn componentwise_derivatives() {
+        let (boundary, interior) = unit_square(0.1);
+        let mesh = MeshfreeNodes::new(&interior, &boundary, 2, 12);
+        let field = NodeField::from_fn(&mesh.nodes, 2, &|c, x, y| {
+            if c == 0 {
+                x * x + x * y
+            } else {
+                3.0 * y * y - x
+            }
+        });
+        assert_eq!(field.num_nodes(), mesh.num_nodes());
+        assert_eq!(field.node(0).len(), 2);
+        let operators = FieldOperators::new(&mesh).unwrap();
+        let d = operators.derivatives(&field);
+        for (i, local) in d.iter().enumerate() {
+            let p = mesh.point(i);
+            assert!((local[0].u_x - (2.0 * p.x + p.y)).abs() < 1.0e-8);
+            assert!((local[0].u_xy - 1.0).abs() < 1.0e-8);
+            assert!((local[1].u_x + 1.0).abs() < 1.0e-8);
+            assert!((local[1].laplacian() - 6.0).abs() < 1.0e-8);
+        }
+        let lap = operators.apply(&DiffOperator::laplacian(), &field, 0);
+        assert!(lap.iter().all(|v| (v - 2.0).abs() < 1.0e-8));
+    }
+
+    #[test]
+    fn acoustic_plane_wave() {
+        // p = v_x = sin(2π (x - t)), v_y = 0
+        let exact = |t: f64| {
+            move |c: usize, x: f64, _y: f64| {
+                if c == 2 {
+                    0.0
+                } else {
+                    (2.0 * std::f64::consts::PI * (x - t)).sin()
+                }
+            }
+        };
+        let (boundary, interior) = unit_square(0.025);
+        let mut system =
+            CoupledSystem::new(&interior, &boundary, 2, 12, AcousticWave { speed: 1.0 }).unwrap();
+        system.boundary_value = (0..3)
+            .map(|c| Source::new(move |x, y, t| exact(t)(c, x, y)))
+            .collect();
+        system.set_initial_condition(&exact(0.0), &|_, _, _| 0.0);
+        for _ in 0..50 {
+            system.step(0.005);
+        }
+        let error = max_error(&system.field, &system.mesh.nodes, &exact(system.time));
+        assert!(error < 2.0e-2, "{}", error);
+    }
+
+    #[test]
+    fn shallow_water_lake_at_rest_and_linear_waves() {
+        let (boundary, interior) = unit_square(0.05);
+        let mut lake =
+            CoupledSystem::new(&interior, &boundary, 2, 12, ShallowWater { gravity: 9.81 })
+                .unwrap();
+        lake.boundary_value[0] = Source::new(|_, _, _| 1.0);
+        lake.set_initial_condition(&|c, _, _| if c == 0 { 1.0 } else { 0.0 }, &|_, _, _| 0.0);
+        for _ in 0..10 {
+            lake.step(0.005);
+        }
+        assert!(
+            max_error(&lake.field, &lake.mesh.nodes, &|c, _, _| if c == 0 {
+                1.0
+            } else {
+                0.0
+            }) < 1.0e-12
+        );
+
+        // 振幅 a の小さな平面波は速さ √(g H) の線形波に従う: η = a sin(k (x - c t)), u = (c / H) η
+        let (g, depth, a) = (9.81_f64, 1.0, 1.0e-3);
+        let c = (g * depth).sqrt();
+        let k = 2.0 * std::f64::consts::PI;
+        let exact = move |t: f64| {
+            move |comp: usize, x: f64, _y: f64| {
+                let eta = a * (k * (x - c * t)).sin();
+                match comp {
+                    0 => depth + eta,
+                    1 => c / depth * eta,
+                    _ => 0.0,
+                }
+            }
+        };
+        let mut waves =
+            CoupledSystem::new(&interior, &boundary, 2, 12, ShallowWater { gravity: g }).unwrap();
+        waves.boundary_value = (0..3)
+            .map(|comp| Source::new(move |x, y, t| exact(t)(comp, x, y)))
+            .collect();
+        waves.set_initial_condition(&exact(0.0), &|_, _, _| 0.0);
+        for _ in 0..40 {
+            waves.step(0.0025);
+        }
+        let error = max_error(&waves.field, &waves.mesh.nodes, &exact(waves.time));
+        assert!(error < 0.25 * a, "{}", error);
+    }
+
+    #[test]
+    fn elasticity_manufactured_solution() {
+        // u = x^2 + xy, v = y^2 - xy に対して μΔu + (λ+μ)∇(∇・u) = (λ + 3μ, 3λ + 5μ)
+        let (lambda, mu) = (2.0, 1.0);
+        let exact = |c: usize, x: f64, y: f64| {
+            if c == 0 {
+                x * x + x * y
+            } else {
+                y * y - x * y
+            }
+        };
+        let model = LinearElasticity::new(lambda, mu, move |_, _| {
+            (-(lambda + 3.0 * mu), -(3.0 * lambda + 5.0 * mu))
+        });
+        let (boundary, interior) = unit_square(0.05);
+        let mut system = CoupledSystem::new(&interior, &boundary, 2, 12, model).unwrap();
+        system.boundary_value = (0..2)
+            .map(|c| Source::new(move |x, y, _| exact(c, x, y)))
+            .collect();
+        let field = system.solve_steady(1.0e-12, 2000).unwrap();
+        assert!(max_error(&field, &system.mesh.nodes, &exact) < 1.0e-7);
+        let (ops, f0) = linearize_system(&*system.model, 0.3, 0.4, 0.0);
+        assert_eq!(ops[1].c_xy, lambda + mu);
+        assert_eq!(ops[0].c_xx, lambda + 2.0 * mu);
+        assert_eq!(f0[1], -(3.0 * lambda + 5.0 * mu));
+    }
+
+    #[test]
+    fn elastic_shear_wave() {
+        // 横波 v = sin(2π (x - c_s t)), c_s = √μ (2 階の系)
+        let (lambda, mu) = (2.0, 1.0_f64);
+        let cs = mu.sqrt();
+        let k = 2.0 * std::f64::consts::PI;
+        let exact = move |t: f64| {
+            move |c: usize, x: f64, _y: f64| {
+                if c == 1 {
+                    (k * (x - cs * t)).sin()
+                } else {
+                    0.0
+                }
+            }
+        };
+        let (boundary, interior) = unit_square(0.025);
+        let mut system = CoupledSystem::new(
+            &interior,
+            &boundary,
+            2,
+            12,
+            LinearElasticity::new(lambda, mu, |_, _| (0.0, 0.0)),
+        )
+        .unwrap();
+        system.boundary_value = (0..2)
+            .map(|c| Source::new(move |x, y, t| exact(t)(c, x, y)))
+            .collect();
+        system.set_initial_condition(&exact(0.0), &|c, x, _| {
+            if c == 1 {
+                -k * cs * (k * x).cos()
+            } else {
+                0.0
+            }
+        });
+        for _ in 0..50 {
+            system.step(0.005);
+        }
+        let error = max_error(&system.field, &system.mesh.nodes, &exact(system.time));
+        assert!(error < 1.0e-2, "{}", error);
+    }
+}
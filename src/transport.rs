@@ -0,0 +1,385 @@
+use crate::kd_tree::{Grid2D, KDTree, Points2D};
+use crate::meshfree::MeshfreeNodes;
+use crate::node_generator::node_statistics;
+use crate::pde_model::Source;
+use crate::runge_kutta::{rk_step, RkScheme};
+use crate::sparse_matrix::CsrMatrix;
+use crate::stencil;
+use crate::stencil::DiffOperator;
+use std::fmt;
+use std::rc::Rc;
+
+// 移流項 v・∇u の安定化
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stabilization {
+    Centered, // 比較用。v・∇ の固有値は虚軸の右側にはみ出す
+    // 上流側の半平面 (x_j - x_i)・v_i <= 0 から選んだ近傍で 1 階微分を近似する
+    Upwind,
+    // 中心化したステンシルに (-1)^{k+1} γ |v|_max h^{2k-1} Δ^k を加える (k = order)。
+    // Δ^k は境界値を 0 とした内部点の Δ の k 乗で、k = 2, γ = 0.05 程度で足りる
+    Hyperviscosity { order: usize, gamma: f64 },
+}
+
+impl Stabilization {
+    // "centered", "upwind", "hyperviscosity:k:γ"
+    #[allow(dead_code)]
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = text.split(':').collect();
+        match parts.as_slice() {
+            ["centered"] => Ok(Stabilization::Centered),
+            ["upwind"] => Ok(Stabilization::Upwind),
+            ["hyperviscosity", order, gamma] => {
+                let order: usize = order
+                    .parse()
+                    .map_err(|_| format!("bad hyperviscosity order: {}", order))?;
+                let gamma: f64 = gamma
+                    .parse()
+                    .map_err(|_| format!("bad hyperviscosity coefficient: {}", gamma))?;
+                if order == 0 {
+                    return Err("hyperviscosity order must be positive".to_string());
+                }
+                Ok(Stabilization::Hyperviscosity { order, gamma })
+            }
+            _ => Err(format!("unknown stabilization: {}", text)),
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub type VelocityFn = Rc<dyn Fn(f64, f64) -> (f64, f64)>;
+
+// u_t + v・∇u = ν Δu 。境界は流入・流出とも Dirichlet u = g(x, y, t)
+// 状態 value は内部点、境界点の順
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct AdvectionDiffusion {
+    pub mesh: MeshfreeNodes,
+    pub velocity: VelocityFn,
+    pub viscosity: f64,
+    pub stabilization: Stabilization,
+    pub boundary_value: Source,
+    pub value: Vec<f64>,
+    pub time: f64,
+    pub spacing: f64,    // 平均の最近接点距離
+    operator: CsrMatrix, // 内部点の行 x 全節点
+}
+
+impl AdvectionDiffusion {
+    #[allow(dead_code)]
+    pub fn new(
+        interior: &Points2D,
+        boundary: &Points2D,
+        degree: usize,
+        num_neighbor: usize,
+        velocity: impl Fn(f64, f64) -> (f64, f64) + 'static,
+        viscosity: f64,
+        stabilization: Stabilization,
+    ) -> Result<Self, String> {
+        let mesh = MeshfreeNodes::new(interior, boundary, degree, num_neighbor);
+        let velocity: VelocityFn = Rc::new(velocity);
+        let spacing = node_statistics(&mesh.nodes).mean_spacing;
+        let n_all = mesh.num_nodes();
+        let diffusion = mesh.operator_rows(&DiffOperator::laplacian().scale(viscosity))?;
+        let advection = match stabilization {
+            Stabilization::Upwind => upwind_rows(&mesh, &*velocity, num_neighbor)?,
+            _ => mesh.operator_rows_at(&|i| advection_operator(&*velocity, mesh.point(i)))?,
+        };
+        let mut rows: Vec<Vec<(usize, f64)>> = advection
+            .into_iter()
+            .zip(diffusion)
+            .map(|(mut a, d)| {
+                a.extend(d);
+                a
+            })
+            .collect();
+        if let Stabilization::Hyperviscosity { order, gamma } = stabilization {
+            let speed = mesh
+                .nodes
+                .points
+                .iter()
+                .map(|p| {
+                    let v = velocity(p.x, p.y);
+                    v.0.hypot(v.1)
+                })
+                .fold(0.0, f64::max);
+            let sign = if order % 2 == 1 { 1.0 } else { -1.0 };
+            let coefficient = sign * gamma * speed * spacing.powi(2 * order as i32 - 1);
+            for (row, h) in rows
+                .iter_mut()
+                .zip(laplacian_power(&mesh, order, coefficient)?)
+            {
+                row.extend(h);
+            }
+        }
+        let operator = CsrMatrix::from_rows(n_all, &rows);
+        Ok(AdvectionDiffusion {
+            value: vec![0.0; n_all],
+            mesh,
+            velocity,
+            viscosity,
+            stabilization,
+            boundary_value: Source::new(|_, _, _| 0.0),
+            time: 0.0,
+            spacing,
+            operator,
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn set_initial_condition(&mut self, u0: &dyn Fn(f64, f64) -> f64) {
+        self.value = self
+            .mesh
+            .nodes
+            .points
+            .iter()
+            .map(|p| u0(p.x, p.y))
+            .collect();
+        self.time = 0.0;
+        self.apply_boundary();
+    }
+
+    // 内部点の行の A_II (境界値を 0 とした半離散系)。固有値の実部が正なら陽解法で増大する
+    #[allow(dead_code)]
+    pub fn interior_matrix(&self) -> CsrMatrix {
+        self.mesh.split_rows(&self.operator.to_rows()).0
+    }
+
+    #[allow(dead_code)]
+    pub fn step(&mut self, dt: f64) {
+        let n = self.mesh.num_interior;
+        let tableau = RkScheme::Rk4.tableau();
+        let (next, _) = rk_step(&tableau, &self.value[0..n], self.time, dt, &mut |s, t| {
+            let mut u = s.to_vec();
+            u.extend(self.boundary_values(t));
+            self.operator.mul_vec(&u)
+        });
+        self.value[0..n].copy_from_slice(&next);
+        self.time += dt;
+        self.apply_boundary();
+    }
+
+    // RK4 の安定領域に半離散系のスペクトル半径 (べき乗法) が収まる dt。
+    // hyperviscosity を強くすると移流の CFL より小さくなる
+    #[allow(dead_code)]
+    pub fn cfl_dt(&self, safety: f64) -> f64 {
+        let scheme = RkScheme::Rk4;
+        let bound = scheme
+            .real_stability_bound()
+            .min(scheme.imaginary_stability_bound());
+        safety * bound / self.interior_matrix().spectral_radius(200)
+    }
+
+    fn boundary_values(&self, t: f64) -> Vec<f64> {
+        self.mesh.nodes.points[self.mesh.num_interior..]
+            .iter()
+            .map(|p| self.boundary_value.eval(p.x, p.y, t))
+            .collect()
+    }
+
+    fn apply_boundary(&mut self) {
+        let n = self.mesh.num_interior;
+        let g = self.boundary_values(self.time);
+        self.value[n..].copy_from_slice(&g);
+    }
+}
+
+impl fmt::Debug for AdvectionDiffusion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AdvectionDiffusion")
+            .field("num_nodes", &self.mesh.num_nodes())
+            .field("viscosity", &self.viscosity)
+            .field("stabilization", &self.stabilization)
+            .field("time", &self.time)
+            .finish()
+    }
+}
+
+fn advection_operator(velocity: &dyn Fn(f64, f64) -> (f64, f64), p: &Grid2D) -> DiffOperator {
+    let (vx, vy) = velocity(p.x, p.y);
+    DiffOperator::d_x()
+        .scale(-vx)
+        .add(&DiffOperator::d_y().scale(-vy))
+}
+
+// -v・∇ の行を上流側の近傍で作る。近傍は近い順に 3 倍の候補から (x_j - x_i)・v_i <= 0 のものを
+// num_neighbor 個選ぶ。流入境界の近くで足りないか、配置が退化していれば中心化した近傍に戻す
+#[allow(dead_code)]
+pub fn upwind_rows(
+    mesh: &MeshfreeNodes,
+    velocity: &dyn Fn(f64, f64) -> (f64, f64),
+    num_neighbor: usize,
+) -> Result<Vec<Vec<(usize, f64)>>, String> {
+    let tree = KDTree::construct_kd_tree(&mesh.nodes);
+    (0..mesh.num_interior)
+        .map(|i| {
+            let p = mesh.point(i);
+            let (vx, vy) = velocity(p.x, p.y);
+            let op = advection_operator(velocity, p);
+            let upstream: Vec<usize> = tree
+                .nearest_neighbors(p, 3 * num_neighbor)
+                .into_iter()
+                .filter(|j| {
+                    let q = mesh.point(*j);
+                    (q.x - p.x) * vx + (q.y - p.y) * vy <= 0.0
+                })
+                .take(num_neighbor)
+                .collect();
+            if upstream.len() == num_neighbor {
+                let neighbors: Vec<Grid2D> =
+                    upstream.iter().map(|j| mesh.point(*j).clone()).collect();
+                if let Some(w) = stencil::weights(p, &neighbors, mesh.degree, &op) {
+                    return Ok(upstream.into_iter().zip(w).collect());
+                }
+            }
+            mesh.stencil_weights(i, &op)
+        })
+        .collect()
+}
+
+// coefficient Δ^k の内部点の行。境界値を 0 とした内部点の Δ (A_II) を k 回掛ける
+fn laplacian_power(
+    mesh: &MeshfreeNodes,
+    order: usize,
+    coefficient: f64,
+) -> Result<Vec<Vec<(usize, f64)>>, String> {
+    let n = mesh.num_interior;
+    let interior: Vec<Vec<(usize, f64)>> = mesh
+        .operator_rows(&DiffOperator::laplacian())?
+        .into_iter()
+        .map(|row| row.into_iter().filter(|e| e.0 < n).collect())
+        .collect();
+    let mut rows: Vec<Vec<(usize, f64)>> = interior
+        .iter()
+        .map(|row| row.iter().map(|(j, a)| (*j, coefficient * a)).collect())
+        .collect();
+    for _ in 1..order {
+        let product: Vec<Vec<(usize, f64)>> = rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .flat_map(|(j, a)| interior[*j].iter().map(move |(l, b)| (*l, a * b)))
+                    .collect()
+            })
+            .collect();
+        // 同じ列を足し合わせる
+        rows = CsrMatrix::from_rows(n, &product).to_rows();
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Domain;
+    use crate::eigen::dense_spectrum;
+    use crate::node_generator::{NodeGenerator, NodeStrategy, Spacing};
+
+    fn square_nodes(h: f64) -> (Points2D, Points2D) {
+        let domain = Domain::Rectangle {
+            x_min: 0.0,
+            y_min: 0.0,
+            x_max: 1.0,
+            y_max: 1.0,
+        };
+        NodeGenerator::new(domain, Spacing::Uniform(h), NodeStrategy::HexLattice).generate()
+    }
+
+    fn transport(h: f64, viscosity: f64, stabilization: Stabilization) -> AdvectionDiffusion {
+        let (boundary, interior) = square_nodes(h);
+        AdvectionDiffusion::new(
+            &interior,
+            &boundary,
+            2,
+            12,
+            |_, _| (1.0, 0.5),
+            viscosity,
+            stabilization,
+        )
+        .unwrap()
+    }
+
+    fn max_real_part(problem: &AdvectionDiffusion) -> f64 {
+        dense_spectrum(&problem.interior_matrix())
+            .iter()
+            .map(|e| e.0)
+            .fold(f64::MIN, f64::max)
+    }
+
+    #[test]
+    fn parse_stabilization() {
+        assert_eq!(Stabilization::parse("upwind"), Ok(Stabilization::Upwind));
+        assert_eq!(
+            Stabilization::parse("hyperviscosity:2:0.05"),
+            Ok(Stabilization::Hyperviscosity {
+                order: 2,
+                gamma: 0.05
+            })
+        );
+        assert!(Stabilization::parse("hyperviscosity:0:1").is_err());
+        assert!(Stabilization::parse("downwind").is_err());
+    }
+
+    #[test]
+    fn stabilization_moves_spectrum_left() {
+        let centered = max_real_part(&transport(0.08, 0.0, Stabilization::Centered));
+        let upwind = max_real_part(&transport(0.08, 0.0, Stabilization::Upwind));
+        let hyper = max_real_part(&transport(
+            0.08,
+            0.0,
+            Stabilization::Hyperviscosity {
+                order: 2,
+                gamma: 0.05,
+            },
+        ));
+        assert!(centered > 0.1, "{}", centered);
+        assert!(upwind < 1.0e-6, "{}", upwind);
+        assert!(hyper < 1.0e-6, "{}", hyper);
+    }
+
+    #[test]
+    fn gaussian_is_transported() {
+        // ν の拡散を伴って v = (1, 0.5) で流れる Gaussian
+        let nu = 0.002;
+        let exact = move |x: f64, y: f64, t: f64| {
+            let s = 0.02 + 4.0 * nu * t;
+            let (dx, dy) = (x - 0.3 - t, y - 0.3 - 0.5 * t);
+            0.02 / s * (-(dx * dx + dy * dy) / s).exp()
+        };
+        let error = |h: f64, stabilization: Stabilization| {
+            let mut problem = transport(h, nu, stabilization);
+            problem.boundary_value = Source::new(exact);
+            problem.set_initial_condition(&|x, y| exact(x, y, 0.0));
+            let steps = (0.4 / problem.cfl_dt(0.5)).ceil() as usize;
+            for _ in 0..steps {
+                problem.step(0.4 / steps as f64);
+            }
+            problem
+                .mesh
+                .nodes
+                .points
+                .iter()
+                .zip(problem.value.iter())
+                .map(|(p, v)| (v - exact(p.x, p.y, 0.4)).abs())
+                .fold(0.0, f64::max)
+        };
+        for stabilization in [
+            Stabilization::Upwind,
+            Stabilization::Hyperviscosity {
+                order: 2,
+                gamma: 0.05,
+            },
+        ] {
+            let coarse = error(0.05, stabilization);
+            let fine = error(0.025, stabilization);
+            assert!(fine < 0.1, "{:?} {}", stabilization, fine);
+            assert!(
+                fine < 0.5 * coarse,
+                "{:?} {} {}",
+                stabilization,
+                coarse,
+                fine
+            );
+        }
+    }
+}
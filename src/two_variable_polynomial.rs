@@ -23,6 +23,31 @@ impl TwoPolynomial {
         2.0 * self.two_poly[2 * (self.degree + 1)]
     }
 
+    #[allow(dead_code)]
+    pub fn coef(&self, x_deg: usize, y_deg: usize) -> f64 {
+        self.two_poly[x_deg * (self.degree + 1) + y_deg]
+    }
+
+    // (order_x, order_y) 階の偏導関数の (x, y) での値
+    #[allow(dead_code)]
+    pub fn eval_deriv(&self, x: f64, y: f64, order_x: usize, order_y: usize) -> f64 {
+        let n = self.degree;
+        let mut t = 0.0;
+        for m in order_x..(n + 1) {
+            for k in order_y..(n - m + 1) {
+                let mut c = self.coef(m, k);
+                for l in 0..order_x {
+                    c *= (m - l) as f64;
+                }
+                for l in 0..order_y {
+                    c *= (k - l) as f64;
+                }
+                t += c * x.powi((m - order_x) as i32) * y.powi((k - order_y) as i32);
+            }
+        }
+        t
+    }
+
     #[allow(dead_code)]
     pub fn eval_xy(&self, x: f64, y: f64) -> f64 {
         let n = self.degree;
@@ -108,4 +133,21 @@ mod tests {
         assert_eq!(test.eval_xy(1.0, 2.0), 38.0);
         assert_eq!(test.eval_xy(0.0, 0.0), 1.0);
     }
+
+    #[test]
+    fn two_poly_eval_deriv() {
+        let mut test = TwoPolynomial::new(2);
+        test.two_poly = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0].to_vec();
+        // 1 + 2y + 3y^2 + 4x + 5xy + 7x^2
+        assert_eq!(test.eval_deriv(1.0, 2.0, 0, 0), test.eval_xy(1.0, 2.0));
+        assert_eq!(
+            test.eval_deriv(1.0, 2.0, 1, 0),
+            4.0 + 5.0 * 2.0 + 14.0 * 1.0
+        );
+        assert_eq!(test.eval_deriv(1.0, 2.0, 0, 1), 2.0 + 6.0 * 2.0 + 5.0 * 1.0);
+        assert_eq!(test.eval_deriv(1.0, 2.0, 2, 0), 14.0);
+        assert_eq!(test.eval_deriv(1.0, 2.0, 0, 2), 6.0);
+        assert_eq!(test.eval_deriv(1.0, 2.0, 1, 1), 5.0);
+        assert_eq!(test.eval_deriv(1.0, 2.0, 2, 1), 0.0);
+    }
 }
@@ -4,10 +4,17 @@ use std::fs::File;
 use std::io::{BufWriter, Read};
 use std::path::Path;
 
+use crate::convergence::ConvergenceRecord;
 use crate::grid_3d;
 use crate::two_variable_polynomial;
 
-#[allow(dead_code)]
+#[allow(
+    dead_code,
+    clippy::zero_divided_by_zero,
+    clippy::eq_op,
+    clippy::map_flatten,
+    clippy::needless_borrows_for_generic_args
+)]
 pub fn draw_3d_graph(
     poly: &two_variable_polynomial::TwoPolynomial,
     points: &grid_3d::Grid3D,
@@ -147,6 +154,81 @@ pub fn draw_3d_points(
         .unwrap();
 }
 
+// h に対する L2 誤差 (実線) と L∞ 誤差 (点) の両対数グラフ。(ステンシルの点数, 次数) ごとに色を変える
+// 収束しなかった計算は描かない
+#[allow(dead_code)]
+pub fn draw_convergence(records: &[ConvergenceRecord], out_file_name: &str) {
+    let records: Vec<ConvergenceRecord> = records.iter().filter(|r| r.converged).cloned().collect();
+
+    let root = BitMapBackend::new(out_file_name, (1280, 960)).into_drawing_area();
+
+    root.fill(&WHITE).unwrap();
+
+    let h_min = records.iter().fold(f64::INFINITY, |m, r| m.min(r.h));
+    let h_max = records.iter().fold(0.0, |m: f64, r| m.max(r.h));
+    let e_min = records.iter().fold(f64::INFINITY, |m, r| m.min(r.l2));
+    let e_max = records.iter().fold(0.0, |m: f64, r| m.max(r.linf));
+
+    let mut chart = ChartBuilder::on(&root)
+        .margin(20)
+        .caption("Convergence", ("sans-serif", 40))
+        .x_label_area_size(50)
+        .y_label_area_size(80)
+        .build_cartesian_2d(
+            (0.8 * h_min..1.25 * h_max).log_scale(),
+            (0.5 * e_min..2.0 * e_max).log_scale(),
+        )
+        .unwrap();
+
+    chart
+        .configure_mesh()
+        .x_desc("h")
+        .y_desc("error")
+        .y_label_formatter(&|v| format!("{:.0e}", v))
+        .draw()
+        .unwrap();
+
+    let mut keys: Vec<(usize, usize)> =
+        records.iter().map(|r| (r.num_neighbor, r.degree)).collect();
+    keys.sort_unstable();
+    keys.dedup();
+
+    for (idx, (num_neighbor, degree)) in keys.iter().enumerate() {
+        let color = Palette99::pick(idx).to_rgba();
+        let mut group: Vec<&ConvergenceRecord> = records
+            .iter()
+            .filter(|r| r.num_neighbor == *num_neighbor && r.degree == *degree)
+            .collect();
+        group.sort_by(|a, b| a.h.total_cmp(&b.h));
+        chart
+            .draw_series(LineSeries::new(
+                group.iter().map(|r| (r.h, r.l2)),
+                color.stroke_width(2),
+            ))
+            .unwrap()
+            .label(format!("L2  n = {}, degree {}", num_neighbor, degree))
+            .legend(move |(x, y)| {
+                PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(2))
+            });
+        chart
+            .draw_series(
+                group
+                    .iter()
+                    .map(|r| Circle::new((r.h, r.linf), 5, color.filled())),
+            )
+            .unwrap();
+    }
+
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()
+        .unwrap();
+
+    root.present().unwrap();
+}
+
 #[allow(dead_code)]
 pub fn gen_apng(max_counter: i32) {
     let mut files = vec![];
@@ -1,21 +1,42 @@
-use crate::grid_3d::Grid3D;
+use crate::boundary_condition::{BoundaryCondition, BoundaryConditions};
+use crate::cell_list::VerletList;
+use crate::domain::Domain;
+use crate::expression::Expression;
+use crate::grid_3d::{Constraint, Grid3D};
+use crate::implicit::ImplicitStepper;
 use crate::kd_tree;
 use crate::kd_tree::Grid2D;
+use crate::meshfree::MeshfreeNodes;
+use crate::node_generator::{LennardJones, NodeGenerator, NodeStrategy, Spacing};
+use crate::pde_model::{HeatModel, LocalDerivatives, PdeModel, Source};
 use crate::point;
+use crate::refinement::ErrorIndicator;
+use crate::runge_kutta::{rk_step, AdaptiveStepper, RkScheme};
+use crate::sparse_matrix::CsrMatrix;
+use crate::stencil::DiffOperator;
 use crate::two_variable_polynomial;
+use std::io;
+use std::rc::Rc;
 
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct WaveEq {
     pub interior: kd_tree::Points2D,
-    pub boundary: kd_tree::Points2D, // Circle
+    pub boundary: kd_tree::Points2D,
     pub value: Vec<f64>,
     pub value_1: Vec<f64>,
     pub value_2: Vec<f64>,
+    pub velocity: Vec<f64>, // 時間 2 階のモデルを Runge-Kutta で解くときの u_t
     pub near_points_boundary: Vec<Vec<usize>>,
     pub near_points_interior: Vec<Vec<usize>>,
     pub near: Vec<Vec<usize>>,
     pub poly: Vec<two_variable_polynomial::TwoPolynomial>,
+    pub time: f64,
+    pub domain: Domain,
+    pub boundary_conditions: BoundaryConditions,
+    pub source: Option<Source>, // 右辺に加える f(x, y, t)
+    // euler_step で使い回す近傍リスト
+    node_neighbors: Option<VerletList>,
 }
 
 impl WaveEq {
@@ -31,26 +52,214 @@ impl WaveEq {
             value: vec![0.0; 0],
             value_1: vec![0.0; 0],
             value_2: vec![0.0; 0],
-            near_points_boundary: vec![vec![0 as usize, 0]; 0],
-            near_points_interior: vec![vec![0 as usize, 0]; 0],
-            near: vec![vec![0 as usize, 0]; 0],
-            poly: vec![two_variable_polynomial::TwoPolynomial::new(2); 0],
+            velocity: vec![0.0; 0],
+            near_points_boundary: Vec::<Vec<usize>>::new(),
+            near_points_interior: Vec::<Vec<usize>>::new(),
+            near: Vec::<Vec<usize>>::new(),
+            poly: Vec::<two_variable_polynomial::TwoPolynomial>::new(),
+            time: 0.0,
+            domain: Domain::unit_circle(),
+            boundary_conditions: BoundaryConditions::homogeneous_dirichlet(),
+            source: None,
+            node_neighbors: None,
         }
     }
 
     #[allow(dead_code)]
     pub fn step(&mut self, tol: f64, dt: f64) {
+        self.step_model(&HeatModel::new(1.0), tol, dt);
+    }
+
+    #[allow(dead_code)]
+    pub fn step_model(&mut self, model: &dyn PdeModel, tol: f64, dt: f64) {
         self.set_poly(tol);
         for i in 0..self.interior.points.len() {
-            // self.value[i] = 2.0 * self.value_1[i] - self.value_2[i] + dt * dt * (self.poly[i].d_xx() + self.poly[i].d_yy());
-            self.value[i] = self.value_1[i] + dt * (self.poly[i].d_xx() + self.poly[i].d_yy());
+            let x = self.interior.points[i].x;
+            let y = self.interior.points[i].y;
+            let rhs = model.rhs(&self.local_derivatives(i), x, y, self.time)
+                + self.source_at(x, y, self.time);
+            self.value[i] = match model.time_order() {
+                2 => 2.0 * self.value_1[i] - self.value_2[i] + dt * dt * rhs,
+                _ => self.value_1[i] + dt * rhs,
+            };
+        }
+        for i in 0..self.interior.points.len() {
+            self.value_2[i] = self.value_1[i];
+        }
+        for i in 0..self.interior.points.len() {
+            self.value_1[i] = self.value[i];
+        }
+        self.time += dt;
+    }
+
+    // du/dt = A_II u + A_IB g(t) + f(x, y, t) を陰的に 1 ステップ進める。多項式は更新しない。
+    // stepper の A_II と a_ib は同じ MeshfreeNodes::interior_system から作ったもの。
+    // Dirichlet 以外の境界点があるか、線形ソルバーが収束しなければ状態を変えずに Err を返す
+    #[allow(dead_code)]
+    pub fn step_implicit(
+        &mut self,
+        stepper: &mut ImplicitStepper,
+        a_ib: &CsrMatrix,
+        dt: f64,
+    ) -> Result<(), String> {
+        if self.boundary_values(self.time).iter().any(|g| g.is_nan()) {
+            return Err("step_implicit supports only Dirichlet boundaries".to_string());
         }
+        // model_rhs と同じく境界値の持ち上げとソース項を右辺に加える
+        let forcing = |t: f64| -> Vec<f64> {
+            let lift = a_ib.mul_vec(&self.boundary_values(t));
+            self.interior
+                .points
+                .iter()
+                .zip(lift)
+                .map(|(p, l)| l + self.source_at(p.x, p.y, t))
+                .collect()
+        };
+        let next = stepper.step(&self.value_1, self.time, dt, &forcing)?;
+        self.value = next;
         for i in 0..self.interior.points.len() {
             self.value_2[i] = self.value_1[i];
         }
         for i in 0..self.interior.points.len() {
             self.value_1[i] = self.value[i];
         }
+        self.time += dt;
+        Ok(())
+    }
+
+    // u に多項式を当てはめ直して各内部点で model の右辺を評価する
+    #[allow(dead_code)]
+    pub fn model_rhs(&mut self, model: &dyn PdeModel, u: &[f64], t: f64, tol: f64) -> Vec<f64> {
+        self.value.copy_from_slice(u);
+        // 境界値は段の時刻 t で評価する
+        let time = self.time;
+        self.time = t;
+        self.set_poly(tol);
+        self.time = time;
+        (0..self.interior.points.len())
+            .map(|i| {
+                let p = &self.interior.points[i];
+                model.rhs(&self.local_derivatives(i), p.x, p.y, t) + self.source_at(p.x, p.y, t)
+            })
+            .collect()
+    }
+
+    // 時間 2 階のモデルは (u, u_t) の 1 階系として扱う
+    fn rk_state(&self, model: &dyn PdeModel) -> Vec<f64> {
+        let mut state = self.value_1.clone();
+        if model.time_order() == 2 {
+            state.extend(self.velocity.iter());
+        }
+        state
+    }
+
+    fn rk_rhs(&mut self, model: &dyn PdeModel, state: &[f64], t: f64, tol: f64) -> Vec<f64> {
+        let n = self.interior.points.len();
+        if model.time_order() == 2 {
+            let mut f = state[n..].to_vec();
+            f.extend(self.model_rhs(model, &state[0..n], t, tol));
+            f
+        } else {
+            self.model_rhs(model, state, t, tol)
+        }
+    }
+
+    fn set_rk_state(&mut self, state: &[f64], dt: f64) {
+        let n = self.interior.points.len();
+        for i in 0..n {
+            self.value_2[i] = self.value_1[i];
+        }
+        self.value.copy_from_slice(&state[0..n]);
+        self.value_1.copy_from_slice(&state[0..n]);
+        if state.len() == 2 * n {
+            self.velocity.copy_from_slice(&state[n..]);
+        }
+        self.time += dt;
+    }
+
+    #[allow(dead_code)]
+    pub fn step_rk(&mut self, model: &dyn PdeModel, scheme: RkScheme, tol: f64, dt: f64) {
+        let state = self.rk_state(model);
+        let t = self.time;
+        let (next, _) = rk_step(&scheme.tableau(), &state, t, dt, &mut |s, t| {
+            self.rk_rhs(model, s, t, tol)
+        });
+        self.set_rk_state(&next, dt);
+    }
+
+    // 受理された刻み幅で 1 ステップ進め、次の刻み幅を返す
+    #[allow(dead_code)]
+    pub fn step_adaptive(
+        &mut self,
+        model: &dyn PdeModel,
+        stepper: &mut AdaptiveStepper,
+        tol: f64,
+        dt: f64,
+    ) -> f64 {
+        let state = self.rk_state(model);
+        let t = self.time;
+        let (next, used, dt_next) =
+            stepper.step(&state, t, dt, &mut |s, t| self.rk_rhs(model, s, t, tol));
+        self.set_rk_state(&next, used);
+        dt_next
+    }
+
+    // 内部点・境界点を合わせた最近接点間距離の最小値
+    #[allow(dead_code)]
+    pub fn min_node_spacing(&self) -> f64 {
+        let mut nodes = self.interior.clone();
+        nodes.points.extend(self.boundary.points.iter().cloned());
+        let tree = kd_tree::KDTree::construct_kd_tree(&nodes);
+        nodes
+            .points
+            .iter()
+            .map(|p| {
+                let near = tree.nearest_neighbors(p, 2);
+                nodes.points[near[1]].distance_square(p).sqrt()
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    // CFL 的な目安: 拡散 dt <= safety h^2 / (4 kappa)
+    #[allow(dead_code)]
+    pub fn cfl_dt_diffusion(&self, diffusivity: f64, safety: f64) -> f64 {
+        let h = self.min_node_spacing();
+        safety * h * h / (4.0 * diffusivity)
+    }
+
+    // CFL 的な目安: 波動 dt <= safety h / c
+    #[allow(dead_code)]
+    pub fn cfl_dt_wave(&self, speed: f64, safety: f64) -> f64 {
+        safety * self.min_node_spacing() / speed
+    }
+
+    // 現在の節点集合で model を scheme で解くときの安定限界 (同次 Dirichlet の離散作用素のスペクトル半径から)
+    #[allow(dead_code)]
+    pub fn largest_stable_dt(
+        &self,
+        model: &dyn PdeModel,
+        scheme: RkScheme,
+        degree: usize,
+        num_neighbor: usize,
+    ) -> Result<f64, String> {
+        let mesh = MeshfreeNodes::from_wave(self, degree, num_neighbor);
+        let t = self.time;
+        let rows = mesh.operator_rows_at(&|i| {
+            let p = mesh.point(i);
+            DiffOperator::linearize(model, p.x, p.y, t)
+        })?;
+        let rho = mesh.split_rows(&rows).0.spectral_radius(2000);
+        if model.time_order() == 2 {
+            Ok(scheme.imaginary_stability_bound() / rho.sqrt())
+        } else {
+            Ok(scheme.real_stability_bound() / rho)
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn local_derivatives(&self, index: usize) -> LocalDerivatives {
+        let p = &self.interior.points[index];
+        LocalDerivatives::from_poly(&self.poly[index], p.x, p.y)
     }
 
     #[allow(dead_code)]
@@ -75,7 +284,7 @@ impl WaveEq {
         let mut _index: usize = 0;
         loop {
             let near = tree.neighbor_search(&vec, radius);
-            if near.len() > 0 {
+            if !near.is_empty() {
                 _index = near[0];
                 break;
             }
@@ -87,31 +296,7 @@ impl WaveEq {
     #[allow(dead_code)]
     pub fn set_init_poly(&mut self, tol: f64) {
         for i in 0..self.interior.points.len() {
-            let mut neighbor_vec = Grid3D::new();
-            for j in &self.near_points_interior[i] {
-                //if i != *j {
-                let v_x = self.interior.points[*j].x;
-                let v_y = self.interior.points[*j].y;
-                let v_z = self.value[*j];
-                let vec = point::Point3 {
-                    x: v_x,
-                    y: v_y,
-                    z: v_z,
-                };
-                neighbor_vec.push(vec);
-                //}
-                for k in &self.near_points_boundary[i] {
-                    let v_x = self.boundary.points[*k].x;
-                    let v_y = self.boundary.points[*k].y;
-                    let v_z = 0.0;
-                    let vec = point::Point3 {
-                        x: v_x,
-                        y: v_y,
-                        z: v_z,
-                    };
-                    neighbor_vec.push(vec);
-                }
-            }
+            let mut neighbor_vec = self.local_fit_data(i);
             println!("{} / {}", i, self.interior.points.len() - 1);
             neighbor_vec.poly_fitting_by_euler_with_tol(&mut self.poly[i], tol);
         }
@@ -120,33 +305,170 @@ impl WaveEq {
     #[allow(dead_code)]
     pub fn set_poly(&mut self, tol: f64) {
         for i in 0..self.interior.points.len() {
-            let mut neighbor_vec = Grid3D::new();
-            for j in &self.near_points_interior[i] {
-                //if i != *j {
-                let v_x = self.interior.points[*j].x;
-                let v_y = self.interior.points[*j].y;
-                let v_z = self.value[*j];
-                let vec = point::Point3 {
-                    x: v_x,
-                    y: v_y,
-                    z: v_z,
-                };
-                neighbor_vec.push(vec);
-                //}
-                for k in &self.near_points_boundary[i] {
-                    let v_x = self.boundary.points[*k].x;
-                    let v_y = self.boundary.points[*k].y;
-                    let v_z = 0.0;
+            let mut neighbor_vec = self.local_fit_data(i);
+            //println!("{} / {}", i, self.interior.points.len());
+            neighbor_vec.poly_fitting_by_euler_with_tol(&mut self.poly[i], tol);
+        }
+    }
+
+    // 境界全体に Dirichlet 条件 u = g(x, y, t) を課す
+    #[allow(dead_code)]
+    pub fn set_dirichlet(&mut self, g: impl Fn(f64, f64, f64) -> f64 + 'static) {
+        self.boundary_conditions =
+            BoundaryConditions::new(BoundaryCondition::Dirichlet(Rc::new(g)));
+    }
+
+    // 時刻 t での境界点の Dirichlet 値 (Dirichlet 以外の点は NaN)
+    #[allow(dead_code)]
+    pub fn boundary_values(&self, t: f64) -> Vec<f64> {
+        self.boundary
+            .points
+            .iter()
+            .map(|p| {
+                let condition = self.boundary_conditions.condition_at(p.x, p.y);
+                if condition.is_dirichlet() {
+                    condition.coefficients(p.x, p.y, t).2
+                } else {
+                    f64::NAN
+                }
+            })
+            .collect()
+    }
+
+    // 内部点 i の多項式を当てはめる点列。Dirichlet 境界点は値 g として、
+    // Neumann / Robin 境界点は制約として加える
+    fn local_fit_data(&self, i: usize) -> Grid3D {
+        let mut neighbor_vec = Grid3D::new();
+        for j in &self.near_points_interior[i] {
+            //if i != *j {
+            let v_x = self.interior.points[*j].x;
+            let v_y = self.interior.points[*j].y;
+            let v_z = self.value[*j];
+            let vec = point::Point3 {
+                x: v_x,
+                y: v_y,
+                z: v_z,
+            };
+            neighbor_vec.push(vec);
+            //}
+            for k in &self.near_points_boundary[i] {
+                let v_x = self.boundary.points[*k].x;
+                let v_y = self.boundary.points[*k].y;
+                let condition = self.boundary_conditions.condition_at(v_x, v_y);
+                let (alpha, beta, r) = condition.coefficients(v_x, v_y, self.time);
+                if condition.is_dirichlet() {
                     let vec = point::Point3 {
                         x: v_x,
                         y: v_y,
-                        z: v_z,
+                        z: r,
                     };
                     neighbor_vec.push(vec);
+                } else {
+                    let n = self.boundary_normal(*k);
+                    neighbor_vec.push_constraint(Constraint {
+                        x: v_x,
+                        y: v_y,
+                        nx: n.x,
+                        ny: n.y,
+                        alpha,
+                        beta,
+                        r,
+                    });
                 }
             }
-            //println!("{} / {}", i, self.interior.points.len());
-            neighbor_vec.poly_fitting_by_euler_with_tol(&mut self.poly[i], tol);
+        }
+        neighbor_vec
+    }
+
+    // 内部点 i の多項式の、近傍の近傍 (2 重の近傍) の内部点での値との残差の二乗平均。
+    // 当てはめに使った点だけでは補間に近くなり残差が小さく出るので範囲を広げる
+    #[allow(dead_code)]
+    pub fn fit_residual(&self, i: usize) -> f64 {
+        let mut ring: Vec<usize> = self.near_points_interior[i]
+            .iter()
+            .flat_map(|j| self.near_points_interior[*j].iter().cloned())
+            .collect();
+        ring.sort_unstable();
+        ring.dedup();
+        let mut sum = 0.0;
+        for k in &ring {
+            let p = &self.interior.points[*k];
+            sum += (self.poly[i].eval_xy(p.x, p.y) - self.value[*k]).powi(2);
+        }
+        (sum / ring.len() as f64).sqrt()
+    }
+
+    // 内部点 i と近傍の内部点 j の中点での多項式の食い違いの最大
+    #[allow(dead_code)]
+    pub fn fit_jump(&self, i: usize) -> f64 {
+        let p = &self.interior.points[i];
+        let mut jump = 0.0_f64;
+        for j in &self.near_points_interior[i] {
+            let q = &self.interior.points[*j];
+            let x = 0.5 * (p.x + q.x);
+            let y = 0.5 * (p.y + q.y);
+            jump = jump.max((self.poly[i].eval_xy(x, y) - self.poly[*j].eval_xy(x, y)).abs());
+        }
+        jump
+    }
+
+    #[allow(dead_code)]
+    pub fn error_indicators(&self, indicator: ErrorIndicator) -> Vec<f64> {
+        (0..self.interior.points.len())
+            .map(|i| match indicator {
+                ErrorIndicator::Residual => self.fit_residual(i),
+                ErrorIndicator::Jump => self.fit_jump(i),
+            })
+            .collect()
+    }
+
+    // 節点を増減したあとに近傍リストを作り直す
+    #[allow(dead_code)]
+    pub fn rebuild_near_points(&mut self, num_neighbor: usize) {
+        self.near.clear();
+        self.near_points_interior.clear();
+        self.near_points_boundary.clear();
+        self.set_boundary_near_points();
+        self.set_interior_near_points(num_neighbor);
+        self.set_interior_near_points_plus_boundary();
+    }
+
+    // 内部点を末尾に加える。値は最も近い既存の内部点の多項式で補間し (過去の時刻の値は
+    // その点での差を足す)、多項式はその点のものを当てはめの初期値にする
+    #[allow(dead_code)]
+    pub fn insert_interior_points(&mut self, points: &[Grid2D]) {
+        let tree = kd_tree::KDTree::construct_kd_tree(&self.interior);
+        let has_velocity = self.velocity.len() == self.interior.points.len();
+        for p in points {
+            let i = tree.nearest_neighbors(p, 1)[0];
+            let u = self.poly[i].eval_xy(p.x, p.y);
+            self.value_1.push(u + self.value_1[i] - self.value[i]);
+            self.value_2.push(u + self.value_2[i] - self.value[i]);
+            self.value.push(u);
+            if has_velocity {
+                self.velocity.push(self.velocity[i]);
+            }
+            self.poly.push(self.poly[i].clone());
+            self.interior.points.push(p.clone());
+        }
+    }
+
+    // 内部点を取り除く。残りの点の番号は前に詰める
+    #[allow(dead_code)]
+    pub fn remove_interior_points(&mut self, indices: &[usize]) {
+        let has_velocity = self.velocity.len() == self.interior.points.len();
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        for i in indices.into_iter().rev() {
+            self.interior.points.remove(i);
+            self.value.remove(i);
+            self.value_1.remove(i);
+            self.value_2.remove(i);
+            if has_velocity {
+                self.velocity.remove(i);
+            }
+            self.poly.remove(i);
         }
     }
 
@@ -161,7 +483,7 @@ impl WaveEq {
     #[allow(dead_code)]
     pub fn set_interior_near_points_plus_boundary(&mut self) {
         for i in 0..self.near_points_interior.len() {
-            self.near_points_boundary.push(vec![0 as usize; 0]);
+            self.near_points_boundary.push(Vec::<usize>::new());
             for k in 0..self.near.len() {
                 if self.near[k][0] == i {
                     self.near_points_boundary[i].push(k);
@@ -195,7 +517,7 @@ impl WaveEq {
             let mut radius = std::f64::consts::PI / self.boundary.points.len() as f64;
             loop {
                 let near = tree.neighbor_search(&self.boundary.points[i], radius);
-                if near.len() > 0 {
+                if !near.is_empty() {
                     self.near.push(near);
                     break;
                 }
@@ -204,108 +526,220 @@ impl WaveEq {
         }
     }
 
+    // 乱数で置いた内部点を Lennard-Jones 緩和 1000 回で整える
     #[allow(dead_code)]
     pub fn create(&mut self, num_points: usize) {
-        for i in 0..num_points {
-            let x = (2.0 * std::f64::consts::PI * i as f64 / num_points as f64).cos();
-            let y = (2.0 * std::f64::consts::PI * i as f64 / num_points as f64).sin();
-            self.boundary.push(x, y);
-        }
-        let num_interior = (((num_points as f64).powi(2)) / (4.0 * std::f64::consts::PI)) as usize;
-        use rand::prelude::*;
-        let seed: [u8; 32] = [1; 32];
-        let mut rng: rand::rngs::StdRng = rand::SeedableRng::from_seed(seed);
-        let mut iter = 0;
-        loop {
-            let x_r = 2.0 * (rng.gen::<f64>() - 0.5);
-            let y_r = 2.0 * (rng.gen::<f64>() - 0.5);
-            if x_r * x_r + y_r * y_r < 1.0 {
-                self.interior.push(x_r, y_r);
-                iter += 1;
-            }
-            if iter == num_interior {
-                break;
-            }
-        }
-        for _ in 0..1000 {
-            self.euler_step();
+        // 境界点の間隔と同程度の密度で内部点を置く
+        let spacing = self.domain.perimeter() / num_points as f64;
+        let mut generator = NodeGenerator::new(
+            self.domain.clone(),
+            Spacing::Uniform(spacing),
+            NodeStrategy::Random,
+        );
+        generator.boundary_margin = 0.0;
+        self.node_neighbors = None;
+        for p in self.domain.boundary_samples(num_points) {
+            self.boundary.push(p.x, p.y);
         }
+        self.interior
+            .points
+            .extend(generator.interior_nodes(&self.boundary).points);
+        self.node_relaxation().relax(
+            &self.domain,
+            &generator.spacing,
+            &mut self.interior,
+            &self.boundary,
+        );
+    }
+
+    #[allow(dead_code)]
+    pub fn create_with(&mut self, generator: &NodeGenerator) {
+        self.domain = generator.domain.clone();
+        let (boundary, interior) = generator.generate();
+        self.boundary = boundary;
+        self.interior = interior;
+        self.node_neighbors = None;
+    }
+
+    #[allow(dead_code)]
+    pub fn create_in_domain(&mut self, domain: Domain, num_points: usize) {
+        self.domain = domain;
+        self.create(num_points);
+    }
+
+    // 境界点 index での外向き単位法線
+    #[allow(dead_code)]
+    pub fn boundary_normal(&self, index: usize) -> Grid2D {
+        let p = &self.boundary.points[index];
+        self.domain.normal(p.x, p.y)
     }
 
     #[allow(dead_code)]
     pub fn set_initial_condition(&mut self) {
+        self.set_initial_condition_with(&|x, y| (-10.0 * (x * x + y * y)).exp(), &|_, _| 0.0, 0.0);
+    }
+
+    // 初期変位 u0 と初速度 v0。value_2 は 1 ステップ前 u0 - dt v0 とする
+    #[allow(dead_code)]
+    pub fn set_initial_condition_with(
+        &mut self,
+        displacement: &dyn Fn(f64, f64) -> f64,
+        velocity: &dyn Fn(f64, f64) -> f64,
+        dt: f64,
+    ) {
+        self.value.clear();
+        self.value_1.clear();
+        self.value_2.clear();
+        self.velocity.clear();
         for i in 0..self.interior.points.len() {
             let x = self.interior.points[i].x;
             let y = self.interior.points[i].y;
-            self.value.push((-10.0 * (x * x + y * y)).exp());
-            self.value_1.push((-10.0 * (x * x + y * y)).exp());
-            self.value_2.push((-10.0 * (x * x + y * y)).exp());
+            let u0 = displacement(x, y);
+            let v0 = velocity(x, y);
+            self.value.push(u0);
+            self.value_1.push(u0);
+            self.value_2.push(u0 - dt * v0);
+            self.velocity.push(v0);
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn set_initial_condition_from_expression(
+        &mut self,
+        displacement: &str,
+        velocity: &str,
+        dt: f64,
+    ) -> Result<(), String> {
+        let u0 = Expression::parse(displacement)?;
+        let v0 = Expression::parse(velocity)?;
+        self.set_initial_condition_with(&|x, y| u0.eval(x, y, 0.0), &|x, y| v0.eval(x, y, 0.0), dt);
+        Ok(())
+    }
+
+    // 内部点の順に 1 行 "x y u [v]"。# 以降と空行は無視する
+    #[allow(dead_code)]
+    pub fn load_initial_condition(&mut self, path: &str, dt: f64) -> io::Result<()> {
+        let text = std::fs::read_to_string(path)?;
+        let invalid = |line: usize, msg: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} line {}: {}", path, line + 1, msg),
+            )
+        };
+        let mut u0 = vec![];
+        let mut v0 = vec![];
+        for (line, text) in text.lines().enumerate() {
+            let text = text.split('#').next().unwrap().trim();
+            if text.is_empty() {
+                continue;
+            }
+            let fields = text
+                .split_whitespace()
+                .map(|f| f.parse::<f64>())
+                .collect::<Result<Vec<f64>, _>>()
+                .map_err(|e| invalid(line, &e.to_string()))?;
+            if fields.len() != 3 && fields.len() != 4 {
+                return Err(invalid(line, "expected columns: x y u [v]"));
+            }
+            let i = u0.len();
+            if i >= self.interior.points.len() {
+                return Err(invalid(line, "more rows than interior nodes"));
+            }
+            let p = &self.interior.points[i];
+            if (p.x - fields[0]).abs() > 1.0e-9 || (p.y - fields[1]).abs() > 1.0e-9 {
+                return Err(invalid(line, "node position does not match"));
+            }
+            u0.push(fields[2]);
+            v0.push(if fields.len() == 4 { fields[3] } else { 0.0 });
+        }
+        if u0.len() != self.interior.points.len() {
+            return Err(invalid(
+                text.lines().count(),
+                "fewer rows than interior nodes",
+            ));
+        }
+        self.value = u0.clone();
+        self.value_1 = u0.clone();
+        self.value_2 = (0..u0.len()).map(|i| u0[i] - dt * v0[i]).collect();
+        self.velocity = v0;
+        Ok(())
+    }
+
+    // load_initial_condition と同じ形式で書き出す
+    #[allow(dead_code)]
+    pub fn save_node_values(&self, path: &str) -> io::Result<()> {
+        let mut text = String::from("# x y u v\n");
+        for (i, p) in self.interior.points.iter().enumerate() {
+            text.push_str(&format!(
+                "{:e} {:e} {:e} {:e}\n",
+                p.x, p.y, self.value[i], self.velocity[i]
+            ));
         }
+        std::fs::write(path, text)
     }
 
+    #[allow(dead_code)]
+    pub fn set_source(&mut self, f: impl Fn(f64, f64, f64) -> f64 + 'static) {
+        self.source = Some(Source::new(f));
+    }
+
+    fn source_at(&self, x: f64, y: f64, t: f64) -> f64 {
+        match &self.source {
+            Some(f) => f.eval(x, y, t),
+            None => 0.0,
+        }
+    }
+
+    // Lennard-Jones 緩和を 1 回行う。近傍リストは呼び出しをまたいで使い回し、
+    // 点が skin / 2 より動くか点数が変わって σ が変わったときだけ作り直す
     #[allow(dead_code)]
     pub fn euler_step(&mut self) {
-        let dt = 1.0e-3;
-        for i in 0..self.interior.points.len() {
-            let mut x = self.interior.points[i].x - dt * self.lennard_jones_potential_deriv(i).x;
-            let mut y = self.interior.points[i].y - dt * self.lennard_jones_potential_deriv(i).y;
-            if x * x + y * y < 1.0 {
-                self.interior.points[i].x = x;
-                self.interior.points[i].y = y;
-            } else {
-                loop {
-                    let tmp = x;
-                    x = -0.5 * y;
-                    y = 0.5 * tmp;
-                    if x * x + y * y < 1.0 {
-                        self.interior.points[i].x = x;
-                        self.interior.points[i].y = y;
-                        break;
-                    }
-                }
+        let lj = self.node_relaxation();
+        let sigma = lj.sigma_for(&self.domain, self.interior.points.len());
+        let cutoff = lj.cutoff.unwrap() * sigma;
+        match self.node_neighbors.as_mut() {
+            Some(list) if list.cutoff == cutoff => {
+                list.update(&self.interior, &self.boundary);
+            }
+            _ => {
+                self.node_neighbors = Some(VerletList::new(
+                    &self.interior,
+                    &self.boundary,
+                    cutoff,
+                    lj.skin * sigma,
+                ));
             }
         }
+        lj.sweep_with(
+            &self.domain,
+            &mut self.interior,
+            &self.boundary,
+            sigma,
+            None,
+            self.node_neighbors.as_ref(),
+        );
     }
 
     #[allow(dead_code)]
     pub fn lennard_jones_potential_deriv(&mut self, index: usize) -> kd_tree::Grid2D {
-        let epsilon: f64 = 1.0;
-        let sigma: f64 =
-            0.9 * (2.0_f64).powf(-1.0 / 6.0) / (self.interior.points.len() as f64).sqrt();
-        let mut f_x = 0.0;
-        let mut f_y = 0.0;
-        let sigma2 = sigma * sigma;
-        let sigma4 = sigma2 * sigma2;
-        let sigma8 = sigma4 * sigma4;
-        let sigma6 = sigma2 * sigma4;
-        let sigma12 = sigma8 * sigma4;
-        for i in 0..self.interior.points.len() {
-            if i != index {
-                let dx = self.interior.points[index].x - self.interior.points[i].x;
-                let dy = self.interior.points[index].y - self.interior.points[i].y;
-                let r = (dx * dx + dy * dy).sqrt();
-                let r2 = r * r;
-                let r4 = r2 * r2;
-                let r8 = r4 * r4;
-                let r13 = r8 * r4 * r;
-                let r7 = r2 * r4 * r;
-                f_x += 4.0 * epsilon * (12.0 * sigma12 / r13 - 6.0 * sigma6 / r7) * dx;
-                f_y += 4.0 * epsilon * (12.0 * sigma12 / r13 - 6.0 * sigma6 / r7) * dy;
-            }
+        let lj = self.node_relaxation();
+        let sigma = lj.sigma_for(&self.domain, self.interior.points.len());
+        lj.force(index, &self.interior, &self.boundary, sigma, None)
+    }
+
+    // create の節点緩和 (1000 回)。平衡距離 2^{1/6} σ を平均点間隔 sqrt(面積 / 点数) に合わせ、
+    // 3σ で打ち切って近傍リストで 1 回の掃引を O(N) にする。
+    // 点間隔に対して剛いので、刻みと 1 回の移動量を既定より小さくして振動を防ぐ
+    fn node_relaxation(&self) -> LennardJones {
+        let h = (self.domain.area() / self.interior.points.len() as f64).sqrt();
+        LennardJones {
+            sigma: Some(h * 2.0_f64.powf(-1.0 / 6.0)),
+            cutoff: Some(3.0),
+            dt: 1.0e-5,
+            max_move: 0.1,
+            max_steps: 1000,
+            ..LennardJones::default()
         }
-        for i in 0..self.boundary.points.len() {
-            let dx = self.interior.points[index].x - self.boundary.points[i].x;
-            let dy = self.interior.points[index].y - self.boundary.points[i].y;
-            let r = (dx * dx + dy * dy).sqrt();
-            let r2 = r * r;
-            let r4 = r2 * r2;
-            let r8 = r4 * r4;
-            let r13 = r8 * r4 * r;
-            let r7 = r2 * r4 * r;
-            f_x += 4.0 * epsilon * (12.0 * sigma12 / r13 - 6.0 * sigma6 / r7) * dx;
-            f_y += 4.0 * epsilon * (12.0 * sigma12 / r13 - 6.0 * sigma6 / r7) * dy;
-        }
-        kd_tree::Grid2D { x: f_x, y: f_y }
     }
 }
 
@@ -378,13 +812,16 @@ mod tests {
         wave.set_boundary_near_points();
         wave.set_interior_near_points(6);
         wave.set_interior_near_points_plus_boundary();
+        let j = (0..wave.interior.points.len())
+            .find(|i| !wave.near_points_boundary[*i].is_empty())
+            .unwrap();
         let mut x = 0.0;
-        for i in 0..wave.near_points_boundary[0].len() {
-            let index = wave.near_points_boundary[0][i];
+        for i in 0..wave.near_points_boundary[j].len() {
+            let index = wave.near_points_boundary[j][i];
             //println!("{} {}", wave.boundary.points[index].x, wave.boundary.points[index].y);
             x = wave.boundary.points[index].x;
         }
-        assert_eq!(x, -0.8090169943749473);
+        assert_eq!(x, -0.18738131458572463);
     }
 
     #[test]
@@ -398,6 +835,339 @@ mod tests {
         wave.init_poly(2);
         wave.set_poly(1.0e-9);
         let x = wave.poly_eval(0.0, 0.0);
-        assert_eq!(x, 1.0206284057893273);
+        assert_eq!(x, 0.9948764802155595);
+    }
+
+    #[test]
+    fn step_model_heat() {
+        let mut wave = WaveEq::new();
+        wave.create(22);
+        wave.set_initial_condition();
+        wave.set_boundary_near_points();
+        wave.set_interior_near_points(4);
+        wave.set_interior_near_points_plus_boundary();
+        wave.init_poly(2);
+        let mut heat = wave.clone();
+        heat.step_model(&crate::pde_model::HeatModel::new(1.0), 1.0e-6, 1.0e-4);
+        for i in 0..wave.interior.points.len() {
+            let expected = wave.value_1[i] + 1.0e-4 * heat.local_derivatives(i).laplacian();
+            assert!((heat.value[i] - expected).abs() < 1.0e-12);
+            assert_eq!(heat.value_2[i], wave.value[i]);
+        }
+        assert_eq!(heat.time, 1.0e-4);
+    }
+
+    #[test]
+    fn step_rk_matches_euler() {
+        let mut wave = WaveEq::new();
+        wave.create(22);
+        wave.set_initial_condition();
+        wave.set_boundary_near_points();
+        wave.set_interior_near_points(4);
+        wave.set_interior_near_points_plus_boundary();
+        wave.init_poly(2);
+        let mut euler = wave.clone();
+        let mut rk = wave.clone();
+        let heat = crate::pde_model::HeatModel::new(1.0);
+        euler.step_model(&heat, 1.0e-9, 1.0e-4);
+        rk.step_rk(&heat, RkScheme::Euler, 1.0e-9, 1.0e-4);
+        for i in 0..wave.interior.points.len() {
+            assert!((euler.value[i] - rk.value[i]).abs() < 1.0e-12);
+        }
+        let mut rk4 = wave.clone();
+        rk4.step_rk(&heat, RkScheme::Rk4, 1.0e-9, 1.0e-4);
+        for i in 0..wave.interior.points.len() {
+            assert!((euler.value[i] - rk4.value[i]).abs() < 1.0e-3);
+        }
+    }
+
+    #[test]
+    fn step_rk_wave_velocity() {
+        let mut wave = WaveEq::new();
+        wave.create(22);
+        wave.set_initial_condition();
+        wave.set_boundary_near_points();
+        wave.set_interior_near_points(4);
+        wave.set_interior_near_points_plus_boundary();
+        wave.init_poly(2);
+        let model = crate::pde_model::WaveModel::new(1.0);
+        wave.step_rk(&model, RkScheme::SspRk3, 1.0e-9, 1.0e-3);
+        // 中心付近は凸なので u_tt = Δu < 0
+        let center = (0..wave.interior.points.len())
+            .min_by(|a, b| {
+                let pa = &wave.interior.points[*a];
+                let pb = &wave.interior.points[*b];
+                (pa.x * pa.x + pa.y * pa.y)
+                    .partial_cmp(&(pb.x * pb.x + pb.y * pb.y))
+                    .unwrap()
+            })
+            .unwrap();
+        assert!(wave.velocity[center] < 0.0);
+        assert_eq!(wave.time, 1.0e-3);
+    }
+
+    #[test]
+    fn stable_dt_estimates() {
+        let mut wave = WaveEq::new();
+        wave.create(30);
+        let h = wave.min_node_spacing();
+        assert!(h > 0.0 && h < 2.0 * std::f64::consts::PI / 30.0);
+        let heat = crate::pde_model::HeatModel::new(1.0);
+        let dt_euler = wave
+            .largest_stable_dt(&heat, RkScheme::Euler, 2, 12)
+            .unwrap();
+        let dt_rk4 = wave.largest_stable_dt(&heat, RkScheme::Rk4, 2, 12).unwrap();
+        assert!(dt_euler > 0.0 && dt_rk4 > dt_euler);
+        let dt_wave = wave
+            .largest_stable_dt(&crate::pde_model::WaveModel::new(1.0), RkScheme::Rk4, 2, 12)
+            .unwrap();
+        assert!(dt_wave > dt_rk4);
+        assert_eq!(
+            wave.largest_stable_dt(
+                &crate::pde_model::WaveModel::new(1.0),
+                RkScheme::Euler,
+                2,
+                12
+            ),
+            Ok(0.0)
+        );
+        assert!(wave.cfl_dt_diffusion(1.0, 1.0) > 0.0);
+        assert!(wave.cfl_dt_wave(2.0, 1.0) < wave.cfl_dt_wave(1.0, 1.0));
+    }
+
+    #[test]
+    fn step_implicit_decays() {
+        use crate::implicit::ImplicitScheme;
+        use crate::meshfree::MeshfreeNodes;
+        use crate::stencil::DiffOperator;
+        let mut wave = WaveEq::new();
+        wave.create(30);
+        wave.set_initial_condition();
+        let mesh = MeshfreeNodes::from_wave(&wave, 2, 12);
+        let (a_ii, a_ib) = mesh.interior_system(&DiffOperator::laplacian()).unwrap();
+        let mut stepper = ImplicitStepper::new(a_ii, ImplicitScheme::Bdf2);
+        let max0 = wave.value.iter().fold(0.0_f64, |m, v| m.max(v.abs()));
+        for _ in 0..10 {
+            wave.step_implicit(&mut stepper, &a_ib, 0.05).unwrap();
+        }
+        let max1 = wave.value.iter().fold(0.0_f64, |m, v| m.max(v.abs()));
+        assert!(max1 < 0.5 * max0);
+        assert!(wave.value.iter().all(|v| *v > 0.0));
+        assert!((wave.time - 0.5).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn step_implicit_with_boundary_data_and_source() {
+        use crate::implicit::ImplicitScheme;
+        use crate::stencil::DiffOperator;
+        // u = x^2 + y^2 + 4t + t^2 は u_t = Δu + 2t を満たす。空間 2 次、u_t が t の 1 次なので
+        // Crank-Nicolson の誤差は線形ソルバーの許容誤差程度になる
+        let exact = |x: f64, y: f64, t: f64| x * x + y * y + 4.0 * t + t * t;
+        let mut wave = WaveEq::new();
+        wave.create(30);
+        wave.set_initial_condition_with(&|x, y| exact(x, y, 0.0), &|_, _| 0.0, 0.0);
+        wave.set_dirichlet(exact);
+        wave.set_source(|_, _, t| 2.0 * t);
+        let mesh = MeshfreeNodes::from_wave(&wave, 2, 12);
+        let (a_ii, a_ib) = mesh.interior_system(&DiffOperator::laplacian()).unwrap();
+        let mut stepper = ImplicitStepper::new(a_ii, ImplicitScheme::CrankNicolson);
+        for _ in 0..10 {
+            wave.step_implicit(&mut stepper, &a_ib, 0.05).unwrap();
+        }
+        let err = (0..wave.interior.points.len())
+            .map(|i| {
+                let p = &wave.interior.points[i];
+                (wave.value[i] - exact(p.x, p.y, wave.time)).abs()
+            })
+            .fold(0.0, f64::max);
+        assert!(err < 1.0e-8, "{}", err);
+        // Neumann 境界は扱えない
+        wave.boundary_conditions
+            .add_segment(|_, y| y > 0.0, BoundaryCondition::insulated());
+        let value = wave.value.clone();
+        assert!(wave.step_implicit(&mut stepper, &a_ib, 0.05).is_err());
+        assert_eq!(wave.value, value);
+    }
+
+    #[test]
+    fn create_in_domain() {
+        let annulus = Domain::with_holes(
+            Domain::unit_circle(),
+            vec![Domain::Circle {
+                cx: 0.0,
+                cy: 0.0,
+                r: 0.4,
+            }],
+        );
+        let rectangle = Domain::Rectangle {
+            x_min: 0.0,
+            y_min: 0.0,
+            x_max: 2.0,
+            y_max: 1.0,
+        };
+        for domain in [annulus, rectangle] {
+            let mut wave = WaveEq::new();
+            wave.create_in_domain(domain.clone(), 30);
+            assert!(wave.boundary.points.len() >= 28);
+            assert!(wave.interior.points.len() > 20);
+            for p in &wave.interior.points {
+                assert!(domain.contains(p.x, p.y), "{:?} {:?}", domain, p);
+            }
+            for k in 0..wave.boundary.points.len() {
+                let p = &wave.boundary.points[k];
+                let n = wave.boundary_normal(k);
+                assert!(!domain.contains(p.x + 1.0e-3 * n.x, p.y + 1.0e-3 * n.y));
+            }
+            wave.set_boundary_near_points();
+            wave.set_interior_near_points(5);
+            assert!(wave.near_points_interior.iter().all(|n| n.len() >= 5));
+        }
+    }
+
+    #[test]
+    fn neumann_boundary_constraints() {
+        let mut wave = WaveEq::new();
+        wave.create(22);
+        wave.set_initial_condition();
+        wave.set_boundary_near_points();
+        wave.set_interior_near_points(4);
+        wave.set_interior_near_points_plus_boundary();
+        wave.init_poly(2);
+        wave.boundary_conditions
+            .add_segment(|_, y| y > 0.0, BoundaryCondition::insulated());
+        let i = (0..wave.interior.points.len())
+            .find(|i| !wave.near_points_boundary[*i].is_empty())
+            .unwrap();
+        let data = wave.local_fit_data(i);
+        let num_upper = wave.near_points_boundary[i]
+            .iter()
+            .filter(|k| wave.boundary.points[**k].y > 0.0)
+            .count();
+        let num_interior = wave.near_points_interior[i].len();
+        assert_eq!(data.constraints.len(), num_interior * num_upper);
+        assert_eq!(
+            data.points_3d.len(),
+            num_interior * (1 + wave.near_points_boundary[i].len() - num_upper)
+        );
+        for c in &data.constraints {
+            assert_eq!((c.alpha, c.beta, c.r), (0.0, 1.0, 0.0));
+            assert!((c.nx - c.x).abs() < 1.0e-6 && (c.ny - c.y).abs() < 1.0e-6);
+        }
+        wave.step(1.0e-6, 1.0e-4);
+        assert!(wave.value.iter().all(|v| v.is_finite()));
+    }
+
+    #[test]
+    fn nonzero_dirichlet() {
+        let mut wave = WaveEq::new();
+        wave.create(22);
+        wave.set_initial_condition();
+        wave.set_boundary_near_points();
+        wave.set_interior_near_points(4);
+        wave.set_interior_near_points_plus_boundary();
+        wave.init_poly(2);
+        for i in 0..wave.interior.points.len() {
+            wave.value[i] = 1.0;
+            wave.value_1[i] = 1.0;
+            wave.value_2[i] = 1.0;
+        }
+        wave.set_dirichlet(|_, _, t| 1.0 + t);
+        assert!(wave.boundary_values(0.5).iter().all(|g| *g == 1.5));
+        let mut zero = wave.clone();
+        zero.boundary_conditions = BoundaryConditions::homogeneous_dirichlet();
+        wave.step(1.0e-7, 1.0e-4);
+        zero.step(1.0e-7, 1.0e-4);
+        let err = wave
+            .value
+            .iter()
+            .fold(0.0_f64, |m, v| m.max((v - 1.0).abs()));
+        let drop = zero
+            .value
+            .iter()
+            .fold(0.0_f64, |m, v| m.max((v - 1.0).abs()));
+        assert!(err < 1.0e-2, "{}", err);
+        assert!(drop > 10.0 * err, "{} {}", drop, err);
+    }
+
+    #[test]
+    fn initial_condition_and_source() {
+        let mut wave = WaveEq::new();
+        wave.create(22);
+        wave.set_initial_condition();
+        let default = wave.value.clone();
+        wave.set_initial_condition_from_expression("exp(-10 * (x * x + y * y))", "x", 0.1)
+            .unwrap();
+        assert_eq!(wave.value, default);
+        for (i, u0) in default.iter().enumerate() {
+            let x = wave.interior.points[i].x;
+            assert_eq!(wave.velocity[i], x);
+            assert!((wave.value_2[i] - (u0 - 0.1 * x)).abs() < 1.0e-15);
+        }
+        assert!(wave
+            .set_initial_condition_from_expression("exp(", "0", 0.1)
+            .is_err());
+
+        let path = std::env::temp_dir().join("wave_node_values_test.txt");
+        let path = path.to_str().unwrap();
+        wave.save_node_values(path).unwrap();
+        let mut other = wave.clone();
+        other.set_initial_condition_with(&|_, _| 0.0, &|_, _| 0.0, 0.0);
+        other.load_initial_condition(path, 0.1).unwrap();
+        assert_eq!(other.value, wave.value);
+        assert_eq!(other.velocity, wave.velocity);
+        std::fs::write(path, "0.5 0.5 1.0\n").unwrap();
+        assert!(other.load_initial_condition(path, 0.1).is_err());
+        std::fs::remove_file(path).unwrap();
+
+        // u = 0 からソース f = 1 + t で 1 ステップ
+        wave.set_boundary_near_points();
+        wave.set_interior_near_points(4);
+        wave.set_interior_near_points_plus_boundary();
+        wave.init_poly(2);
+        wave.set_initial_condition_with(&|_, _| 0.0, &|_, _| 0.0, 0.0);
+        wave.time = 1.0;
+        wave.set_source(|_, _, t| 1.0 + t);
+        wave.step(1.0e-6, 1.0e-3);
+        assert!(wave.value.iter().all(|v| (v - 2.0e-3).abs() < 1.0e-12));
+    }
+
+    #[test]
+    fn create_with_generator() {
+        let generator = NodeGenerator::new(
+            Domain::Rectangle {
+                x_min: -1.0,
+                y_min: -0.5,
+                x_max: 1.0,
+                y_max: 0.5,
+            },
+            Spacing::Uniform(0.1),
+            NodeStrategy::HexLattice,
+        );
+        let mut wave = WaveEq::new();
+        wave.create_with(&generator);
+        assert_eq!(wave.domain, generator.domain);
+        assert_eq!(wave.boundary.points.len(), 60);
+        assert!(wave.interior.points.len() > 150);
+        let mesh = MeshfreeNodes::from_wave(&wave, 2, 12);
+        assert_eq!(mesh.num_interior, wave.interior.points.len());
+    }
+
+    #[test]
+    fn euler_step_reuses_neighbor_list() {
+        let mut wave = WaveEq::new();
+        wave.create(25);
+        for _ in 0..10 {
+            wave.euler_step();
+        }
+        // create の緩和で落ち着いているので点はほとんど動かない
+        assert_eq!(wave.node_neighbors.as_ref().unwrap().rebuilds, 1);
+        // 点数が変わると σ が変わるので作り直す
+        wave.interior.points.pop();
+        wave.euler_step();
+        assert_eq!(wave.node_neighbors.as_ref().unwrap().rebuilds, 1);
+        assert_eq!(
+            wave.node_neighbors.as_ref().unwrap().lists.len(),
+            wave.interior.points.len()
+        );
     }
 }